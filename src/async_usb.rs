@@ -0,0 +1,380 @@
+/// Pipelined bulk OUT transfers on libusb's asynchronous API
+use anyhow::{anyhow, Result};
+use rusb::constants::*;
+use rusb::ffi::{
+    libusb_alloc_transfer, libusb_cancel_transfer, libusb_fill_bulk_transfer, libusb_free_transfer,
+    libusb_handle_events_completed, libusb_submit_transfer, libusb_transfer,
+};
+use rusb::{Context, DeviceHandle, UsbContext};
+use std::collections::VecDeque;
+use std::ffi::{c_int, c_void};
+use std::io::Read;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Largest bulk transfer libusb accepts; its length is a C `int`
+pub const MAX_TRANSFER_SIZE: usize = c_int::MAX as usize;
+
+/// Bulk OUT transfers that complete in the order they were queued
+///
+/// `BulkPipeline` decides what to queue and when; this only moves the bytes.
+pub trait TransferQueue {
+    /// Queue a transfer of `len` bytes starting at `data`
+    ///
+    /// # Safety
+    ///
+    /// The `len` bytes at `data` must stay valid until the transfer has been
+    /// waited for or cancelled, and `len` is at most `MAX_TRANSFER_SIZE`.
+    unsafe fn submit(&mut self, data: *const u8, len: usize) -> Result<()>;
+
+    /// Wait for the oldest transfer and return how many bytes it sent; `None` when nothing is queued
+    ///
+    /// A transfer that fails or sends fewer bytes than queued is an error.
+    fn wait_oldest(&mut self) -> Result<Option<usize>>;
+
+    /// Transfers queued and not yet waited for
+    fn queued(&self) -> usize;
+
+    /// Cancel everything queued and wait until none of it is in use
+    fn cancel_all(&mut self);
+}
+
+/// A submitted libusb transfer
+struct InFlight {
+    transfer: *mut libusb_transfer,
+    len: usize,
+    // Set by the completion callback; boxed so its address stays put
+    completed: Box<c_int>,
+}
+
+extern "system" fn on_complete(transfer: *mut libusb_transfer) {
+    // SAFETY: user_data points at the InFlight's boxed flag, which outlives the transfer
+    unsafe {
+        let completed = (*transfer).user_data as *mut c_int;
+        *completed = 1;
+    }
+}
+
+/// Transfers on one bulk OUT endpoint, through libusb's asynchronous API
+pub struct LibusbQueue<'a> {
+    handle: &'a DeviceHandle<Context>,
+    endpoint: u8,
+    timeout: Duration,
+    in_flight: VecDeque<InFlight>,
+}
+
+impl<'a> LibusbQueue<'a> {
+    pub fn new(handle: &'a DeviceHandle<Context>, endpoint: u8, timeout: Duration) -> Self {
+        Self {
+            handle,
+            endpoint,
+            timeout,
+            in_flight: VecDeque::new(),
+        }
+    }
+
+    fn wait(&self, entry: &mut InFlight) {
+        let context = self.handle.context().as_raw();
+        while *entry.completed == 0 {
+            // SAFETY: the context outlives the handle; completed is checked under libusb's lock
+            let rc = unsafe { libusb_handle_events_completed(context, &mut *entry.completed) };
+            if rc != 0 && rc != LIBUSB_ERROR_INTERRUPTED {
+                debug!("libusb event handling failed: {}", rc);
+            }
+        }
+    }
+}
+
+impl TransferQueue for LibusbQueue<'_> {
+    unsafe fn submit(&mut self, data: *const u8, len: usize) -> Result<()> {
+        let mut completed = Box::new(0);
+
+        // SAFETY: the caller keeps the data alive until the transfer is waited
+        // for or cancelled, and the flag is owned by the InFlight entry; libusb
+        // only reads the data of an OUT transfer
+        unsafe {
+            let transfer = libusb_alloc_transfer(0);
+            if transfer.is_null() {
+                return Err(rusb::Error::NoMem.into());
+            }
+
+            libusb_fill_bulk_transfer(
+                transfer,
+                self.handle.as_raw(),
+                self.endpoint,
+                data as *mut u8,
+                len as c_int,
+                on_complete,
+                &mut *completed as *mut c_int as *mut c_void,
+                self.timeout.as_millis() as u32,
+            );
+
+            let rc = libusb_submit_transfer(transfer);
+            if rc != 0 {
+                libusb_free_transfer(transfer);
+                return Err(error_from_code(rc).into());
+            }
+
+            self.in_flight.push_back(InFlight {
+                transfer,
+                len,
+                completed,
+            });
+        }
+        Ok(())
+    }
+
+    fn wait_oldest(&mut self) -> Result<Option<usize>> {
+        let Some(mut entry) = self.in_flight.pop_front() else {
+            return Ok(None);
+        };
+
+        self.wait(&mut entry);
+
+        // SAFETY: the transfer has completed, so libusb no longer touches it
+        let (status, actual) = unsafe {
+            let status = (*entry.transfer).status;
+            let actual = (*entry.transfer).actual_length as usize;
+            libusb_free_transfer(entry.transfer);
+            (status, actual)
+        };
+
+        match status {
+            LIBUSB_TRANSFER_COMPLETED if actual == entry.len => Ok(Some(actual)),
+            LIBUSB_TRANSFER_COMPLETED => {
+                warn!("Short bulk transfer: {} of {} bytes", actual, entry.len);
+                Err(rusb::Error::Io.into())
+            }
+            status => Err(error_from_status(status).into()),
+        }
+    }
+
+    fn queued(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    fn cancel_all(&mut self) {
+        for entry in &self.in_flight {
+            // SAFETY: the transfer was submitted and not yet freed
+            unsafe {
+                libusb_cancel_transfer(entry.transfer);
+            }
+        }
+        while let Some(mut entry) = self.in_flight.pop_front() {
+            self.wait(&mut entry);
+            // SAFETY: completed or cancelled, so libusb is done with it
+            unsafe {
+                libusb_free_transfer(entry.transfer);
+            }
+        }
+    }
+}
+
+impl Drop for LibusbQueue<'_> {
+    fn drop(&mut self) {
+        self.cancel_all();
+    }
+}
+
+/// Keeps up to `depth` bulk OUT transfers queued so the host controller never idles
+///
+/// Completed buffers go back into `pool` and are refilled for the next transfer.
+///
+/// A failed transfer is not retried: how much of it reached the console is
+/// unknown, so the rest is cancelled and the `rusb::Error` is returned for
+/// `ErrorClass` to judge, ending the range like a failed synchronous write.
+pub struct BulkPipeline<'a, Q: TransferQueue> {
+    queue: Q,
+    depth: usize,
+    transfer_size: usize,
+    pool: &'a mut Vec<Vec<u8>>,
+    // One per queued transfer: its buffer, or `None` when it points into a caller's slice
+    buffers: VecDeque<Option<Vec<u8>>>,
+}
+
+impl<'a, Q: TransferQueue> BulkPipeline<'a, Q> {
+    pub fn new(queue: Q, depth: usize, transfer_size: usize, pool: &'a mut Vec<Vec<u8>>) -> Self {
+        Self {
+            queue,
+            depth: depth.max(1),
+            transfer_size: transfer_size.clamp(1, MAX_TRANSFER_SIZE),
+            pool,
+            buffers: VecDeque::new(),
+        }
+    }
+
+    /// Send `size` bytes from `reader`, calling `pace` before each transfer is
+    /// queued and `on_sent` as each one completes
+    ///
+    /// Returns the number of bytes sent, which is less than `size` if the
+    /// reader hits EOF first.
+    pub fn stream(
+        &mut self,
+        reader: &mut dyn Read,
+        size: usize,
+        pace: &mut dyn FnMut(usize),
+        on_sent: &mut dyn FnMut(usize),
+    ) -> Result<usize> {
+        let result = self.stream_chunks(reader, size, pace, on_sent);
+        if result.is_err() {
+            self.cancel_all();
+        }
+        result
+    }
+
+    fn stream_chunks(
+        &mut self,
+        reader: &mut dyn Read,
+        size: usize,
+        pace: &mut dyn FnMut(usize),
+        on_sent: &mut dyn FnMut(usize),
+    ) -> Result<usize> {
+        let mut queued = 0;
+        let mut sent = 0;
+        let mut eof = false;
+
+        loop {
+            while !eof && queued < size && self.queue.queued() < self.depth {
+                let mut buffer = self.pool.pop().unwrap_or_default();
+                buffer.resize(self.transfer_size, 0);
+
+                let want = self.transfer_size.min(size - queued);
+                let len = match read_up_to(reader, &mut buffer[..want]) {
+                    Ok(len) => len,
+                    Err(e) => {
+                        self.pool.push(buffer);
+                        return Err(e);
+                    }
+                };
+                if len == 0 {
+                    eof = true;
+                    self.pool.push(buffer);
+                    break;
+                }
+
+                pace(len);
+                // SAFETY: the buffer is kept in `buffers` until the transfer is
+                // waited for or cancelled; moving the Vec doesn't move its data
+                let data = buffer.as_ptr();
+                if let Err(e) = unsafe { self.queue.submit(data, len) } {
+                    self.pool.push(buffer);
+                    return Err(e);
+                }
+                self.buffers.push_back(Some(buffer));
+                queued += len;
+            }
+
+            let Some(len) = self.wait_oldest()? else {
+                break;
+            };
+            sent += len;
+            on_sent(len);
+        }
+
+        Ok(sent)
+    }
+
+    /// Send `data` without copying it, calling `pace` and `on_sent` as `stream` does
+    ///
+    /// Every transfer has finished or been cancelled by the time this
+    /// returns, so nothing refers to `data` afterwards.
+    pub fn send_slice(
+        &mut self,
+        data: &[u8],
+        pace: &mut dyn FnMut(usize),
+        on_sent: &mut dyn FnMut(usize),
+    ) -> Result<usize> {
+        let result = self.send_chunks(data, pace, on_sent);
+        if result.is_err() {
+            self.cancel_all();
+        }
+        result
+    }
+
+    fn send_chunks(
+        &mut self,
+        data: &[u8],
+        pace: &mut dyn FnMut(usize),
+        on_sent: &mut dyn FnMut(usize),
+    ) -> Result<usize> {
+        let mut chunks = data.chunks(self.transfer_size);
+        let mut sent = 0;
+
+        loop {
+            while self.queue.queued() < self.depth {
+                let Some(chunk) = chunks.next() else {
+                    break;
+                };
+                pace(chunk.len());
+                // SAFETY: `send_slice` waits for or cancels every transfer before `data` is released
+                unsafe { self.queue.submit(chunk.as_ptr(), chunk.len())? };
+                self.buffers.push_back(None);
+            }
+
+            let Some(len) = self.wait_oldest()? else {
+                break;
+            };
+            sent += len;
+            on_sent(len);
+        }
+
+        Ok(sent)
+    }
+
+    /// Wait for the oldest transfer and recycle its buffer
+    fn wait_oldest(&mut self) -> Result<Option<usize>> {
+        let result = self.queue.wait_oldest();
+        if let Some(buffer) = self.buffers.pop_front() {
+            self.pool.extend(buffer);
+        }
+        result
+    }
+
+    /// Cancel everything queued; buffers are only recycled once the queue lets go of them
+    fn cancel_all(&mut self) {
+        self.queue.cancel_all();
+        self.pool.extend(self.buffers.drain(..).flatten());
+    }
+}
+
+impl<Q: TransferQueue> Drop for BulkPipeline<'_, Q> {
+    fn drop(&mut self) {
+        self.cancel_all();
+    }
+}
+
+/// Fill `buf` as far as the reader allows
+fn read_up_to(reader: &mut dyn Read, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(anyhow!(e)),
+        }
+    }
+    Ok(filled)
+}
+
+fn error_from_status(status: c_int) -> rusb::Error {
+    match status {
+        LIBUSB_TRANSFER_TIMED_OUT => rusb::Error::Timeout,
+        LIBUSB_TRANSFER_STALL => rusb::Error::Pipe,
+        LIBUSB_TRANSFER_NO_DEVICE => rusb::Error::NoDevice,
+        LIBUSB_TRANSFER_CANCELLED => rusb::Error::Interrupted,
+        LIBUSB_TRANSFER_OVERFLOW => rusb::Error::Overflow,
+        _ => rusb::Error::Io,
+    }
+}
+
+fn error_from_code(code: c_int) -> rusb::Error {
+    match code {
+        LIBUSB_ERROR_NO_DEVICE => rusb::Error::NoDevice,
+        LIBUSB_ERROR_BUSY => rusb::Error::Busy,
+        LIBUSB_ERROR_NO_MEM => rusb::Error::NoMem,
+        LIBUSB_ERROR_PIPE => rusb::Error::Pipe,
+        LIBUSB_ERROR_TIMEOUT => rusb::Error::Timeout,
+        _ => rusb::Error::Io,
+    }
+}
@@ -0,0 +1,572 @@
+/// Portable library exports, importing them into another library, and database backups
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::database::Database;
+
+pub const FORMAT_VERSION: u32 = 1;
+
+/// First line of CSV exports, which have no other place for the version
+const CSV_MARKER: &str = "# switchlink library export v";
+
+/// The library written out as JSON (or CSV, files only), to move it to another machine
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryExport {
+    pub version: u32,
+    pub exported_at: i64,
+    pub files: Vec<ExportedFile>,
+    #[serde(default)]
+    pub tags: Vec<ExportedTag>,
+    #[serde(default)]
+    pub collections: Vec<ExportedCollection>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportedFile {
+    pub path: String,
+    pub filename: String,
+    pub size: u64,
+    pub hash: Option<String>,
+    pub title_id: Option<String>,
+    pub version: Option<u32>,
+    pub added_at: i64,
+    pub favorite: bool,
+    pub install_count: i32,
+    pub last_installed: Option<i64>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedTag {
+    pub name: String,
+    /// `#RRGGBB`, or empty for the default
+    #[serde(default)]
+    pub color: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedCollection {
+    pub name: String,
+    /// Paths as they were on the exporting machine
+    pub items: Vec<String>,
+}
+
+/// One CSV row; tags are joined with `;`
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvRow {
+    path: String,
+    filename: String,
+    size: u64,
+    hash: Option<String>,
+    title_id: Option<String>,
+    version: Option<u32>,
+    added_at: i64,
+    favorite: bool,
+    install_count: i32,
+    last_installed: Option<i64>,
+    tags: String,
+}
+
+impl LibraryExport {
+    pub fn from_db(db: &Database) -> Result<Self> {
+        let files = db
+            .get_files()?
+            .into_iter()
+            .map(|f| ExportedFile {
+                path: f.path,
+                filename: f.filename,
+                size: f.size,
+                hash: f.hash,
+                title_id: f.title_id,
+                version: f.version,
+                added_at: f.added_at,
+                favorite: f.favorite,
+                install_count: f.install_count,
+                last_installed: f.last_installed,
+                tags: f.tags,
+            })
+            .collect();
+        let tags = db
+            .get_tags()?
+            .into_iter()
+            .map(|t| ExportedTag { name: t.name, color: t.color })
+            .collect();
+        let collections = db
+            .get_collections()?
+            .into_iter()
+            .map(|c| {
+                let items = db.get_collection_items(&c.name)?;
+                Ok(ExportedCollection {
+                    name: c.name,
+                    items: items.iter().map(|p| p.to_string_lossy().to_string()).collect(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            version: FORMAT_VERSION,
+            exported_at: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or(0),
+            files,
+            tags,
+            collections,
+        })
+    }
+
+    /// Write as JSON, or as CSV if the path ends in `.csv` (leaving out tag colors and collections)
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if !is_csv(path) {
+            std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+            return Ok(());
+        }
+
+        let mut file = std::fs::File::create(path)?;
+        writeln!(file, "{}{}", CSV_MARKER, self.version)?;
+        let mut writer = csv::Writer::from_writer(file);
+        for f in &self.files {
+            writer.serialize(CsvRow {
+                path: f.path.clone(),
+                filename: f.filename.clone(),
+                size: f.size,
+                hash: f.hash.clone(),
+                title_id: f.title_id.clone(),
+                version: f.version,
+                added_at: f.added_at,
+                favorite: f.favorite,
+                install_count: f.install_count,
+                last_installed: f.last_installed,
+                tags: f.tags.join(";"),
+            })?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        let export = if is_csv(path) {
+            Self::parse_csv(&data)?
+        } else {
+            serde_json::from_str(&data).map_err(|e| anyhow!("Not a SwitchLink library export: {}", e))?
+        };
+        if export.version > FORMAT_VERSION {
+            return Err(anyhow!(
+                "This export is format v{}, from a newer SwitchLink; this version reads up to v{}",
+                export.version,
+                FORMAT_VERSION
+            ));
+        }
+        Ok(export)
+    }
+
+    fn parse_csv(data: &str) -> Result<Self> {
+        // Only the first line is the marker; paths further down may start with `#`
+        let (marker, rows) = data.split_once('\n').unwrap_or((data, ""));
+        let version = marker
+            .trim()
+            .strip_prefix(CSV_MARKER)
+            .and_then(|v| v.trim().parse().ok())
+            .ok_or_else(|| anyhow!("Not a SwitchLink library export: missing the {:?} line", CSV_MARKER))?;
+
+        let mut reader = csv::Reader::from_reader(rows.as_bytes());
+        let files = reader
+            .deserialize::<CsvRow>()
+            .map(|row| {
+                let row = row?;
+                Ok(ExportedFile {
+                    path: row.path,
+                    filename: row.filename,
+                    size: row.size,
+                    hash: row.hash,
+                    title_id: row.title_id,
+                    version: row.version,
+                    added_at: row.added_at,
+                    favorite: row.favorite,
+                    install_count: row.install_count,
+                    last_installed: row.last_installed,
+                    tags: row.tags.split(';').map(str::trim).filter(|t| !t.is_empty()).map(String::from).collect(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { version, exported_at: 0, files, tags: Vec::new(), collections: Vec::new() })
+    }
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+}
+
+/// Hash every file in the library that has no hash yet, so an export can be matched by content
+///
+/// Calls `on_progress(done, total)` after each file and returns how many were hashed.
+/// Files that no longer exist are left alone.
+pub fn hash_unhashed(db: &Database, mut on_progress: impl FnMut(usize, usize)) -> Result<usize> {
+    let pending: Vec<_> = db
+        .get_files()?
+        .into_iter()
+        .filter(|f| f.hash.is_none() && Path::new(&f.path).is_file())
+        .collect();
+    let total = pending.len();
+    for (done, file) in pending.into_iter().enumerate() {
+        db.set_hash(file.id, &crate::inbox::hash_file(Path::new(&file.path))?)?;
+        on_progress(done + 1, total);
+    }
+    Ok(total)
+}
+
+/// How an exported file is matched to one already in the library
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchBy {
+    /// The same path, after rewriting
+    #[default]
+    Path,
+    /// The same SHA-256; unhashed local files of a matching size are hashed
+    Hash,
+    /// The same title ID and version
+    TitleId,
+}
+
+impl MatchBy {
+    pub const ALL: [MatchBy; 3] = [MatchBy::Path, MatchBy::Hash, MatchBy::TitleId];
+
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "path" => Ok(Self::Path),
+            "hash" => Ok(Self::Hash),
+            "title" | "title-id" | "titleid" => Ok(Self::TitleId),
+            other => Err(anyhow!("Unknown match {:?}, expected path, hash or title", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Path => "path",
+            Self::Hash => "hash",
+            Self::TitleId => "title",
+        }
+    }
+}
+
+/// Replace a leading directory, e.g. `/mnt/games=D:\Games` when moving a library between machines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathRewrite {
+    pub from: String,
+    pub to: String,
+}
+
+impl PathRewrite {
+    /// `FROM=TO`
+    pub fn parse(value: &str) -> Result<Self> {
+        let (from, to) = value
+            .split_once('=')
+            .filter(|(from, to)| !from.trim().is_empty() && !to.trim().is_empty())
+            .ok_or_else(|| anyhow!("Path rewrites look like FROM=TO, got {:?}", value))?;
+        Ok(Self { from: from.trim().to_string(), to: to.trim().to_string() })
+    }
+
+    /// The rewritten path, if it starts with `from` as whole directories
+    ///
+    /// Separators after the prefix are changed to the kind `to` uses, so
+    /// Windows paths map onto Unix ones and back.
+    pub fn apply(&self, path: &str) -> Option<String> {
+        let from = self.from.trim_end_matches(['/', '\\']);
+        let rest = path.strip_prefix(from)?;
+        if !(rest.is_empty() || rest.starts_with(['/', '\\'])) {
+            return None;
+        }
+
+        let separator = if self.to.contains('\\') && !self.to.contains('/') {
+            '\\'
+        } else if self.to.contains('/') {
+            '/'
+        } else {
+            std::path::MAIN_SEPARATOR
+        };
+        let rest = rest.trim_start_matches(['/', '\\']).replace(['/', '\\'], &separator.to_string());
+        let to = self.to.trim_end_matches(['/', '\\']);
+        Some(if rest.is_empty() { to.to_string() } else { format!("{}{}{}", to, separator, rest) })
+    }
+}
+
+/// The first matching rewrite applied to `path`, or `path` unchanged
+pub fn rewrite_path(path: &str, rewrites: &[PathRewrite]) -> String {
+    rewrites
+        .iter()
+        .find_map(|r| r.apply(path))
+        .unwrap_or_else(|| path.to_string())
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    pub match_by: MatchBy,
+    pub rewrites: Vec<PathRewrite>,
+    /// Add exported files that aren't in the library but exist at their rewritten path
+    pub add_missing: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportSummary {
+    /// Exported files merged into library files
+    pub matched: usize,
+    /// Exported files added to the library
+    pub added: usize,
+    /// Exported paths that matched nothing
+    pub unmatched: Vec<String>,
+    pub collections_created: usize,
+    pub collections_merged: usize,
+}
+
+/// Merge an export into the library
+///
+/// Nothing is removed or lowered: favorites and tags are added, install
+/// counts and times keep the higher value, tag colors are only set where
+/// the library has none, and collections gain the items they lack. The
+/// merge is one transaction: if any of it fails, none of it is kept.
+pub fn import_library(db: &Database, export: &LibraryExport, options: &ImportOptions) -> Result<ImportSummary> {
+    let local = db.get_files()?;
+    let local_paths: HashMap<i64, String> = local.iter().map(|f| (f.id, f.path.clone())).collect();
+
+    let index: HashMap<String, i64> = match options.match_by {
+        MatchBy::Path => local.iter().map(|f| (f.path.clone(), f.id)).collect(),
+        MatchBy::Hash => {
+            let wanted: HashSet<u64> = export.files.iter().filter(|f| f.hash.is_some()).map(|f| f.size).collect();
+            let mut index = HashMap::new();
+            for file in &local {
+                let hash = match &file.hash {
+                    Some(hash) => hash.clone(),
+                    None if wanted.contains(&file.size) && Path::new(&file.path).is_file() => {
+                        let hash = crate::inbox::hash_file(Path::new(&file.path))?;
+                        db.set_hash(file.id, &hash)?;
+                        hash
+                    }
+                    None => continue,
+                };
+                index.entry(hash.to_ascii_lowercase()).or_insert(file.id);
+            }
+            index
+        }
+        MatchBy::TitleId => {
+            let mut index = HashMap::new();
+            for file in &local {
+                if let Some(title_id) = &file.title_id {
+                    index.entry(title_key(title_id, file.version)).or_insert(file.id);
+                }
+            }
+            index
+        }
+    };
+
+    // Hashing happens first, so the transaction isn't held open while files are read
+    db.atomically(|| merge_export(db, export, options, &index, &local_paths))
+}
+
+/// Apply an export once its files can be looked up in `index`
+fn merge_export(
+    db: &Database,
+    export: &LibraryExport,
+    options: &ImportOptions,
+    index: &HashMap<String, i64>,
+    local_paths: &HashMap<i64, String>,
+) -> Result<ImportSummary> {
+    let mut summary = ImportSummary::default();
+    for tag in &export.tags {
+        db.create_tag(&tag.name)?;
+        let uncolored = db
+            .get_tags()?
+            .iter()
+            .any(|t| t.name.eq_ignore_ascii_case(tag.name.trim()) && t.color.is_empty());
+        if uncolored && !tag.color.is_empty() {
+            db.set_tag_color(&tag.name, &tag.color)?;
+        }
+    }
+
+    // Exported path -> library path, for mapping collection items
+    let mut resolved: HashMap<&str, String> = HashMap::new();
+    for file in &export.files {
+        let target = rewrite_path(&file.path, &options.rewrites);
+        let key = match options.match_by {
+            MatchBy::Path => Some(target.clone()),
+            MatchBy::Hash => file.hash.as_ref().map(|h| h.to_ascii_lowercase()),
+            MatchBy::TitleId => file.title_id.as_ref().map(|t| title_key(t, file.version)),
+        };
+        let (id, path) = match key.and_then(|key| index.get(&key)) {
+            Some(&id) => {
+                summary.matched += 1;
+                (id, local_paths[&id].clone())
+            }
+            None if options.add_missing && Path::new(&target).is_file() => {
+                summary.added += 1;
+                (db.add_file(Path::new(&target))?, target)
+            }
+            None => {
+                summary.unmatched.push(file.path.clone());
+                continue;
+            }
+        };
+
+        if file.favorite {
+            db.set_favorite(id, true)?;
+        }
+        for tag in &file.tags {
+            db.add_tag(id, tag)?;
+        }
+        db.merge_install_stats(id, file.install_count, file.last_installed)?;
+        resolved.insert(&file.path, path);
+    }
+
+    let existing = db.get_collections()?;
+    for collection in &export.collections {
+        let items: Vec<PathBuf> = collection
+            .items
+            .iter()
+            .map(|item| {
+                resolved
+                    .get(item.as_str())
+                    .cloned()
+                    .unwrap_or_else(|| rewrite_path(item, &options.rewrites))
+                    .into()
+            })
+            .collect();
+
+        match existing.iter().find(|c| c.name.eq_ignore_ascii_case(collection.name.trim())) {
+            Some(local) => {
+                let mut merged = db.get_collection_items(&local.name)?;
+                let before = merged.len();
+                for item in items {
+                    if !merged.contains(&item) {
+                        merged.push(item);
+                    }
+                }
+                if merged.len() > before {
+                    db.set_collection_items(&local.name, &merged)?;
+                    summary.collections_merged += 1;
+                }
+            }
+            None => {
+                db.create_collection(&collection.name, &items)?;
+                summary.collections_created += 1;
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+fn title_key(title_id: &str, version: Option<u32>) -> String {
+    format!("{}:{}", title_id.trim().to_ascii_uppercase(), version.unwrap_or(0))
+}
+
+/// Automatic copies of the library database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    pub dir: PathBuf,
+    /// Back up when the newest backup is older than this; 0 turns automatic backups off
+    pub interval_hours: u64,
+    /// Backups to keep, oldest removed first (0 keeps them all)
+    pub keep: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            dir: crate::config::data_dir().join("backups"),
+            interval_hours: 24,
+            keep: 7,
+        }
+    }
+}
+
+/// Copy the database into `dir` as `library-YYYYMMDD-HHMMSS.db`
+pub fn backup_database(db: &Database, dir: &Path) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let mut path = dir.join(format!("library-{}.db", stamp));
+    // `_` sorts after `.`, so names stay in creation order
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("library-{}_{}.db", stamp, n));
+        n += 1;
+    }
+    db.backup_to(&path)?;
+    Ok(path)
+}
+
+/// Backups in `dir`, oldest first
+pub fn list_backups(dir: &Path) -> Result<Vec<PathBuf>> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+    let mut backups = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        if name.starts_with("library-") && name.ends_with(".db") && path.is_file() {
+            backups.push(path);
+        }
+    }
+    backups.sort();
+    Ok(backups)
+}
+
+/// Delete all but the newest `keep` backups, returning what was removed
+pub fn prune_backups(dir: &Path, keep: usize) -> Result<Vec<PathBuf>> {
+    let backups = list_backups(dir)?;
+    if keep == 0 || backups.len() <= keep {
+        return Ok(Vec::new());
+    }
+    let old = backups[..backups.len() - keep].to_vec();
+    for path in &old {
+        std::fs::remove_file(path)?;
+    }
+    Ok(old)
+}
+
+/// Back up and prune if automatic backups are on and the newest is older than the interval
+pub fn backup_if_due(db: &Database, config: &BackupConfig) -> Result<Option<PathBuf>> {
+    if config.interval_hours == 0 {
+        return Ok(None);
+    }
+    let interval = Duration::from_secs(config.interval_hours * 3600);
+    let newest = list_backups(&config.dir)?
+        .last()
+        .and_then(|path| std::fs::metadata(path).ok()?.modified().ok());
+    let due = newest.is_none_or(|modified| modified.elapsed().is_ok_and(|age| age >= interval));
+    if !due {
+        return Ok(None);
+    }
+
+    let path = backup_database(db, &config.dir)?;
+    prune_backups(&config.dir, config.keep)?;
+    Ok(Some(path))
+}
+
+/// Replace the database at `db_path` with a backup, first backing up the current one
+///
+/// Nothing may have the database open. Returns the backup of the replaced database, if there was one.
+pub fn restore_database(db_path: &Path, backup: &Path, dir: &Path) -> Result<Option<PathBuf>> {
+    let mut header = [0u8; 16];
+    let valid = std::fs::File::open(backup)
+        .and_then(|mut file| std::io::Read::read_exact(&mut file, &mut header))
+        .is_ok()
+        && &header == b"SQLite format 3\0";
+    if !valid {
+        return Err(anyhow!("{} is not a database backup", backup.display()));
+    }
+
+    let previous = if db_path.exists() {
+        Some(backup_database(&Database::new(db_path)?, dir)?)
+    } else {
+        None
+    };
+    std::fs::copy(backup, db_path)?;
+    Ok(previous)
+}
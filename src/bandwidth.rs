@@ -0,0 +1,198 @@
+/// Bandwidth limits, time-of-day schedules and pausing, shared by every session
+use chrono::{Local, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const BYTES_PER_MB: f64 = 1_000_000.0;
+
+/// How fast file data may be read and sent, in MB/s (`None` is unlimited)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BandwidthConfig {
+    /// Across all consoles together
+    pub limit_mbps: Option<f64>,
+    /// For each console on its own
+    pub device_limit_mbps: Option<f64>,
+    /// Overrides for `limit_mbps` at certain times of day; the first match wins
+    pub schedule: Vec<ScheduleRule>,
+}
+
+impl BandwidthConfig {
+    /// The overall limit in effect at `time`
+    pub fn limit_at(&self, time: NaiveTime) -> Option<f64> {
+        self.schedule
+            .iter()
+            .find(|rule| rule.window.contains(time))
+            .map_or(self.limit_mbps, |rule| rule.limit_mbps)
+    }
+}
+
+/// A limit that applies during a daily window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleRule {
+    pub window: TimeWindow,
+    /// `None` lifts the limit; 0 holds transfers until the window ends
+    pub limit_mbps: Option<f64>,
+}
+
+/// A daily span of local time, written as `"22:00-06:00"` in the config
+///
+/// The start is inclusive and the end exclusive; a window whose end is
+/// before its start runs past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl TimeWindow {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl TryFrom<String> for TimeWindow {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        let (start, end) = value
+            .split_once('-')
+            .ok_or_else(|| format!("expected HH:MM-HH:MM, got {:?}", value))?;
+        let parse = |s: &str| {
+            NaiveTime::parse_from_str(s.trim(), "%H:%M")
+                .map_err(|e| format!("invalid time {:?} in {:?}: {}", s.trim(), value, e))
+        };
+        Ok(Self {
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+}
+
+impl From<TimeWindow> for String {
+    fn from(window: TimeWindow) -> Self {
+        format!(
+            "{:02}:{:02}-{:02}:{:02}",
+            window.start.hour(),
+            window.start.minute(),
+            window.end.hour(),
+            window.end.minute()
+        )
+    }
+}
+
+/// Token bucket holding up to one second of traffic
+///
+/// Sending more than is available puts the bucket in debt, and the caller
+/// waits for the debt to be paid back before sending again.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl Default for TokenBucket {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TokenBucket {
+    /// A full bucket: the first second's worth goes without waiting
+    pub fn new() -> Self {
+        Self {
+            // Capped to the rate on the first take, whatever the rate is
+            tokens: f64::INFINITY,
+            last: Instant::now(),
+        }
+    }
+
+    /// Take `bytes` at `rate` bytes per second, returning how long to wait
+    pub fn take(&mut self, bytes: usize, rate: f64, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * rate).min(rate) - bytes as f64;
+
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+}
+
+/// Limits and the pause switch for all sessions
+///
+/// Each session keeps its own `TokenBucket` for the per-console limit and
+/// shares the overall one through this.
+#[derive(Debug, Default)]
+pub struct Bandwidth {
+    config: Mutex<BandwidthConfig>,
+    overall: Mutex<TokenBucket>,
+    paused: AtomicBool,
+}
+
+impl Bandwidth {
+    pub fn new(config: BandwidthConfig) -> Self {
+        Self {
+            config: Mutex::new(config),
+            ..Default::default()
+        }
+    }
+
+    pub fn config(&self) -> BandwidthConfig {
+        self.config.lock().map(|c| c.clone()).unwrap_or_default()
+    }
+
+    /// Change the limits; sessions pick them up on their next transfer
+    pub fn set_config(&self, config: BandwidthConfig) {
+        if let Ok(mut current) = self.config.lock() {
+            *current = config;
+        }
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// The overall limit in effect now
+    pub fn current_limit(&self) -> Option<f64> {
+        self.config().limit_at(Local::now().time())
+    }
+
+    /// Paused, or inside a schedule window that allows nothing
+    pub fn is_held(&self) -> bool {
+        self.is_paused() || self.current_limit().is_some_and(|limit| limit <= 0.0)
+    }
+
+    /// Account for `bytes` sent by one console, returning how long it should wait
+    pub fn consume(&self, device: &mut TokenBucket, bytes: usize) -> Duration {
+        let config = self.config();
+        let now = Instant::now();
+
+        let device_wait = match config.device_limit_mbps {
+            Some(limit) if limit > 0.0 => device.take(bytes, limit * BYTES_PER_MB, now),
+            _ => Duration::ZERO,
+        };
+        let overall_wait = match config.limit_at(Local::now().time()) {
+            Some(limit) if limit > 0.0 => self
+                .overall
+                .lock()
+                .map(|mut bucket| bucket.take(bytes, limit * BYTES_PER_MB, now))
+                .unwrap_or_default(),
+            _ => Duration::ZERO,
+        };
+        device_wait.max(overall_wait)
+    }
+}
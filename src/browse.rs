@@ -0,0 +1,184 @@
+/// Remote browsing of exposed PC directories
+use anyhow::Result;
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum BrowseError {
+    #[error("browsing is disabled (no roots configured)")]
+    Disabled,
+    #[error("path is outside the exposed roots: {0}")]
+    NotAllowed(String),
+    #[error("path not found: {0}")]
+    NotFound(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct BrowseEntry {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BrowsePage {
+    pub total: usize,
+    pub entries: Vec<BrowseEntry>,
+}
+
+impl BrowsePage {
+    /// Text payload sent to the Switch:
+    /// first line is the total entry count, then `D|name|0` or `F|name|size` per entry
+    pub fn to_payload(&self) -> String {
+        let mut payload = format!("{}\n", self.total);
+        for entry in &self.entries {
+            payload.push_str(if entry.is_dir { "D|" } else { "F|" });
+            payload.push_str(&entry.name);
+            payload.push('|');
+            payload.push_str(&entry.size.to_string());
+            payload.push('\n');
+        }
+        payload
+    }
+}
+
+/// Directories exposed to the console, addressed as `/<root name>/sub/dir`
+#[derive(Debug, Clone, Default)]
+pub struct BrowseRoots {
+    roots: Vec<(String, PathBuf)>,
+}
+
+impl BrowseRoots {
+    pub fn new(dirs: &[PathBuf]) -> Self {
+        let mut roots: Vec<(String, PathBuf)> = Vec::new();
+
+        for dir in dirs {
+            let base = dir
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("root")
+                .to_string();
+
+            // Two roots with the same folder name get a numeric suffix
+            let mut name = base.clone();
+            let mut i = 2;
+            while roots.iter().any(|(n, _)| *n == name) {
+                name = format!("{} ({})", base, i);
+                i += 1;
+            }
+
+            roots.push((name, dir.clone()));
+        }
+
+        Self { roots }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+
+    /// Map a virtual path to a real path inside one of the roots
+    pub fn resolve(&self, virtual_path: &str) -> Result<PathBuf> {
+        if self.is_empty() {
+            return Err(BrowseError::Disabled.into());
+        }
+
+        let mut parts = Vec::new();
+        for component in Path::new(virtual_path).components() {
+            match component {
+                Component::RootDir | Component::CurDir => {}
+                Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+                _ => return Err(BrowseError::NotAllowed(virtual_path.to_string()).into()),
+            }
+        }
+
+        let Some((root_name, rest)) = parts.split_first() else {
+            return Err(BrowseError::NotAllowed(virtual_path.to_string()).into());
+        };
+        let root = self
+            .roots
+            .iter()
+            .find(|(name, _)| name == root_name)
+            .map(|(_, path)| path)
+            .ok_or_else(|| BrowseError::NotFound(virtual_path.to_string()))?;
+
+        // Hidden entries aren't listed, so they can't be opened by name either
+        if rest.iter().any(|part| is_hidden(part)) {
+            return Err(BrowseError::NotAllowed(virtual_path.to_string()).into());
+        }
+
+        let path = rest.iter().fold(root.clone(), |path, part| path.join(part));
+
+        // Symlinks must not lead out of the root
+        let canonical_root = root
+            .canonicalize()
+            .map_err(|_| BrowseError::NotFound(virtual_path.to_string()))?;
+        let canonical = path
+            .canonicalize()
+            .map_err(|_| BrowseError::NotFound(virtual_path.to_string()))?;
+        let Ok(inside) = canonical.strip_prefix(&canonical_root) else {
+            return Err(BrowseError::NotAllowed(virtual_path.to_string()).into());
+        };
+        if inside.iter().any(|part| is_hidden(&part.to_string_lossy())) {
+            return Err(BrowseError::NotAllowed(virtual_path.to_string()).into());
+        }
+
+        Ok(canonical)
+    }
+
+    /// List one page of a directory; `/` lists the roots themselves
+    pub fn list(&self, virtual_path: &str, offset: usize, limit: usize) -> Result<BrowsePage> {
+        if self.is_empty() {
+            return Err(BrowseError::Disabled.into());
+        }
+
+        let mut entries = if virtual_path.trim_matches('/').is_empty() {
+            self.roots
+                .iter()
+                .map(|(name, _)| BrowseEntry {
+                    name: name.clone(),
+                    is_dir: true,
+                    size: 0,
+                })
+                .collect()
+        } else {
+            let dir = self.resolve(virtual_path)?;
+            if !dir.is_dir() {
+                return Err(BrowseError::NotFound(virtual_path.to_string()).into());
+            }
+
+            let mut entries = Vec::new();
+            for entry in std::fs::read_dir(&dir)?.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if is_hidden(&name) {
+                    continue;
+                }
+                let Ok(metadata) = std::fs::metadata(entry.path()) else {
+                    continue;
+                };
+                entries.push(BrowseEntry {
+                    name,
+                    is_dir: metadata.is_dir(),
+                    size: if metadata.is_dir() { 0 } else { metadata.len() },
+                });
+            }
+            entries
+        };
+
+        // Folders first, then case-insensitive by name
+        entries.sort_by(|a, b| {
+            b.is_dir
+                .cmp(&a.is_dir)
+                .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+        });
+
+        let total = entries.len();
+        let entries = entries.into_iter().skip(offset).take(limit).collect();
+
+        Ok(BrowsePage { total, entries })
+    }
+}
+
+fn is_hidden(name: &str) -> bool {
+    name.starts_with('.')
+}
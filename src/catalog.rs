@@ -0,0 +1,146 @@
+/// Structured catalog served to newer clients
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use crate::database::Database;
+use crate::title::{self, ContentType};
+
+pub const CATALOG_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Grouping {
+    /// By library root, or by folder for files outside every root
+    #[default]
+    Root,
+    Tag,
+    Favorites,
+    /// Base game, update or DLC
+    ContentType,
+}
+
+impl Grouping {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            1 => Self::Tag,
+            2 => Self::Favorites,
+            3 => Self::ContentType,
+            _ => Self::Root,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogEntry {
+    /// Library ID, or 0 for files that aren't in the library
+    pub id: i64,
+    /// Name to request with FILE_RANGE
+    pub name: String,
+    pub display_name: String,
+    pub size: u64,
+    pub hash: Option<String>,
+    pub title_id: Option<String>,
+    pub content_type: ContentType,
+    pub favorite: bool,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogGroup {
+    pub name: String,
+    pub entries: Vec<CatalogEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Catalog {
+    pub version: u32,
+    pub grouping: Grouping,
+    pub groups: Vec<CatalogGroup>,
+}
+
+impl Catalog {
+    /// Build a catalog of the served files, enriched with library metadata when available
+    ///
+    /// With `tag`, only files carrying that tag (ignoring case) are listed.
+    pub fn build(
+        file_list: &HashMap<String, PathBuf>,
+        db: Option<&Database>,
+        grouping: Grouping,
+        tag: Option<&str>,
+    ) -> Self {
+        let mut groups: BTreeMap<String, Vec<CatalogEntry>> = BTreeMap::new();
+        let roots: HashMap<i64, PathBuf> = match (grouping, db) {
+            (Grouping::Root, Some(db)) => db
+                .get_roots()
+                .map(|roots| roots.into_iter().map(|root| (root.id, root.path)).collect())
+                .unwrap_or_default(),
+            _ => HashMap::new(),
+        };
+
+        for (name, path) in file_list {
+            let record = db.and_then(|db| db.get_file_by_path(path).ok().flatten());
+            let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+
+            let tags = record.as_ref().map(|r| r.tags.clone()).unwrap_or_default();
+            if tag.is_some_and(|wanted| !tags.iter().any(|t| t.eq_ignore_ascii_case(wanted.trim()))) {
+                continue;
+            }
+
+            let entry = CatalogEntry {
+                id: record.as_ref().map(|r| r.id).unwrap_or(0),
+                name: name.clone(),
+                display_name: record
+                    .as_ref()
+                    .map_or_else(|| title::display_name(name), |r| r.display_name.clone()),
+                size,
+                hash: record.as_ref().and_then(|r| r.hash.clone()),
+                title_id: title::parse_title_id(name).map(|id| format!("{:016X}", id)),
+                content_type: title::content_type(name),
+                favorite: record.as_ref().is_some_and(|r| r.favorite),
+                tags,
+            };
+
+            let root = record.as_ref().and_then(|r| r.root_id).and_then(|id| roots.get(&id));
+            let group_names = match grouping {
+                Grouping::Root => vec![match root {
+                    Some(root) => root.to_string_lossy().to_string(),
+                    None => path
+                        .parent()
+                        .and_then(|p| p.file_name())
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or_else(|| "/".to_string()),
+                }],
+                Grouping::Tag if entry.tags.is_empty() => vec!["Untagged".to_string()],
+                Grouping::Tag => entry.tags.clone(),
+                Grouping::Favorites if entry.favorite => vec!["Favorites".to_string()],
+                Grouping::Favorites => vec!["Other".to_string()],
+                Grouping::ContentType => vec![entry.content_type.label().to_string()],
+            };
+
+            for group in group_names {
+                groups.entry(group).or_default().push(entry.clone());
+            }
+        }
+
+        let groups = groups
+            .into_iter()
+            .map(|(name, mut entries)| {
+                // Base game and update share a display name; the file name keeps their order stable
+                entries.sort_by(|a, b| {
+                    a.display_name
+                        .to_lowercase()
+                        .cmp(&b.display_name.to_lowercase())
+                        .then_with(|| a.name.cmp(&b.name))
+                });
+                CatalogGroup { name, entries }
+            })
+            .collect();
+
+        Self {
+            version: CATALOG_VERSION,
+            grouping,
+            groups,
+        }
+    }
+}
@@ -1,9 +1,23 @@
 /// CLI version for SwitchLink - No GUI dependencies
+use anyhow::anyhow;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::io::{self, Write};
 
-pub fn run_cli() {
+use crate::backup::{self, ImportOptions, LibraryExport, MatchBy, PathRewrite};
+use crate::collection::ServedList;
+use crate::config::Config;
+use crate::control;
+use crate::database::{Database, GameFile};
+use crate::devices::DeviceManager;
+use crate::keys::KeySet;
+use crate::file_manager::format_timestamp;
+use crate::titledb;
+use crate::trash;
+use crate::verify;
+use crate::volumes;
+
+pub fn run_cli(config: Config) {
     println!(" SwitchLink - Rust Edition (CLI Mode)");
     println!("========================================");
     println!();
@@ -16,8 +30,10 @@ pub fn run_cli() {
         println!("2.  Add files");
         println!("3.  List files");
         println!("4.  Clear list");
-        println!("5.  Start server (demo)");
-        println!("6.  Exit");
+        println!("5.  Start server");
+        println!("6.  Load collection");
+        println!("7.  Save list as collection");
+        println!("8.  Exit");
         print!("\nChoose option: ");
 
         io::stdout().flush().unwrap();
@@ -82,42 +98,718 @@ pub fn run_cli() {
                 println!(" Cleared {} files", count);
             }
             "5" => {
-                println!("\n Starting SwitchLink Server Demo...");
-                println!(" Files in queue: {}", file_list.len());
-                println!();
-                println!(" Instructions:");
-                println!("  1. Connect Nintendo Switch via USB");
-                println!("  2. Launch SwitchLink Client on your Switch");
-                println!("  3. Select 'Install title from SwitchLink'");
-                println!();
-                println!(" Server demo mode - Press Enter to stop...");
-                let mut input = String::new();
-                io::stdin().read_line(&mut input).unwrap();
-                println!(" Server stopped");
+                if file_list.is_empty() {
+                    println!(" Please add files first");
+                    continue;
+                }
+                run_server(&config, &file_list);
             }
             "6" => {
+                let Some(db) = open_database(&config) else {
+                    continue;
+                };
+                match db.get_collections() {
+                    Ok(collections) if collections.is_empty() => {
+                        println!(" No collections saved yet");
+                        continue;
+                    }
+                    Ok(collections) => {
+                        for collection in collections {
+                            println!("  • {} ({} files)", collection.name, collection.item_count);
+                        }
+                    }
+                    Err(e) => {
+                        println!(" Database error: {}", e);
+                        continue;
+                    }
+                }
+                let name = prompt("Collection name: ");
+                match ServedList::load(&db, &name) {
+                    Ok(list) => {
+                        report_skipped(&list);
+                        println!(" Loaded {} files from {}", list.files.len(), name);
+                        file_list = list.files;
+                    }
+                    Err(e) => println!(" {}", e),
+                }
+            }
+            "7" => {
+                if file_list.is_empty() {
+                    println!(" Please add files first");
+                    continue;
+                }
+                let Some(db) = open_database(&config) else {
+                    continue;
+                };
+                let name = prompt("Collection name: ");
+                let mut paths: Vec<PathBuf> = file_list.values().cloned().collect();
+                paths.sort();
+                match db.create_collection(&name, &paths) {
+                    Ok(_) => println!(" Saved {} files as {}", paths.len(), name),
+                    Err(e) => println!(" {}", e),
+                }
+            }
+            "8" => {
                 println!(" Goodbye!");
                 break;
             }
             _ => {
-                println!(" Invalid choice. Please select 1-6.");
+                println!(" Invalid choice. Please select 1-8.");
             }
         }
     }
 }
 
+/// `serve --collection NAME`: serve a saved collection without the menu
+pub fn run_serve(config: Config, args: &[String]) {
+    let name = args.iter().enumerate().find_map(|(i, arg)| match arg.split_once('=') {
+        Some(("--collection", name)) => Some(name.to_string()),
+        _ if arg == "--collection" => args.get(i + 1).cloned(),
+        _ => None,
+    });
+
+    let Some(db) = open_database(&config) else {
+        std::process::exit(1);
+    };
+    let Some(name) = name else {
+        eprintln!("Usage: switchlink serve --collection NAME");
+        if let Ok(collections) = db.get_collections() {
+            for collection in collections {
+                eprintln!("  {} ({} files)", collection.name, collection.item_count);
+            }
+        }
+        std::process::exit(2);
+    };
+
+    let list = match ServedList::load(&db, &name) {
+        Ok(list) => list,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    report_skipped(&list);
+    if list.files.is_empty() {
+        eprintln!("Error: collection {:?} has no files to serve", name);
+        std::process::exit(1);
+    }
+
+    println!(" Serving collection {}", name);
+    run_server(&config, &list.files);
+}
+
+/// `import-titles [PATH]`: import a titledb JSON/CSV dump, or re-import the last one
+pub fn run_import_titles(config: Config, path: Option<PathBuf>) {
+    let Some(path) = path.or_else(|| config.titledb_path.clone()) else {
+        eprintln!("Usage: switchlink import-titles PATH (a titledb .json or .csv dump)");
+        std::process::exit(2);
+    };
+    let Some(db) = open_database(&config) else {
+        std::process::exit(1);
+    };
+
+    match titledb::import_file(&db, &path) {
+        Ok(summary) => {
+            println!(" Imported {} titles from {}", summary.imported, path.display());
+            if summary.skipped > 0 {
+                println!(" Skipped {} entries without a title ID", summary.skipped);
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    // Remember the dump so running without PATH refreshes it
+    let mut saved = Config::load();
+    saved.titledb_path = Some(path);
+    if let Err(e) = saved.save() {
+        eprintln!("Warning: could not save config: {}", e);
+    }
+}
+
+/// `read-icons [KEYS]`: read names, versions and icons from control NCAs
+///
+/// Files that failed before are retried. A keys file given here is remembered.
+pub fn run_read_icons(config: Config, keys_path: Option<PathBuf>) {
+    let path = keys_path.clone().unwrap_or_else(|| config.keys_file());
+    let keys = match KeySet::load(&path) {
+        Ok(keys) => keys,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let Some(db) = open_database(&config) else {
+        std::process::exit(1);
+    };
+
+    let summary = control::extract_library(&db, &keys, &config.icon_cache_dir, true, |done, total| {
+        print!("\r Reading control data {}/{}", done, total);
+        io::stdout().flush().ok();
+    });
+    println!();
+    match summary {
+        Ok(summary) => {
+            println!(" Read names and icons of {} files", summary.extracted);
+            if summary.without_control > 0 {
+                println!(" {} files have no control data (DLC or compressed)", summary.without_control);
+            }
+            for (error, count) in &summary.key_errors {
+                println!(" {} ({} files)", error, count);
+            }
+            for (filename, error) in &summary.failed {
+                println!(" {}: {}", filename, error);
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(path) = keys_path {
+        let mut saved = Config::load();
+        saved.keys_path = Some(path);
+        if let Err(e) = saved.save() {
+            eprintln!("Warning: could not save config: {}", e);
+        }
+    }
+}
+
+/// `verify [--all] [KEYS]`: check new and changed files against their hashes and signatures
+///
+/// `--all` re-checks every file. Exits with status 1 if any file failed.
+pub fn run_verify(config: Config, args: &[String]) {
+    let all = args.iter().any(|arg| arg == "--all");
+    let keys_path = args.iter().find(|arg| !arg.starts_with("--")).map(PathBuf::from);
+    let keys = match KeySet::load(&keys_path.clone().unwrap_or_else(|| config.keys_file())) {
+        Ok(keys) => keys,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let Some(db) = open_database(&config) else {
+        std::process::exit(1);
+    };
+
+    let summary = verify::verify_library(&db, &keys, all, |done, total| {
+        print!("\r Verifying {}/{}", done, total);
+        io::stdout().flush().ok();
+    });
+    println!();
+    let summary = match summary {
+        Ok(summary) => summary,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    println!(" {} files verified", summary.verified);
+    for (filename, checks) in &summary.failed {
+        println!(" FAILED {}", filename);
+        for check in checks {
+            println!("   {} {}: {}", check.nca, check.check, check.detail);
+        }
+    }
+    for (error, count) in &summary.key_errors {
+        println!(" {} ({} files not checked)", error, count);
+    }
+    for (filename, error) in &summary.errors {
+        println!(" {}: {}", filename, error);
+    }
+
+    if let Some(path) = keys_path {
+        let mut saved = Config::load();
+        saved.keys_path = Some(path);
+        if let Err(e) = saved.save() {
+            eprintln!("Warning: could not save config: {}", e);
+        }
+    }
+    if !summary.failed.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// `export-library PATH [--hash]`: write the library as JSON, or CSV for a `.csv` path
+pub fn run_export_library(config: Config, args: &[String]) {
+    let Some(path) = args.iter().find(|arg| !arg.starts_with("--")).map(PathBuf::from) else {
+        eprintln!("Usage: switchlink export-library PATH [--hash]");
+        std::process::exit(2);
+    };
+    let Some(db) = open_database(&config) else {
+        std::process::exit(1);
+    };
+
+    // Hashes let the other side match files that moved
+    if args.iter().any(|arg| arg == "--hash") {
+        let hashed = backup::hash_unhashed(&db, |done, total| {
+            print!("\r Hashing {}/{}", done, total);
+            io::stdout().flush().ok();
+        });
+        println!();
+        if let Err(e) = hashed {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    match LibraryExport::from_db(&db).and_then(|export| export.write(&path).map(|_| export)) {
+        Ok(export) => println!(" Exported {} files to {}", export.files.len(), path.display()),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `import-library PATH [--match path|hash|title] [--rewrite FROM=TO]... [--add-missing]`
+pub fn run_import_library(config: Config, args: &[String]) {
+    let usage = "Usage: switchlink import-library PATH [--match path|hash|title] [--rewrite FROM=TO]... [--add-missing]";
+    let mut options = ImportOptions::default();
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let parsed = match flag {
+            "--match" => inline_value
+                .or_else(|| args.next().cloned())
+                .ok_or_else(|| anyhow!("--match needs a value"))
+                .and_then(|value| MatchBy::parse(&value))
+                .map(|match_by| options.match_by = match_by),
+            "--rewrite" => inline_value
+                .or_else(|| args.next().cloned())
+                .ok_or_else(|| anyhow!("--rewrite needs FROM=TO"))
+                .and_then(|value| PathRewrite::parse(&value))
+                .map(|rewrite| options.rewrites.push(rewrite)),
+            "--add-missing" => {
+                options.add_missing = true;
+                Ok(())
+            }
+            _ if !arg.starts_with("--") && path.is_none() => {
+                path = Some(PathBuf::from(arg));
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = parsed {
+            eprintln!("Error: {}\n{}", e, usage);
+            std::process::exit(2);
+        }
+    }
+    let Some(path) = path else {
+        eprintln!("{}", usage);
+        std::process::exit(2);
+    };
+    let Some(db) = open_database(&config) else {
+        std::process::exit(1);
+    };
+
+    let summary = LibraryExport::read(&path).and_then(|export| backup::import_library(&db, &export, &options));
+    match summary {
+        Ok(summary) => {
+            println!(" Matched {} files by {}, added {}", summary.matched, options.match_by.as_str(), summary.added);
+            println!(
+                " Collections: {} created, {} merged",
+                summary.collections_created, summary.collections_merged
+            );
+            if !summary.unmatched.is_empty() {
+                println!(" {} files matched nothing:", summary.unmatched.len());
+                for path in &summary.unmatched {
+                    println!("   {}", path);
+                }
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `backup [--restore FILE]`: back up the database now, or replace it with a backup
+pub fn run_backup(config: Config, args: &[String]) {
+    let restore = args.iter().enumerate().find_map(|(i, arg)| match arg.split_once('=') {
+        Some(("--restore", file)) => Some(PathBuf::from(file)),
+        _ if arg == "--restore" => args.get(i + 1).map(PathBuf::from),
+        _ => None,
+    });
+
+    if let Some(file) = restore {
+        match backup::restore_database(&config.db_path, &file, &config.backup.dir) {
+            Ok(previous) => {
+                if let Some(previous) = previous {
+                    println!(" Saved the replaced library as {}", previous.display());
+                }
+                println!(" Restored {}", file.display());
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let Some(db) = open_database(&config) else {
+        std::process::exit(1);
+    };
+    match backup::backup_database(&db, &config.backup.dir) {
+        Ok(path) => println!(" Backed up to {}", path.display()),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+    if let Err(e) = backup::prune_backups(&config.backup.dir, config.backup.keep) {
+        eprintln!("Warning: could not remove old backups: {}", e);
+    }
+    for path in backup::list_backups(&config.backup.dir).unwrap_or_default() {
+        println!("   {}", path.display());
+    }
+}
+
+/// `roots [add PATH | remove PATH]`: list library roots and their drives, or change them
+pub fn run_roots(config: Config, args: &[String]) {
+    let Some(db) = open_database(&config) else {
+        std::process::exit(1);
+    };
+    let mounts = volumes::current_mounts();
+
+    let result = match (args.first().map(String::as_str), args.get(1)) {
+        (Some("add"), Some(path)) => std::path::absolute(path).map_err(Into::into).and_then(|path| {
+            volumes::add_root(&db, &path, &mounts)?;
+            let added = db.add_directory(&path, &["nsp", "nsz", "xci", "xcz"])?;
+            println!(" Added {} as a library root ({} files)", path.display(), added);
+            Ok(())
+        }),
+        (Some("remove"), Some(path)) => {
+            match db.get_roots().map(|roots| roots.into_iter().find(|root| root.path == Path::new(path))) {
+                Ok(Some(root)) => db.remove_root(root.id),
+                Ok(None) => Err(anyhow!("{} is not a library root", path)),
+                Err(e) => Err(e),
+            }
+        }
+        (None, _) => Ok(()),
+        _ => {
+            eprintln!("Usage: switchlink roots [add PATH | remove PATH]");
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+
+    report_roots(&db, &mounts);
+    for root in db.get_roots().unwrap_or_default() {
+        println!(
+            "  {} {} ({} files{})",
+            if root.online { "●" } else { "○ offline" },
+            root.path.display(),
+            root.file_count,
+            root.volume_id.map(|id| format!(", {}", id)).unwrap_or_default()
+        );
+    }
+}
+
+/// Follow roots to new mount points and say which are offline
+fn report_roots(db: &Database, mounts: &[volumes::Mount]) {
+    match volumes::resolve_roots(db, mounts) {
+        Ok(check) => {
+            for (root, path) in check.relocated {
+                println!(" {} is now at {}", root.path.display(), path.display());
+            }
+            for root in check.went_offline {
+                println!(" {} is offline; its files are kept", root.path.display());
+            }
+        }
+        Err(e) => println!(" Could not check library roots: {}", e),
+    }
+}
+
+/// `trash [restore|quarantine|delete ID... [--yes]]`: list the trash, or act on files in it
+///
+/// Deleting removes files from disk for good, so it only lists them unless `--yes` is given.
+pub fn run_trash(config: Config, args: &[String]) {
+    let Some(db) = open_database(&config) else {
+        std::process::exit(1);
+    };
+    let trash = db.get_trash().unwrap_or_default();
+    let confirmed = args.iter().any(|arg| arg == "--yes");
+    let ids: Vec<i64> = args.iter().skip(1).filter(|arg| *arg != "--yes").filter_map(|arg| arg.parse().ok()).collect();
+    let picked: Vec<GameFile> = trash.iter().filter(|file| ids.contains(&file.id)).cloned().collect();
+
+    let result = match args.first().map(String::as_str) {
+        Some(_) if picked.len() != ids.len() || ids.is_empty() => {
+            Err(anyhow!("Give the IDs of trashed files, as listed by `switchlink trash`"))
+        }
+        Some("restore") => picked.iter().try_for_each(|file| {
+            trash::restore(&db, file)?;
+            println!(" Restored {}", file.path);
+            Ok(())
+        }),
+        Some("quarantine") => trash::quarantine(&db, &picked, &config.trash_dir).map(|moved| {
+            println!(" Moved {} files to {}", moved.len(), config.trash_dir.display());
+        }),
+        Some("delete") if !confirmed => {
+            for file in &picked {
+                println!(" Would delete {}", file.quarantined_path.as_deref().unwrap_or(&file.path));
+            }
+            println!(" Nothing deleted; add --yes to delete these files from disk for good");
+            Ok(())
+        }
+        Some("delete") => trash::delete_files(&db, &picked).map(|deleted| {
+            println!(" Deleted {} files from disk", deleted);
+        }),
+        None => {
+            if trash.is_empty() {
+                println!(" The trash is empty");
+            }
+            for file in &trash {
+                println!(
+                    "  {:>5}  {}  {}{}",
+                    file.id,
+                    format_timestamp(file.deleted_at.unwrap_or_default()),
+                    file.path,
+                    file.quarantined_path.as_ref().map(|path| format!(" (in {})", path)).unwrap_or_default()
+                );
+            }
+            Ok(())
+        }
+        _ => {
+            eprintln!("Usage: switchlink trash [restore|quarantine|delete ID... [--yes]]");
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// `cleanup [--apply]`: list library files missing from disk, and with `--apply` move them to the trash
+pub fn run_cleanup(config: Config, args: &[String]) {
+    let Some(db) = open_database(&config) else {
+        std::process::exit(1);
+    };
+    report_roots(&db, &volumes::current_mounts());
+    let missing = match db.missing_files() {
+        Ok(missing) => missing,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let unrooted = db.unrooted_paths().unwrap_or_default();
+    for file in &missing {
+        let note = if unrooted.contains(Path::new(&file.path)) { "  (not under a library root)" } else { "" };
+        println!("  missing  {}{}", file.path, note);
+    }
+    let loose = missing.iter().filter(|file| unrooted.contains(Path::new(&file.path))).count();
+    if loose > 0 {
+        println!(
+            " Warning: {} of these aren't under a library root, so they are reported missing even if only \
+             their drive is unplugged; `switchlink roots add FOLDER` keeps them while it's away",
+            loose
+        );
+    }
+
+    if args.iter().any(|arg| arg == "--apply") {
+        let ids: Vec<i64> = missing.iter().map(|file| file.id).collect();
+        match db.trash_files(&ids) {
+            Ok(()) => println!(" Moved {} missing files to the trash; `switchlink trash` lists them", ids.len()),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        println!(" {} files are missing; run `cleanup --apply` to move them to the trash", missing.len());
+    }
+}
+
+/// `history [--limit N]`: recent installs with the console's outcome, and session events
+pub fn run_history(config: Config, args: &[String]) {
+    let limit = args.iter().enumerate().find_map(|(i, arg)| match arg.split_once('=') {
+        Some(("--limit", n)) => Some(n.to_string()),
+        _ if arg == "--limit" => args.get(i + 1).cloned(),
+        _ => None,
+    });
+    let limit = match limit.map(|n| n.parse::<usize>()) {
+        Some(Ok(n)) => n,
+        Some(Err(_)) => {
+            eprintln!("Usage: switchlink history [--limit N]");
+            std::process::exit(2);
+        }
+        None => 20,
+    };
+    let Some(db) = open_database(&config) else {
+        std::process::exit(1);
+    };
+
+    let installs = db.get_install_history(limit).unwrap_or_default();
+    println!(" Installs:");
+    if installs.is_empty() {
+        println!("   Nothing sent yet");
+    }
+    for record in &installs {
+        let outcome = match record.status.as_str() {
+            "failed" if record.phase.is_empty() => format!(" (result 0x{:X})", record.result_code),
+            "failed" => format!(" ({}, result 0x{:X})", record.phase, record.result_code),
+            _ => String::new(),
+        };
+        println!(
+            "  {}  {:<9}  {}{}",
+            format_timestamp(record.finished_at.or(record.sent_at).unwrap_or_default()),
+            record.status,
+            record.filename,
+            outcome
+        );
+    }
+
+    let events = db.get_session_events(limit).unwrap_or_default();
+    println!(" Session events:");
+    if events.is_empty() {
+        println!("   No stalls or recoveries");
+    }
+    for event in &events {
+        println!(
+            "  {}  {:<9}  {}",
+            format_timestamp(event.occurred_at),
+            event.event,
+            event.detail
+        );
+    }
+}
+
+fn open_database(config: &Config) -> Option<Database> {
+    if let Some(parent) = config.db_path.parent() {
+        std::fs::create_dir_all(parent).ok();
+    }
+    match Database::new(&config.db_path) {
+        Ok(db) => Some(db),
+        Err(e) => {
+            println!(" Could not open library: {}", e);
+            None
+        }
+    }
+}
+
+fn report_skipped(list: &ServedList) {
+    for path in &list.missing {
+        println!(" Skipping missing file: {}", path.display());
+    }
+    for path in &list.duplicates {
+        println!(" Skipping duplicate name: {}", path.display());
+    }
+}
+
+fn prompt(message: &str) -> String {
+    print!("{}", message);
+    io::stdout().flush().unwrap();
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).unwrap();
+    input.trim().to_string()
+}
+
+/// Serve the list until the user stops, with pause and limit controls
+fn run_server(config: &Config, file_list: &HashMap<String, PathBuf>) {
+    println!("\n Starting SwitchLink Server...");
+    println!(" Files in queue: {}", file_list.len());
+
+    if let Some(db) = open_database(config) {
+        report_roots(&db, &volumes::current_mounts());
+        match backup::backup_if_due(&db, &config.backup) {
+            Ok(Some(path)) => println!(" Backed up the library to {}", path.display()),
+            Ok(None) => {}
+            Err(e) => println!(" Library backup failed: {}", e),
+        }
+    }
+
+    let mut devices = DeviceManager::new(config.clone());
+    devices.set_catalog(file_list);
+    if config.refuse_failed_verification {
+        let refused = open_database(config)
+            .map(|db| verify::refused_paths(&db, true).unwrap_or_default())
+            .unwrap_or_default();
+        for path in file_list.values().filter(|path| refused.contains(*path)) {
+            println!(" Not serving {} (failed verification)", path.display());
+        }
+        devices.set_withheld(refused);
+    }
+    match devices.start() {
+        Ok(found) => println!(" Consoles found: {}", found),
+        Err(e) => {
+            println!(" USB error: {}", e);
+            return;
+        }
+    }
+
+    println!();
+    println!(" Instructions:");
+    println!("  1. Connect Nintendo Switch via USB");
+    println!("  2. Launch SwitchLink Client on your Switch");
+    println!("  3. Select 'Install title from SwitchLink'");
+    println!();
+    println!(" Commands: p = pause/resume all transfers, l <MB/s|none> = bandwidth limit, Enter = stop");
+
+    loop {
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input).unwrap_or(0) == 0 {
+            break;
+        }
+
+        let bandwidth = devices.bandwidth();
+        match input.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [] | ["q"] => break,
+            ["p"] => {
+                let paused = !bandwidth.is_paused();
+                bandwidth.set_paused(paused);
+                println!(" Transfers {}", if paused { "paused" } else { "resumed" });
+            }
+            ["l", rate] => {
+                let mut limits = bandwidth.config();
+                limits.limit_mbps = match *rate {
+                    "none" => None,
+                    rate => match rate.parse::<f64>() {
+                        Ok(rate) if rate > 0.0 => Some(rate),
+                        _ => {
+                            println!(" Expected a rate in MB/s or 'none'");
+                            continue;
+                        }
+                    },
+                };
+                match limits.limit_mbps {
+                    Some(rate) => println!(" Bandwidth limited to {:.1} MB/s", rate),
+                    None => println!(" Bandwidth limit removed"),
+                }
+                bandwidth.set_config(limits);
+            }
+            _ => println!(" Unknown command"),
+        }
+    }
+
+    devices.stop();
+    println!(" Server stopped");
+}
+
 fn add_folder_files(file_list: &mut HashMap<String, PathBuf>, folder_path: PathBuf) -> usize {
     let mut count = 0;
     if let Ok(entries) = std::fs::read_dir(folder_path) {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if path.is_file() {
-                    if let Some(file_name) = path.file_name() {
-                        if let Some(name_str) = file_name.to_str() {
-                            file_list.insert(name_str.to_string(), path);
-                            count += 1;
-                        }
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_file() {
+                if let Some(file_name) = path.file_name() {
+                    if let Some(name_str) = file_name.to_str() {
+                        file_list.insert(name_str.to_string(), path);
+                        count += 1;
                     }
                 }
             }
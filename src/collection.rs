@@ -0,0 +1,119 @@
+/// Saved collections: resolving them to a served list and exporting them
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::database::Database;
+
+pub const EXPORT_VERSION: u32 = 1;
+
+/// A collection ready to serve
+#[derive(Debug, Clone, Default)]
+pub struct ServedList {
+    /// Served name -> path, as used for the queue
+    pub files: HashMap<String, PathBuf>,
+    /// Files that no longer exist
+    pub missing: Vec<PathBuf>,
+    /// Files left out because an earlier one has the same name
+    pub duplicates: Vec<PathBuf>,
+}
+
+impl ServedList {
+    /// Keep the files that exist, named by filename like the queue is
+    pub fn resolve(paths: &[PathBuf]) -> Self {
+        let mut list = Self::default();
+        for path in paths {
+            let Some(name) = path.file_name().map(|n| n.to_string_lossy().to_string()) else {
+                list.missing.push(path.clone());
+                continue;
+            };
+            if !path.is_file() {
+                list.missing.push(path.clone());
+                continue;
+            }
+            match list.files.entry(name) {
+                Entry::Occupied(_) => list.duplicates.push(path.clone()),
+                Entry::Vacant(entry) => {
+                    entry.insert(path.clone());
+                }
+            }
+        }
+        list
+    }
+
+    /// Load a collection from the library by name
+    pub fn load(db: &Database, name: &str) -> Result<Self> {
+        Ok(Self::resolve(&db.get_collection_items(name)?))
+    }
+}
+
+/// A collection written out as JSON, for sharing or keeping outside the library
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionExport {
+    pub version: u32,
+    pub name: String,
+    pub exported_at: i64,
+    pub items: Vec<ExportedItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedItem {
+    pub filename: String,
+    pub path: String,
+    /// `None` if the file was missing at export time
+    pub size: Option<u64>,
+    /// From the library, if the file is in it and has been hashed
+    pub hash: Option<String>,
+}
+
+impl CollectionExport {
+    pub fn from_db(db: &Database, name: &str) -> Result<Self> {
+        let paths = db.get_collection_items(name)?;
+        let name = db
+            .get_collections()?
+            .into_iter()
+            .find(|c| c.name.eq_ignore_ascii_case(name.trim()))
+            .map_or_else(|| name.to_string(), |c| c.name);
+
+        let items = paths
+            .iter()
+            .map(|path| ExportedItem {
+                filename: path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                path: path.to_string_lossy().to_string(),
+                size: std::fs::metadata(path).ok().map(|m| m.len()),
+                hash: db.get_file_by_path(path).ok().flatten().and_then(|f| f.hash),
+            })
+            .collect();
+
+        Ok(Self {
+            version: EXPORT_VERSION,
+            name,
+            exported_at: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or(0),
+            items,
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// A filename for the export, e.g. `Family console.collection.json`
+    pub fn default_filename(&self) -> String {
+        let stem: String = self
+            .name
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == ' ' || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        format!("{}.collection.json", stem.trim())
+    }
+}
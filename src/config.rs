@@ -0,0 +1,243 @@
+/// Persistent application settings
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tracing::warn;
+
+use crate::backup::BackupConfig;
+use crate::bandwidth::{BandwidthConfig, ScheduleRule, TimeWindow};
+use crate::saves::RetentionPolicy;
+use crate::usb::{check_transfer_size, DeviceProfile, TransferPolicy};
+
+/// Base directory for SwitchLink data (database, inbox, caches)
+pub fn data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("switchlink")
+}
+
+/// Location of the settings file
+pub fn config_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("switchlink")
+        .join("config.json")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Library database
+    pub db_path: PathBuf,
+    /// Where files uploaded from the Switch are stored
+    pub inbox_dir: PathBuf,
+    /// Save backup library
+    pub saves_dir: PathBuf,
+    /// Quarantine folder: where trashed library files are moved on disk
+    pub trash_dir: PathBuf,
+    /// Title database dump last imported, for refreshing
+    pub titledb_path: Option<PathBuf>,
+    /// The user's `prod.keys`, for reading control NCAs (default `~/.switch/prod.keys`)
+    pub keys_path: Option<PathBuf>,
+    /// Icons extracted from control NCAs
+    pub icon_cache_dir: PathBuf,
+    /// Don't serve files whose last verification failed
+    pub refuse_failed_verification: bool,
+    pub save_retention: RetentionPolicy,
+    /// PC directories the Switch may browse (empty disables BROWSE)
+    pub browse_roots: Vec<PathBuf>,
+    /// Seconds of client silence before a session counts as stalled
+    pub stall_timeout_secs: u64,
+    /// Which USB devices to serve and how to open them
+    pub usb: DeviceProfile,
+    /// USB timeouts and retries
+    pub transfer: TransferPolicy,
+    /// Throttling of file data
+    pub bandwidth: BandwidthConfig,
+    /// Automatic database backups
+    pub backup: BackupConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let data_dir = data_dir();
+        Self {
+            db_path: data_dir.join("games.db"),
+            inbox_dir: data_dir.join("inbox"),
+            saves_dir: data_dir.join("saves"),
+            trash_dir: data_dir.join("trash"),
+            titledb_path: None,
+            keys_path: None,
+            icon_cache_dir: data_dir.join("icons"),
+            refuse_failed_verification: false,
+            save_retention: RetentionPolicy::default(),
+            browse_roots: Vec::new(),
+            stall_timeout_secs: 30,
+            usb: DeviceProfile::default(),
+            transfer: TransferPolicy::default(),
+            bandwidth: BandwidthConfig::default(),
+            backup: BackupConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Load settings, falling back to defaults if the file is missing or invalid
+    pub fn load() -> Self {
+        let path = config_path();
+        let Ok(data) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+
+        match serde_json::from_str(&data) {
+            Ok(config) => config,
+            Err(e) => {
+                warn!("Ignoring invalid config {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// The keys file to use, configured or default
+    pub fn keys_file(&self) -> PathBuf {
+        self.keys_path.clone().unwrap_or_else(crate::keys::default_path)
+    }
+    
+    /// Save settings
+    pub fn save(&self) -> Result<()> {
+        let path = config_path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Apply command-line overrides such as `--data-timeout-ms 20000`
+    ///
+    /// Flags this doesn't know (e.g. `--cli`) are left to the caller.
+    pub fn apply_args(&mut self, args: &[String]) -> Result<()> {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag, Some(value.to_string())),
+                None => (arg.as_str(), None),
+            };
+
+            let target = match flag {
+                "--poll-timeout-ms" => &mut self.transfer.poll_timeout_ms,
+                "--response-timeout-ms" => &mut self.transfer.response_timeout_ms,
+                "--write-timeout-ms" => &mut self.transfer.write_timeout_ms,
+                "--data-timeout-ms" => &mut self.transfer.data_timeout_ms,
+                "--backoff-ms" => &mut self.transfer.backoff_ms,
+                "--max-backoff-ms" => &mut self.transfer.max_backoff_ms,
+                "--retries" => {
+                    self.transfer.retries = parse_count(flag, inline_value, &mut args)?;
+                    continue;
+                }
+                "--queue-depth" => {
+                    self.transfer.queue_depth = parse_count(flag, inline_value, &mut args)?;
+                    continue;
+                }
+                "--transfer-size" => {
+                    let size = parse_count(flag, inline_value, &mut args)?;
+                    self.transfer.transfer_size = check_transfer_size(size).map_err(|e| anyhow!("{}: {}", flag, e))?;
+                    continue;
+                }
+                "--limit-mbps" => {
+                    self.bandwidth.limit_mbps = parse_limit(flag, inline_value, &mut args)?;
+                    continue;
+                }
+                "--device-limit-mbps" => {
+                    self.bandwidth.device_limit_mbps = parse_limit(flag, inline_value, &mut args)?;
+                    continue;
+                }
+                "--schedule" => {
+                    let value = flag_value(flag, inline_value, &mut args)?;
+                    self.bandwidth.schedule.push(parse_schedule_rule(&value)?);
+                    continue;
+                }
+                "--mmap" => {
+                    self.transfer.mmap = true;
+                    continue;
+                }
+                "--no-mmap" => {
+                    self.transfer.mmap = false;
+                    continue;
+                }
+                "--refuse-failed" => {
+                    self.refuse_failed_verification = true;
+                    continue;
+                }
+                "--serve-failed" => {
+                    self.refuse_failed_verification = false;
+                    continue;
+                }
+                _ => continue,
+            };
+
+            let value = flag_value(flag, inline_value, &mut args)?;
+            *target = value
+                .parse()
+                .map_err(|_| anyhow!("{} expects a number of milliseconds, got {:?}", flag, value))?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_count<'a, T: std::str::FromStr>(
+    flag: &str,
+    inline_value: Option<String>,
+    args: &mut impl Iterator<Item = &'a String>,
+) -> Result<T> {
+    let value = flag_value(flag, inline_value, args)?;
+    value
+        .parse()
+        .map_err(|_| anyhow!("{} expects a number, got {:?}", flag, value))
+}
+
+/// A rate in MB/s, or `none` for no limit
+fn parse_limit<'a>(
+    flag: &str,
+    inline_value: Option<String>,
+    args: &mut impl Iterator<Item = &'a String>,
+) -> Result<Option<f64>> {
+    let value = flag_value(flag, inline_value, args)?;
+    parse_rate(&value).map_err(|_| anyhow!("{} expects MB/s or \"none\", got {:?}", flag, value))
+}
+
+/// `--schedule 09:00-17:00=2`: 2 MB/s between 9 and 5 (`=0` holds transfers, `=none` lifts the limit)
+fn parse_schedule_rule(value: &str) -> Result<ScheduleRule> {
+    let (window, rate) = value
+        .split_once('=')
+        .ok_or_else(|| anyhow!("--schedule expects HH:MM-HH:MM=MBPS, got {:?}", value))?;
+    Ok(ScheduleRule {
+        window: TimeWindow::try_from(window.to_string()).map_err(|e| anyhow!("--schedule: {}", e))?,
+        limit_mbps: parse_rate(rate)
+            .map_err(|_| anyhow!("--schedule expects MB/s or \"none\" after '=', got {:?}", rate))?,
+    })
+}
+
+fn parse_rate(value: &str) -> std::result::Result<Option<f64>, ()> {
+    match value.trim() {
+        "none" | "unlimited" => Ok(None),
+        rate => match rate.parse::<f64>() {
+            Ok(rate) if rate >= 0.0 => Ok(Some(rate)),
+            _ => Err(()),
+        },
+    }
+}
+
+fn flag_value<'a>(
+    flag: &str,
+    inline_value: Option<String>,
+    args: &mut impl Iterator<Item = &'a String>,
+) -> Result<String> {
+    match inline_value {
+        Some(value) => Ok(value),
+        None => args
+            .next()
+            .cloned()
+            .ok_or_else(|| anyhow!("{} needs a value", flag)),
+    }
+}
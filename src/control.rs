@@ -0,0 +1,194 @@
+/// Names, versions and icons read from titles' control NCAs with the user's keys
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::database::{ControlInfo, Database, LocalizedTitle};
+use crate::keys::KeySet;
+use crate::nca::{NcaContentType, NcaError, Package};
+
+/// NACP title entries and `icon_<Language>.dat` files, in NACP order
+pub const LANGUAGES: [&str; 16] = [
+    "AmericanEnglish",
+    "BritishEnglish",
+    "Japanese",
+    "French",
+    "German",
+    "LatinAmericanSpanish",
+    "Spanish",
+    "Italian",
+    "Dutch",
+    "CanadianFrench",
+    "Portuguese",
+    "Russian",
+    "Korean",
+    "TraditionalChinese",
+    "SimplifiedChinese",
+    "BrazilianPortuguese",
+];
+
+const NACP_SIZE: usize = 0x4000;
+// Icons are 256x256 JPEGs of at most 128 KiB
+const MAX_ICON_SIZE: u64 = 0x20000;
+
+/// What a title's control NCA holds
+#[derive(Debug, Clone, Default)]
+pub struct ControlData {
+    /// Program ID of the control NCA, as 16 uppercase hex digits
+    pub title_id: String,
+    pub display_version: String,
+    pub titles: Vec<LocalizedTitle>,
+    /// JPEG icon of the first language that has one
+    pub icon: Option<Vec<u8>>,
+}
+
+/// Outcome of reading control data for the library
+#[derive(Debug, Clone, Default)]
+pub struct ExtractSummary {
+    pub extracted: usize,
+    /// Files without a control NCA, such as DLC
+    pub without_control: usize,
+    /// Problems with the user's keys and how many files each affected
+    pub key_errors: BTreeMap<String, usize>,
+    /// Other failures as (filename, error)
+    pub failed: Vec<(String, String)>,
+}
+
+/// Read the control NCA of an NSP or XCI
+pub fn read_control(path: &Path, keys: &KeySet) -> Result<ControlData, NcaError> {
+    let package = Package::open(path)?;
+    let ncas = package.ncas(keys)?;
+    let nca = ncas
+        .iter()
+        .find(|nca| nca.header.content_type() == NcaContentType::Control)
+        .ok_or(NcaError::NoControl)?;
+    let section = nca
+        .header
+        .sections()
+        .into_iter()
+        .find(|section| section.fs_type == 0)
+        .ok_or_else(|| NcaError::Invalid("control NCA: no RomFS section".to_string()))?;
+
+    let files = package.romfs_files(nca, &section, keys)?;
+    let read = |name: &str, max_size: u64| -> Result<Option<Vec<u8>>, NcaError> {
+        let Some(file) = files.iter().find(|f| f.name == name) else {
+            return Ok(None);
+        };
+        if file.size > max_size {
+            return Err(NcaError::Invalid(format!("control NCA: {} is too large", name)));
+        }
+        package.read_section(nca, &section, keys, file.offset, file.size as usize).map(Some)
+    };
+
+    let nacp = read("control.nacp", NACP_SIZE as u64)?
+        .ok_or_else(|| NcaError::Invalid("control NCA: no control.nacp".to_string()))?;
+    let (titles, display_version) = parse_nacp(&nacp)?;
+
+    let mut icon = None;
+    for language in LANGUAGES {
+        icon = read(&format!("icon_{}.dat", language), MAX_ICON_SIZE)?;
+        if icon.is_some() {
+            break;
+        }
+    }
+
+    Ok(ControlData {
+        title_id: nca.header.title_id(),
+        display_version,
+        titles,
+        icon,
+    })
+}
+
+/// Localized names and the display version from a `control.nacp`
+pub fn parse_nacp(nacp: &[u8]) -> Result<(Vec<LocalizedTitle>, String), NcaError> {
+    if nacp.len() < NACP_SIZE {
+        return Err(NcaError::Invalid("control.nacp: too short".to_string()));
+    }
+
+    let titles = LANGUAGES
+        .iter()
+        .enumerate()
+        .filter_map(|(i, language)| {
+            let entry = &nacp[i * 0x300..(i + 1) * 0x300];
+            let name = c_string(&entry[..0x200]);
+            (!name.is_empty()).then(|| LocalizedTitle {
+                language: language.to_string(),
+                name,
+                publisher: c_string(&entry[0x200..]),
+            })
+        })
+        .collect();
+    Ok((titles, c_string(&nacp[0x3060..0x3070])))
+}
+
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+}
+
+/// Read control data for new and changed library files, caching icons in `icon_dir`
+///
+/// Problems with single files, including missing or wrong keys, are recorded
+/// and summarized rather than returned. Files that failed before are only
+/// retried with `retry_failed`.
+pub fn extract_library(
+    db: &Database,
+    keys: &KeySet,
+    icon_dir: &Path,
+    retry_failed: bool,
+    mut on_progress: impl FnMut(usize, usize),
+) -> Result<ExtractSummary> {
+    let files = db.files_needing_control(retry_failed)?;
+    let mut summary = ExtractSummary::default();
+
+    for (done, file) in files.iter().enumerate() {
+        on_progress(done, files.len());
+        let result = read_control(Path::new(&file.path), keys)
+            .map_err(|e| e.into())
+            .and_then(|data| save_icon(&data, icon_dir).map(|icon_path| (data, icon_path)));
+
+        match result {
+            Ok((data, icon_path)) => {
+                db.save_control_info(
+                    file.id,
+                    &ControlInfo {
+                        title_id: data.title_id,
+                        display_version: data.display_version,
+                        titles: data.titles,
+                        icon_path: icon_path.map(|p| p.to_string_lossy().to_string()),
+                    },
+                )?;
+                summary.extracted += 1;
+            }
+            Err(e) => {
+                db.save_control_error(file.id, &e.to_string())?;
+                match e.downcast_ref::<NcaError>() {
+                    Some(NcaError::NoControl) => summary.without_control += 1,
+                    Some(key_error) if key_error.is_key_error() => {
+                        *summary.key_errors.entry(key_error.to_string()).or_default() += 1;
+                    }
+                    _ => summary.failed.push((file.filename.clone(), e.to_string())),
+                }
+            }
+        }
+    }
+    on_progress(files.len(), files.len());
+
+    // Updates and DLC take their name and icon from the base game
+    if summary.extracted > 0 {
+        db.rebuild_search_index()?;
+    }
+    Ok(summary)
+}
+
+/// Write the icon as `<title ID>.jpg`
+fn save_icon(data: &ControlData, icon_dir: &Path) -> Result<Option<PathBuf>> {
+    let Some(icon) = &data.icon else {
+        return Ok(None);
+    };
+    std::fs::create_dir_all(icon_dir)?;
+    let path = icon_dir.join(format!("{}.jpg", data.title_id));
+    std::fs::write(&path, icon)?;
+    Ok(Some(path))
+}
@@ -1,9 +1,16 @@
 /// SQLite database for file management
-use anyhow::Result;
-use rusqlite::{params, Connection};
+use anyhow::{anyhow, Result};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use std::collections::{BTreeSet, HashSet};
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
+use crate::search::{edit_distance, typo_budget, SearchQuery};
+use crate::title;
+use crate::verify::{CheckStatus, VerifyStatus};
+use crate::volumes;
+
 #[derive(Debug, Clone)]
 pub struct GameFile {
     pub id: i64,
@@ -16,17 +23,312 @@ pub struct GameFile {
     pub install_count: i32,
     pub last_installed: Option<i64>,
     pub favorite: bool,
-    pub tags: String,
+    /// Tag names, alphabetical
+    pub tags: Vec<String>,
+    /// Parsed from the filename, as 16 hex digits
+    pub title_id: Option<String>,
+    pub version: Option<u32>,
+    /// Name from the title database, or the filename without tags
+    pub display_name: String,
+    /// Icon from a title override, the file's control data or the title database
+    pub icon_path: Option<String>,
+    /// Last verification result, if the file hasn't changed since it was verified
+    pub verification: Option<VerifyStatus>,
+    /// Library root the file is under, if any
+    pub root_id: Option<i64>,
+    /// On a library root whose drive or share isn't connected
+    pub offline: bool,
+    /// When the file was moved to the library trash
+    pub deleted_at: Option<i64>,
+    /// Where a trashed file was moved on disk, to put it back on restore
+    pub quarantined_path: Option<String>,
+    /// Trashed by cleanup because it was gone, so whatever is at its path now isn't it
+    pub trashed_missing: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct UploadRecord {
+    pub id: i64,
+    pub filename: String,
+    pub path: String,
+    pub category: String,
+    pub size: u64,
+    pub hash: String,
+    pub received_at: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SaveSnapshot {
+    pub id: i64,
+    pub title_id: String,
+    pub user_id: String,
+    pub path: String,
+    pub size: u64,
+    pub hash: String,
+    pub created_at: i64,
+    pub pinned: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionEvent {
+    pub id: i64,
+    pub occurred_at: i64,
+    pub event: String,
+    pub detail: String,
+}
+
+/// A file sent to the console and what the console reported back
+#[derive(Debug, Clone)]
+pub struct InstallRecord {
+    pub id: i64,
+    pub filename: String,
+    pub path: String,
+    pub content_id: String,
+    /// `sent`, `installed` or `failed`
+    pub status: String,
+    pub phase: String,
+    pub result_code: u32,
+    pub sent_at: Option<i64>,
+    pub finished_at: Option<i64>,
+}
+
+/// A tag and how many files carry it
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    /// `#RRGGBB`, or empty for the default color
+    pub color: String,
+    pub file_count: usize,
+}
+
+/// Title metadata from an imported title database, with user overrides applied
+///
+/// Also used for overrides themselves, where `None` keeps the imported value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TitleInfo {
+    /// 16 uppercase hex digits
+    pub title_id: String,
+    pub name: Option<String>,
+    pub publisher: Option<String>,
+    /// `YYYY-MM-DD`
+    pub release_date: Option<String>,
+    pub region: Option<String>,
+    pub description: Option<String>,
+    /// A local image file
+    pub icon_path: Option<String>,
+    /// At least one field comes from a user override
+    pub overridden: bool,
+}
+
+/// A title's name and publisher in one language
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalizedTitle {
+    /// NACP language name, e.g. `AmericanEnglish`
+    pub language: String,
+    pub name: String,
+    pub publisher: String,
+}
+
+/// Metadata read from a file's control NCA
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ControlInfo {
+    /// Program ID of the control NCA, as 16 uppercase hex digits
+    pub title_id: String,
+    /// Version shown on the console, e.g. `1.6.0`
+    pub display_version: String,
+    /// Languages the title is named in, in NACP order
+    pub titles: Vec<LocalizedTitle>,
+    /// Cached JPEG icon
+    pub icon_path: Option<String>,
+}
+
+/// One check made while verifying a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyCheck {
+    /// NCA the check covers, or empty for the whole file
+    pub nca: String,
+    /// e.g. `header signature`, `content hash`, `ticket` or `section 0 hashes`
+    pub check: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+/// Outcome of checking a file against its hashes and signatures
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Verification {
+    pub status: VerifyStatus,
+    pub checks: Vec<VerifyCheck>,
+    /// Why the file couldn't be checked, such as missing keys
+    pub error: Option<String>,
+    pub checked_at: i64,
+}
+
+/// A folder added to the library, and the volume it was on so it can be found after a remount
+#[derive(Debug, Clone)]
+pub struct LibraryRoot {
+    pub id: i64,
+    /// Where the root is mounted now, or was last seen
+    pub path: PathBuf,
+    /// `uuid:…`, `label:…` or `net:…`, if the volume could be identified
+    pub volume_id: Option<String>,
+    pub label: Option<String>,
+    /// The root's folder within its volume, `/`-separated
+    pub volume_path: String,
+    pub online: bool,
+    pub file_count: usize,
+}
+
+/// A named, ordered list of files that can be served together
+#[derive(Debug, Clone)]
+pub struct Collection {
+    pub id: i64,
+    pub name: String,
+    pub item_count: usize,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// One page of search results
+#[derive(Debug, Clone, Default)]
+pub struct SearchResults {
+    pub files: Vec<GameFile>,
+    /// Matches across all pages
+    pub total: usize,
+    /// Nothing matched as typed, so these are near misses
+    pub fuzzy: bool,
+}
+
+/// All snapshots of one title for one user
+#[derive(Debug, Clone)]
+pub struct SaveGroup {
+    pub title_id: String,
+    pub user_id: String,
+    pub snapshot_count: usize,
+    pub total_size: u64,
+    pub latest: i64,
+}
+
+/// What `Database::update_hashes` did
+#[derive(Debug, Clone, Default)]
+pub struct HashSummary {
+    pub hashed: usize,
+    /// Files that couldn't be read, as (path, error); they're left unhashed
+    pub failed: Vec<(String, String)>,
 }
 
 pub struct Database {
     conn: Connection,
 }
 
+/// Changes that are rolled back unless committed
+///
+/// Unlike a transaction a savepoint can be nested, so methods that need one
+/// still work inside `Database::atomically`.
+struct Savepoint<'a> {
+    conn: &'a Connection,
+    committed: bool,
+}
+
+impl Savepoint<'_> {
+    fn commit(mut self) -> Result<()> {
+        self.conn.execute_batch("RELEASE changes")?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for Savepoint<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = self.conn.execute_batch("ROLLBACK TO changes; RELEASE changes");
+        }
+    }
+}
+
+// Tags come back joined by TAG_SEPARATOR
+const FILE_COLUMNS: &str = "files.id, files.filename, files.path, files.size, files.hash,
+    files.added_at, files.last_modified, files.install_count, files.last_installed,
+    files.favorite,
+    (SELECT group_concat(name, char(31)) FROM (
+        SELECT tags.name FROM file_tags JOIN tags ON tags.id = file_tags.tag_id
+        WHERE file_tags.file_id = files.id ORDER BY tags.name COLLATE NOCASE)),
+    files.title_id, files.version, files.display_name, files.icon_path,
+    (SELECT v.status FROM verifications v WHERE v.file_id = files.id
+        AND v.size = files.size AND v.last_modified = files.last_modified),
+    COALESCE((SELECT NOT r.online FROM library_roots r WHERE r.id = files.root_id), 0),
+    files.deleted_at, files.quarantined_path, files.trashed_missing, files.root_id";
+
+const TAG_SEPARATOR: char = '\u{1f}';
+
+// Column weights for ranking: filename, display_name, title_id, publisher, tags
+const SEARCH_RANK: &str = "bm25(files_fts, 5.0, 10.0, 10.0, 2.0, 3.0)";
+
+/// The deepest of `roots` containing `path`, and the path relative to it
+fn deepest_root(roots: &[(i64, PathBuf)], path: &Path) -> Option<(i64, String)> {
+    roots
+        .iter()
+        .filter_map(|(id, root)| volumes::relative_path(root, path).map(|rel| (*id, root, rel)))
+        .max_by_key(|(_, root, _)| root.components().count())
+        .map(|(id, _, rel)| (id, rel))
+}
+
+fn table_exists(conn: &Connection, table: &str) -> Result<bool> {
+    Ok(conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        params![table],
+        |row| row.get(0),
+    )?)
+}
+
+/// Add a column to a table created by an older version, returning whether it was added
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<bool> {
+    let exists = conn
+        .prepare(&format!("SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1", table))?
+        .exists(params![column])?;
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl), [])?;
+    }
+    Ok(!exists)
+}
+
+fn game_file_from_row(row: &Row) -> rusqlite::Result<GameFile> {
+    Ok(GameFile {
+        id: row.get(0)?,
+        filename: row.get(1)?,
+        path: row.get(2)?,
+        size: row.get::<_, i64>(3)? as u64,
+        hash: row.get(4)?,
+        added_at: row.get(5)?,
+        last_modified: row.get(6)?,
+        install_count: row.get(7)?,
+        last_installed: row.get(8)?,
+        favorite: row.get::<_, i32>(9)? != 0,
+        tags: row
+            .get::<_, Option<String>>(10)?
+            .map(|tags| tags.split(TAG_SEPARATOR).map(str::to_string).collect())
+            .unwrap_or_default(),
+        title_id: row.get::<_, Option<String>>(11)?.filter(|id| !id.is_empty()),
+        version: row.get::<_, Option<i64>>(12)?.map(|v| v as u32),
+        display_name: match row.get::<_, Option<String>>(13)? {
+            Some(name) => name,
+            None => title::display_name(&row.get::<_, String>(1)?),
+        },
+        icon_path: row.get(14)?,
+        verification: row.get::<_, Option<String>>(15)?.and_then(|s| VerifyStatus::parse(&s)),
+        offline: row.get::<_, i32>(16)? != 0,
+        deleted_at: row.get(17)?,
+        quarantined_path: row.get(18)?,
+        trashed_missing: row.get::<_, i32>(19)? != 0,
+        root_id: row.get(20)?,
+    })
+}
+
 impl Database {
     /// Create or open database
     pub fn new(db_path: &Path) -> Result<Self> {
         let conn = Connection::open(db_path)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON")?;
         
         // Create tables
         conn.execute(
@@ -57,11 +359,415 @@ impl Database {
             [],
         )?;
         
-        Ok(Self { conn })
+        // Tags, shared between files; names are unique ignoring case
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS tags (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                color TEXT DEFAULT ''
+            )",
+            [],
+        )?;
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS file_tags (
+                file_id INTEGER NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+                tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+                PRIMARY KEY (file_id, tag_id)
+            )",
+            [],
+        )?;
+        
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_file_tags_tag ON file_tags(tag_id)",
+            [],
+        )?;
+        
+        // Metadata parsed from the filename, for search filters
+        add_column_if_missing(&conn, "files", "display_name", "TEXT")?;
+        add_column_if_missing(&conn, "files", "title_id", "TEXT")?;
+        add_column_if_missing(&conn, "files", "content_type", "TEXT")?;
+        let versions_missing = add_column_if_missing(&conn, "files", "version", "INTEGER")?;
+        let icons_missing = add_column_if_missing(&conn, "files", "icon_path", "TEXT")?;
+        
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_files_hash ON files(hash)",
+            [],
+        )?;
+        
+        // Full-text index over files, keyed by files.id
+        conn.execute(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS files_fts USING fts5(
+                filename, display_name, title_id, publisher, tags,
+                tokenize = 'unicode61 remove_diacritics 2'
+            )",
+            [],
+        )?;
+        
+        // Indexed words, for typo-tolerant matching
+        conn.execute(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS files_fts_vocab USING fts5vocab(files_fts, 'row')",
+            [],
+        )?;
+        
+        // Files received from the Switch
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS uploads (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                filename TEXT NOT NULL,
+                path TEXT NOT NULL UNIQUE,
+                category TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                hash TEXT NOT NULL,
+                received_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        
+        // Save-data backups
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS save_snapshots (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                title_id TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                path TEXT NOT NULL UNIQUE,
+                size INTEGER NOT NULL,
+                hash TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                pinned INTEGER DEFAULT 0
+            )",
+            [],
+        )?;
+        
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_save_title_user ON save_snapshots(title_id, user_id)",
+            [],
+        )?;
+        
+        // Session history (stalls, recoveries)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS session_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                occurred_at INTEGER NOT NULL,
+                event TEXT NOT NULL,
+                detail TEXT DEFAULT ''
+            )",
+            [],
+        )?;
+        
+        // Install history: sent by the PC, then confirmed by the console's STATUS
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS install_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                filename TEXT NOT NULL,
+                path TEXT NOT NULL,
+                content_id TEXT DEFAULT '',
+                status TEXT NOT NULL,
+                phase TEXT DEFAULT '',
+                result_code INTEGER DEFAULT 0,
+                sent_at INTEGER,
+                finished_at INTEGER
+            )",
+            [],
+        )?;
+        
+        // Title metadata imported from a local title database dump
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS titles (
+                title_id TEXT PRIMARY KEY,
+                name TEXT,
+                publisher TEXT,
+                release_date TEXT,
+                region TEXT,
+                description TEXT,
+                icon_path TEXT
+            )",
+            [],
+        )?;
+        
+        // User corrections, kept apart so re-imports don't overwrite them
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS title_overrides (
+                title_id TEXT PRIMARY KEY,
+                name TEXT,
+                publisher TEXT,
+                release_date TEXT,
+                region TEXT,
+                description TEXT,
+                icon_path TEXT
+            )",
+            [],
+        )?;
+        
+        // Collections, by path so files outside the library can be included
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS collections (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS collection_items (
+                collection_id INTEGER NOT NULL REFERENCES collections(id) ON DELETE CASCADE,
+                position INTEGER NOT NULL,
+                path TEXT NOT NULL,
+                PRIMARY KEY (collection_id, position),
+                UNIQUE (collection_id, path)
+            )",
+            [],
+        )?;
+        
+        // The GUI queue, restored on the next start
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS queue_items (
+                position INTEGER PRIMARY KEY,
+                path TEXT NOT NULL
+            )",
+            [],
+        )?;
+        
+        // Metadata from each file's control NCA, or why it couldn't be read
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS control_data (
+                file_id INTEGER PRIMARY KEY REFERENCES files(id) ON DELETE CASCADE,
+                size INTEGER NOT NULL,
+                last_modified INTEGER NOT NULL,
+                title_id TEXT,
+                name TEXT,
+                publisher TEXT,
+                display_version TEXT,
+                icon_path TEXT,
+                error TEXT,
+                extracted_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS control_titles (
+                file_id INTEGER NOT NULL REFERENCES control_data(file_id) ON DELETE CASCADE,
+                language TEXT NOT NULL,
+                name TEXT NOT NULL,
+                publisher TEXT NOT NULL,
+                PRIMARY KEY (file_id, language)
+            )",
+            [],
+        )?;
+        
+        // Integrity checks against each file's hashes and signatures
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS verifications (
+                file_id INTEGER PRIMARY KEY REFERENCES files(id) ON DELETE CASCADE,
+                size INTEGER NOT NULL,
+                last_modified INTEGER NOT NULL,
+                status TEXT NOT NULL,
+                error TEXT,
+                checked_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS verification_checks (
+                file_id INTEGER NOT NULL REFERENCES verifications(file_id) ON DELETE CASCADE,
+                position INTEGER NOT NULL,
+                nca TEXT NOT NULL,
+                check_name TEXT NOT NULL,
+                status TEXT NOT NULL,
+                detail TEXT NOT NULL,
+                PRIMARY KEY (file_id, position)
+            )",
+            [],
+        )?;
+        
+        // Folders files were added from; files keep their path relative to the root
+        // so a drive or share that comes back at a new mount point can be followed
+        let roots_missing = !table_exists(&conn, "library_roots")?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS library_roots (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                path TEXT NOT NULL UNIQUE,
+                volume_id TEXT,
+                label TEXT,
+                volume_path TEXT NOT NULL DEFAULT '',
+                online INTEGER NOT NULL DEFAULT 1,
+                added_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        add_column_if_missing(&conn, "files", "root_id", "INTEGER REFERENCES library_roots(id) ON DELETE SET NULL")?;
+        add_column_if_missing(&conn, "files", "rel_path", "TEXT")?;
+        
+        // Trashed files keep their row, tags and history until the trash is emptied
+        add_column_if_missing(&conn, "files", "deleted_at", "INTEGER")?;
+        add_column_if_missing(&conn, "files", "quarantined_path", "TEXT")?;
+        add_column_if_missing(&conn, "files", "trashed_missing", "INTEGER NOT NULL DEFAULT 0")?;
+        
+        let db = Self { conn };
+        db.migrate_legacy_tags()?;
+        if roots_missing {
+            db.register_legacy_roots()?;
+        }
+        if versions_missing || icons_missing {
+            db.rebuild_search_index()?;
+        } else {
+            db.ensure_search_index()?;
+        }
+        Ok(db)
+    }
+    
+    fn savepoint(&self) -> Result<Savepoint<'_>> {
+        self.conn.execute_batch("SAVEPOINT changes")?;
+        Ok(Savepoint { conn: &self.conn, committed: false })
+    }
+    
+    /// Run `f`, keeping its changes only if it succeeds
+    pub fn atomically<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let savepoint = self.savepoint()?;
+        let result = f()?;
+        savepoint.commit()?;
+        Ok(result)
+    }
+    
+    /// Move comma-separated `files.tags` from older versions into `file_tags`
+    fn migrate_legacy_tags(&self) -> Result<()> {
+        let legacy = self.conn
+            .prepare("SELECT id, tags FROM files WHERE tags != ''")?
+            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        if legacy.is_empty() {
+            return Ok(());
+        }
+        
+        let tx = self.savepoint()?;
+        for (id, tags) in legacy {
+            for tag in tags.split(',') {
+                if let Ok(name) = normalize_name(tag) {
+                    let tag_id = self.tag_id_or_create(&name)?;
+                    self.conn.execute(
+                        "INSERT OR IGNORE INTO file_tags (file_id, tag_id) VALUES (?1, ?2)",
+                        params![id, tag_id],
+                    )?;
+                }
+            }
+            self.conn.execute("UPDATE files SET tags = '' WHERE id = ?1", params![id])?;
+            self.index_file(id)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+    
+    /// Register the folders of files added before roots existed
+    ///
+    /// Without a root, a file on an unplugged drive looks the same as one
+    /// that was deleted, and cleanup would trash it. Nested folders share
+    /// their topmost one.
+    fn register_legacy_roots(&self) -> Result<()> {
+        let folders: BTreeSet<PathBuf> = self
+            .paths_in("SELECT path FROM files", [])?
+            .iter()
+            .filter_map(|path| path.parent().map(Path::to_path_buf))
+            .collect();
+        if folders.is_empty() {
+            return Ok(());
+        }
+        
+        // Parents sort before the folders inside them
+        let mounts = volumes::current_mounts();
+        let mut registered: Vec<&PathBuf> = Vec::new();
+        for folder in &folders {
+            if !registered.iter().any(|root| folder.starts_with(root)) {
+                volumes::add_root(self, folder, &mounts)?;
+                registered.push(folder);
+            }
+        }
+        Ok(())
+    }
+    
+    /// Index files added before search existed
+    fn ensure_search_index(&self) -> Result<()> {
+        let stale: bool = self.conn.query_row(
+            "SELECT (SELECT COUNT(*) FROM files) != (SELECT COUNT(*) FROM files_fts)
+                 OR EXISTS (SELECT 1 FROM files WHERE display_name IS NULL)",
+            [],
+            |row| row.get(0),
+        )?;
+        if stale {
+            self.rebuild_search_index()?;
+        }
+        Ok(())
+    }
+    
+    /// Re-derive search metadata and the full-text index for every file
+    pub fn rebuild_search_index(&self) -> Result<()> {
+        let tx = self.savepoint()?;
+        self.conn.execute("DELETE FROM files_fts", [])?;
+        let ids = self.conn
+            .prepare("SELECT id FROM files")?
+            .query_map([], |row| row.get::<_, i64>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        for id in ids {
+            self.index_file(id)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+    
+    /// Refresh one file's search metadata and index entry
+    fn index_file(&self, id: i64) -> Result<()> {
+        let Some(filename) = self.conn.query_row(
+            "SELECT filename FROM files WHERE id = ?1",
+            params![id],
+            |row| row.get::<_, String>(0),
+        ).optional()? else {
+            return Ok(());
+        };
+        let tags = self.get_file_tags(id)?.join(" ");
+        
+        let title_id = title::parse_title_id(&filename)
+            .map(|id| format!("{:016X}", id))
+            .unwrap_or_default();
+        let content_type = title::content_type(&filename);
+        let version = title::parse_version(&filename);
+        
+        // Title database and overrides first, then what the file itself says
+        let info = self.lookup_title(&title_id)?.unwrap_or_default();
+        let (control_name, control_publisher, control_icon) = self.control_summary(id, &title_id)?;
+        let display_name = info.name
+            .or(control_name)
+            .unwrap_or_else(|| title::display_name(&filename));
+        let publisher = info.publisher.or(control_publisher).unwrap_or_default();
+        let override_icon = match info.overridden {
+            true => self.get_title_override(&info.title_id)?.and_then(|o| o.icon_path),
+            false => None,
+        };
+        let icon_path = override_icon.or(control_icon).or(info.icon_path);
+        
+        self.conn.execute(
+            "UPDATE files SET display_name = ?1, title_id = ?2, content_type = ?3, version = ?4, icon_path = ?5
+             WHERE id = ?6",
+            params![display_name, title_id, content_type.as_str(), version, icon_path, id],
+        )?;
+        self.conn.execute("DELETE FROM files_fts WHERE rowid = ?1", params![id])?;
+        self.conn.execute(
+            "INSERT INTO files_fts (rowid, filename, display_name, title_id, publisher, tags)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![id, filename, display_name, title_id, publisher, tags],
+        )?;
+        Ok(())
     }
     
     /// Add a file to database
     pub fn add_file(&self, path: &Path) -> Result<i64> {
+        self.add_file_under(path, &self.root_paths()?)
+    }
+    
+    /// `add_file` with the library roots already loaded
+    fn add_file_under(&self, path: &Path, roots: &[(i64, PathBuf)]) -> Result<i64> {
         let metadata = std::fs::metadata(path)?;
         let filename = path.file_name()
             .and_then(|n| n.to_str())
@@ -78,17 +784,35 @@ impl Database {
             .duration_since(SystemTime::UNIX_EPOCH)?
             .as_secs() as i64;
         
-        // Insert or update
+        // Insert or update; a quarantined row is left alone so its moved copy isn't lost
         self.conn.execute(
             "INSERT INTO files (filename, path, size, added_at, last_modified)
              VALUES (?1, ?2, ?3, ?4, ?5)
              ON CONFLICT(path) DO UPDATE SET
+                hash = CASE WHEN size = excluded.size AND last_modified = excluded.last_modified
+                    THEN hash END,
                 size = excluded.size,
-                last_modified = excluded.last_modified",
+                last_modified = excluded.last_modified,
+                deleted_at = NULL,
+                trashed_missing = 0
+             WHERE files.quarantined_path IS NULL",
             params![filename, path_str, size as i64, now, last_modified],
         )?;
         
-        Ok(self.conn.last_insert_rowid())
+        // last_insert_rowid isn't updated when the path already existed
+        let id = self.conn.query_row(
+            "SELECT id FROM files WHERE path = ?1",
+            params![path_str],
+            |row| row.get(0),
+        )?;
+        let (root_id, rel_path) = deepest_root(roots, path).unzip();
+        self.conn.execute(
+            "UPDATE files SET root_id = ?1, rel_path = ?2 WHERE id = ?3",
+            params![root_id, rel_path, id],
+        )?;
+        self.index_file(id)?;
+        
+        Ok(id)
     }
     
     /// Add files from directory recursively
@@ -99,6 +823,7 @@ impl Database {
             db: &Database,
             dir: &Path,
             extensions: &[&str],
+            roots: &[(i64, PathBuf)],
             count: &mut usize,
         ) -> Result<()> {
             if !dir.is_dir() {
@@ -111,13 +836,13 @@ impl Database {
                 
                 if path.is_dir() {
                     // Recursive scan
-                    scan_dir(db, &path, extensions, count)?;
+                    scan_dir(db, &path, extensions, roots, count)?;
                 } else if path.is_file() {
                     // Check extension
                     if let Some(ext) = path.extension() {
                         let ext_str = ext.to_string_lossy().to_lowercase();
                         if extensions.iter().any(|&e| e == ext_str) {
-                            db.add_file(&path)?;
+                            db.add_file_under(&path, roots)?;
                             *count += 1;
                         }
                     }
@@ -127,103 +852,202 @@ impl Database {
             Ok(())
         }
         
-        scan_dir(self, dir, extensions, &mut count)?;
+        scan_dir(self, dir, extensions, &self.root_paths()?, &mut count)?;
         Ok(count)
     }
     
-    /// Get all files
+    /// Get all files, leaving out the trash
     pub fn get_files(&self) -> Result<Vec<GameFile>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, filename, path, size, hash, added_at, last_modified,
-                    install_count, last_installed, favorite, tags
-             FROM files
-             ORDER BY added_at DESC"
-        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM files
+             WHERE deleted_at IS NULL
+             ORDER BY added_at DESC",
+            FILE_COLUMNS
+        ))?;
         
-        let files = stmt.query_map([], |row| {
-            Ok(GameFile {
-                id: row.get(0)?,
-                filename: row.get(1)?,
-                path: row.get(2)?,
-                size: row.get::<_, i64>(3)? as u64,
-                hash: row.get(4)?,
-                added_at: row.get(5)?,
-                last_modified: row.get(6)?,
-                install_count: row.get(7)?,
-                last_installed: row.get(8)?,
-                favorite: row.get::<_, i32>(9)? != 0,
-                tags: row.get(10)?,
-            })
-        })?
+        let files = stmt.query_map([], game_file_from_row)?
         .collect::<Result<Vec<_>, _>>()?;
         
         Ok(files)
     }
     
-    /// Get favorites
-    pub fn get_favorites(&self) -> Result<Vec<GameFile>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, filename, path, size, hash, added_at, last_modified,
-                    install_count, last_installed, favorite, tags
-             FROM files
-             WHERE favorite = 1
-             ORDER BY added_at DESC"
-        )?;
+    /// Get a file by its ID, even if it's in the trash
+    pub fn get_file(&self, id: i64) -> Result<Option<GameFile>> {
+        let file = self.conn.query_row(
+            &format!("SELECT {} FROM files WHERE id = ?1", FILE_COLUMNS),
+            params![id],
+            game_file_from_row,
+        ).optional()?;
         
-        let files = stmt.query_map([], |row| {
-            Ok(GameFile {
-                id: row.get(0)?,
-                filename: row.get(1)?,
-                path: row.get(2)?,
-                size: row.get::<_, i64>(3)? as u64,
-                hash: row.get(4)?,
-                added_at: row.get(5)?,
-                last_modified: row.get(6)?,
-                install_count: row.get(7)?,
-                last_installed: row.get(8)?,
-                favorite: row.get::<_, i32>(9)? != 0,
-                tags: row.get(10)?,
-            })
-        })?
+        Ok(file)
+    }
+    
+    /// Get a file by its path, even if it's in the trash
+    pub fn get_file_by_path(&self, path: &Path) -> Result<Option<GameFile>> {
+        let file = self.conn.query_row(
+            &format!("SELECT {} FROM files WHERE path = ?1", FILE_COLUMNS),
+            params![path.to_string_lossy()],
+            game_file_from_row,
+        ).optional()?;
+        
+        Ok(file)
+    }
+    
+    /// Get favorites
+    pub fn get_favorites(&self) -> Result<Vec<GameFile>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM files
+             WHERE favorite = 1 AND deleted_at IS NULL
+             ORDER BY added_at DESC",
+            FILE_COLUMNS
+        ))?;
+        
+        let files = stmt.query_map([], game_file_from_row)?
         .collect::<Result<Vec<_>, _>>()?;
         
         Ok(files)
     }
     
-    /// Search files
+    /// Search files, see `SearchQuery` for the syntax
     pub fn search(&self, query: &str) -> Result<Vec<GameFile>> {
-        let pattern = format!("%{}%", query);
-        let mut stmt = self.conn.prepare(
-            "SELECT id, filename, path, size, hash, added_at, last_modified,
-                    install_count, last_installed, favorite, tags
-             FROM files
-             WHERE filename LIKE ?1 OR tags LIKE ?1
-             ORDER BY favorite DESC, install_count DESC"
+        Ok(self.search_page(&SearchQuery::parse(query), 0, usize::MAX)?.files)
+    }
+    
+    /// One page of ranked matches
+    ///
+    /// Words match as prefixes; if nothing matches, words within a typo or
+    /// two of an indexed word are tried instead.
+    pub fn search_page(&self, query: &SearchQuery, offset: usize, limit: usize) -> Result<SearchResults> {
+        if query.terms.is_empty() {
+            return self.run_search(query, None, offset, limit);
+        }
+        
+        let exact = query.terms.iter()
+            .map(|term| format!("\"{}\"*", term))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let results = self.run_search(query, Some(&exact), offset, limit)?;
+        if results.total > 0 {
+            return Ok(results);
+        }
+        
+        let Some(fuzzy) = self.fuzzy_match_expression(&query.terms)? else {
+            return Ok(results);
+        };
+        let mut results = self.run_search(query, Some(&fuzzy), offset, limit)?;
+        results.fuzzy = true;
+        Ok(results)
+    }
+    
+    /// Each term OR'd with indexed words a few typos away; `None` if some term has none
+    fn fuzzy_match_expression(&self, terms: &[String]) -> Result<Option<String>> {
+        let vocabulary = self.conn
+            .prepare("SELECT term FROM files_fts_vocab")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        
+        let mut groups = Vec::new();
+        for term in terms {
+            let len = term.chars().count();
+            let budget = typo_budget(len);
+            let mut alternatives: Vec<String> = vocabulary.iter()
+                .filter(|word| {
+                    // Words still being typed: compare with the start of longer words too
+                    (len.saturating_sub(budget)..=len + budget).any(|prefix_len| {
+                        let prefix: String = word.chars().take(prefix_len).collect();
+                        budget > 0 && edit_distance(term, &prefix, budget).is_some()
+                    })
+                })
+                .map(|word| format!("\"{}\"", word))
+                .collect();
+            if alternatives.is_empty() {
+                return Ok(None);
+            }
+            alternatives.push(format!("\"{}\"*", term));
+            groups.push(format!("({})", alternatives.join(" OR ")));
+        }
+        Ok(Some(groups.join(" AND ")))
+    }
+    
+    fn run_search(
+        &self,
+        query: &SearchQuery,
+        match_expression: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<SearchResults> {
+        let mut conditions = vec!["files.deleted_at IS NULL".to_string()];
+        let mut values: Vec<Value> = Vec::new();
+        
+        let (from, order) = match match_expression {
+            Some(expression) => {
+                conditions.push("files_fts MATCH ?".to_string());
+                values.push(Value::Text(expression.to_string()));
+                (
+                    "files JOIN files_fts ON files_fts.rowid = files.id",
+                    format!("{}, files.favorite DESC, files.install_count DESC", SEARCH_RANK),
+                )
+            }
+            // The whole library lists newest first, as before search existed
+            None if query.is_empty() => ("files", "files.added_at DESC, files.id DESC".to_string()),
+            None => ("files", "files.favorite DESC, files.install_count DESC, files.added_at DESC".to_string()),
+        };
+        
+        if let Some(content_type) = query.content_type {
+            conditions.push("files.content_type = ?".to_string());
+            values.push(Value::Text(content_type.as_str().to_string()));
+        }
+        for tag in &query.tags {
+            conditions.push(
+                "EXISTS (SELECT 1 FROM file_tags JOIN tags ON tags.id = file_tags.tag_id
+                         WHERE file_tags.file_id = files.id AND tags.name = ?)".to_string(),
+            );
+            values.push(Value::Text(tag.clone()));
+        }
+        if let Some(min) = query.min_size {
+            conditions.push("files.size >= ?".to_string());
+            values.push(Value::Integer(min.min(i64::MAX as u64) as i64));
+        }
+        if let Some(max) = query.max_size {
+            conditions.push("files.size <= ?".to_string());
+            values.push(Value::Integer(max.min(i64::MAX as u64) as i64));
+        }
+        match query.installed {
+            Some(true) => conditions.push("files.install_count > 0".to_string()),
+            Some(false) => conditions.push("files.install_count = 0".to_string()),
+            None => {}
+        }
+        
+        let filter = format!("WHERE {}", conditions.join(" AND "));
+        
+        let total: i64 = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM {} {}", from, filter),
+            params_from_iter(values.iter()),
+            |row| row.get(0),
         )?;
         
-        let files = stmt.query_map([pattern], |row| {
-            Ok(GameFile {
-                id: row.get(0)?,
-                filename: row.get(1)?,
-                path: row.get(2)?,
-                size: row.get::<_, i64>(3)? as u64,
-                hash: row.get(4)?,
-                added_at: row.get(5)?,
-                last_modified: row.get(6)?,
-                install_count: row.get(7)?,
-                last_installed: row.get(8)?,
-                favorite: row.get::<_, i32>(9)? != 0,
-                tags: row.get(10)?,
-            })
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
+        values.push(Value::Integer(i64::try_from(limit).unwrap_or(-1)));
+        values.push(Value::Integer(i64::try_from(offset).unwrap_or(i64::MAX)));
+        let files = self.conn
+            .prepare(&format!(
+                "SELECT {} FROM {} {} ORDER BY {} LIMIT ? OFFSET ?",
+                FILE_COLUMNS, from, filter, order
+            ))?
+            .query_map(params_from_iter(values.iter()), game_file_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
         
-        Ok(files)
+        Ok(SearchResults {
+            files,
+            total: total as usize,
+            fuzzy: false,
+        })
     }
     
-    /// Remove file
+    /// Remove a file's row for good, with its tags and history; see `trash_files` to keep them
     pub fn remove_file(&self, id: i64) -> Result<()> {
+        // file_tags rows go with it (ON DELETE CASCADE)
         self.conn.execute("DELETE FROM files WHERE id = ?1", params![id])?;
+        self.conn.execute("DELETE FROM files_fts WHERE rowid = ?1", params![id])?;
         Ok(())
     }
     
@@ -236,15 +1060,571 @@ impl Database {
         Ok(())
     }
     
-    /// Add tag
+    pub fn set_favorite(&self, id: i64, favorite: bool) -> Result<()> {
+        self.conn.execute(
+            "UPDATE files SET favorite = ?1 WHERE id = ?2",
+            params![favorite, id],
+        )?;
+        Ok(())
+    }
+    
+    /// Tag a file, creating the tag if needed; tagging twice is a no-op
     pub fn add_tag(&self, id: i64, tag: &str) -> Result<()> {
+        self.tag_files(&[id], tag)
+    }
+    
+    pub fn remove_tag(&self, id: i64, tag: &str) -> Result<()> {
+        self.untag_files(&[id], tag)
+    }
+    
+    /// Tag several files at once
+    pub fn tag_files(&self, ids: &[i64], tag: &str) -> Result<()> {
+        let name = normalize_name(tag)?;
+        let tx = self.savepoint()?;
+        let tag_id = self.tag_id_or_create(&name)?;
+        for &id in ids {
+            self.conn.execute(
+                "INSERT OR IGNORE INTO file_tags (file_id, tag_id) VALUES (?1, ?2)",
+                params![id, tag_id],
+            )?;
+            self.index_file(id)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+    
+    pub fn untag_files(&self, ids: &[i64], tag: &str) -> Result<()> {
+        let Some(tag_id) = self.tag_id(tag)? else {
+            return Ok(());
+        };
+        let tx = self.savepoint()?;
+        for &id in ids {
+            self.conn.execute(
+                "DELETE FROM file_tags WHERE file_id = ?1 AND tag_id = ?2",
+                params![id, tag_id],
+            )?;
+            self.index_file(id)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+    
+    /// All tags by name, with usage counts
+    pub fn get_tags(&self) -> Result<Vec<Tag>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT tags.id, tags.name, tags.color, COUNT(file_tags.file_id)
+             FROM tags LEFT JOIN file_tags ON file_tags.tag_id = tags.id
+             GROUP BY tags.id
+             ORDER BY tags.name COLLATE NOCASE"
+        )?;
+        
+        let tags = stmt.query_map([], |row| {
+            Ok(Tag {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                color: row.get(2)?,
+                file_count: row.get::<_, i64>(3)? as usize,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(tags)
+    }
+    
+    pub fn get_file_tags(&self, id: i64) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT tags.name FROM file_tags JOIN tags ON tags.id = file_tags.tag_id
+             WHERE file_tags.file_id = ?1
+             ORDER BY tags.name COLLATE NOCASE"
+        )?;
+        
+        let tags = stmt.query_map(params![id], |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(tags)
+    }
+    
+    /// Create an unused tag, e.g. to pick its color before tagging anything
+    pub fn create_tag(&self, name: &str) -> Result<i64> {
+        self.tag_id_or_create(&normalize_name(name)?)
+    }
+    
+    /// Rename a tag; use `merge_tags` if the new name is already taken
+    pub fn rename_tag(&self, from: &str, to: &str) -> Result<()> {
+        let to = normalize_name(to)?;
+        let tag_id = self.tag_id(from)?.ok_or_else(|| anyhow!("No tag named {:?}", from))?;
+        if self.tag_id(&to)?.is_some_and(|existing| existing != tag_id) {
+            return Err(anyhow!("A tag named {:?} already exists; merge instead", to));
+        }
+        
+        let tx = self.savepoint()?;
+        self.conn.execute("UPDATE tags SET name = ?1 WHERE id = ?2", params![to, tag_id])?;
+        self.reindex_tagged(tag_id)?;
+        tx.commit()?;
+        Ok(())
+    }
+    
+    /// Move every file from one tag to another and delete the first
+    pub fn merge_tags(&self, from: &str, into: &str) -> Result<()> {
+        let from_id = self.tag_id(from)?.ok_or_else(|| anyhow!("No tag named {:?}", from))?;
+        let into_id = self.tag_id_or_create(&normalize_name(into)?)?;
+        if from_id == into_id {
+            return Ok(());
+        }
+        
+        let tx = self.savepoint()?;
         self.conn.execute(
-            "UPDATE files SET tags = tags || ?1 || ',' WHERE id = ?2",
-            params![tag, id],
+            "INSERT OR IGNORE INTO file_tags (file_id, tag_id)
+             SELECT file_id, ?1 FROM file_tags WHERE tag_id = ?2",
+            params![into_id, from_id],
         )?;
+        self.conn.execute("DELETE FROM tags WHERE id = ?1", params![from_id])?;
+        self.reindex_tagged(into_id)?;
+        tx.commit()?;
+        Ok(())
+    }
+    
+    /// Delete a tag and remove it from every file
+    pub fn delete_tag(&self, name: &str) -> Result<()> {
+        let Some(tag_id) = self.tag_id(name)? else {
+            return Ok(());
+        };
+        
+        let tx = self.savepoint()?;
+        let files = self.tagged_file_ids(tag_id)?;
+        self.conn.execute("DELETE FROM tags WHERE id = ?1", params![tag_id])?;
+        for id in files {
+            self.index_file(id)?;
+        }
+        tx.commit()?;
         Ok(())
     }
     
+    /// Set a tag's color as `#RRGGBB`, or empty for the default
+    pub fn set_tag_color(&self, name: &str, color: &str) -> Result<()> {
+        let valid = color.is_empty()
+            || (color.len() == 7
+                && color.starts_with('#')
+                && color[1..].chars().all(|c| c.is_ascii_hexdigit()));
+        if !valid {
+            return Err(anyhow!("Invalid tag color {:?}, expected #RRGGBB", color));
+        }
+        
+        let updated = self.conn.execute(
+            "UPDATE tags SET color = ?1 WHERE name = ?2",
+            params![color, name.trim()],
+        )?;
+        if updated == 0 {
+            return Err(anyhow!("No tag named {:?}", name));
+        }
+        Ok(())
+    }
+    
+    fn tag_id(&self, name: &str) -> Result<Option<i64>> {
+        let id = self.conn.query_row(
+            "SELECT id FROM tags WHERE name = ?1",
+            params![name.trim()],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(id)
+    }
+    
+    fn tag_id_or_create(&self, name: &str) -> Result<i64> {
+        self.conn.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", params![name])?;
+        self.tag_id(name)?.ok_or_else(|| anyhow!("Tag {:?} vanished", name))
+    }
+    
+    fn tagged_file_ids(&self, tag_id: i64) -> Result<Vec<i64>> {
+        let ids = self.conn
+            .prepare("SELECT file_id FROM file_tags WHERE tag_id = ?1")?
+            .query_map(params![tag_id], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ids)
+    }
+    
+    fn reindex_tagged(&self, tag_id: i64) -> Result<()> {
+        for id in self.tagged_file_ids(tag_id)? {
+            self.index_file(id)?;
+        }
+        Ok(())
+    }
+    
+    /// Replace the imported title database, keeping user overrides
+    pub fn import_titles(&self, titles: &[TitleInfo]) -> Result<usize> {
+        let tx = self.savepoint()?;
+        self.conn.execute("DELETE FROM titles", [])?;
+        let mut imported = 0;
+        for info in titles {
+            imported += self.conn.execute(
+                "INSERT OR REPLACE INTO titles
+                    (title_id, name, publisher, release_date, region, description, icon_path)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    info.title_id.to_uppercase(),
+                    info.name,
+                    info.publisher,
+                    info.release_date,
+                    info.region,
+                    info.description,
+                    info.icon_path,
+                ],
+            )?;
+        }
+        tx.commit()?;
+        
+        // Names and publishers are part of the search index
+        self.rebuild_search_index()?;
+        Ok(imported)
+    }
+    
+    /// Number of titles imported
+    pub fn title_count(&self) -> Result<usize> {
+        let count: i64 = self.conn.query_row("SELECT COUNT(*) FROM titles", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+    
+    /// Metadata for exactly this title ID, overrides applied
+    pub fn get_title(&self, title_id: &str) -> Result<Option<TitleInfo>> {
+        let info = self.conn.query_row(
+            "SELECT ids.title_id,
+                    COALESCE(o.name, t.name), COALESCE(o.publisher, t.publisher),
+                    COALESCE(o.release_date, t.release_date), COALESCE(o.region, t.region),
+                    COALESCE(o.description, t.description), COALESCE(o.icon_path, t.icon_path),
+                    o.title_id IS NOT NULL
+             FROM (SELECT ?1 AS title_id) ids
+             LEFT JOIN titles t ON t.title_id = ids.title_id
+             LEFT JOIN title_overrides o ON o.title_id = ids.title_id
+             WHERE t.title_id IS NOT NULL OR o.title_id IS NOT NULL",
+            params![title_id.to_uppercase()],
+            |row| {
+                Ok(TitleInfo {
+                    title_id: row.get(0)?,
+                    name: row.get(1)?,
+                    publisher: row.get(2)?,
+                    release_date: row.get(3)?,
+                    region: row.get(4)?,
+                    description: row.get(5)?,
+                    icon_path: row.get(6)?,
+                    overridden: row.get(7)?,
+                })
+            },
+        ).optional()?;
+        Ok(info)
+    }
+    
+    /// Metadata for a title ID, falling back to the base game for updates and DLC
+    pub fn lookup_title(&self, title_id: &str) -> Result<Option<TitleInfo>> {
+        if title_id.is_empty() {
+            return Ok(None);
+        }
+        if let Some(info) = self.get_title(title_id)? {
+            return Ok(Some(info));
+        }
+        match u64::from_str_radix(title_id, 16) {
+            Ok(id) if title::base_title_id(id) != id => {
+                self.get_title(&format!("{:016X}", title::base_title_id(id)))
+            }
+            _ => Ok(None),
+        }
+    }
+    
+    /// Metadata as imported, without overrides
+    pub fn get_imported_title(&self, title_id: &str) -> Result<Option<TitleInfo>> {
+        let info = self.conn.query_row(
+            "SELECT title_id, name, publisher, release_date, region, description, icon_path
+             FROM titles WHERE title_id = ?1",
+            params![title_id.to_uppercase()],
+            |row| {
+                Ok(TitleInfo {
+                    title_id: row.get(0)?,
+                    name: row.get(1)?,
+                    publisher: row.get(2)?,
+                    release_date: row.get(3)?,
+                    region: row.get(4)?,
+                    description: row.get(5)?,
+                    icon_path: row.get(6)?,
+                    overridden: false,
+                })
+            },
+        ).optional()?;
+        Ok(info)
+    }
+    
+    /// The user's corrections for a title, if any
+    pub fn get_title_override(&self, title_id: &str) -> Result<Option<TitleInfo>> {
+        let info = self.conn.query_row(
+            "SELECT title_id, name, publisher, release_date, region, description, icon_path
+             FROM title_overrides WHERE title_id = ?1",
+            params![title_id.to_uppercase()],
+            |row| {
+                Ok(TitleInfo {
+                    title_id: row.get(0)?,
+                    name: row.get(1)?,
+                    publisher: row.get(2)?,
+                    release_date: row.get(3)?,
+                    region: row.get(4)?,
+                    description: row.get(5)?,
+                    icon_path: row.get(6)?,
+                    overridden: true,
+                })
+            },
+        ).optional()?;
+        Ok(info)
+    }
+    
+    /// Override fields of a title; `None` fields keep the imported value
+    pub fn set_title_override(&self, info: &TitleInfo) -> Result<()> {
+        let title_id = info.title_id.to_uppercase();
+        let fields = [&info.name, &info.publisher, &info.release_date, &info.region, &info.description, &info.icon_path];
+        if fields.iter().all(|field| field.is_none()) {
+            return self.clear_title_override(&title_id);
+        }
+        
+        self.conn.execute(
+            "INSERT OR REPLACE INTO title_overrides
+                (title_id, name, publisher, release_date, region, description, icon_path)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                title_id,
+                info.name,
+                info.publisher,
+                info.release_date,
+                info.region,
+                info.description,
+                info.icon_path,
+            ],
+        )?;
+        self.reindex_title(&title_id)
+    }
+    
+    pub fn clear_title_override(&self, title_id: &str) -> Result<()> {
+        let title_id = title_id.to_uppercase();
+        self.conn.execute("DELETE FROM title_overrides WHERE title_id = ?1", params![title_id])?;
+        self.reindex_title(&title_id)
+    }
+    
+    /// Re-index files of a title and of its updates and DLC
+    fn reindex_title(&self, title_id: &str) -> Result<()> {
+        let files = self.conn
+            .prepare("SELECT id, title_id FROM files WHERE title_id != ''")?
+            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        
+        let tx = self.savepoint()?;
+        for (id, file_title_id) in files {
+            let base = u64::from_str_radix(&file_title_id, 16)
+                .map(|id| format!("{:016X}", title::base_title_id(id)))
+                .unwrap_or_default();
+            if file_title_id == title_id || base == title_id {
+                self.index_file(id)?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+    
+    /// Name, publisher and icon from a file's control data, or from its base game's
+    fn control_summary(&self, id: i64, title_id: &str) -> Result<(Option<String>, Option<String>, Option<String>)> {
+        let base = u64::from_str_radix(title_id, 16)
+            .map(|id| format!("{:016X}", title::base_title_id(id)))
+            .unwrap_or_default();
+        let summary = self.conn.query_row(
+            "SELECT name, publisher, icon_path FROM control_data
+             WHERE error IS NULL AND (file_id = ?1 OR title_id = ?2)
+             ORDER BY file_id = ?1 DESC, extracted_at DESC LIMIT 1",
+            params![id, base],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        ).optional()?;
+        Ok(summary.unwrap_or_default())
+    }
+    
+    /// Files whose control data hasn't been read since they last changed
+    ///
+    /// Files that failed before are only included with `retry_failed`.
+    pub fn files_needing_control(&self, retry_failed: bool) -> Result<Vec<GameFile>> {
+        let files = self.conn
+            .prepare(&format!(
+                "SELECT {} FROM files LEFT JOIN control_data c ON c.file_id = files.id
+                 WHERE files.deleted_at IS NULL
+                    AND (c.file_id IS NULL OR c.size != files.size OR c.last_modified != files.last_modified
+                        OR (?1 AND c.error IS NOT NULL))
+                 ORDER BY files.filename",
+                FILE_COLUMNS
+            ))?
+            .query_map(params![retry_failed], game_file_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(files)
+    }
+    
+    /// Store what a file's control NCA holds and update its name and icon
+    pub fn save_control_info(&self, id: i64, info: &ControlInfo) -> Result<()> {
+        let preferred = info.titles.first();
+        let tx = self.savepoint()?;
+        self.conn.execute(
+            "INSERT OR REPLACE INTO control_data
+                (file_id, size, last_modified, title_id, name, publisher, display_version, icon_path, error, extracted_at)
+             SELECT id, size, last_modified, ?2, ?3, ?4, ?5, ?6, NULL, ?7 FROM files WHERE id = ?1",
+            params![
+                id,
+                info.title_id.to_uppercase(),
+                preferred.map(|t| &t.name),
+                preferred.map(|t| &t.publisher),
+                info.display_version,
+                info.icon_path,
+                unix_now()?,
+            ],
+        )?;
+        self.conn.execute("DELETE FROM control_titles WHERE file_id = ?1", params![id])?;
+        for title in &info.titles {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO control_titles (file_id, language, name, publisher)
+                 SELECT file_id, ?2, ?3, ?4 FROM control_data WHERE file_id = ?1",
+                params![id, title.language, title.name, title.publisher],
+            )?;
+        }
+        self.index_file(id)?;
+        tx.commit()?;
+        Ok(())
+    }
+    
+    /// Remember why a file's control data couldn't be read, so scans don't retry it
+    pub fn save_control_error(&self, id: i64, error: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO control_data (file_id, size, last_modified, error, extracted_at)
+             SELECT id, size, last_modified, ?2, ?3 FROM files WHERE id = ?1",
+            params![id, error, unix_now()?],
+        )?;
+        Ok(())
+    }
+    
+    /// Control data of a file, if it was read successfully
+    pub fn get_control_info(&self, id: i64) -> Result<Option<ControlInfo>> {
+        let info = self.conn.query_row(
+            "SELECT title_id, display_version, icon_path FROM control_data
+             WHERE file_id = ?1 AND error IS NULL",
+            params![id],
+            |row| {
+                Ok(ControlInfo {
+                    title_id: row.get::<_, Option<String>>(0)?.unwrap_or_default(),
+                    display_version: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                    titles: Vec::new(),
+                    icon_path: row.get(2)?,
+                })
+            },
+        ).optional()?;
+        let Some(mut info) = info else {
+            return Ok(None);
+        };
+        
+        // NACP order, which starts with American English
+        info.titles = self.conn
+            .prepare("SELECT language, name, publisher FROM control_titles WHERE file_id = ?1 ORDER BY rowid")?
+            .query_map(params![id], |row| {
+                Ok(LocalizedTitle {
+                    language: row.get(0)?,
+                    name: row.get(1)?,
+                    publisher: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(info))
+    }
+    
+    /// Why reading a file's control data failed, if it did
+    pub fn get_control_error(&self, id: i64) -> Result<Option<String>> {
+        let error = self.conn.query_row(
+            "SELECT error FROM control_data WHERE file_id = ?1",
+            params![id],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(error.flatten())
+    }
+    
+    /// Files that haven't been verified since they last changed, or all files with `all`
+    pub fn files_needing_verification(&self, all: bool) -> Result<Vec<GameFile>> {
+        let files = self.conn
+            .prepare(&format!(
+                "SELECT {} FROM files LEFT JOIN verifications v ON v.file_id = files.id
+                 WHERE files.deleted_at IS NULL
+                    AND (?1 OR v.file_id IS NULL OR v.size != files.size OR v.last_modified != files.last_modified)
+                 ORDER BY files.filename",
+                FILE_COLUMNS
+            ))?
+            .query_map(params![all], game_file_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(files)
+    }
+    
+    /// Store a file's verification report, replacing any earlier one; `checked_at` is set to now
+    pub fn save_verification(&self, id: i64, verification: &Verification) -> Result<()> {
+        let tx = self.savepoint()?;
+        self.conn.execute(
+            "INSERT OR REPLACE INTO verifications (file_id, size, last_modified, status, error, checked_at)
+             SELECT id, size, last_modified, ?2, ?3, ?4 FROM files WHERE id = ?1",
+            params![id, verification.status.as_str(), verification.error, unix_now()?],
+        )?;
+        self.conn.execute("DELETE FROM verification_checks WHERE file_id = ?1", params![id])?;
+        for (position, check) in verification.checks.iter().enumerate() {
+            self.conn.execute(
+                "INSERT INTO verification_checks (file_id, position, nca, check_name, status, detail)
+                 SELECT file_id, ?2, ?3, ?4, ?5, ?6 FROM verifications WHERE file_id = ?1",
+                params![id, position as i64, check.nca, check.check, check.status.as_str(), check.detail],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+    
+    /// The latest verification report of a file, even if it has changed since
+    pub fn get_verification(&self, id: i64) -> Result<Option<Verification>> {
+        let verification = self.conn.query_row(
+            "SELECT status, error, checked_at FROM verifications WHERE file_id = ?1",
+            params![id],
+            |row| {
+                Ok(Verification {
+                    // Only this library writes statuses, so anything else is an old or broken row
+                    status: VerifyStatus::parse(&row.get::<_, String>(0)?).unwrap_or_default(),
+                    checks: Vec::new(),
+                    error: row.get(1)?,
+                    checked_at: row.get(2)?,
+                })
+            },
+        ).optional()?;
+        let Some(mut verification) = verification else {
+            return Ok(None);
+        };
+        
+        verification.checks = self.conn
+            .prepare(
+                "SELECT nca, check_name, status, detail FROM verification_checks
+                 WHERE file_id = ?1 ORDER BY position",
+            )?
+            .query_map(params![id], |row| {
+                Ok(VerifyCheck {
+                    nca: row.get(0)?,
+                    check: row.get(1)?,
+                    status: CheckStatus::parse(&row.get::<_, String>(2)?).unwrap_or(CheckStatus::Skipped),
+                    detail: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(verification))
+    }
+    
+    /// Paths of unchanged files whose last verification failed
+    pub fn failed_verification_paths(&self) -> Result<Vec<PathBuf>> {
+        let paths = self.conn
+            .prepare(
+                "SELECT files.path FROM files JOIN verifications v ON v.file_id = files.id
+                 WHERE v.status = ?1 AND v.size = files.size AND v.last_modified = files.last_modified
+                    AND files.deleted_at IS NULL",
+            )?
+            .query_map(params![VerifyStatus::Failed.as_str()], |row| row.get::<_, String>(0))?
+            .map(|path| path.map(PathBuf::from))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(paths)
+    }
+    
     /// Record installation
     pub fn record_install(&self, id: i64) -> Result<()> {
         let now = SystemTime::now()
@@ -261,10 +1641,86 @@ impl Database {
         Ok(())
     }
     
+    /// Raise a file's install count and last install time to at least these, e.g. from another library
+    pub fn merge_install_stats(&self, id: i64, install_count: i32, last_installed: Option<i64>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE files SET
+                install_count = MAX(COALESCE(install_count, 0), ?1),
+                last_installed = COALESCE(MAX(last_installed, ?2), last_installed, ?2)
+             WHERE id = ?3",
+            params![install_count, last_installed, id],
+        )?;
+        Ok(())
+    }
+    
+    /// Store a file's SHA-256, computed by the caller from its current content
+    pub fn set_hash(&self, id: i64, hash: &str) -> Result<()> {
+        self.conn.execute("UPDATE files SET hash = ?1 WHERE id = ?2", params![hash, id])?;
+        Ok(())
+    }
+    
+    /// Hash files that could be duplicates: those sharing a size with another file
+    ///
+    /// Hashes are kept until the file's size or modification time changes, so
+    /// only new and changed files are read. Calls `on_progress(done, total)`
+    /// after each file. A file that can't be read is skipped and its stale
+    /// hash cleared, so it can't be mistaken for a duplicate.
+    pub fn update_hashes(&self, mut on_progress: impl FnMut(usize, usize)) -> Result<HashSummary> {
+        let candidates = self.conn
+            .prepare(
+                "SELECT id, path, size, last_modified, hash FROM files
+                 WHERE deleted_at IS NULL AND size IN (
+                    SELECT size FROM files WHERE deleted_at IS NULL GROUP BY size HAVING COUNT(*) > 1)"
+            )?
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    PathBuf::from(row.get::<_, String>(1)?),
+                    row.get::<_, i64>(2)? as u64,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        
+        // Skip files whose hash is still current
+        let stale: Vec<_> = candidates
+            .into_iter()
+            .filter_map(|(id, path, size, modified, hash)| {
+                let metadata = std::fs::metadata(&path).ok()?;
+                let current_modified = metadata.modified().ok()?
+                    .duration_since(SystemTime::UNIX_EPOCH).ok()?
+                    .as_secs() as i64;
+                let unchanged = metadata.len() == size && current_modified == modified;
+                (hash.is_none() || !unchanged).then_some((id, path, metadata.len(), current_modified))
+            })
+            .collect();
+        
+        let total = stale.len();
+        let mut summary = HashSummary::default();
+        for (done, (id, path, size, modified)) in stale.into_iter().enumerate() {
+            match crate::inbox::hash_file(&path) {
+                Ok(hash) => {
+                    self.conn.execute(
+                        "UPDATE files SET hash = ?1, size = ?2, last_modified = ?3 WHERE id = ?4",
+                        params![hash, size as i64, modified, id],
+                    )?;
+                    summary.hashed += 1;
+                }
+                Err(e) => {
+                    self.conn.execute("UPDATE files SET hash = NULL WHERE id = ?1", params![id])?;
+                    summary.failed.push((path.to_string_lossy().into_owned(), e.to_string()));
+                }
+            }
+            on_progress(done + 1, total);
+        }
+        Ok(summary)
+    }
+    
     /// Get statistics
     pub fn get_stats(&self) -> Result<(usize, u64, i32)> {
         let mut stmt = self.conn.prepare(
-            "SELECT COUNT(*), SUM(size), SUM(install_count) FROM files"
+            "SELECT COUNT(*), SUM(size), SUM(install_count) FROM files WHERE deleted_at IS NULL"
         )?;
         
         let (count, total_size, total_installs) = stmt.query_row([], |row| {
@@ -278,18 +1734,668 @@ impl Database {
         Ok((count, total_size, total_installs))
     }
     
-    /// Clean up missing files
+    /// Files whose path no longer exists, leaving out offline roots: what `cleanup` would trash
+    pub fn missing_files(&self) -> Result<Vec<GameFile>> {
+        Ok(self.get_files()?
+            .into_iter()
+            .filter(|file| !file.offline && !Path::new(&file.path).exists())
+            .collect())
+    }
+    
+    /// Library files outside every root, which are reported missing even if only their drive is gone
+    pub fn unrooted_paths(&self) -> Result<HashSet<PathBuf>> {
+        Ok(self.paths_in("SELECT path FROM files WHERE root_id IS NULL AND deleted_at IS NULL", [])?
+            .into_iter()
+            .collect())
+    }
+    
+    /// Move missing files to the trash, returning how many
     pub fn cleanup(&self) -> Result<usize> {
-        let files = self.get_files()?;
-        let mut removed = 0;
+        let missing: Vec<i64> = self.missing_files()?.iter().map(|file| file.id).collect();
+        let tx = self.savepoint()?;
+        self.trash_files(&missing)?;
+        for &id in &missing {
+            self.conn.execute("UPDATE files SET trashed_missing = 1 WHERE id = ?1", params![id])?;
+        }
+        tx.commit()?;
+        Ok(missing.len())
+    }
+    
+    /// Move files to the library trash; they keep their tags and history and can be restored
+    pub fn trash_files(&self, ids: &[i64]) -> Result<()> {
+        let now = unix_now()?;
+        let tx = self.savepoint()?;
+        for &id in ids {
+            self.conn.execute(
+                "UPDATE files SET deleted_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+                params![now, id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+    
+    /// Take a file out of the trash; moving it back from quarantine is up to the caller
+    pub fn restore_file(&self, id: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE files SET deleted_at = NULL, quarantined_path = NULL, trashed_missing = 0 WHERE id = ?1",
+            params![id],
+        )?;
+        Ok(())
+    }
+    
+    /// Record where a trashed file was moved on disk
+    pub fn set_quarantined_path(&self, id: i64, path: &Path) -> Result<()> {
+        self.conn.execute(
+            "UPDATE files SET quarantined_path = ?1 WHERE id = ?2",
+            params![path.to_string_lossy(), id],
+        )?;
+        Ok(())
+    }
+    
+    /// Trashed files, most recent first
+    pub fn get_trash(&self) -> Result<Vec<GameFile>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM files
+             WHERE deleted_at IS NOT NULL
+             ORDER BY deleted_at DESC, id DESC",
+            FILE_COLUMNS
+        ))?;
+        
+        let files = stmt.query_map([], game_file_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(files)
+    }
+    
+    /// Write a consistent copy of the whole database to a new file
+    pub fn backup_to(&self, path: &Path) -> Result<()> {
+        if path.exists() {
+            return Err(anyhow!("{} already exists", path.display()));
+        }
+        self.conn.execute("VACUUM INTO ?1", params![path.to_string_lossy()])?;
+        Ok(())
+    }
+    
+    /// Record a file received from the Switch
+    pub fn record_upload(
+        &self,
+        filename: &str,
+        path: &Path,
+        category: &str,
+        size: u64,
+        hash: &str,
+    ) -> Result<i64> {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs() as i64;
+        
+        self.conn.execute(
+            "INSERT INTO uploads (filename, path, category, size, hash, received_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(path) DO UPDATE SET
+                size = excluded.size,
+                hash = excluded.hash,
+                received_at = excluded.received_at",
+            params![filename, path.to_string_lossy(), category, size as i64, hash, now],
+        )?;
+        
+        Ok(self.conn.last_insert_rowid())
+    }
+    
+    /// Get files received from the Switch
+    pub fn get_uploads(&self) -> Result<Vec<UploadRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, filename, path, category, size, hash, received_at
+             FROM uploads
+             ORDER BY received_at DESC"
+        )?;
         
-        for file in files {
-            if !Path::new(&file.path).exists() {
-                self.remove_file(file.id)?;
-                removed += 1;
+        let uploads = stmt.query_map([], |row| {
+            Ok(UploadRecord {
+                id: row.get(0)?,
+                filename: row.get(1)?,
+                path: row.get(2)?,
+                category: row.get(3)?,
+                size: row.get::<_, i64>(4)? as u64,
+                hash: row.get(5)?,
+                received_at: row.get(6)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(uploads)
+    }
+    
+    /// Add a save snapshot
+    pub fn add_save_snapshot(
+        &self,
+        title_id: &str,
+        user_id: &str,
+        path: &Path,
+        size: u64,
+        hash: &str,
+        created_at: i64,
+    ) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO save_snapshots (title_id, user_id, path, size, hash, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![title_id, user_id, path.to_string_lossy(), size as i64, hash, created_at],
+        )?;
+        
+        Ok(self.conn.last_insert_rowid())
+    }
+    
+    /// Get titles and users that have save snapshots
+    pub fn get_save_groups(&self) -> Result<Vec<SaveGroup>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT title_id, user_id, COUNT(*), SUM(size), MAX(created_at)
+             FROM save_snapshots
+             GROUP BY title_id, user_id
+             ORDER BY MAX(created_at) DESC"
+        )?;
+        
+        let groups = stmt.query_map([], |row| {
+            Ok(SaveGroup {
+                title_id: row.get(0)?,
+                user_id: row.get(1)?,
+                snapshot_count: row.get::<_, i64>(2)? as usize,
+                total_size: row.get::<_, i64>(3)? as u64,
+                latest: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(groups)
+    }
+    
+    /// Get snapshots of one title and user, newest first
+    pub fn get_save_snapshots(&self, title_id: &str, user_id: &str) -> Result<Vec<SaveSnapshot>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, title_id, user_id, path, size, hash, created_at, pinned
+             FROM save_snapshots
+             WHERE title_id = ?1 AND user_id = ?2
+             ORDER BY created_at DESC, id DESC"
+        )?;
+        
+        let snapshots = stmt.query_map(params![title_id, user_id], |row| {
+            Ok(SaveSnapshot {
+                id: row.get(0)?,
+                title_id: row.get(1)?,
+                user_id: row.get(2)?,
+                path: row.get(3)?,
+                size: row.get::<_, i64>(4)? as u64,
+                hash: row.get(5)?,
+                created_at: row.get(6)?,
+                pinned: row.get::<_, i32>(7)? != 0,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(snapshots)
+    }
+    
+    /// Toggle whether a snapshot is protected from retention
+    pub fn toggle_save_pinned(&self, id: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE save_snapshots SET pinned = NOT pinned WHERE id = ?1",
+            params![id],
+        )?;
+        Ok(())
+    }
+    
+    /// Remove a save snapshot
+    pub fn remove_save_snapshot(&self, id: i64) -> Result<()> {
+        self.conn.execute("DELETE FROM save_snapshots WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+    
+    /// Record a session event
+    pub fn record_session_event(&self, event: &str, detail: &str) -> Result<()> {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs() as i64;
+        
+        self.conn.execute(
+            "INSERT INTO session_events (occurred_at, event, detail) VALUES (?1, ?2, ?3)",
+            params![now, event, detail],
+        )?;
+        Ok(())
+    }
+    
+    /// Get the most recent session events
+    pub fn get_session_events(&self, limit: usize) -> Result<Vec<SessionEvent>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, occurred_at, event, detail
+             FROM session_events
+             ORDER BY occurred_at DESC, id DESC
+             LIMIT ?1"
+        )?;
+        
+        let events = stmt.query_map(params![limit as i64], |row| {
+            Ok(SessionEvent {
+                id: row.get(0)?,
+                occurred_at: row.get(1)?,
+                event: row.get(2)?,
+                detail: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(events)
+    }
+    
+    /// Record that the last byte of a file was sent to the console
+    ///
+    /// The client may request the final range again after a retry; until the
+    /// console reports an outcome that refreshes the same entry.
+    pub fn record_sent(&self, filename: &str, path: &Path) -> Result<()> {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs() as i64;
+        let path = path.to_string_lossy().to_string();
+        
+        let updated = self.conn.execute(
+            "UPDATE install_history SET path = ?1, sent_at = ?2
+             WHERE id = (SELECT id FROM install_history
+                         WHERE filename = ?3 AND status = 'sent'
+                         ORDER BY id DESC LIMIT 1)",
+            params![path, now, filename],
+        )?;
+        
+        if updated == 0 {
+            self.conn.execute(
+                "INSERT INTO install_history (filename, path, status, sent_at)
+                 VALUES (?1, ?2, 'sent', ?3)",
+                params![filename, path, now],
+            )?;
+        }
+        Ok(())
+    }
+    
+    /// Record the console's final install outcome for a file
+    ///
+    /// Completes the latest `sent` entry for the file, or adds a new entry if
+    /// the file was sent before history was kept.
+    pub fn record_install_outcome(
+        &self,
+        filename: &str,
+        path: &Path,
+        content_id: &str,
+        success: bool,
+        phase: &str,
+        result_code: u32,
+    ) -> Result<()> {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs() as i64;
+        let status = if success { "installed" } else { "failed" };
+        
+        let updated = self.conn.execute(
+            "UPDATE install_history
+             SET content_id = ?1, status = ?2, phase = ?3, result_code = ?4, finished_at = ?5
+             WHERE id = (SELECT id FROM install_history
+                         WHERE filename = ?6 AND status = 'sent'
+                         ORDER BY id DESC LIMIT 1)",
+            params![content_id, status, phase, result_code, now, filename],
+        )?;
+        
+        if updated == 0 {
+            self.conn.execute(
+                "INSERT INTO install_history
+                    (filename, path, content_id, status, phase, result_code, finished_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    filename,
+                    path.to_string_lossy().to_string(),
+                    content_id,
+                    status,
+                    phase,
+                    result_code,
+                    now
+                ],
+            )?;
+        }
+        
+        if success {
+            if let Some(file) = self.get_file_by_path(path)? {
+                self.record_install(file.id)?;
             }
         }
         
-        Ok(removed)
+        Ok(())
     }
+    
+    /// Get the most recent install history entries
+    pub fn get_install_history(&self, limit: usize) -> Result<Vec<InstallRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, filename, path, content_id, status, phase, result_code, sent_at, finished_at
+             FROM install_history
+             ORDER BY id DESC
+             LIMIT ?1"
+        )?;
+        
+        let records = stmt.query_map(params![limit as i64], |row| {
+            Ok(InstallRecord {
+                id: row.get(0)?,
+                filename: row.get(1)?,
+                path: row.get(2)?,
+                content_id: row.get(3)?,
+                status: row.get(4)?,
+                phase: row.get(5)?,
+                result_code: row.get(6)?,
+                sent_at: row.get(7)?,
+                finished_at: row.get(8)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(records)
+    }
+    
+    /// All collections by name, with item counts
+    pub fn get_collections(&self) -> Result<Vec<Collection>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT collections.id, collections.name, COUNT(collection_items.path),
+                    collections.created_at, collections.updated_at
+             FROM collections LEFT JOIN collection_items ON collection_items.collection_id = collections.id
+             GROUP BY collections.id
+             ORDER BY collections.name COLLATE NOCASE"
+        )?;
+        
+        let collections = stmt.query_map([], |row| {
+            Ok(Collection {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                item_count: row.get::<_, i64>(2)? as usize,
+                created_at: row.get(3)?,
+                updated_at: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(collections)
+    }
+    
+    /// A collection's files, in order
+    pub fn get_collection_items(&self, name: &str) -> Result<Vec<PathBuf>> {
+        let id = self.collection_id(name)?
+            .ok_or_else(|| anyhow!("No collection named {:?}", name))?;
+        self.paths_in(
+            "SELECT path FROM collection_items WHERE collection_id = ?1 ORDER BY position",
+            params![id],
+        )
+    }
+    
+    /// Create a collection; fails if the name is taken
+    pub fn create_collection(&self, name: &str, paths: &[PathBuf]) -> Result<i64> {
+        let name = normalize_name(name)?;
+        if self.collection_id(&name)?.is_some() {
+            return Err(anyhow!("A collection named {:?} already exists", name));
+        }
+        
+        let now = unix_now()?;
+        let tx = self.savepoint()?;
+        self.conn.execute(
+            "INSERT INTO collections (name, created_at, updated_at) VALUES (?1, ?2, ?2)",
+            params![name, now],
+        )?;
+        let id = self.conn.last_insert_rowid();
+        self.insert_collection_items(id, paths)?;
+        tx.commit()?;
+        Ok(id)
+    }
+    
+    /// Replace a collection's files, keeping the first of any repeated path
+    pub fn set_collection_items(&self, name: &str, paths: &[PathBuf]) -> Result<()> {
+        let id = self.collection_id(name)?
+            .ok_or_else(|| anyhow!("No collection named {:?}", name))?;
+        
+        let tx = self.savepoint()?;
+        self.conn.execute("DELETE FROM collection_items WHERE collection_id = ?1", params![id])?;
+        self.insert_collection_items(id, paths)?;
+        self.conn.execute(
+            "UPDATE collections SET updated_at = ?1 WHERE id = ?2",
+            params![unix_now()?, id],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+    
+    pub fn rename_collection(&self, from: &str, to: &str) -> Result<()> {
+        let to = normalize_name(to)?;
+        let id = self.collection_id(from)?
+            .ok_or_else(|| anyhow!("No collection named {:?}", from))?;
+        if self.collection_id(&to)?.is_some_and(|existing| existing != id) {
+            return Err(anyhow!("A collection named {:?} already exists", to));
+        }
+        
+        self.conn.execute(
+            "UPDATE collections SET name = ?1, updated_at = ?2 WHERE id = ?3",
+            params![to, unix_now()?, id],
+        )?;
+        Ok(())
+    }
+    
+    /// Copy a collection under a new name
+    pub fn duplicate_collection(&self, from: &str, to: &str) -> Result<i64> {
+        let paths = self.get_collection_items(from)?;
+        self.create_collection(to, &paths)
+    }
+    
+    pub fn delete_collection(&self, name: &str) -> Result<()> {
+        if let Some(id) = self.collection_id(name)? {
+            self.conn.execute("DELETE FROM collections WHERE id = ?1", params![id])?;
+        }
+        Ok(())
+    }
+    
+    /// Register a folder as a library root and attach the files under it
+    ///
+    /// Adding a root that exists updates its volume and marks it online.
+    pub fn add_root(&self, path: &Path, volume_id: Option<&str>, label: Option<&str>, volume_path: &str) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO library_roots (path, volume_id, label, volume_path, online, added_at)
+             VALUES (?1, ?2, ?3, ?4, 1, ?5)
+             ON CONFLICT(path) DO UPDATE SET
+                volume_id = excluded.volume_id,
+                label = excluded.label,
+                volume_path = excluded.volume_path,
+                online = 1",
+            params![path.to_string_lossy(), volume_id, label, volume_path, unix_now()?],
+        )?;
+        let id = self.conn.query_row(
+            "SELECT id FROM library_roots WHERE path = ?1",
+            params![path.to_string_lossy()],
+            |row| row.get(0),
+        )?;
+        self.assign_roots()?;
+        Ok(id)
+    }
+    
+    /// Library roots by path, with file counts
+    pub fn get_roots(&self) -> Result<Vec<LibraryRoot>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT r.id, r.path, r.volume_id, r.label, r.volume_path, r.online,
+                    (SELECT COUNT(*) FROM files WHERE files.root_id = r.id AND files.deleted_at IS NULL)
+             FROM library_roots r
+             ORDER BY r.path"
+        )?;
+        
+        let roots = stmt.query_map([], |row| {
+            Ok(LibraryRoot {
+                id: row.get(0)?,
+                path: PathBuf::from(row.get::<_, String>(1)?),
+                volume_id: row.get(2)?,
+                label: row.get(3)?,
+                volume_path: row.get(4)?,
+                online: row.get::<_, i32>(5)? != 0,
+                file_count: row.get::<_, i64>(6)? as usize,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(roots)
+    }
+    
+    /// Forget a root; its files keep their current paths, or move to a root containing it
+    pub fn remove_root(&self, id: i64) -> Result<()> {
+        let tx = self.savepoint()?;
+        self.conn.execute("UPDATE files SET root_id = NULL, rel_path = NULL WHERE root_id = ?1", params![id])?;
+        self.conn.execute("DELETE FROM library_roots WHERE id = ?1", params![id])?;
+        tx.commit()?;
+        self.assign_roots()
+    }
+    
+    pub fn set_root_online(&self, id: i64, online: bool) -> Result<()> {
+        self.conn.execute("UPDATE library_roots SET online = ?1 WHERE id = ?2", params![online, id])?;
+        Ok(())
+    }
+    
+    /// Point a root at a new mount point and mark it online
+    ///
+    /// Its files' paths follow, and so do collection and queue entries for
+    /// them, as do roots nested inside it on the same volume. A file whose
+    /// new path is already in the library keeps the old one. Returns how
+    /// many files moved.
+    pub fn relocate_root(&self, id: i64, path: &Path) -> Result<usize> {
+        let roots = self.get_roots()?;
+        let Some(outer) = roots.iter().find(|root| root.id == id) else {
+            return Ok(0);
+        };
+        
+        let tx = self.savepoint()?;
+        let mut moved = self.move_root_files(id, path)?;
+        for nested in roots.iter().filter(|root| volumes::moves_with(outer, root)) {
+            let rel = volumes::relative_path(&outer.path, &nested.path).unwrap_or_default();
+            moved += self.move_root_files(nested.id, &volumes::join_relative(path, &rel))?;
+        }
+        tx.commit()?;
+        Ok(moved)
+    }
+    
+    /// Point one root and its files at `path`, without touching nested roots
+    fn move_root_files(&self, id: i64, path: &Path) -> Result<usize> {
+        let files = self.conn
+            .prepare("SELECT id, path, rel_path FROM files WHERE root_id = ?1")?
+            .query_map(params![id], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        
+        let mut moved = 0;
+        for (file_id, old, rel_path) in files {
+            let new = volumes::join_relative(path, &rel_path).to_string_lossy().to_string();
+            if new == old {
+                continue;
+            }
+            let updated = self.conn.execute(
+                "UPDATE OR IGNORE files SET path = ?1 WHERE id = ?2",
+                params![new, file_id],
+            )?;
+            if updated == 0 {
+                continue;
+            }
+            self.conn.execute(
+                "UPDATE OR IGNORE collection_items SET path = ?1 WHERE path = ?2",
+                params![new, old],
+            )?;
+            self.conn.execute("UPDATE queue_items SET path = ?1 WHERE path = ?2", params![new, old])?;
+            moved += 1;
+        }
+        self.conn.execute(
+            "UPDATE library_roots SET path = ?1, online = 1 WHERE id = ?2",
+            params![path.to_string_lossy(), id],
+        )?;
+        Ok(moved)
+    }
+    
+    /// Root IDs and paths, without the file counts `get_roots` adds
+    fn root_paths(&self) -> Result<Vec<(i64, PathBuf)>> {
+        let mut stmt = self.conn.prepare("SELECT id, path FROM library_roots")?;
+        let roots = stmt.query_map([], |row| {
+            Ok((row.get(0)?, PathBuf::from(row.get::<_, String>(1)?)))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        Ok(roots)
+    }
+    
+    /// Attach every file to its deepest root
+    fn assign_roots(&self) -> Result<()> {
+        let roots = self.root_paths()?;
+        let files = self.paths_in("SELECT path FROM files", [])?;
+        let tx = self.savepoint()?;
+        for path in files {
+            let (root_id, rel_path) = deepest_root(&roots, &path).unzip();
+            self.conn.execute(
+                "UPDATE files SET root_id = ?1, rel_path = ?2 WHERE path = ?3",
+                params![root_id, rel_path, path.to_string_lossy()],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+    
+    /// Remember the queue for the next start
+    pub fn save_queue(&self, paths: &[PathBuf]) -> Result<()> {
+        let tx = self.savepoint()?;
+        self.conn.execute("DELETE FROM queue_items", [])?;
+        for (position, path) in paths.iter().enumerate() {
+            self.conn.execute(
+                "INSERT INTO queue_items (position, path) VALUES (?1, ?2)",
+                params![position as i64, path.to_string_lossy()],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+    
+    pub fn load_queue(&self) -> Result<Vec<PathBuf>> {
+        self.paths_in("SELECT path FROM queue_items ORDER BY position", [])
+    }
+    
+    fn collection_id(&self, name: &str) -> Result<Option<i64>> {
+        // Stored names are normalized, so an empty one can't match
+        let Ok(name) = normalize_name(name) else {
+            return Ok(None);
+        };
+        let id = self.conn.query_row(
+            "SELECT id FROM collections WHERE name = ?1",
+            params![name],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(id)
+    }
+    
+    fn insert_collection_items(&self, id: i64, paths: &[PathBuf]) -> Result<()> {
+        let mut position = 0i64;
+        for path in paths {
+            position += self.conn.execute(
+                "INSERT OR IGNORE INTO collection_items (collection_id, position, path) VALUES (?1, ?2, ?3)",
+                params![id, position, path.to_string_lossy()],
+            )? as i64;
+        }
+        Ok(())
+    }
+    
+    fn paths_in(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<PathBuf>> {
+        let paths = self.conn
+            .prepare(sql)?
+            .query_map(params, |row| Ok(PathBuf::from(row.get::<_, String>(0)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(paths)
+    }
+}
+
+/// Trim and collapse whitespace; tag and collection names can't be empty
+fn normalize_name(name: &str) -> Result<String> {
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    if name.is_empty() {
+        return Err(anyhow!("Name is empty"));
+    }
+    Ok(name)
+}
+
+fn unix_now() -> Result<i64> {
+    Ok(SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs() as i64)
 }
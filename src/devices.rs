@@ -0,0 +1,263 @@
+/// One session per connected Switch, all serving the same catalog
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tracing::{error, info};
+
+use crate::bandwidth::Bandwidth;
+use crate::config::Config;
+use crate::usb::{DeviceInfo, SwitchLinkServer, TransferProgress, UsbConnection};
+
+/// Session ID used when no console could be enumerated up front
+const AUTO_DEVICE_ID: &str = "auto";
+
+/// A server thread bound to one console
+pub struct DeviceSession {
+    /// `None` when the session takes whichever console it finds first
+    pub device: Option<DeviceInfo>,
+    pub progress: Arc<Mutex<TransferProgress>>,
+    queue: Arc<Mutex<HashMap<String, PathBuf>>>,
+    running: Arc<Mutex<bool>>,
+    held: Arc<Mutex<Option<DeviceInfo>>>,
+    handle: thread::JoinHandle<()>,
+}
+
+impl DeviceSession {
+    pub fn id(&self) -> String {
+        self.device
+            .as_ref()
+            .map(|d| d.id())
+            .unwrap_or_else(|| AUTO_DEVICE_ID.to_string())
+    }
+
+    pub fn label(&self) -> String {
+        self.device
+            .as_ref()
+            .map(|d| d.label())
+            .unwrap_or_else(|| "Switch".to_string())
+    }
+
+    /// The session thread has exited (console unplugged or an error)
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// The console the session has open, if it connected
+    pub fn held(&self) -> Option<DeviceInfo> {
+        self.held.lock().ok().and_then(|held| held.clone())
+    }
+
+    /// Number of files this console is currently offered
+    pub fn queue_len(&self) -> usize {
+        self.queue.lock().map(|q| q.len()).unwrap_or(0)
+    }
+}
+
+/// Runs a session per console and hands each its share of the queue
+///
+/// Files pinned to a console are only offered to that console; unpinned
+/// files are offered to every console.
+#[derive(Default)]
+pub struct DeviceManager {
+    config: Config,
+    sessions: Vec<DeviceSession>,
+    catalog: HashMap<String, PathBuf>,
+    // file name -> device ID
+    pins: HashMap<String, String>,
+    // Files kept out of every queue, e.g. ones that failed verification
+    // Shared with every session, which also refuses them by browse path
+    withheld: Arc<Mutex<HashSet<PathBuf>>>,
+    bandwidth: Arc<Bandwidth>,
+}
+
+impl DeviceManager {
+    pub fn new(config: Config) -> Self {
+        Self {
+            bandwidth: Arc::new(Bandwidth::new(config.bandwidth.clone())),
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Keep limits and the pause state from an earlier run
+    pub fn with_bandwidth(mut self, bandwidth: Arc<Bandwidth>) -> Self {
+        self.bandwidth = bandwidth;
+        self
+    }
+
+    /// Limits and "pause all", shared by every session
+    pub fn bandwidth(&self) -> &Arc<Bandwidth> {
+        &self.bandwidth
+    }
+
+    pub fn sessions(&self) -> &[DeviceSession] {
+        &self.sessions
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.sessions.is_empty()
+    }
+
+    /// Start a session for every connected console, returning how many were found
+    ///
+    /// With no console connected a single session waits for the first one,
+    /// as a single-console setup always did.
+    pub fn start(&mut self) -> Result<usize> {
+        let devices = UsbConnection::enumerate(&self.config.usb, &[])?;
+        let found = devices.len();
+
+        if devices.is_empty() {
+            self.spawn(None);
+        } else {
+            for device in devices {
+                self.spawn(Some(device));
+            }
+        }
+
+        Ok(found)
+    }
+
+    /// Start sessions for consoles plugged in since `start`, returning how many were added
+    ///
+    /// Consoles a running session has open are never opened again, since
+    /// opening resets the device and would end that session's transfer.
+    pub fn rescan(&mut self) -> Result<usize> {
+        // A session started without a console takes on the ID of the one it found
+        let mut identified = false;
+        for session in self.sessions.iter_mut().filter(|s| s.device.is_none()) {
+            session.device = session.held();
+            identified |= session.device.is_some();
+        }
+        if identified {
+            self.distribute();
+        }
+
+        let held: Vec<DeviceInfo> = self
+            .sessions
+            .iter()
+            .filter(|s| !s.is_finished())
+            .filter_map(|s| s.held())
+            .collect();
+
+        let mut added = 0;
+        for device in UsbConnection::enumerate(&self.config.usb, &held)? {
+            let id = device.id();
+            if let Some(pos) = self.sessions.iter().position(|s| s.id() == id) {
+                // Replug into the same port: replace the finished session
+                if !self.sessions[pos].is_finished() {
+                    continue;
+                }
+                self.sessions.remove(pos);
+            }
+            self.spawn(Some(device));
+            added += 1;
+        }
+        Ok(added)
+    }
+
+    pub fn stop(&mut self) {
+        for session in &self.sessions {
+            if let Ok(mut running) = session.running.lock() {
+                *running = false;
+            }
+        }
+        // Threads notice the flag on their next poll and exit on their own
+        self.sessions.clear();
+        info!("All SwitchLink sessions stopped");
+    }
+
+    /// Update the files being served; unchanged lists are a no-op
+    pub fn set_catalog(&mut self, files: &HashMap<String, PathBuf>) {
+        if &self.catalog != files {
+            self.catalog = files.clone();
+            self.distribute();
+        }
+    }
+
+    /// Keep these files from every console; unchanged sets are a no-op
+    pub fn set_withheld(&mut self, paths: HashSet<PathBuf>) {
+        let changed = match self.withheld.lock() {
+            Ok(mut withheld) if *withheld != paths => {
+                *withheld = paths;
+                true
+            }
+            _ => false,
+        };
+        if changed {
+            self.distribute();
+        }
+    }
+
+    /// Pin a file to one console, or unpin it with `None`
+    pub fn pin(&mut self, name: &str, device_id: Option<String>) {
+        match device_id {
+            Some(id) => self.pins.insert(name.to_string(), id),
+            None => self.pins.remove(name),
+        };
+        self.distribute();
+    }
+
+    pub fn pinned_to(&self, name: &str) -> Option<&str> {
+        self.pins.get(name).map(|id| id.as_str())
+    }
+
+    fn spawn(&mut self, device: Option<DeviceInfo>) {
+        let progress = Arc::new(Mutex::new(TransferProgress::default()));
+        let queue = Arc::new(Mutex::new(HashMap::new()));
+
+        let mut server = SwitchLinkServer::new_with_progress(queue.clone(), progress.clone())
+            .with_config(self.config.clone())
+            .with_bandwidth(self.bandwidth.clone())
+            .with_refused(self.withheld.clone());
+        if let Some(device) = &device {
+            server = server.with_device(device.clone());
+        }
+        let running = server.running_flag();
+        *running.lock().unwrap() = true;
+        let held = server.held_device();
+
+        let thread_progress = progress.clone();
+        let label = device.as_ref().map(|d| d.label()).unwrap_or_default();
+        let handle = thread::spawn(move || {
+            if let Err(e) = server.run() {
+                error!("Server error {}: {}", label, e);
+                if let Ok(mut p) = thread_progress.lock() {
+                    p.add_log(format!("[!] Session ended: {}", e));
+                }
+            }
+        });
+
+        self.sessions.push(DeviceSession {
+            device,
+            progress,
+            queue,
+            running,
+            held,
+            handle,
+        });
+        self.distribute();
+    }
+
+    /// The files offered to the console with this ID
+    pub fn queue_for(&self, device_id: &str) -> HashMap<String, PathBuf> {
+        let withheld = self.withheld.lock().map(|w| w.clone()).unwrap_or_default();
+        self.catalog
+            .iter()
+            .filter(|(name, path)| {
+                !withheld.contains(*path) && self.pins.get(*name).is_none_or(|pinned| pinned == device_id)
+            })
+            .map(|(name, path)| (name.clone(), path.clone()))
+            .collect()
+    }
+
+    fn distribute(&self) {
+        for session in &self.sessions {
+            let files = self.queue_for(&session.id());
+            if let Ok(mut queue) = session.queue.lock() {
+                *queue = files;
+            }
+        }
+    }
+}
@@ -71,3 +71,9 @@ pub fn get_file_size(path: &PathBuf) -> u64 {
         .map(|m| m.len())
         .unwrap_or(0)
 }
+
+pub fn format_timestamp(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
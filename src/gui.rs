@@ -1,15 +1,38 @@
 /// eGUI version - Modern, Beautiful UI for SwitchLink
 use eframe::egui;
 use eframe::egui::{CentralPanel, Context, ProgressBar, ScrollArea, Ui, Color32, Stroke, Rounding, Vec2};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use crate::database::Database;
-use crate::file_manager::{add_files, add_files_from_directory, format_file_size};
-use crate::usb::{SwitchLinkServer, TransferProgress};
+use crate::backup::{self, ImportOptions, LibraryExport, MatchBy, PathRewrite};
+use crate::config::Config;
+use crate::collection::{CollectionExport, ServedList};
+use crate::control::{self, ExtractSummary};
+use crate::database::{Collection, Database, GameFile, SearchResults, Tag, TitleInfo};
+use crate::devices::{DeviceManager, DeviceSession};
+use crate::file_manager::{format_file_size, format_timestamp};
+use crate::health::{self, HealthReport};
+use crate::keys::KeySet;
+use crate::nca::NcaError;
+use crate::protocol::InstallOutcome;
+use crate::saves::{diff_snapshots, restore_name, DiffChange, SaveDiffEntry, SaveLibrary};
+use crate::search::SearchQuery;
+use crate::trash;
+use crate::verify::{self, CheckStatus, VerifyStatus, VerifySummary};
+use crate::volumes;
+use crate::{title, titledb};
+
+/// Library rows shown at a time
+const LIBRARY_PAGE_SIZE: usize = 200;
+
+/// Re-run the library query this often so installs and new files show up
+const LIBRARY_REFRESH: Duration = Duration::from_secs(5);
+
+/// Icon size in the library table and grid
+const TABLE_ICON_SIZE: f32 = 32.0;
+const GRID_ICON_SIZE: f32 = 128.0;
 
 // Modern Color Palette
 pub struct ColorTheme {
@@ -20,6 +43,8 @@ pub struct ColorTheme {
     
     // Accent colors
     accent: Color32,
+    // Part of the palette, not applied to any widget yet
+    #[allow(dead_code)]
     accent_hover: Color32,
     
     // Status colors
@@ -40,6 +65,7 @@ pub struct ColorTheme {
     
     // Border colors
     border: Color32,
+    #[allow(dead_code)]
     border_hover: Color32,
 }
 
@@ -73,28 +99,171 @@ impl Default for ColorTheme {
     }
 }
 
+#[derive(Default, Clone, Copy, PartialEq)]
+enum AppTab {
+    #[default]
+    Library,
+    Saves,
+    Tags,
+    Collections,
+    Health,
+    Trash,
+}
+
+/// Labels of the title override form, in `title_fields` order
+const TITLE_FIELDS: [&str; 6] = ["Name", "Publisher", "Release date", "Region", "Description", "Icon path"];
+
+/// Override form for one title
+struct TitleEditor {
+    title_id: String,
+    /// Values from the imported title database
+    imported: TitleInfo,
+    fields: [String; 6],
+}
+
+/// A library health scan running in the background
+#[derive(Default)]
+struct HealthScan {
+    hashed: usize,
+    to_hash: usize,
+    // Files that couldn't be hashed, as (path, error)
+    unreadable: Vec<(String, String)>,
+    result: Option<Result<HealthReport, String>>,
+}
+
+/// Control NCAs being read in the background
+#[derive(Default)]
+struct ControlScan {
+    done: usize,
+    total: usize,
+    result: Option<Result<ExtractSummary, String>>,
+}
+
+/// Library files being verified in the background
+#[derive(Default)]
+struct VerifyScan {
+    done: usize,
+    total: usize,
+    result: Option<Result<VerifySummary, String>>,
+}
+
+/// An export being imported, with the options picked for it
+struct LibraryImport {
+    path: PathBuf,
+    export: LibraryExport,
+    match_by: MatchBy,
+    // FROM and TO of each path rewrite, as typed
+    rewrites: Vec<(String, String)>,
+    add_missing: bool,
+}
+
+/// How long trashing or deleting files can still be taken back
+const UNDO_WINDOW: Duration = Duration::from_secs(10);
+
+/// The last change to the library that can still be undone
+struct Undo {
+    message: String,
+    action: UndoAction,
+    until: Instant,
+}
+
+enum UndoAction {
+    // IDs of files just moved to the trash; undo restores them
+    Restore(Vec<i64>),
+    // Trashed files to delete from disk once the window is over; undo keeps them
+    Delete(Vec<GameFile>),
+}
+
+/// A trash action waiting to be confirmed
+enum TrashConfirm {
+    DeleteFiles(Vec<GameFile>),
+    Forget(Vec<GameFile>),
+}
+
+/// A button pressed in the trash, applied once the list is drawn
+enum TrashAction {
+    FindMissing,
+    TrashMissing,
+    DismissMissing,
+    Restore(GameFile),
+    Quarantine(GameFile),
+    Delete(GameFile),
+    Empty,
+}
+
+/// A button pressed on a collection, applied once the list is drawn
+enum CollectionAction {
+    Serve,
+    Load,
+    Edit,
+    Duplicate,
+    Export,
+    Delete,
+}
+
 #[derive(Default)]
 pub struct DbiApp {
     file_list: HashMap<String, PathBuf>,
     log_messages: Vec<String>,
     server_running: bool,
     connection_status: String,
-    server_thread: Option<thread::JoinHandle<()>>,
-    server_instance: Option<Arc<Mutex<SwitchLinkServer>>>,
-    progress: Arc<Mutex<TransferProgress>>,
+    devices: DeviceManager,
     database: Option<Database>,
+    config: Config,
     search_query: String,
+    // Current page of the library table, re-queried only when stale
+    search_results: Option<SearchResults>,
+    search_loaded_at: Option<Instant>,
+    search_page: usize,
+    // Tag name -> color, refreshed with the library page
+    tag_colors: HashMap<String, Color32>,
+    selected_files: HashSet<i64>,
+    bulk_tag: String,
+    new_tag: String,
+    // Tag ID -> name being typed in the rename box
+    tag_renames: HashMap<i64, String>,
+    // Queue as last written to the database, to save only on change
+    saved_queue: HashMap<String, PathBuf>,
+    collection_name: String,
+    // Collection being edited: its name, new name and files in order
+    editing_collection: Option<String>,
+    collection_rename: String,
+    collection_draft: Vec<PathBuf>,
+    health_scan: Option<Arc<Mutex<HealthScan>>>,
+    health_report: Option<HealthReport>,
+    // File IDs ticked in the health report
+    health_selected: HashSet<i64>,
+    title_editor: Option<TitleEditor>,
+    control_scan: Option<Arc<Mutex<ControlScan>>>,
+    verify_scan: Option<Arc<Mutex<VerifyScan>>>,
+    library_import: Option<LibraryImport>,
+    // When automatic backups were last checked for being due
+    backup_checked_at: Option<Instant>,
+    // When library roots were last looked for on the mounted volumes
+    roots_checked_at: Option<Instant>,
+    undo: Option<Undo>,
+    trash_confirm: Option<TrashConfirm>,
+    // Files gone from disk, found by a cleanup dry run and not yet trashed
+    cleanup_report: Option<Vec<GameFile>>,
+    // Icon path -> thumbnail, or None if the image couldn't be read
+    icon_textures: HashMap<String, Option<egui::TextureHandle>>,
+    library_grid: bool,
     theme: ColorTheme,
     animation_time: f32,
+    active_tab: AppTab,
+    selected_save_group: Option<(String, String)>,
+    save_compare: Vec<i64>,
+    save_diff: Option<Vec<SaveDiffEntry>>,
 }
 
 impl DbiApp {
     pub fn new() -> Self {
+        Self::with_config(Config::load())
+    }
+    
+    pub fn with_config(config: Config) -> Self {
         // Initialize database
-        let db_path = dirs::data_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("switchlink")
-            .join("games.db");
+        let db_path = config.db_path.clone();
         
         // Create directory if needed
         if let Some(parent) = db_path.parent() {
@@ -103,11 +272,27 @@ impl DbiApp {
         
         let database = Database::new(&db_path).ok();
         
+        // Pick up the queue from last time
+        let mut log_messages = vec!["🚀 SwitchLink started - Ready to transfer!".to_string()];
+        let restored = database.as_ref()
+            .and_then(|db| db.load_queue().ok())
+            .map(|paths| ServedList::resolve(&paths))
+            .unwrap_or_default();
+        if !restored.files.is_empty() {
+            log_messages.push(format!("[i] Restored {} queued files", restored.files.len()));
+        }
+        if !restored.missing.is_empty() {
+            log_messages.push(format!("[!] {} queued files are no longer there", restored.missing.len()));
+        }
+        
         Self {
-            log_messages: vec!["🚀 SwitchLink started - Ready to transfer!".to_string()],
+            file_list: restored.files.clone(),
+            saved_queue: restored.files,
+            log_messages,
             connection_status: "Disconnected".to_string(),
-            progress: Arc::new(Mutex::new(TransferProgress::default())),
+            devices: DeviceManager::new(config.clone()),
             database,
+            config,
             search_query: String::new(),
             theme: ColorTheme::default(),
             animation_time: 0.0,
@@ -121,30 +306,22 @@ impl DbiApp {
             return;
         }
 
-        // Reset progress
-        if let Ok(mut progress) = self.progress.lock() {
-            *progress = TransferProgress::default();
-        }
-
-        // Create shared file list
-        let file_list = Arc::new(Mutex::new(self.file_list.clone()));
-        
-        // Create server instance with progress tracking
-        let server = SwitchLinkServer::new_with_progress(file_list, self.progress.clone());
-        let server_arc = Arc::new(Mutex::new(server));
-        self.server_instance = Some(server_arc.clone());
-
-        // Start server in background thread
-        let server_clone = server_arc.clone();
-        let handle = thread::spawn(move || {
-            if let Ok(mut server) = server_clone.lock() {
-                if let Err(e) = server.start() {
-                    eprintln!("Server error: {}", e);
-                }
+        // One session per connected console, all sharing the queue and the limits
+        let bandwidth = self.devices.bandwidth().clone();
+        self.devices = DeviceManager::new(self.config.clone()).with_bandwidth(bandwidth);
+        self.devices.set_catalog(&self.file_list);
+        self.refresh_withheld();
+        match self.devices.start() {
+            Ok(found) if found > 1 => {
+                self.log_messages.push(format!("[i] Found {} consoles", found));
             }
-        });
+            Ok(_) => {}
+            Err(e) => {
+                self.log_messages.push(format!("[!] USB error: {}", e));
+                return;
+            }
+        }
 
-        self.server_thread = Some(handle);
         self.server_running = true;
         self.connection_status = "Connected".to_string();
         self.log_messages.push("[>] Starting SwitchLink server...".to_string());
@@ -152,65 +329,168 @@ impl DbiApp {
     }
 
     fn stop_server(&mut self) {
-        if let Some(server) = &self.server_instance {
-            if let Ok(mut server) = server.lock() {
-                server.stop();
-            }
-        }
+        self.devices.stop();
         
         self.server_running = false;
         self.connection_status = "Disconnected".to_string();
         self.log_messages.push("[x] Server stopped".to_string());
-        self.server_thread = None;
-        self.server_instance = None;
+    }
+    
+    fn toggle_pause(&mut self) {
+        let bandwidth = self.devices.bandwidth();
+        let paused = !bandwidth.is_paused();
+        bandwidth.set_paused(paused);
+        self.log_messages.push(if paused {
+            "[||] Transfers paused".to_string()
+        } else {
+            "[>] Transfers resumed".to_string()
+        });
+    }
+    
+    /// Apply edited limits to running sessions and keep them for next time
+    fn apply_bandwidth(&mut self) {
+        self.devices.bandwidth().set_config(self.config.bandwidth.clone());
+        
+        // Only the limits are saved, not command-line overrides of other settings
+        let mut saved = Config::load();
+        saved.bandwidth = self.config.bandwidth.clone();
+        if let Err(e) = saved.save() {
+            self.log_messages.push(format!("[!] Could not save bandwidth limits: {}", e));
+        }
+    }
+    
+    /// Write the queue to the database so it survives a restart
+    fn save_queue(&mut self) {
+        if let Some(db) = &self.database {
+            if let Err(e) = db.save_queue(&self.queue_paths()) {
+                self.log_messages.push(format!("[!] Could not save the queue: {}", e));
+            }
+        }
+        self.saved_queue = self.file_list.clone();
+    }
+    
+    /// Queued files by name, the order the Switch lists them in
+    fn queue_paths(&self) -> Vec<PathBuf> {
+        let mut queue: Vec<(&String, &PathBuf)> = self.file_list.iter().collect();
+        queue.sort();
+        queue.into_iter().map(|(_, path)| path.clone()).collect()
+    }
+    
+    /// Replace the queue with a collection and start serving it
+    fn serve_collection(&mut self, name: &str) {
+        if self.load_collection(name) && !self.server_running {
+            self.start_server();
+        }
+    }
+    
+    fn load_collection(&mut self, name: &str) -> bool {
+        let Some(db) = &self.database else {
+            return false;
+        };
+        match ServedList::load(db, name) {
+            Ok(list) => {
+                if !list.missing.is_empty() {
+                    self.log_messages.push(format!("[!] {} files in {} are missing", list.missing.len(), name));
+                }
+                if !list.duplicates.is_empty() {
+                    self.log_messages.push(format!("[!] Skipped {} files with repeated names", list.duplicates.len()));
+                }
+                self.log_messages.push(format!("[+] Queued {} files from {}", list.files.len(), name));
+                self.file_list = list.files;
+                true
+            }
+            Err(e) => {
+                self.log_messages.push(format!("[!] {}", e));
+                false
+            }
+        }
+    }
+    
+    fn rescan_devices(&mut self) {
+        match self.devices.rescan() {
+            Ok(0) => self.log_messages.push("[i] No new consoles found".to_string()),
+            Ok(added) => self.log_messages.push(format!("[+] Serving {} more console(s)", added)),
+            Err(e) => self.log_messages.push(format!("[!] USB error: {}", e)),
+        }
     }
 }
 
 impl eframe::App for DbiApp {
-    fn update(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
         // Update animation time
         self.animation_time += ctx.input(|i| i.stable_dt);
         
         // Request repaint continuously when server is running for smooth progress updates
         if self.server_running {
             ctx.request_repaint();
+            // Queue edits reach the consoles while they're connected
+            self.devices.set_catalog(&self.file_list);
+        }
+        
+        if self.file_list != self.saved_queue {
+            self.save_queue();
+        }
+        
+        if self.control_scan.is_some() {
+            self.poll_control_scan();
+            ctx.request_repaint_after(Duration::from_millis(200));
+        }
+        
+        if self.verify_scan.is_some() {
+            self.poll_verify_scan();
+            ctx.request_repaint_after(Duration::from_millis(200));
+        }
+        
+        if self.backup_checked_at.is_none_or(|at| at.elapsed() >= Duration::from_secs(3600)) {
+            self.run_due_backup();
+        }
+        
+        // Drives and shares come and go; follow them without a restart
+        if self.roots_checked_at.is_none_or(|at| at.elapsed() >= Duration::from_secs(5)) {
+            self.check_roots();
+        }
+        ctx.request_repaint_after(Duration::from_secs(5));
+        
+        if self.undo.as_ref().is_some_and(|undo| Instant::now() >= undo.until) {
+            self.expire_undo();
+        }
+        if self.undo.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(250));
         }
         
         // Apply custom theme
         self.apply_custom_theme(ctx);
         
         // Check for close request and show confirmation if transfer is in progress
-        if ctx.input(|i| i.viewport().close_requested()) {
-            if self.server_running {
-                // Show confirmation dialog
-                ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
-                
-                egui::Window::new("⚠️ Confirm Close")
-                    .collapsible(false)
-                    .resizable(false)
-                    .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
-                    .show(ctx, |ui| {
-                        ui.vertical_centered(|ui| {
-                            ui.add_space(10.0);
-                            ui.heading("Transfer in Progress");
+        if ctx.input(|i| i.viewport().close_requested()) && self.server_running {
+            // Show confirmation dialog
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            
+            egui::Window::new("⚠️ Confirm Close")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.vertical_centered(|ui| {
+                        ui.add_space(10.0);
+                        ui.heading("Transfer in Progress");
+                        ui.add_space(10.0);
+                        ui.label("Are you sure you want to close?");
+                        ui.label("This will interrupt the current transfer.");
+                        ui.add_space(20.0);
+                        
+                        ui.horizontal(|ui| {
+                            if self.danger_button(ui, "Yes, Close").clicked() {
+                                self.stop_server();
+                                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                            }
                             ui.add_space(10.0);
-                            ui.label("Are you sure you want to close?");
-                            ui.label("This will interrupt the current transfer.");
-                            ui.add_space(20.0);
-                            
-                            ui.horizontal(|ui| {
-                                if self.danger_button(ui, "Yes, Close").clicked() {
-                                    self.stop_server();
-                                    ctx.send_viewport_cmd(egui::ViewportCommand::Close);
-                                }
-                                ui.add_space(10.0);
-                                if self.secondary_button(ui, "Cancel").clicked() {
-                                    // Just close the dialog
-                                }
-                            });
+                            if self.secondary_button(ui, "Cancel").clicked() {
+                                // Just close the dialog
+                            }
                         });
                     });
-            }
+                });
         }
         
         // Top panel - Header with gradient
@@ -228,7 +508,13 @@ impl eframe::App for DbiApp {
                     ui.add_space(20.0);
                     
                     // Server status badge
-                    let (status_text, status_color) = if self.server_running {
+                    let stalled = self.devices.sessions().iter()
+                        .any(|session| session.progress.lock().map(|p| p.stalled).unwrap_or(false));
+                    let (status_text, status_color) = if self.server_running && stalled {
+                        ("● Stalled", self.theme.error)
+                    } else if self.server_running && self.devices.bandwidth().is_held() {
+                        ("● Paused", self.theme.warning)
+                    } else if self.server_running {
                         ("● Running", self.theme.success)
                     } else {
                         ("○ Stopped", self.theme.text_muted)
@@ -248,12 +534,33 @@ impl eframe::App for DbiApp {
                                 .size(13.0));
                         });
                     
+                    ui.add_space(20.0);
+                    
+                    // Tabs
+                    ui.selectable_value(&mut self.active_tab, AppTab::Library, "📁 Library");
+                    ui.selectable_value(&mut self.active_tab, AppTab::Saves, "💾 Saves");
+                    ui.selectable_value(&mut self.active_tab, AppTab::Tags, "🏷 Tags");
+                    ui.selectable_value(&mut self.active_tab, AppTab::Collections, "📚 Collections");
+                    ui.selectable_value(&mut self.active_tab, AppTab::Health, "🩺 Health");
+                    ui.selectable_value(&mut self.active_tab, AppTab::Trash, "🗑 Trash");
+                    
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                         // Control buttons
                         if self.server_running {
                             if self.danger_button(ui, "■ Stop Server").clicked() {
                                 self.stop_server();
                             }
+                            if self.secondary_button(ui, "🔄 Rescan").clicked() {
+                                self.rescan_devices();
+                            }
+                            let pause_label = if self.devices.bandwidth().is_paused() {
+                                "▶ Resume All"
+                            } else {
+                                "⏸ Pause All"
+                            };
+                            if self.secondary_button(ui, pause_label).clicked() {
+                                self.toggle_pause();
+                            }
                         } else {
                             if self.primary_button(ui, "▶ Start Server").clicked() {
                                 self.start_server();
@@ -292,6 +599,9 @@ impl eframe::App for DbiApp {
                         {
                             let _ = open::that("https://buymeacoffee.com/silkyland");
                         }
+                        
+                        ui.separator();
+                        self.bandwidth_controls(ui);
                     });
                 });
             });
@@ -320,14 +630,36 @@ impl eframe::App for DbiApp {
                 });
             });
         
+        // Undo bar, just above the activity log while a change can be taken back
+        if self.undo.is_some() {
+            egui::TopBottomPanel::bottom("undo_bar")
+                .frame(egui::Frame::none()
+                    .fill(self.theme.bg_tertiary)
+                    .inner_margin(egui::Margin::symmetric(20.0, 8.0)))
+                .show(ctx, |ui| {
+                    self.undo_bar(ui);
+                });
+        }
+        
         // Central panel - File Library with gradient background
         CentralPanel::default()
             .frame(egui::Frame::none()
                 .fill(self.theme.bg_primary)
                 .inner_margin(egui::Margin::same(20.0)))
             .show(ctx, |ui| {
-                self.file_panel(ui);
+                match self.active_tab {
+                    AppTab::Library => self.file_panel(ui),
+                    AppTab::Saves => self.saves_panel(ui),
+                    AppTab::Tags => self.tags_panel(ui),
+                    AppTab::Collections => self.collections_panel(ui),
+                    AppTab::Health => self.health_panel(ui),
+                    AppTab::Trash => self.trash_panel(ui),
+                }
             });
+        
+        self.title_editor_window(ctx);
+        self.library_import_window(ctx);
+        self.trash_confirm_window(ctx);
     }
 }
 
@@ -468,13 +800,134 @@ impl DbiApp {
             .inner_margin(egui::Margin::same(20.0))
             .stroke(Stroke::new(1.0, self.theme.border))
             .show(ui, |ui| {
-                if let Ok(progress) = self.progress.lock() {
-                    ui.heading(egui::RichText::new("📊 Transfer Progress")
+                ui.heading(egui::RichText::new("📊 Transfer Progress")
+                    .color(self.theme.text_primary)
+                    .size(18.0));
+                
+                ui.add_space(15.0);
+                
+                ScrollArea::vertical()
+                    .id_source("device_cards")
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for session in self.devices.sessions() {
+                            self.device_progress_card(ui, session);
+                            ui.add_space(10.0);
+                        }
+                    });
+            });
+    }
+    
+    // Progress card for one console
+    /// Overall and per-console limits, laid out right to left in the status bar
+    fn bandwidth_controls(&mut self, ui: &mut Ui) {
+        let mut changed = false;
+        
+        for (label, limit) in [
+            ("Per console", &mut self.config.bandwidth.device_limit_mbps),
+            ("Limit", &mut self.config.bandwidth.limit_mbps),
+        ] {
+            let mut enabled = limit.is_some();
+            let mut value = limit.unwrap_or(20.0);
+            
+            ui.add_enabled_ui(enabled, |ui| {
+                changed |= ui.add(egui::DragValue::new(&mut value)
+                    .speed(0.5)
+                    .clamp_range(0.1..=10_000.0)
+                    .suffix(" MB/s"))
+                    .changed();
+            });
+            changed |= ui.checkbox(&mut enabled, egui::RichText::new(label)
+                .color(self.theme.text_muted)
+                .size(12.0))
+                .changed();
+            
+            *limit = enabled.then_some(value);
+        }
+        
+        // A schedule window is overriding the overall limit right now
+        let current = self.devices.bandwidth().current_limit();
+        if current != self.config.bandwidth.limit_mbps {
+            let text = match current {
+                Some(limit) if limit <= 0.0 => "⏱ Scheduled pause".to_string(),
+                Some(limit) => format!("⏱ Scheduled: {:.1} MB/s", limit),
+                None => "⏱ Scheduled: unlimited".to_string(),
+            };
+            ui.label(egui::RichText::new(text)
+                .color(self.theme.warning)
+                .size(12.0));
+        }
+        
+        if changed {
+            self.apply_bandwidth();
+        }
+    }
+    
+    fn device_progress_card(&self, ui: &mut Ui, session: &DeviceSession) {
+        egui::Frame::none()
+            .fill(self.theme.bg_tertiary)
+            .rounding(Rounding::same(8.0))
+            .inner_margin(egui::Margin::same(12.0))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(format!("🎮 {}", session.label()))
                         .color(self.theme.text_primary)
-                        .size(18.0));
-                    
-                    ui.add_space(15.0);
+                        .size(14.0)
+                        .strong());
+                    if session.is_finished() {
+                        ui.label(egui::RichText::new("Disconnected")
+                            .color(self.theme.text_muted)
+                            .size(12.0));
+                    }
+                    ui.label(egui::RichText::new(format!("Queue: {}", session.queue_len()))
+                        .color(self.theme.text_muted)
+                        .size(12.0));
+                });
+                
+                ui.add_space(10.0);
+                
+                if let Ok(progress) = session.progress.lock() {
+
+                    if progress.paused {
+                        ui.label(egui::RichText::new("⏸ Paused - the Switch is waiting for the transfer to resume")
+                            .color(self.theme.warning)
+                            .size(14.0)
+                            .strong());
+                        ui.add_space(10.0);
+                    }
                     
+                    // Session health from heartbeats and stall detection
+                    if progress.stalled {
+                        ui.label(egui::RichText::new("⚠ Session stalled - the Switch stopped responding")
+                            .color(self.theme.error)
+                            .size(14.0)
+                            .strong());
+                        ui.add_space(10.0);
+                    }
+                    if let Some(state) = &progress.client_state {
+                        ui.label(egui::RichText::new("Switch")
+                            .color(self.theme.text_muted)
+                            .size(12.0));
+                        ui.label(egui::RichText::new(state.describe())
+                            .color(self.theme.text_primary)
+                            .size(14.0));
+                        ui.add_space(15.0);
+                    }
+                    if let Some(status) = &progress.install_status {
+                        let color = match status.outcome {
+                            InstallOutcome::InProgress => self.theme.info,
+                            InstallOutcome::Success => self.theme.success,
+                            InstallOutcome::Failure => self.theme.error,
+                        };
+                        ui.label(egui::RichText::new("Install on Switch")
+                            .color(self.theme.text_muted)
+                            .size(12.0));
+                        ui.label(egui::RichText::new(format!("{} - {}", status.name, status.describe()))
+                            .color(color)
+                            .size(14.0));
+                        ui.add_space(15.0);
+                    }
+
                     // Current file
                     if !progress.current_file.is_empty() {
                         ui.label(egui::RichText::new("Current File")
@@ -524,6 +977,18 @@ impl DbiApp {
                                     &format!("{:.2} MB/s", progress.speed_mbps), 
                                     self.theme.warning);
                             }
+                            
+                            if progress.average_mbps > 0.0 {
+                                ui.add_space(10.0);
+                                let queue = if progress.queue_depth > 1 {
+                                    format!(" · {} in flight", progress.queue_depth)
+                                } else {
+                                    String::new()
+                                };
+                                self.stat_card(ui, "📈 Throughput", 
+                                    &format!("{:.1} avg / {:.1} peak MB/s{}", progress.average_mbps, progress.peak_mbps, queue), 
+                                    self.theme.info);
+                            }
                         });
                         
                         // Right column
@@ -629,10 +1094,15 @@ impl DbiApp {
                     {
                         if let Some(path) = rfd::FileDialog::new().pick_folder() {
                             if let Some(db) = &self.database {
+                                // A root, so the files are found again if the drive remounts elsewhere
+                                if let Err(e) = volumes::add_root(db, &path, &volumes::current_mounts()) {
+                                    self.log_messages.push(format!("[!] Could not add {} as a library root: {}", path.display(), e));
+                                }
                                 match db.add_directory(&path, &["nsp", "nsz", "xci", "xcz"]) {
                                     Ok(count) => {
                                         self.log_messages.push(format!("✅ Added {} files from folder", count));
                                         self.reload_file_list();
+                                        self.start_control_scan(false);
                                     }
                                     Err(e) => {
                                         self.log_messages.push(format!("❌ Error: {}", e));
@@ -665,6 +1135,7 @@ impl DbiApp {
                                 }
                                 self.log_messages.push(format!("✅ Added {} files", count));
                                 self.reload_file_list();
+                                self.start_control_scan(false);
                             }
                         }
                     }
@@ -699,6 +1170,141 @@ impl DbiApp {
                         self.log_messages.push("🔄 Refreshed file list".to_string());
                     }
                     
+                    // Title database import
+                    if ui.add(egui::Button::new(
+                        egui::RichText::new("🗂 Import Titles")
+                            .color(self.theme.text_primary)
+                            .size(13.0))
+                        .fill(self.theme.bg_tertiary)
+                        .rounding(Rounding::same(8.0)))
+                        .on_hover_text("Load names, publishers and release dates from a titledb JSON or CSV dump")
+                        .on_hover_cursor(egui::CursorIcon::PointingHand)
+                        .clicked()
+                    {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("Title database", &["json", "csv"])
+                            .pick_file()
+                        {
+                            self.import_titles(path);
+                        }
+                    }
+                    if let Some(path) = self.config.titledb_path.clone() {
+                        if ui.small_button("⟳")
+                            .on_hover_text(format!("Re-import {}", path.display()))
+                            .clicked()
+                        {
+                            self.import_titles(path);
+                        }
+                    }
+                    
+                    // Names and icons from the files themselves
+                    let scanning = self.control_scan.as_ref()
+                        .and_then(|scan| scan.lock().ok().map(|scan| (scan.done, scan.total)));
+                    match scanning {
+                        Some((done, total)) => {
+                            ui.spinner();
+                            ui.label(egui::RichText::new(format!("Reading icons {}/{}", done, total))
+                                .color(self.theme.text_muted)
+                                .size(12.0));
+                        }
+                        None => {
+                            if ui.add(egui::Button::new(
+                                egui::RichText::new("🖼 Read Icons")
+                                    .color(self.theme.text_primary)
+                                    .size(13.0))
+                                .fill(self.theme.bg_tertiary)
+                                .rounding(Rounding::same(8.0)))
+                                .on_hover_text("Read names, versions and icons from each file's control NCA using your prod.keys")
+                                .on_hover_cursor(egui::CursorIcon::PointingHand)
+                                .clicked()
+                            {
+                                self.start_control_scan(true);
+                            }
+                        }
+                    }
+                    
+                    // Integrity checks against hashes and signatures in the files
+                    let verifying = self.verify_scan.as_ref()
+                        .and_then(|scan| scan.lock().ok().map(|scan| (scan.done, scan.total)));
+                    match verifying {
+                        Some((done, total)) => {
+                            ui.spinner();
+                            ui.label(egui::RichText::new(format!("Verifying {}/{}", done, total))
+                                .color(self.theme.text_muted)
+                                .size(12.0));
+                        }
+                        None => {
+                            if ui.add(egui::Button::new(
+                                egui::RichText::new("🛡 Verify")
+                                    .color(self.theme.text_primary)
+                                    .size(13.0))
+                                .fill(self.theme.bg_tertiary)
+                                .rounding(Rounding::same(8.0)))
+                                .on_hover_text("Check new and changed files against their hashes and signatures using your prod.keys")
+                                .on_hover_cursor(egui::CursorIcon::PointingHand)
+                                .clicked()
+                            {
+                                self.start_verify_scan();
+                            }
+                        }
+                    }
+                    let mut refuse = self.config.refuse_failed_verification;
+                    if ui.checkbox(&mut refuse, "Refuse failed")
+                        .on_hover_text("Don't serve files whose last verification failed")
+                        .changed()
+                    {
+                        self.set_refuse_failed(refuse);
+                    }
+                    if ui.small_button("🔑")
+                        .on_hover_text(format!("Keys: {}", self.config.keys_file().display()))
+                        .clicked()
+                    {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("Keys", &["keys", "txt"])
+                            .pick_file()
+                        {
+                            self.set_keys_path(path);
+                        }
+                    }
+                    
+                    // Moving the library to another machine
+                    if ui.add(egui::Button::new(
+                        egui::RichText::new("📤 Export Library")
+                            .color(self.theme.text_primary)
+                            .size(13.0))
+                        .fill(self.theme.bg_tertiary)
+                        .rounding(Rounding::same(8.0)))
+                        .on_hover_text("Save tags, favorites, install history and collections as JSON, or files only as CSV")
+                        .on_hover_cursor(egui::CursorIcon::PointingHand)
+                        .clicked()
+                    {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("JSON", &["json"])
+                            .add_filter("CSV", &["csv"])
+                            .set_file_name("library.json")
+                            .save_file()
+                        {
+                            self.export_library(&path);
+                        }
+                    }
+                    if ui.add(egui::Button::new(
+                        egui::RichText::new("📥 Import Library")
+                            .color(self.theme.text_primary)
+                            .size(13.0))
+                        .fill(self.theme.bg_tertiary)
+                        .rounding(Rounding::same(8.0)))
+                        .on_hover_text("Merge a library export into this one")
+                        .on_hover_cursor(egui::CursorIcon::PointingHand)
+                        .clicked()
+                    {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("Library export", &["json", "csv"])
+                            .pick_file()
+                        {
+                            self.open_library_import(path);
+                        }
+                    }
+                    
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                         // Queue badge
                         egui::Frame::none()
@@ -738,25 +1344,25 @@ impl DbiApp {
                     
                     let search_response = ui.add(
                         egui::TextEdit::singleline(&mut self.search_query)
-                            .hint_text("Search files...")
+                            .hint_text("Search files...  type:dlc  tag:kids  size>4GB  installed:never")
                             .desired_width(ui.available_width() - 40.0)
                     );
                     
                     if search_response.changed() {
+                        self.search_page = 0;
                         self.reload_file_list();
                     }
                     
-                    if !self.search_query.is_empty() {
-                        if ui.add(egui::Button::new(
-                            egui::RichText::new("✕")
-                                .size(14.0))
-                            .fill(self.theme.bg_tertiary)
-                            .rounding(Rounding::same(6.0)))
-                            .clicked() 
-                        {
-                            self.search_query.clear();
-                            self.reload_file_list();
-                        }
+                    if !self.search_query.is_empty() && ui.add(egui::Button::new(
+                        egui::RichText::new("✕")
+                            .size(14.0))
+                        .fill(self.theme.bg_tertiary)
+                        .rounding(Rounding::same(6.0)))
+                        .clicked() 
+                    {
+                        self.search_query.clear();
+                        self.search_page = 0;
+                        self.reload_file_list();
                     }
                 });
             });
@@ -766,32 +1372,92 @@ impl DbiApp {
         // File table
         use egui_extras::{TableBuilder, Column};
         
-        let files_to_display = if let Some(db) = &self.database {
-            if self.search_query.is_empty() {
-                db.get_files().unwrap_or_default()
-            } else {
-                db.search(&self.search_query).unwrap_or_default()
+        if self.search_loaded_at.is_none_or(|loaded| loaded.elapsed() >= LIBRARY_REFRESH) {
+            self.load_search_page();
+        }
+        let (files_to_display, total, fuzzy) = match &self.search_results {
+            Some(results) => (results.files.clone(), results.total, results.fuzzy),
+            None => (Vec::new(), 0, false),
+        };
+        
+        // Pager
+        let pages = total.div_ceil(LIBRARY_PAGE_SIZE).max(1);
+        ui.horizontal(|ui| {
+            if ui.add_enabled(self.search_page > 0, egui::Button::new("◀")).clicked() {
+                self.search_page -= 1;
+                self.reload_file_list();
             }
+            ui.label(egui::RichText::new(format!("Page {} of {} ({} files)", self.search_page + 1, pages, total))
+                .color(self.theme.text_muted)
+                .size(12.0));
+            if ui.add_enabled(self.search_page + 1 < pages, egui::Button::new("▶")).clicked() {
+                self.search_page += 1;
+                self.reload_file_list();
+            }
+            if fuzzy {
+                ui.label(egui::RichText::new("≈ No exact matches, showing close ones")
+                    .color(self.theme.warning)
+                    .size(12.0));
+            }
+            
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                ui.selectable_value(&mut self.library_grid, true, "▦ Grid");
+                ui.selectable_value(&mut self.library_grid, false, "☰ List");
+                ui.separator();
+                if ui.small_button("Select page").clicked() {
+                    self.selected_files.extend(files_to_display.iter().map(|f| f.id));
+                }
+            });
+        });
+        ui.add_space(5.0);
+        
+        if !self.selected_files.is_empty() {
+            self.bulk_tag_bar(ui);
+            ui.add_space(5.0);
+        }
+
+        if self.library_grid {
+            self.library_grid(ui, &files_to_display);
+            return;
+        }
+        
+        // Taller rows only when there are thumbnails to show
+        let row_height = if files_to_display.iter().any(|f| f.icon_path.is_some()) {
+            TABLE_ICON_SIZE + 4.0
         } else {
-            vec![]
+            18.0
         };
-
+        
         TableBuilder::new(ui)
             .striped(true)
             .resizable(true)
             .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .column(Column::auto().at_least(20.0)) // Selection
             .column(Column::auto().at_least(30.0)) // Favorite
+            .column(Column::exact(TABLE_ICON_SIZE)) // Icon
             .column(Column::remainder().at_least(200.0)) // Filename
+            .column(Column::exact(20.0)) // Verification
+            .column(Column::auto().at_least(100.0)) // Tags
             .column(Column::auto().at_least(80.0)) // Size
             .column(Column::auto().at_least(60.0)) // Installs
             .column(Column::auto().at_least(100.0)) // Actions
             .header(20.0, |mut header| {
+                header.col(|ui| {
+                    ui.strong("☑");
+                });
                 header.col(|ui| {
                     ui.strong("⭐");
                 });
+                header.col(|_| {});
                 header.col(|ui| {
                     ui.strong("Filename");
                 });
+                header.col(|ui| {
+                    ui.strong("🛡").on_hover_text("Verification");
+                });
+                header.col(|ui| {
+                    ui.strong("Tags");
+                });
                 header.col(|ui| {
                     ui.strong("Size");
                 });
@@ -801,88 +1467,2105 @@ impl DbiApp {
                 header.col(|ui| {
                     ui.strong("Actions");
                 });
-            })
-            .body(|mut body| {
-                for file in &files_to_display {
-                    body.row(18.0, |mut row| {
-                        // Favorite column
-                        row.col(|ui| {
-                            let star = if file.favorite { "⭐" } else { "☆" };
-                            if ui.button(star).clicked() {
-                                if let Some(db) = &self.database {
-                                    let _ = db.toggle_favorite(file.id);
-                                    self.reload_file_list();
-                                }
+            })
+            .body(|mut body| {
+                for file in &files_to_display {
+                    body.row(row_height, |mut row| {
+                        // Selection for bulk tagging
+                        row.col(|ui| {
+                            let mut selected = self.selected_files.contains(&file.id);
+                            if ui.checkbox(&mut selected, "").changed() {
+                                if selected {
+                                    self.selected_files.insert(file.id);
+                                } else {
+                                    self.selected_files.remove(&file.id);
+                                }
+                            }
+                        });
+                        
+                        // Favorite column
+                        row.col(|ui| {
+                            let star = if file.favorite { "⭐" } else { "☆" };
+                            if ui.button(star).clicked() {
+                                if let Some(db) = &self.database {
+                                    let _ = db.toggle_favorite(file.id);
+                                    self.reload_file_list();
+                                }
+                            }
+                        });
+                        
+                        // Icon column
+                        row.col(|ui| {
+                            let icon = file.icon_path.as_deref().and_then(|path| self.icon_texture(ui.ctx(), path));
+                            if let Some(texture) = icon {
+                                ui.image((texture.id(), Vec2::splat(TABLE_ICON_SIZE)));
+                            }
+                        });
+                        
+                        // Filename column with truncation; titles from the title database show their name
+                        row.col(|ui| {
+                            if file.offline {
+                                ui.label(egui::RichText::new(format!("⏏ {}", file.display_name)).color(self.theme.text_muted))
+                                    .on_hover_text(format!("{}\nOn a drive or share that isn't connected", file.path));
+                            } else if file.display_name != title::display_name(&file.filename) {
+                                ui.label(egui::RichText::new(&file.display_name).strong())
+                                    .on_hover_text(&file.filename);
+                            } else {
+                                ui.label(&file.filename)
+                                    .on_hover_text(&file.filename); // Show full name on hover
+                            }
+                        });
+                        
+                        // Verification badge; the report shows on hover
+                        row.col(|ui| {
+                            self.verification_badge(ui, file);
+                        });
+                        
+                        // Tags column; clicking a tag filters by it
+                        row.col(|ui| {
+                            for tag in &file.tags {
+                                if self.tag_chip(ui, tag).on_hover_text("Show files with this tag").clicked() {
+                                    self.search_query = tag_query(tag);
+                                    self.search_page = 0;
+                                    self.reload_file_list();
+                                }
+                            }
+                        });
+                        
+                        // Size column
+                        row.col(|ui| {
+                            ui.label(format_file_size(file.size));
+                        });
+                        
+                        // Install count column
+                        row.col(|ui| {
+                            ui.label(format!("{}", file.install_count));
+                        });
+                        
+                        // Actions column
+                        row.col(|ui| {
+                            ui.horizontal(|ui| {
+                                // Add/Remove from queue button
+                                let in_queue = self.file_list.contains_key(&file.filename);
+                                self.queue_button(ui, file);
+                                
+                                // Pin to one console when several are connected
+                                if in_queue && self.devices.sessions().len() > 1 {
+                                    let pinned = self.devices.pinned_to(&file.filename).map(|id| id.to_string());
+                                    let mut selected = pinned.clone();
+                                    egui::ComboBox::from_id_source(("pin", file.id))
+                                        .width(90.0)
+                                        .selected_text(pinned.as_deref().unwrap_or("All consoles"))
+                                        .show_ui(ui, |ui| {
+                                            ui.selectable_value(&mut selected, None, "All consoles");
+                                            for session in self.devices.sessions() {
+                                                ui.selectable_value(&mut selected, Some(session.id()), session.label());
+                                            }
+                                        });
+                                    if selected != pinned {
+                                        self.devices.pin(&file.filename, selected);
+                                    }
+                                }
+                                
+                                if let Some(title_id) = &file.title_id {
+                                    if ui.small_button("ℹ").on_hover_text("Title info").clicked() {
+                                        self.open_title_editor(title_id);
+                                    }
+                                }
+                                
+                                // Delete button
+                                if ui.small_button("Del").on_hover_text("Move to the trash").clicked() {
+                                    if let Some(db) = &self.database {
+                                        match db.trash_files(&[file.id]) {
+                                            Ok(()) => {
+                                                self.file_list.retain(|_, path| path != Path::new(&file.path));
+                                                self.reload_file_list();
+                                                self.log_messages.push(format!("[-] Moved to the trash: {}", file.filename));
+                                                self.set_undo(
+                                                    format!("{} moved to the trash", file.filename),
+                                                    UndoAction::Restore(vec![file.id]),
+                                                );
+                                            }
+                                            Err(e) => self.log_messages.push(format!("[!] {}", e)),
+                                        }
+                                    }
+                                }
+                            });
+                        });
+                    });
+                }
+            });
+    }
+    
+    fn saves_panel(&mut self, ui: &mut Ui) {
+        let library = SaveLibrary::new(self.config.saves_dir.clone());
+        let groups = self.database.as_ref()
+            .and_then(|db| db.get_save_groups().ok())
+            .unwrap_or_default();
+        
+        // Header card
+        egui::Frame::none()
+            .fill(self.theme.bg_secondary)
+            .rounding(Rounding::same(12.0))
+            .inner_margin(egui::Margin::same(20.0))
+            .stroke(Stroke::new(1.0, self.theme.border))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading(egui::RichText::new("💾 Save Backups")
+                        .color(self.theme.text_primary)
+                        .size(20.0));
+                    
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.add(egui::Button::new(
+                            egui::RichText::new("🧹 Apply Retention")
+                                .color(self.theme.text_primary)
+                                .size(13.0))
+                            .fill(self.theme.bg_tertiary)
+                            .rounding(Rounding::same(8.0)))
+                            .on_hover_cursor(egui::CursorIcon::PointingHand)
+                            .clicked()
+                        {
+                            if let Some(db) = &self.database {
+                                match library.apply_retention_all(db, &self.config.save_retention) {
+                                    Ok(removed) => self.log_messages.push(format!("🧹 Removed {} old save snapshots", removed)),
+                                    Err(e) => self.log_messages.push(format!("❌ Error: {}", e)),
+                                }
+                            }
+                        }
+                        
+                        let keep = self.config.save_retention.keep_last
+                            .map(|n| format!("keep last {}", n))
+                            .unwrap_or_else(|| "keep all".to_string());
+                        ui.label(egui::RichText::new(format!("📦 {} titles · {}", groups.len(), keep))
+                            .color(self.theme.text_secondary)
+                            .size(12.0));
+                    });
+                });
+            });
+        
+        ui.add_space(15.0);
+        
+        ui.columns(2, |columns| {
+            // Left - titles and users
+            columns[0].vertical(|ui| {
+                egui::Frame::none()
+                    .fill(self.theme.bg_secondary)
+                    .rounding(Rounding::same(12.0))
+                    .inner_margin(egui::Margin::same(15.0))
+                    .stroke(Stroke::new(1.0, self.theme.border))
+                    .show(ui, |ui| {
+                        if groups.is_empty() {
+                            ui.label(egui::RichText::new("No save backups yet. Back up a save from the Switch client to see it here.")
+                                .color(self.theme.text_muted)
+                                .size(13.0));
+                        }
+                        
+                        ScrollArea::vertical()
+                            .id_source("save_groups")
+                            .auto_shrink([false, true])
+                            .show(ui, |ui| {
+                                for group in &groups {
+                                    let key = (group.title_id.clone(), group.user_id.clone());
+                                    let selected = self.selected_save_group.as_ref() == Some(&key);
+                                    let text = format!(
+                                        "{}  ·  user {}…\n{} snapshots · {} · {}",
+                                        group.title_id,
+                                        &group.user_id[..8.min(group.user_id.len())],
+                                        group.snapshot_count,
+                                        format_file_size(group.total_size),
+                                        format_timestamp(group.latest),
+                                    );
+                                    if ui.selectable_label(selected, text).clicked() {
+                                        self.selected_save_group = Some(key);
+                                        self.save_compare.clear();
+                                        self.save_diff = None;
+                                    }
+                                }
+                            });
+                    });
+            });
+            
+            // Right - snapshots of the selected title
+            columns[1].vertical(|ui| {
+                self.save_snapshots_card(ui, &library);
+            });
+        });
+    }
+    
+    fn save_snapshots_card(&mut self, ui: &mut Ui, library: &SaveLibrary) {
+        let Some((title_id, user_id)) = self.selected_save_group.clone() else {
+            return;
+        };
+        let snapshots = self.database.as_ref()
+            .and_then(|db| db.get_save_snapshots(&title_id, &user_id).ok())
+            .unwrap_or_default();
+        
+        egui::Frame::none()
+            .fill(self.theme.bg_secondary)
+            .rounding(Rounding::same(12.0))
+            .inner_margin(egui::Margin::same(15.0))
+            .stroke(Stroke::new(1.0, self.theme.border))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(format!("Snapshots of {}", title_id))
+                        .color(self.theme.text_primary)
+                        .size(15.0)
+                        .strong());
+                    
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        let can_compare = self.save_compare.len() == 2;
+                        if ui.add_enabled(can_compare, egui::Button::new("⇄ Compare"))
+                            .on_hover_text("Select two snapshots to compare")
+                            .clicked()
+                        {
+                            let find = |id: i64| snapshots.iter().find(|s| s.id == id);
+                            if let (Some(a), Some(b)) = (find(self.save_compare[0]), find(self.save_compare[1])) {
+                                // Compare older -> newer
+                                let (old, new) = if a.created_at <= b.created_at { (a, b) } else { (b, a) };
+                                match diff_snapshots(std::path::Path::new(&old.path), std::path::Path::new(&new.path)) {
+                                    Ok(diff) => self.save_diff = Some(diff),
+                                    Err(e) => self.log_messages.push(format!("❌ Compare failed: {}", e)),
+                                }
+                            }
+                        }
+                    });
+                });
+                
+                ui.add_space(10.0);
+                
+                let mut restore = None;
+                let mut toggle_pin = None;
+                let mut delete = None;
+                
+                for snapshot in &snapshots {
+                    ui.horizontal(|ui| {
+                        let mut checked = self.save_compare.contains(&snapshot.id);
+                        if ui.checkbox(&mut checked, "").changed() {
+                            if checked {
+                                self.save_compare.push(snapshot.id);
+                                if self.save_compare.len() > 2 {
+                                    self.save_compare.remove(0);
+                                }
+                            } else {
+                                self.save_compare.retain(|&id| id != snapshot.id);
+                            }
+                            self.save_diff = None;
+                        }
+                        
+                        ui.label(egui::RichText::new(format_timestamp(snapshot.created_at))
+                            .color(self.theme.text_primary)
+                            .size(13.0));
+                        ui.label(egui::RichText::new(format_file_size(snapshot.size))
+                            .color(self.theme.text_muted)
+                            .size(12.0));
+                        
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui.small_button("Del").on_hover_text("Delete snapshot").clicked() {
+                                delete = Some(snapshot.clone());
+                            }
+                            let pin = if snapshot.pinned { "📌" } else { "📍" };
+                            let pin_tip = if snapshot.pinned { "Unpin (allow retention to remove it)" } else { "Pin (keep forever)" };
+                            if ui.small_button(pin).on_hover_text(pin_tip).clicked() {
+                                toggle_pin = Some(snapshot.id);
+                            }
+                            if ui.small_button("⟲ Restore").on_hover_text("Add to queue so the Switch can download it").clicked() {
+                                restore = Some(snapshot.clone());
+                            }
+                        });
+                    });
+                }
+                
+                if let Some(snapshot) = restore {
+                    let name = restore_name(&snapshot);
+                    self.file_list.insert(name.clone(), PathBuf::from(&snapshot.path));
+                    self.log_messages.push(format!("[+] Added to queue: {}", name));
+                }
+                if let Some(db) = &self.database {
+                    if let Some(id) = toggle_pin {
+                        let _ = db.toggle_save_pinned(id);
+                    }
+                    if let Some(snapshot) = delete {
+                        match library.delete(db, &snapshot) {
+                            Ok(()) => self.log_messages.push(format!("[x] Deleted save snapshot: {}", format_timestamp(snapshot.created_at))),
+                            Err(e) => self.log_messages.push(format!("❌ Error: {}", e)),
+                        }
+                        self.save_compare.retain(|&id| id != snapshot.id);
+                        self.save_diff = None;
+                    }
+                }
+                
+                // Diff result
+                if let Some(diff) = &self.save_diff {
+                    ui.add_space(10.0);
+                    ui.separator();
+                    if diff.is_empty() {
+                        ui.label(egui::RichText::new("Snapshots are identical")
+                            .color(self.theme.success)
+                            .size(13.0));
+                    }
+                    ScrollArea::vertical()
+                        .id_source("save_diff")
+                        .max_height(200.0)
+                        .show(ui, |ui| {
+                            for entry in diff {
+                                let (marker, color) = match entry.change {
+                                    DiffChange::Added => ("+", self.theme.success),
+                                    DiffChange::Removed => ("-", self.theme.error),
+                                    DiffChange::Modified => ("~", self.theme.warning),
+                                };
+                                ui.colored_label(color, format!(
+                                    "{} {} ({} → {})",
+                                    marker,
+                                    entry.path,
+                                    format_file_size(entry.old_size),
+                                    format_file_size(entry.new_size),
+                                ));
+                            }
+                        });
+                }
+            });
+    }
+    
+    /// A small colored tag label
+    fn tag_chip(&self, ui: &mut Ui, name: &str) -> egui::Response {
+        let color = self.tag_colors.get(name).copied().unwrap_or(self.theme.primary_dark);
+        ui.add(egui::Button::new(egui::RichText::new(name)
+                .color(self.theme.text_primary)
+                .size(11.0))
+            .fill(color)
+            .rounding(Rounding::same(6.0)))
+            .on_hover_cursor(egui::CursorIcon::PointingHand)
+    }
+    
+    /// Tag or untag every selected file
+    fn bulk_tag_bar(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new(format!("{} selected", self.selected_files.len()))
+                .color(self.theme.text_secondary)
+                .size(13.0));
+            ui.add(egui::TextEdit::singleline(&mut self.bulk_tag)
+                .hint_text("Tag name")
+                .desired_width(140.0));
+            
+            let ids: Vec<i64> = self.selected_files.iter().copied().collect();
+            let tag = self.bulk_tag.trim().to_string();
+            if ui.add_enabled(!tag.is_empty(), egui::Button::new("🏷 Tag")).clicked() {
+                if let Some(db) = &self.database {
+                    match db.tag_files(&ids, &tag) {
+                        Ok(()) => self.log_messages.push(format!("[+] Tagged {} files with {}", ids.len(), tag)),
+                        Err(e) => self.log_messages.push(format!("[!] Tagging failed: {}", e)),
+                    }
+                    self.reload_file_list();
+                }
+            }
+            if ui.add_enabled(!tag.is_empty(), egui::Button::new("Untag")).clicked() {
+                if let Some(db) = &self.database {
+                    match db.untag_files(&ids, &tag) {
+                        Ok(()) => self.log_messages.push(format!("[-] Removed {} from {} files", tag, ids.len())),
+                        Err(e) => self.log_messages.push(format!("[!] Untagging failed: {}", e)),
+                    }
+                    self.reload_file_list();
+                }
+            }
+            if ui.button("Clear selection").clicked() {
+                self.selected_files.clear();
+            }
+        });
+    }
+    
+    fn tags_panel(&mut self, ui: &mut Ui) {
+        let tags = self.database.as_ref()
+            .and_then(|db| db.get_tags().ok())
+            .unwrap_or_default();
+        
+        // Header card
+        egui::Frame::none()
+            .fill(self.theme.bg_secondary)
+            .rounding(Rounding::same(12.0))
+            .inner_margin(egui::Margin::same(20.0))
+            .stroke(Stroke::new(1.0, self.theme.border))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading(egui::RichText::new("🏷 Tags")
+                        .color(self.theme.text_primary)
+                        .size(20.0));
+                    
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        let name = self.new_tag.trim().to_string();
+                        if ui.add_enabled(!name.is_empty(), egui::Button::new("➕ New Tag")).clicked() {
+                            if let Some(db) = &self.database {
+                                if let Err(e) = db.create_tag(&name) {
+                                    self.log_messages.push(format!("[!] {}", e));
+                                }
+                            }
+                            self.new_tag.clear();
+                        }
+                        ui.add(egui::TextEdit::singleline(&mut self.new_tag)
+                            .hint_text("New tag")
+                            .desired_width(160.0));
+                    });
+                });
+            });
+        
+        ui.add_space(15.0);
+        
+        if tags.is_empty() {
+            ui.label(egui::RichText::new("No tags yet. Select files in the library and tag them, or create one above.")
+                .color(self.theme.text_muted)
+                .size(13.0));
+            return;
+        }
+        
+        ScrollArea::vertical()
+            .id_source("tags")
+            .auto_shrink([false, true])
+            .show(ui, |ui| {
+                for tag in &tags {
+                    self.tag_row(ui, tag, &tags);
+                    ui.add_space(4.0);
+                }
+            });
+    }
+    
+    /// Color, rename, merge, filter and delete for one tag
+    fn tag_row(&mut self, ui: &mut Ui, tag: &Tag, all: &[Tag]) {
+        let Some(db) = &self.database else {
+            return;
+        };
+        let mut result = Ok(());
+        
+        ui.horizontal(|ui| {
+            let mut color = parse_tag_color(&tag.color).unwrap_or(self.theme.primary_dark);
+            if ui.color_edit_button_srgba(&mut color).changed() {
+                result = db.set_tag_color(&tag.name, &format!("#{:02X}{:02X}{:02X}", color.r(), color.g(), color.b()));
+            }
+            
+            let name = self.tag_renames.entry(tag.id).or_insert_with(|| tag.name.clone());
+            let response = ui.add(egui::TextEdit::singleline(name).desired_width(160.0));
+            if response.lost_focus() && name.trim() != tag.name {
+                result = db.rename_tag(&tag.name, name);
+                self.tag_renames.remove(&tag.id);
+            }
+            
+            ui.label(egui::RichText::new(format!("{} files", tag.file_count))
+                .color(self.theme.text_muted)
+                .size(12.0));
+            
+            egui::ComboBox::from_id_source(("merge", tag.id))
+                .width(120.0)
+                .selected_text("Merge into…")
+                .show_ui(ui, |ui| {
+                    for other in all.iter().filter(|t| t.id != tag.id) {
+                        if ui.selectable_label(false, &other.name).clicked() {
+                            result = db.merge_tags(&tag.name, &other.name);
+                        }
+                    }
+                });
+            
+            if ui.button("🔍 Show").clicked() {
+                self.search_query = tag_query(&tag.name);
+                self.search_page = 0;
+                self.search_loaded_at = None;
+                self.active_tab = AppTab::Library;
+            }
+            if ui.button("🗑 Delete").on_hover_text("Remove this tag from every file").clicked() {
+                result = db.delete_tag(&tag.name);
+            }
+        });
+        
+        if let Err(e) = result {
+            self.log_messages.push(format!("[!] {}", e));
+        }
+        self.search_loaded_at = None;
+    }
+    
+    fn collections_panel(&mut self, ui: &mut Ui) {
+        let collections = self.database.as_ref()
+            .and_then(|db| db.get_collections().ok())
+            .unwrap_or_default();
+        
+        // Header card
+        egui::Frame::none()
+            .fill(self.theme.bg_secondary)
+            .rounding(Rounding::same(12.0))
+            .inner_margin(egui::Margin::same(20.0))
+            .stroke(Stroke::new(1.0, self.theme.border))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading(egui::RichText::new("📚 Collections")
+                        .color(self.theme.text_primary)
+                        .size(20.0));
+                    
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        let name = self.collection_name.trim().to_string();
+                        let mut created = None;
+                        if ui.add_enabled(!name.is_empty(), egui::Button::new("➕ New Empty")).clicked() {
+                            created = Some(Vec::new());
+                        }
+                        if ui.add_enabled(!name.is_empty() && !self.file_list.is_empty(), egui::Button::new("💾 Save Queue As"))
+                            .on_hover_text("Save the current queue as a new collection")
+                            .clicked()
+                        {
+                            created = Some(self.queue_paths());
+                        }
+                        ui.add(egui::TextEdit::singleline(&mut self.collection_name)
+                            .hint_text("Collection name")
+                            .desired_width(180.0));
+                        
+                        if let (Some(paths), Some(db)) = (created, &self.database) {
+                            match db.create_collection(&name, &paths) {
+                                Ok(_) => {
+                                    self.log_messages.push(format!("[+] Saved collection {} ({} files)", name, paths.len()));
+                                    self.collection_name.clear();
+                                }
+                                Err(e) => self.log_messages.push(format!("[!] {}", e)),
+                            }
+                        }
+                    });
+                });
+            });
+        
+        ui.add_space(15.0);
+        
+        if self.editing_collection.is_some() {
+            self.collection_editor(ui);
+            ui.add_space(15.0);
+        }
+        
+        if collections.is_empty() {
+            ui.label(egui::RichText::new("No collections yet. Queue some files in the library and save them here.")
+                .color(self.theme.text_muted)
+                .size(13.0));
+            return;
+        }
+        
+        let mut action = None;
+        ScrollArea::vertical()
+            .id_source("collections")
+            .auto_shrink([false, true])
+            .show(ui, |ui| {
+                for collection in &collections {
+                    if let Some(pressed) = self.collection_row(ui, collection) {
+                        action = Some((pressed, collection.name.clone()));
+                    }
+                    ui.add_space(4.0);
+                }
+            });
+        
+        if let Some((action, name)) = action {
+            self.apply_collection_action(action, &name);
+        }
+    }
+    
+    fn collection_row(&self, ui: &mut Ui, collection: &Collection) -> Option<CollectionAction> {
+        let mut action = None;
+        egui::Frame::none()
+            .fill(self.theme.bg_secondary)
+            .rounding(Rounding::same(10.0))
+            .inner_margin(egui::Margin::symmetric(15.0, 10.0))
+            .stroke(Stroke::new(1.0, self.theme.border))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.vertical(|ui| {
+                        ui.label(egui::RichText::new(&collection.name)
+                            .color(self.theme.text_primary)
+                            .size(15.0)
+                            .strong());
+                        ui.label(egui::RichText::new(format!(
+                                "{} files · updated {}",
+                                collection.item_count,
+                                format_timestamp(collection.updated_at)))
+                            .color(self.theme.text_muted)
+                            .size(12.0));
+                    });
+                    
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.button("🗑").on_hover_text("Delete collection").clicked() {
+                            action = Some(CollectionAction::Delete);
+                        }
+                        if ui.button("📤").on_hover_text("Export as JSON").clicked() {
+                            action = Some(CollectionAction::Export);
+                        }
+                        if ui.button("⧉").on_hover_text("Duplicate").clicked() {
+                            action = Some(CollectionAction::Duplicate);
+                        }
+                        if ui.button("✏ Edit").clicked() {
+                            action = Some(CollectionAction::Edit);
+                        }
+                        if ui.button("Load").on_hover_text("Replace the queue with this collection").clicked() {
+                            action = Some(CollectionAction::Load);
+                        }
+                        if self.primary_button(ui, "▶ Serve").clicked() {
+                            action = Some(CollectionAction::Serve);
+                        }
+                    });
+                });
+            });
+        action
+    }
+    
+    fn apply_collection_action(&mut self, action: CollectionAction, name: &str) {
+        match action {
+            CollectionAction::Serve => self.serve_collection(name),
+            CollectionAction::Load => {
+                self.load_collection(name);
+            }
+            CollectionAction::Edit => {
+                if let Some(db) = &self.database {
+                    match db.get_collection_items(name) {
+                        Ok(items) => {
+                            self.collection_draft = items;
+                            self.collection_rename = name.to_string();
+                            self.editing_collection = Some(name.to_string());
+                        }
+                        Err(e) => self.log_messages.push(format!("[!] {}", e)),
+                    }
+                }
+            }
+            CollectionAction::Duplicate => {
+                let Some(db) = &self.database else {
+                    return;
+                };
+                let existing: Vec<String> = db.get_collections()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|c| c.name.to_lowercase())
+                    .collect();
+                let copy = (1..)
+                    .map(|n| if n == 1 { format!("{} (copy)", name) } else { format!("{} (copy {})", name, n) })
+                    .find(|candidate| !existing.contains(&candidate.to_lowercase()))
+                    .unwrap_or_default();
+                match db.duplicate_collection(name, &copy) {
+                    Ok(_) => self.log_messages.push(format!("[+] Duplicated {} as {}", name, copy)),
+                    Err(e) => self.log_messages.push(format!("[!] {}", e)),
+                }
+            }
+            CollectionAction::Export => {
+                let Some(db) = &self.database else {
+                    return;
+                };
+                let export = match CollectionExport::from_db(db, name) {
+                    Ok(export) => export,
+                    Err(e) => {
+                        self.log_messages.push(format!("[!] {}", e));
+                        return;
+                    }
+                };
+                if let Some(path) = rfd::FileDialog::new()
+                    .set_file_name(export.default_filename())
+                    .add_filter("JSON", &["json"])
+                    .save_file()
+                {
+                    match export.write(&path) {
+                        Ok(()) => self.log_messages.push(format!("[+] Exported {} to {}", name, path.display())),
+                        Err(e) => self.log_messages.push(format!("[!] Export failed: {}", e)),
+                    }
+                }
+            }
+            CollectionAction::Delete => {
+                if let Some(db) = &self.database {
+                    match db.delete_collection(name) {
+                        Ok(()) => self.log_messages.push(format!("[-] Deleted collection {}", name)),
+                        Err(e) => self.log_messages.push(format!("[!] {}", e)),
+                    }
+                }
+                if self.editing_collection.as_deref() == Some(name) {
+                    self.editing_collection = None;
+                }
+            }
+        }
+    }
+    
+    /// Rename, reorder, add and remove files in the collection being edited
+    fn collection_editor(&mut self, ui: &mut Ui) {
+        let mut save = false;
+        let mut close = false;
+        
+        egui::Frame::none()
+            .fill(self.theme.bg_secondary)
+            .rounding(Rounding::same(12.0))
+            .inner_margin(egui::Margin::same(15.0))
+            .stroke(Stroke::new(1.0, self.theme.primary))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("✏ Name")
+                        .color(self.theme.text_secondary)
+                        .size(13.0));
+                    ui.add(egui::TextEdit::singleline(&mut self.collection_rename).desired_width(220.0));
+                    
+                    if ui.button("➕ Add Queue").on_hover_text("Append queued files that aren't in the collection yet").clicked() {
+                        for path in self.queue_paths() {
+                            if !self.collection_draft.contains(&path) {
+                                self.collection_draft.push(path);
+                            }
+                        }
+                    }
+                    if ui.button("📄 Add Files…").clicked() {
+                        if let Some(files) = rfd::FileDialog::new()
+                            .add_filter("Switch Files", &["nsp", "nsz", "xci", "xcz"])
+                            .pick_files()
+                        {
+                            for path in files {
+                                if !self.collection_draft.contains(&path) {
+                                    self.collection_draft.push(path);
+                                }
+                            }
+                        }
+                    }
+                    
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.button("Cancel").clicked() {
+                            close = true;
+                        }
+                        if ui.button("💾 Save").clicked() {
+                            save = true;
+                        }
+                    });
+                });
+                
+                ui.add_space(8.0);
+                
+                if self.collection_draft.is_empty() {
+                    ui.label(egui::RichText::new("Empty collection")
+                        .color(self.theme.text_muted)
+                        .size(12.0));
+                }
+                
+                let mut swap = None;
+                let mut remove = None;
+                let last = self.collection_draft.len().saturating_sub(1);
+                ScrollArea::vertical()
+                    .id_source("collection_draft")
+                    .max_height(260.0)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for (i, path) in self.collection_draft.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                if ui.add_enabled(i > 0, egui::Button::new("↑").small()).clicked() {
+                                    swap = Some((i - 1, i));
+                                }
+                                if ui.add_enabled(i < last, egui::Button::new("↓").small()).clicked() {
+                                    swap = Some((i, i + 1));
+                                }
+                                if ui.small_button("✕").clicked() {
+                                    remove = Some(i);
+                                }
+                                
+                                let name = path.file_name()
+                                    .map(|n| n.to_string_lossy().to_string())
+                                    .unwrap_or_default();
+                                let color = if path.is_file() { self.theme.text_primary } else { self.theme.error };
+                                ui.label(egui::RichText::new(name).color(color).size(13.0))
+                                    .on_hover_text(path.display().to_string());
+                            });
+                        }
+                    });
+                
+                if let Some((a, b)) = swap {
+                    self.collection_draft.swap(a, b);
+                }
+                if let Some(i) = remove {
+                    self.collection_draft.remove(i);
+                }
+            });
+        
+        if save {
+            if let (Some(name), Some(db)) = (&self.editing_collection, &self.database) {
+                let result = db.set_collection_items(name, &self.collection_draft)
+                    .and_then(|()| db.rename_collection(name, &self.collection_rename));
+                match result {
+                    Ok(()) => {
+                        self.log_messages.push(format!("[+] Saved collection {}", self.collection_rename.trim()));
+                        close = true;
+                    }
+                    Err(e) => self.log_messages.push(format!("[!] {}", e)),
+                }
+            }
+        }
+        if close {
+            self.editing_collection = None;
+            self.collection_draft.clear();
+        }
+    }
+    
+    /// Library page as icon cards
+    fn library_grid(&mut self, ui: &mut Ui, files: &[GameFile]) {
+        ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    ui.spacing_mut().item_spacing = Vec2::splat(12.0);
+                    for file in files {
+                        let in_queue = self.file_list.contains_key(&file.filename);
+                        egui::Frame::none()
+                            .fill(self.theme.bg_secondary)
+                            .rounding(Rounding::same(10.0))
+                            .inner_margin(egui::Margin::same(8.0))
+                            .stroke(Stroke::new(1.0, if in_queue { self.theme.primary } else { self.theme.border }))
+                            .show(ui, |ui| {
+                                ui.set_width(GRID_ICON_SIZE);
+                                ui.vertical(|ui| {
+                                    let icon = file.icon_path.as_deref()
+                                        .and_then(|path| self.icon_texture(ui.ctx(), path));
+                                    match icon {
+                                        Some(texture) => ui.image((texture.id(), Vec2::splat(GRID_ICON_SIZE))),
+                                        None => ui.add_sized(
+                                            Vec2::splat(GRID_ICON_SIZE),
+                                            egui::Label::new(egui::RichText::new("🎮").size(48.0)),
+                                        ),
+                                    }
+                                    .on_hover_text(&file.filename);
+                                    
+                                    ui.add(egui::Label::new(egui::RichText::new(&file.display_name)
+                                        .color(self.theme.text_primary)
+                                        .strong()
+                                        .size(12.0))
+                                        .truncate(true));
+                                    ui.horizontal(|ui| {
+                                        ui.label(egui::RichText::new(format_file_size(file.size))
+                                            .color(self.theme.text_muted)
+                                            .size(11.0));
+                                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                            self.queue_button(ui, file);
+                                        });
+                                    });
+                                });
+                            });
+                    }
+                });
+            });
+    }
+    
+    /// "+" / "-" button adding a library file to the queue or taking it out
+    fn queue_button(&mut self, ui: &mut Ui, file: &GameFile) {
+        let in_queue = self.file_list.contains_key(&file.filename);
+        let button_text = if in_queue { "-" } else { "+" };
+        let tooltip = if in_queue { "Remove from queue" } else { "Add to queue" };
+        
+        if ui.small_button(button_text).on_hover_text(tooltip).clicked() {
+            if in_queue {
+                self.file_list.remove(&file.filename);
+                self.log_messages.push(format!("[-] Removed from queue: {}", file.filename));
+            } else {
+                let path = PathBuf::from(&file.path);
+                if path.exists() {
+                    self.file_list.insert(file.filename.clone(), path);
+                    self.log_messages.push(format!("[+] Added to queue: {}", file.filename));
+                } else {
+                    self.log_messages.push(format!("[!] File not found: {}", file.filename));
+                }
+            }
+        }
+    }
+    
+    /// Thumbnail of an icon file, decoded once per session
+    fn icon_texture(&mut self, ctx: &Context, path: &str) -> Option<egui::TextureHandle> {
+        self.icon_textures
+            .entry(path.to_string())
+            .or_insert_with(|| {
+                let image = image::open(path).ok()?.thumbnail(GRID_ICON_SIZE as u32, GRID_ICON_SIZE as u32).to_rgba8();
+                let size = [image.width() as usize, image.height() as usize];
+                let pixels = egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw());
+                Some(ctx.load_texture(path, pixels, egui::TextureOptions::LINEAR))
+            })
+            .clone()
+    }
+    
+    fn set_keys_path(&mut self, path: PathBuf) {
+        match KeySet::load(&path) {
+            Ok(keys) => self.log_messages.push(format!("[+] Loaded {} keys from {}", keys.len(), path.display())),
+            Err(e) => {
+                self.log_messages.push(format!("[!] {}", e));
+                return;
+            }
+        }
+        
+        self.config.keys_path = Some(path.clone());
+        let mut saved = Config::load();
+        saved.keys_path = Some(path);
+        if let Err(e) = saved.save() {
+            self.log_messages.push(format!("[!] Could not save settings: {}", e));
+        }
+        self.start_control_scan(true);
+    }
+    
+    /// Read control NCAs of new and changed files without blocking the UI
+    ///
+    /// Scans started by adding files stay quiet when no keys are set up.
+    fn start_control_scan(&mut self, retry_failed: bool) {
+        if self.control_scan.is_some() {
+            return;
+        }
+        let keys = match KeySet::load(&self.config.keys_file()) {
+            Ok(keys) => keys,
+            Err(NcaError::NoKeys(_)) if !retry_failed => return,
+            Err(e) => {
+                self.log_messages.push(format!("[!] Can't read icons: {}", e));
+                return;
+            }
+        };
+        
+        let scan = Arc::new(Mutex::new(ControlScan::default()));
+        self.control_scan = Some(scan.clone());
+        
+        let db_path = self.config.db_path.clone();
+        let icon_dir = self.config.icon_cache_dir.clone();
+        std::thread::spawn(move || {
+            let result = Database::new(&db_path)
+                .and_then(|db| {
+                    control::extract_library(&db, &keys, &icon_dir, retry_failed, |done, total| {
+                        if let Ok(mut scan) = scan.lock() {
+                            scan.done = done;
+                            scan.total = total;
+                        }
+                    })
+                })
+                .map_err(|e| e.to_string());
+            if let Ok(mut scan) = scan.lock() {
+                scan.result = Some(result);
+            }
+        });
+    }
+    
+    fn poll_control_scan(&mut self) {
+        let finished = self.control_scan.as_ref()
+            .and_then(|scan| scan.lock().ok().and_then(|mut scan| scan.result.take()));
+        let Some(result) = finished else {
+            return;
+        };
+        self.control_scan = None;
+        
+        match result {
+            Ok(summary) => {
+                if summary.extracted > 0 {
+                    self.log_messages.push(format!("[+] Read names and icons of {} files", summary.extracted));
+                }
+                for (error, count) in &summary.key_errors {
+                    self.log_messages.push(format!("[!] {} ({} files)", error, count));
+                }
+                for (filename, error) in &summary.failed {
+                    self.log_messages.push(format!("[!] {}: {}", filename, error));
+                }
+            }
+            Err(e) => self.log_messages.push(format!("[!] Reading icons failed: {}", e)),
+        }
+        
+        // Icons may have been rewritten at the same paths
+        self.icon_textures.clear();
+        self.reload_file_list();
+    }
+    
+    /// Verify new and changed files without blocking the UI
+    fn start_verify_scan(&mut self) {
+        if self.verify_scan.is_some() {
+            return;
+        }
+        let keys = match KeySet::load(&self.config.keys_file()) {
+            Ok(keys) => keys,
+            Err(e) => {
+                self.log_messages.push(format!("[!] Can't verify: {}", e));
+                return;
+            }
+        };
+        
+        let scan = Arc::new(Mutex::new(VerifyScan::default()));
+        self.verify_scan = Some(scan.clone());
+        
+        let db_path = self.config.db_path.clone();
+        std::thread::spawn(move || {
+            let result = Database::new(&db_path)
+                .and_then(|db| {
+                    verify::verify_library(&db, &keys, false, |done, total| {
+                        if let Ok(mut scan) = scan.lock() {
+                            scan.done = done;
+                            scan.total = total;
+                        }
+                    })
+                })
+                .map_err(|e| e.to_string());
+            if let Ok(mut scan) = scan.lock() {
+                scan.result = Some(result);
+            }
+        });
+    }
+    
+    fn poll_verify_scan(&mut self) {
+        let finished = self.verify_scan.as_ref()
+            .and_then(|scan| scan.lock().ok().and_then(|mut scan| scan.result.take()));
+        let Some(result) = finished else {
+            return;
+        };
+        self.verify_scan = None;
+        
+        match result {
+            Ok(summary) => {
+                self.log_messages.push(format!("[+] Verified {} files", summary.verified));
+                for (filename, checks) in &summary.failed {
+                    let failed: Vec<String> = checks.iter().map(|c| c.check.clone()).collect();
+                    self.log_messages.push(format!("[!] {} failed verification: {}", filename, failed.join(", ")));
+                }
+                for (error, count) in &summary.key_errors {
+                    self.log_messages.push(format!("[!] {} ({} files not checked)", error, count));
+                }
+                for (filename, error) in &summary.errors {
+                    self.log_messages.push(format!("[!] {}: {}", filename, error));
+                }
+            }
+            Err(e) => self.log_messages.push(format!("[!] Verification failed: {}", e)),
+        }
+        
+        self.refresh_withheld();
+        self.reload_file_list();
+    }
+    
+    fn set_refuse_failed(&mut self, refuse: bool) {
+        self.config.refuse_failed_verification = refuse;
+        let mut saved = Config::load();
+        saved.refuse_failed_verification = refuse;
+        if let Err(e) = saved.save() {
+            self.log_messages.push(format!("[!] Could not save settings: {}", e));
+        }
+        self.refresh_withheld();
+    }
+    
+    /// Keep files that failed verification from the consoles, if the user refuses them
+    fn refresh_withheld(&mut self) {
+        let Some(db) = &self.database else {
+            return;
+        };
+        match verify::refused_paths(db, self.config.refuse_failed_verification) {
+            Ok(refused) => {
+                let queued = self.file_list.values().filter(|path| refused.contains(*path)).count();
+                if queued > 0 {
+                    self.log_messages.push(format!("[i] Not serving {} queued files that failed verification", queued));
+                }
+                self.devices.set_withheld(refused);
+            }
+            Err(e) => self.log_messages.push(format!("[!] {}", e)),
+        }
+    }
+    
+    /// ✔, ✖ or ? for a file's last verification, with its checks on hover
+    fn verification_badge(&self, ui: &mut Ui, file: &GameFile) {
+        let (symbol, color) = match file.verification {
+            Some(VerifyStatus::Verified) => ("✔", self.theme.success),
+            Some(VerifyStatus::Failed) => ("✖", self.theme.error),
+            Some(VerifyStatus::Error) => ("?", self.theme.text_muted),
+            None => return,
+        };
+        let response = ui.label(egui::RichText::new(symbol).color(color));
+        let Some(db) = &self.database else {
+            return;
+        };
+        response.on_hover_ui(|ui| match db.get_verification(file.id) {
+            Ok(Some(report)) => {
+                if let Some(error) = &report.error {
+                    ui.label(format!("Not checked: {}", error));
+                }
+                for check in &report.checks {
+                    let color = match check.status {
+                        CheckStatus::Passed => self.theme.success,
+                        CheckStatus::Failed => self.theme.error,
+                        CheckStatus::Skipped => self.theme.text_muted,
+                    };
+                    ui.label(egui::RichText::new(format!("{} {} {}: {}", check.status.as_str(), check.nca, check.check, check.detail))
+                        .color(color)
+                        .size(11.0));
+                }
+                ui.label(format!("Checked {}", format_timestamp(report.checked_at)));
+            }
+            Ok(None) => {}
+            Err(e) => {
+                ui.label(e.to_string());
+            }
+        });
+    }
+    
+    /// Import a title database dump and remember it for refreshing
+    fn import_titles(&mut self, path: PathBuf) {
+        let Some(db) = &self.database else {
+            return;
+        };
+        match titledb::import_file(db, &path) {
+            Ok(summary) => {
+                self.log_messages.push(format!("[+] Imported {} titles from {}", summary.imported, path.display()));
+                if summary.skipped > 0 {
+                    self.log_messages.push(format!("[i] Skipped {} entries without a title ID", summary.skipped));
+                }
+            }
+            Err(e) => {
+                self.log_messages.push(format!("[!] Title import failed: {}", e));
+                return;
+            }
+        }
+        
+        self.config.titledb_path = Some(path.clone());
+        let mut saved = Config::load();
+        saved.titledb_path = Some(path);
+        if let Err(e) = saved.save() {
+            self.log_messages.push(format!("[!] Could not save settings: {}", e));
+        }
+        self.reload_file_list();
+    }
+    
+    fn export_library(&mut self, path: &Path) {
+        let Some(db) = &self.database else {
+            return;
+        };
+        match LibraryExport::from_db(db).and_then(|export| export.write(path).map(|_| export)) {
+            Ok(export) => self.log_messages.push(format!("[+] Exported {} files to {}", export.files.len(), path.display())),
+            Err(e) => self.log_messages.push(format!("[!] Library export failed: {}", e)),
+        }
+    }
+    
+    fn open_library_import(&mut self, path: PathBuf) {
+        match LibraryExport::read(&path) {
+            Ok(export) => {
+                self.library_import = Some(LibraryImport {
+                    path,
+                    export,
+                    match_by: MatchBy::Path,
+                    rewrites: vec![(String::new(), String::new())],
+                    add_missing: false,
+                });
+            }
+            Err(e) => self.log_messages.push(format!("[!] {}", e)),
+        }
+    }
+    
+    fn library_import_window(&mut self, ctx: &Context) {
+        let Some(import) = &mut self.library_import else {
+            return;
+        };
+        let mut open = true;
+        let mut run = false;
+        
+        egui::Window::new("📥 Import Library")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{}: {} files, {} tags, {} collections",
+                    import.path.display(),
+                    import.export.files.len(),
+                    import.export.tags.len(),
+                    import.export.collections.len()
+                ));
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    ui.label("Match files by");
+                    for match_by in MatchBy::ALL {
+                        ui.radio_value(&mut import.match_by, match_by, match_by.as_str());
+                    }
+                });
+                
+                ui.add_space(8.0);
+                ui.label("Rewrite paths that start with");
+                let mut remove = None;
+                for (i, (from, to)) in import.rewrites.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.add(egui::TextEdit::singleline(from).hint_text("/mnt/games").desired_width(200.0));
+                        ui.label("→");
+                        ui.add(egui::TextEdit::singleline(to).hint_text("D:\\Games").desired_width(200.0));
+                        if ui.small_button("✖").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = remove {
+                    import.rewrites.remove(i);
+                }
+                if ui.small_button("➕ Add rewrite").clicked() {
+                    import.rewrites.push((String::new(), String::new()));
+                }
+                
+                ui.add_space(8.0);
+                ui.checkbox(&mut import.add_missing, "Add files that aren't in the library but exist at their new path");
+                ui.label(egui::RichText::new("Favorites, tags and collections are added to; nothing is removed.")
+                    .color(self.theme.text_muted)
+                    .size(12.0));
+                run = ui.button("📥 Import").clicked();
+            });
+        
+        if run {
+            let options = ImportOptions {
+                match_by: import.match_by,
+                rewrites: import.rewrites.iter()
+                    .filter(|(from, to)| !from.trim().is_empty() && !to.trim().is_empty())
+                    .map(|(from, to)| PathRewrite { from: from.trim().to_string(), to: to.trim().to_string() })
+                    .collect(),
+                add_missing: import.add_missing,
+            };
+            if let Some(db) = &self.database {
+                match backup::import_library(db, &import.export, &options) {
+                    Ok(summary) => {
+                        self.log_messages.push(format!(
+                            "[+] Imported {}: {} files matched by {}, {} added, {} collections created, {} merged",
+                            import.path.display(),
+                            summary.matched,
+                            options.match_by.as_str(),
+                            summary.added,
+                            summary.collections_created,
+                            summary.collections_merged
+                        ));
+                        if !summary.unmatched.is_empty() {
+                            self.log_messages.push(format!("[!] {} exported files matched nothing", summary.unmatched.len()));
+                        }
+                    }
+                    Err(e) => self.log_messages.push(format!("[!] Library import failed: {}", e)),
+                }
+            }
+            open = false;
+            self.reload_file_list();
+        }
+        if !open {
+            self.library_import = None;
+        }
+    }
+    
+    /// Follow library roots to new mount points and note which went offline
+    fn check_roots(&mut self) {
+        self.roots_checked_at = Some(Instant::now());
+        let Some(db) = &self.database else {
+            return;
+        };
+        let check = match volumes::resolve_roots(db, &volumes::current_mounts()) {
+            Ok(check) => check,
+            Err(e) => {
+                self.log_messages.push(format!("[!] Could not check library roots: {}", e));
+                return;
+            }
+        };
+        for (root, path) in &check.relocated {
+            self.log_messages.push(format!("[+] {} is now at {}", root.path.display(), path.display()));
+        }
+        for root in &check.went_offline {
+            self.log_messages.push(format!("[i] {} is offline; its {} files are kept", root.path.display(), root.file_count));
+        }
+        for root in &check.came_back {
+            self.log_messages.push(format!("[+] {} is back online", root.path.display()));
+        }
+        if !check.is_empty() {
+            self.reload_file_list();
+        }
+    }
+    
+    /// The folders files were added from, and whether their drives are connected
+    fn roots_card(&mut self, ui: &mut Ui) {
+        let Some(db) = &self.database else {
+            return;
+        };
+        let roots = db.get_roots().unwrap_or_default();
+        let mut remove = None;
+        
+        egui::Frame::none()
+            .fill(self.theme.bg_secondary)
+            .rounding(Rounding::same(12.0))
+            .inner_margin(egui::Margin::same(20.0))
+            .stroke(Stroke::new(1.0, self.theme.border))
+            .show(ui, |ui| {
+                ui.label(egui::RichText::new("💽 Library Roots")
+                    .color(self.theme.text_primary)
+                    .size(16.0)
+                    .strong());
+                if roots.is_empty() {
+                    ui.label(egui::RichText::new("Folders added with \"Add Folder\" appear here.")
+                        .color(self.theme.text_muted)
+                        .size(12.0));
+                }
+                for root in &roots {
+                    ui.horizontal(|ui| {
+                        let (status, color) = if root.online {
+                            ("● online", self.theme.success)
+                        } else {
+                            ("○ offline", self.theme.text_muted)
+                        };
+                        ui.label(egui::RichText::new(status).color(color).size(12.0));
+                        ui.label(root.path.display().to_string())
+                            .on_hover_text(root.volume_id.as_deref().unwrap_or("Volume not identified"));
+                        if let Some(label) = &root.label {
+                            ui.label(egui::RichText::new(label).color(self.theme.text_muted).size(12.0));
+                        }
+                        ui.label(egui::RichText::new(format!("{} files", root.file_count))
+                            .color(self.theme.text_muted)
+                            .size(12.0));
+                        if ui.small_button("✖").on_hover_text("Forget this root; its files stay in the library").clicked() {
+                            remove = Some(root.id);
+                        }
+                    });
+                }
+            });
+        
+        if let Some(id) = remove {
+            if let Err(e) = db.remove_root(id) {
+                self.log_messages.push(format!("[!] {}", e));
+            }
+        }
+    }
+    
+    /// Back up the database if the last automatic backup is old enough
+    fn run_due_backup(&mut self) {
+        self.backup_checked_at = Some(Instant::now());
+        let Some(db) = &self.database else {
+            return;
+        };
+        match backup::backup_if_due(db, &self.config.backup) {
+            Ok(Some(path)) => self.log_messages.push(format!("[i] Backed up the library to {}", path.display())),
+            Ok(None) => {}
+            Err(e) => self.log_messages.push(format!("[!] Library backup failed: {}", e)),
+        }
+    }
+    
+    /// Edit the title a file belongs to; updates and DLC without their own entry edit the base game
+    fn open_title_editor(&mut self, title_id: &str) {
+        let Some(db) = &self.database else {
+            return;
+        };
+        let current = db.lookup_title(title_id).ok().flatten();
+        let title_id = current.as_ref().map_or_else(|| title_id.to_string(), |info| info.title_id.clone());
+        let current = current.unwrap_or_default();
+        let imported = db.get_imported_title(&title_id).ok().flatten().unwrap_or_default();
+        
+        self.title_editor = Some(TitleEditor {
+            fields: title_fields(&current).map(|field| field.unwrap_or_default()),
+            title_id,
+            imported,
+        });
+    }
+    
+    fn title_editor_window(&mut self, ctx: &Context) {
+        let Some(editor) = &mut self.title_editor else {
+            return;
+        };
+        let mut open = true;
+        let mut save = false;
+        let mut reset = false;
+        
+        egui::Window::new(format!("ℹ Title {}", editor.title_id))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("title_fields").num_columns(2).spacing([10.0, 6.0]).show(ui, |ui| {
+                    let imported = title_fields(&editor.imported);
+                    for (i, label) in TITLE_FIELDS.iter().enumerate() {
+                        ui.label(*label);
+                        let hint = imported[i].clone().unwrap_or_default();
+                        let edit = if *label == "Description" {
+                            egui::TextEdit::multiline(&mut editor.fields[i]).desired_rows(3)
+                        } else {
+                            egui::TextEdit::singleline(&mut editor.fields[i])
+                        };
+                        ui.add(edit.hint_text(hint).desired_width(320.0));
+                        ui.end_row();
+                    }
+                });
+                
+                ui.add_space(8.0);
+                ui.label(egui::RichText::new("Changed fields are kept when the title database is re-imported.")
+                    .color(self.theme.text_muted)
+                    .size(12.0));
+                ui.horizontal(|ui| {
+                    save = ui.button("💾 Save").clicked();
+                    reset = ui.button("↺ Reset to Imported").clicked();
+                });
+            });
+        
+        if save || reset {
+            if let Some(db) = &self.database {
+                let result = if reset {
+                    db.clear_title_override(&editor.title_id)
+                } else {
+                    // Only fields that differ from the import become overrides
+                    let imported = title_fields(&editor.imported);
+                    let mut fields = editor.fields.iter().zip(imported).map(|(value, imported)| {
+                        let value = value.trim();
+                        (!value.is_empty() && Some(value) != imported.as_deref()).then(|| value.to_string())
+                    });
+                    let mut next = || fields.next().flatten();
+                    db.set_title_override(&TitleInfo {
+                        title_id: editor.title_id.clone(),
+                        name: next(),
+                        publisher: next(),
+                        release_date: next(),
+                        region: next(),
+                        description: next(),
+                        icon_path: next(),
+                        overridden: true,
+                    })
+                };
+                match result {
+                    Ok(()) => self.log_messages.push(format!("[+] Updated title {}", editor.title_id)),
+                    Err(e) => self.log_messages.push(format!("[!] {}", e)),
+                }
+            }
+            open = false;
+            self.search_loaded_at = None;
+        }
+        if !open {
+            self.title_editor = None;
+        }
+    }
+    
+    /// Hash size-matched files and build the health report without blocking the UI
+    fn start_health_scan(&mut self) {
+        let scan = Arc::new(Mutex::new(HealthScan::default()));
+        self.health_scan = Some(scan.clone());
+        self.health_selected.clear();
+        
+        let db_path = self.config.db_path.clone();
+        std::thread::spawn(move || {
+            let result = Database::new(&db_path)
+                .and_then(|db| {
+                    let summary = db.update_hashes(|done, total| {
+                        if let Ok(mut scan) = scan.lock() {
+                            scan.hashed = done;
+                            scan.to_hash = total;
+                        }
+                    })?;
+                    if let Ok(mut scan) = scan.lock() {
+                        scan.unreadable = summary.failed;
+                    }
+                    HealthReport::from_db(&db)
+                })
+                .map_err(|e| e.to_string());
+            if let Ok(mut scan) = scan.lock() {
+                scan.result = Some(result);
+            }
+        });
+    }
+    
+    /// Rebuild the report from stored hashes, e.g. after moving files away
+    fn refresh_health_report(&mut self) {
+        if let Some(db) = &self.database {
+            self.health_report = HealthReport::from_db(db).ok();
+        }
+    }
+    
+    fn trash_files(&mut self, files: &[GameFile]) {
+        if files.is_empty() {
+            return;
+        }
+        let Some(db) = &self.database else {
+            return;
+        };
+        match health::move_to_trash(db, files, &self.config.trash_dir) {
+            Ok(moved) => {
+                self.log_messages.push(format!(
+                    "[-] Moved {} files ({}) to {}",
+                    moved.len(),
+                    format_file_size(files.iter().map(|f| f.size).sum()),
+                    self.config.trash_dir.display()
+                ));
+                self.set_undo(
+                    format!("{} files moved to the trash", files.len()),
+                    UndoAction::Restore(files.iter().map(|f| f.id).collect()),
+                );
+            }
+            Err(e) => self.log_messages.push(format!("[!] Move to trash failed: {}", e)),
+        }
+        
+        // Trashed files can't be served any more
+        let trashed: HashSet<PathBuf> = files.iter().map(|f| PathBuf::from(&f.path)).collect();
+        self.file_list.retain(|_, path| !trashed.contains(path));
+        self.health_selected.clear();
+        self.refresh_health_report();
+        self.reload_file_list();
+    }
+    
+    /// Offer to undo a change for a while; a pending delete is carried out first
+    fn set_undo(&mut self, message: String, action: UndoAction) {
+        self.expire_undo();
+        self.undo = Some(Undo { message, action, until: Instant::now() + UNDO_WINDOW });
+    }
+    
+    /// End the undo window, deleting files that were waiting for it
+    fn expire_undo(&mut self) {
+        let Some(undo) = self.undo.take() else {
+            return;
+        };
+        if let (UndoAction::Delete(files), Some(db)) = (undo.action, &self.database) {
+            match trash::delete_files(db, &files) {
+                Ok(deleted) => self.log_messages.push(format!("[-] Deleted {} files from disk", deleted)),
+                Err(e) => self.log_messages.push(format!("[!] Delete failed: {}", e)),
+            }
+        }
+    }
+    
+    fn undo_last(&mut self) {
+        let Some(undo) = self.undo.take() else {
+            return;
+        };
+        match undo.action {
+            UndoAction::Restore(ids) => {
+                let trashed = self.database.as_ref().and_then(|db| db.get_trash().ok()).unwrap_or_default();
+                for file in trashed.iter().filter(|file| ids.contains(&file.id)) {
+                    self.restore_file(file);
+                }
+            }
+            UndoAction::Delete(files) => {
+                self.log_messages.push(format!("[i] Kept {} files in the trash", files.len()));
+            }
+        }
+    }
+    
+    fn undo_bar(&mut self, ui: &mut Ui) {
+        let Some(undo) = &self.undo else {
+            return;
+        };
+        let seconds_left = undo.until.saturating_duration_since(Instant::now()).as_secs() + 1;
+        let mut clicked = false;
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new(&undo.message)
+                .color(self.theme.text_primary)
+                .size(14.0));
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                clicked = ui.add(egui::Button::new(
+                    egui::RichText::new(format!("↩ Undo ({}s)", seconds_left))
+                        .color(self.theme.text_primary))
+                    .fill(self.theme.bg_secondary)
+                    .rounding(Rounding::same(8.0)))
+                    .clicked();
+            });
+        });
+        if clicked {
+            self.undo_last();
+        }
+    }
+    
+    fn restore_file(&mut self, file: &GameFile) {
+        let Some(db) = &self.database else {
+            return;
+        };
+        match trash::restore(db, file) {
+            Ok(()) => self.log_messages.push(format!("[+] Restored: {}", file.path)),
+            Err(e) => self.log_messages.push(format!("[!] {}", e)),
+        }
+        if self.health_report.is_some() {
+            self.refresh_health_report();
+        }
+        self.reload_file_list();
+    }
+    
+    fn trash_panel(&mut self, ui: &mut Ui) {
+        // Files waiting out the undo window before deletion are already gone as far as the user knows
+        let deleting: HashSet<i64> = match &self.undo {
+            Some(Undo { action: UndoAction::Delete(files), .. }) => files.iter().map(|f| f.id).collect(),
+            _ => HashSet::new(),
+        };
+        let trash: Vec<GameFile> = self.database.as_ref()
+            .and_then(|db| db.get_trash().ok())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|file| !deleting.contains(&file.id))
+            .collect();
+        let mut action = None;
+        
+        // Header card
+        egui::Frame::none()
+            .fill(self.theme.bg_secondary)
+            .rounding(Rounding::same(12.0))
+            .inner_margin(egui::Margin::same(20.0))
+            .stroke(Stroke::new(1.0, self.theme.border))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading(egui::RichText::new("🗑 Trash")
+                        .color(self.theme.text_primary)
+                        .size(20.0));
+                    
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.add_enabled(!trash.is_empty(), egui::Button::new("Empty Trash"))
+                            .on_hover_text("Forget the trashed files; they stay on disk")
+                            .clicked()
+                        {
+                            action = Some(TrashAction::Empty);
+                        }
+                        if ui.button("🧹 Find Missing Files")
+                            .on_hover_text("List library files that are gone from disk, without changing anything")
+                            .clicked()
+                        {
+                            action = Some(TrashAction::FindMissing);
+                        }
+                    });
+                });
+                ui.label(egui::RichText::new(format!(
+                    "Removed files keep their tags, favorites and history here until they're restored or deleted. \
+                     Quarantine moves the file itself to {}.",
+                    self.config.trash_dir.display()
+                ))
+                    .color(self.theme.text_muted)
+                    .size(12.0));
+            });
+        
+        // Cleanup dry run
+        if let Some(missing) = &self.cleanup_report {
+            ui.add_space(10.0);
+            egui::Frame::none()
+                .fill(self.theme.bg_secondary)
+                .rounding(Rounding::same(12.0))
+                .inner_margin(egui::Margin::same(20.0))
+                .stroke(Stroke::new(1.0, self.theme.border))
+                .show(ui, |ui| {
+                    ui.label(egui::RichText::new(if missing.is_empty() {
+                        "🧹 No library files are missing".to_string()
+                    } else {
+                        format!("🧹 {} library files are missing from disk", missing.len())
+                    })
+                        .color(self.theme.text_primary)
+                        .size(16.0)
+                        .strong());
+                    ScrollArea::vertical()
+                        .id_source("cleanup_report")
+                        .max_height(150.0)
+                        .show(ui, |ui| {
+                            for file in missing {
+                                ui.label(egui::RichText::new(&file.path).color(self.theme.text_secondary).size(12.0));
                             }
                         });
-                        
-                        // Filename column with truncation
-                        row.col(|ui| {
-                            ui.label(&file.filename)
-                                .on_hover_text(&file.filename); // Show full name on hover
-                        });
-                        
-                        // Size column
-                        row.col(|ui| {
-                            ui.label(format_file_size(file.size));
-                        });
-                        
-                        // Install count column
-                        row.col(|ui| {
-                            ui.label(format!("{}", file.install_count));
-                        });
-                        
-                        // Actions column
-                        row.col(|ui| {
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(!missing.is_empty(), egui::Button::new("🗑 Move All to Trash")).clicked() {
+                            action = Some(TrashAction::TrashMissing);
+                        }
+                        if ui.button("Dismiss").clicked() {
+                            action = Some(TrashAction::DismissMissing);
+                        }
+                    });
+                });
+        }
+        
+        ui.add_space(10.0);
+        if trash.is_empty() {
+            ui.label(egui::RichText::new("The trash is empty.")
+                .color(self.theme.text_muted));
+        }
+        ScrollArea::vertical()
+            .id_source("trash_list")
+            .show(ui, |ui| {
+                for file in &trash {
+                    egui::Frame::none()
+                        .fill(self.theme.bg_secondary)
+                        .rounding(Rounding::same(8.0))
+                        .inner_margin(egui::Margin::symmetric(12.0, 8.0))
+                        .show(ui, |ui| {
                             ui.horizontal(|ui| {
-                                // Add/Remove from queue button
-                                let in_queue = self.file_list.contains_key(&file.filename);
-                                let button_text = if in_queue { "-" } else { "+" };
-                                let tooltip = if in_queue { "Remove from queue" } else { "Add to queue" };
-                                
-                                if ui.small_button(button_text).on_hover_text(tooltip).clicked() {
-                                    if in_queue {
-                                        self.file_list.remove(&file.filename);
-                                        self.log_messages.push(format!("[-] Removed from queue: {}", file.filename));
-                                    } else {
-                                        let path = PathBuf::from(&file.path);
-                                        if path.exists() {
-                                            self.file_list.insert(file.filename.clone(), path);
-                                            self.log_messages.push(format!("[+] Added to queue: {}", file.filename));
-                                        } else {
-                                            self.log_messages.push(format!("[!] File not found: {}", file.filename));
-                                        }
+                                ui.vertical(|ui| {
+                                    ui.label(egui::RichText::new(&file.filename)
+                                        .color(self.theme.text_primary)
+                                        .strong())
+                                        .on_hover_text(&file.path);
+                                    let mut details = format!(
+                                        "{} · trashed {}",
+                                        format_file_size(file.size),
+                                        format_timestamp(file.deleted_at.unwrap_or_default())
+                                    );
+                                    if let Some(quarantined) = &file.quarantined_path {
+                                        details.push_str(&format!(" · 📦 in {}", quarantined));
+                                    } else if !Path::new(&file.path).exists() {
+                                        details.push_str(" · missing from disk");
                                     }
-                                }
+                                    ui.label(egui::RichText::new(details)
+                                        .color(self.theme.text_muted)
+                                        .size(12.0));
+                                });
                                 
-                                // Delete button
-                                if ui.small_button("Del").on_hover_text("Remove from library").clicked() {
-                                    if let Some(db) = &self.database {
-                                        let _ = db.remove_file(file.id);
-                                        self.reload_file_list();
-                                        self.log_messages.push(format!("[x] Removed: {}", file.filename));
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    if ui.small_button("Delete File")
+                                        .on_hover_text("Delete the file from disk for good")
+                                        .clicked()
+                                    {
+                                        action = Some(TrashAction::Delete(file.clone()));
                                     }
-                                }
+                                    if file.quarantined_path.is_none() && Path::new(&file.path).exists()
+                                        && ui.small_button("📦")
+                                            .on_hover_text("Move the file to the quarantine folder")
+                                            .clicked()
+                                    {
+                                        action = Some(TrashAction::Quarantine(file.clone()));
+                                    }
+                                    if ui.small_button("↩ Restore").clicked() {
+                                        action = Some(TrashAction::Restore(file.clone()));
+                                    }
+                                });
                             });
                         });
+                    ui.add_space(4.0);
+                }
+            });
+        
+        let Some(action) = action else {
+            return;
+        };
+        match action {
+            TrashAction::FindMissing => {
+                // Files on a drive that was unplugged since the last check aren't missing
+                self.check_roots();
+                if let Some(db) = &self.database {
+                    match db.missing_files() {
+                        Ok(missing) => {
+                            let unrooted = db.unrooted_paths().unwrap_or_default();
+                            let loose = missing.iter().filter(|f| unrooted.contains(Path::new(&f.path))).count();
+                            if loose > 0 {
+                                self.log_messages.push(format!(
+                                    "[!] {} missing files aren't under a library root and may only be on an unplugged drive; \
+                                     add their folder as a root to keep them",
+                                    loose
+                                ));
+                            }
+                            self.cleanup_report = Some(missing);
+                        }
+                        Err(e) => self.log_messages.push(format!("[!] {}", e)),
+                    }
+                }
+            }
+            TrashAction::TrashMissing => {
+                let ids: Vec<i64> = self.cleanup_report.take().unwrap_or_default().iter().map(|f| f.id).collect();
+                if let Some(db) = &self.database {
+                    match db.trash_files(&ids) {
+                        Ok(()) => {
+                            self.log_messages.push(format!("[-] Moved {} missing files to the trash", ids.len()));
+                            self.set_undo(
+                                format!("{} missing files moved to the trash", ids.len()),
+                                UndoAction::Restore(ids),
+                            );
+                        }
+                        Err(e) => self.log_messages.push(format!("[!] {}", e)),
+                    }
+                }
+                self.reload_file_list();
+            }
+            TrashAction::DismissMissing => self.cleanup_report = None,
+            TrashAction::Restore(file) => self.restore_file(&file),
+            TrashAction::Quarantine(file) => {
+                if let Some(db) = &self.database {
+                    match trash::quarantine(db, &[file], &self.config.trash_dir) {
+                        Ok(moved) => {
+                            for path in moved {
+                                self.log_messages.push(format!("[-] Quarantined: {}", path.display()));
+                            }
+                        }
+                        Err(e) => self.log_messages.push(format!("[!] Quarantine failed: {}", e)),
+                    }
+                }
+            }
+            TrashAction::Delete(file) => self.trash_confirm = Some(TrashConfirm::DeleteFiles(vec![file])),
+            TrashAction::Empty => self.trash_confirm = Some(TrashConfirm::Forget(trash)),
+        }
+    }
+    
+    fn trash_confirm_window(&mut self, ctx: &Context) {
+        let Some(confirm) = &self.trash_confirm else {
+            return;
+        };
+        let (title, question, button) = match confirm {
+            TrashConfirm::DeleteFiles(files) => (
+                "🗑 Delete Files",
+                format!(
+                    "Delete {} ({}) from disk? This can't be undone once the {} second undo window is over.",
+                    match files.as_slice() {
+                        [file] => file.filename.clone(),
+                        _ => format!("{} files", files.len()),
+                    },
+                    format_file_size(files.iter().map(|f| f.size).sum()),
+                    UNDO_WINDOW.as_secs()
+                ),
+                "Delete",
+            ),
+            TrashConfirm::Forget(files) => (
+                "🗑 Empty Trash",
+                format!(
+                    "Forget {} trashed files? Their tags, favorites and history are lost; the files stay on disk.",
+                    files.len()
+                ),
+                "Empty Trash",
+            ),
+        };
+        let mut open = true;
+        let mut confirmed = false;
+        let mut cancelled = false;
+        
+        egui::Window::new(title)
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(question);
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    confirmed = self.danger_button(ui, button).clicked();
+                    ui.add_space(10.0);
+                    cancelled = self.secondary_button(ui, "Cancel").clicked();
+                });
+            });
+        
+        if confirmed {
+            match self.trash_confirm.take() {
+                Some(TrashConfirm::DeleteFiles(files)) => {
+                    let message = format!("Deleting {} files from disk", files.len());
+                    self.set_undo(message, UndoAction::Delete(files));
+                }
+                Some(TrashConfirm::Forget(files)) => {
+                    if let Some(db) = &self.database {
+                        match trash::forget(db, &files) {
+                            Ok(()) => self.log_messages.push(format!("[-] Emptied the trash ({} files)", files.len())),
+                            Err(e) => self.log_messages.push(format!("[!] {}", e)),
+                        }
+                    }
+                }
+                None => {}
+            }
+        } else if cancelled || !open {
+            self.trash_confirm = None;
+        }
+    }
+    
+    fn health_panel(&mut self, ui: &mut Ui) {
+        // Pick up a finished scan
+        let finished = self.health_scan.as_ref()
+            .and_then(|scan| scan.lock().ok().and_then(|mut scan| {
+                Some((scan.result.take()?, std::mem::take(&mut scan.unreadable)))
+            }));
+        if let Some((result, unreadable)) = finished {
+            for (path, error) in unreadable {
+                self.log_messages.push(format!("[!] Couldn't hash {}: {}", path, error));
+            }
+            match result {
+                Ok(report) => self.health_report = Some(report),
+                Err(e) => self.log_messages.push(format!("[!] Health scan failed: {}", e)),
+            }
+            self.health_scan = None;
+        }
+        let progress = self.health_scan.as_ref()
+            .and_then(|scan| scan.lock().ok().map(|scan| (scan.hashed, scan.to_hash)));
+        
+        // Header card
+        egui::Frame::none()
+            .fill(self.theme.bg_secondary)
+            .rounding(Rounding::same(12.0))
+            .inner_margin(egui::Margin::same(20.0))
+            .stroke(Stroke::new(1.0, self.theme.border))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading(egui::RichText::new("🩺 Library Health")
+                        .color(self.theme.text_primary)
+                        .size(20.0));
+                    
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.add_enabled(progress.is_none(), egui::Button::new("🔍 Scan Library"))
+                            .on_hover_text("Hash files of matching size and look for duplicates and old versions")
+                            .clicked()
+                        {
+                            self.start_health_scan();
+                        }
+                        
+                        if let Some(report) = &self.health_report {
+                            ui.label(egui::RichText::new(format!(
+                                    "{} reclaimable",
+                                    format_file_size(report.reclaimable())))
+                                .color(self.theme.success)
+                                .size(13.0)
+                                .strong());
+                        }
+                    });
+                });
+                
+                if let Some((hashed, to_hash)) = progress {
+                    ui.add_space(8.0);
+                    let fraction = if to_hash == 0 { 0.0 } else { hashed as f32 / to_hash as f32 };
+                    ui.add(ProgressBar::new(fraction)
+                        .text(format!("Hashing {} / {} files", hashed, to_hash)));
+                    ui.ctx().request_repaint_after(Duration::from_millis(200));
+                }
+            });
+        
+        ui.add_space(15.0);
+        self.roots_card(ui);
+        ui.add_space(15.0);
+        
+        let Some(report) = self.health_report.clone() else {
+            ui.label(egui::RichText::new("Scan the library to find duplicate files, NSP/NSZ copies of the same release and superseded updates.")
+                .color(self.theme.text_muted)
+                .size(13.0));
+            return;
+        };
+        if report.is_empty() {
+            ui.label(egui::RichText::new("✅ No redundant files found")
+                .color(self.theme.success)
+                .size(14.0));
+            return;
+        }
+        
+        // Actions
+        let keep_newest = report.keep_newest();
+        let prefer_compressed = report.prefer_compressed();
+        let mut seen = HashSet::new();
+        let selected: Vec<GameFile> = report.duplicates.iter().flat_map(|g| g.files.iter())
+            .chain(report.redundant.iter().flat_map(|g| g.files.iter()))
+            .chain(report.superseded.iter().flat_map(|s| s.obsolete.iter()))
+            .filter(|f| self.health_selected.contains(&f.id) && seen.insert(f.id))
+            .cloned()
+            .collect();
+        let trash_hint = format!("Files are moved to {}", self.config.trash_dir.display());
+        
+        let mut to_trash = None;
+        ui.horizontal(|ui| {
+            let label = |files: &[GameFile]| format_file_size(files.iter().map(|f| f.size).sum());
+            if ui.add_enabled(!keep_newest.is_empty(), egui::Button::new(
+                    format!("🕒 Keep Newest ({} files, {})", keep_newest.len(), label(&keep_newest))))
+                .on_hover_text(format!("Trash extra copies of duplicates and superseded updates. {}", trash_hint))
+                .clicked()
+            {
+                to_trash = Some(keep_newest.clone());
+            }
+            if ui.add_enabled(!prefer_compressed.is_empty(), egui::Button::new(
+                    format!("🗜 Prefer Compressed ({} files, {})", prefer_compressed.len(), label(&prefer_compressed))))
+                .on_hover_text(format!("Trash NSP/XCI files that also have an NSZ/XCZ copy. {}", trash_hint))
+                .clicked()
+            {
+                to_trash = Some(prefer_compressed.clone());
+            }
+            if ui.add_enabled(!selected.is_empty(), egui::Button::new(
+                    format!("🗑 Move Selected to Trash ({})", selected.len())))
+                .on_hover_text(trash_hint.clone())
+                .clicked()
+            {
+                to_trash = Some(selected.clone());
+            }
+        });
+        
+        ui.add_space(10.0);
+        
+        ScrollArea::vertical()
+            .id_source("health")
+            .auto_shrink([false, true])
+            .show(ui, |ui| {
+                if !report.duplicates.is_empty() {
+                    self.health_section(ui, &format!("Exact duplicates ({})", report.duplicates.len()));
+                    for group in &report.duplicates {
+                        self.health_group(ui, &format!("SHA-256 {}…", &group.hash[..12.min(group.hash.len())]), &group.files);
+                    }
+                }
+                if !report.redundant.is_empty() {
+                    self.health_section(ui, &format!("Compressed and uncompressed copies ({})", report.redundant.len()));
+                    for group in &report.redundant {
+                        self.health_group(ui, &format!("{} v{}", group.title_id, group.version), &group.files);
+                    }
+                }
+                if !report.superseded.is_empty() {
+                    self.health_section(ui, &format!("Superseded updates ({})", report.superseded.len()));
+                    for update in &report.superseded {
+                        let mut files = vec![update.latest.clone()];
+                        files.extend(update.obsolete.iter().rev().cloned());
+                        self.health_group(ui, &format!("{} (latest v{})", update.title_id, update.latest.version.unwrap_or(0)), &files);
+                    }
+                }
+            });
+        
+        if let Some(files) = to_trash {
+            self.trash_files(&files);
+        }
+    }
+    
+    fn health_section(&self, ui: &mut Ui, title: &str) {
+        ui.add_space(8.0);
+        ui.label(egui::RichText::new(title)
+            .color(self.theme.text_primary)
+            .size(15.0)
+            .strong());
+        ui.add_space(4.0);
+    }
+    
+    /// One group of related files; the first is the one the actions keep
+    fn health_group(&mut self, ui: &mut Ui, title: &str, files: &[GameFile]) {
+        egui::Frame::none()
+            .fill(self.theme.bg_secondary)
+            .rounding(Rounding::same(10.0))
+            .inner_margin(egui::Margin::symmetric(15.0, 8.0))
+            .stroke(Stroke::new(1.0, self.theme.border))
+            .show(ui, |ui| {
+                ui.label(egui::RichText::new(title)
+                    .color(self.theme.text_secondary)
+                    .size(12.0));
+                for (i, file) in files.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        let mut selected = self.health_selected.contains(&file.id);
+                        if ui.checkbox(&mut selected, "").changed() {
+                            if selected {
+                                self.health_selected.insert(file.id);
+                            } else {
+                                self.health_selected.remove(&file.id);
+                            }
+                        }
+                        ui.label(egui::RichText::new(&file.filename)
+                                .color(self.theme.text_primary)
+                                .size(13.0))
+                            .on_hover_text(&file.path);
+                        ui.label(egui::RichText::new(format!(
+                                "{} · {}",
+                                format_file_size(file.size),
+                                format_timestamp(file.last_modified)))
+                            .color(self.theme.text_muted)
+                            .size(12.0));
+                        if i == 0 {
+                            ui.label(egui::RichText::new("keep")
+                                .color(self.theme.success)
+                                .size(11.0));
+                        }
                     });
                 }
             });
+        ui.add_space(4.0);
     }
     
+    /// Mark the library table stale after a database change; it's re-queried on the next frame
     fn reload_file_list(&mut self) {
-        // Reload file list from database
-        // This will be called after any database operation
+        self.search_loaded_at = None;
+    }
+    
+    fn load_search_page(&mut self) {
+        self.search_loaded_at = Some(Instant::now());
+        let Some(db) = &self.database else {
+            self.search_results = None;
+            return;
+        };
+        
+        self.tag_colors = db.get_tags()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|tag| Some((tag.name, parse_tag_color(&tag.color)?)))
+            .collect();
+        
+        let query = SearchQuery::parse(&self.search_query);
+        let offset = self.search_page * LIBRARY_PAGE_SIZE;
+        match db.search_page(&query, offset, LIBRARY_PAGE_SIZE) {
+            Ok(results) if results.files.is_empty() && self.search_page > 0 => {
+                // The last page emptied out (e.g. files removed); step back
+                self.search_page = results.total.saturating_sub(1) / LIBRARY_PAGE_SIZE;
+                self.search_loaded_at = None;
+            }
+            Ok(results) => self.search_results = Some(results),
+            Err(e) => {
+                self.log_messages.push(format!("[!] Search failed: {}", e));
+                self.search_results = None;
+            }
+        }
     }
     
     fn activity_log_panel(&mut self, ui: &mut Ui) {
-        // Get logs from progress
-        if let Ok(progress) = self.progress.lock() {
-            if !progress.logs.is_empty() {
+        // Get logs from every console's progress
+        let multiple = self.devices.sessions().len() > 1;
+        for session in self.devices.sessions() {
+            if let Ok(progress) = session.progress.lock() {
                 // Merge server logs with app logs
                 for log in progress.logs.iter().rev().take(10) {
-                    if !self.log_messages.contains(log) {
-                        self.log_messages.push(log.clone());
+                    let log = if multiple {
+                        format!("[{}] {}", session.id(), log)
+                    } else {
+                        log.clone()
+                    };
+                    if !self.log_messages.contains(&log) {
+                        self.log_messages.push(log);
                     }
                 }
             }
@@ -936,7 +3619,35 @@ impl DbiApp {
     }
 }
 
-pub fn launch_gui() {
+/// Editable fields of a title, in `TITLE_FIELDS` order
+fn title_fields(info: &TitleInfo) -> [Option<String>; 6] {
+    [
+        info.name.clone(),
+        info.publisher.clone(),
+        info.release_date.clone(),
+        info.region.clone(),
+        info.description.clone(),
+        info.icon_path.clone(),
+    ]
+}
+
+/// Search box text that filters by one tag
+fn tag_query(name: &str) -> String {
+    if name.contains(char::is_whitespace) {
+        format!("tag:\"{}\"", name)
+    } else {
+        format!("tag:{}", name)
+    }
+}
+
+/// `#RRGGBB` as stored for tags
+fn parse_tag_color(color: &str) -> Option<Color32> {
+    let hex = color.strip_prefix('#').filter(|h| h.len() == 6)?;
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(Color32::from_rgb(channel(0)?, channel(2)?, channel(4)?))
+}
+
+pub fn launch_gui(config: Config) {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1400.0, 900.0]) // Larger window for better UX
@@ -948,6 +3659,6 @@ pub fn launch_gui() {
     eframe::run_native(
         "SwitchLink - Modern Edition",
         options,
-        Box::new(|_cc| Box::new(DbiApp::new())),
+        Box::new(|_cc| Box::new(DbiApp::with_config(config))),
     ).unwrap();
 }
@@ -0,0 +1,162 @@
+/// Library health: duplicate files, NSP/NSZ copies of the same title and superseded updates
+use anyhow::Result;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::database::{Database, GameFile};
+use crate::title::{self, ContentType};
+
+/// Identical files (same SHA-256) at different paths
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    pub hash: String,
+    /// Newest first; the first is the copy to keep
+    pub files: Vec<GameFile>,
+}
+
+/// The same title and version stored both compressed and uncompressed
+#[derive(Debug, Clone)]
+pub struct RedundantGroup {
+    pub title_id: String,
+    pub version: u32,
+    /// Compressed copies first, newest first within each kind; the first is the copy to keep
+    pub files: Vec<GameFile>,
+}
+
+/// Older updates of a title whose newer update is also in the library
+#[derive(Debug, Clone)]
+pub struct SupersededUpdate {
+    pub title_id: String,
+    pub latest: GameFile,
+    /// Oldest version first
+    pub obsolete: Vec<GameFile>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HealthReport {
+    pub duplicates: Vec<DuplicateGroup>,
+    pub redundant: Vec<RedundantGroup>,
+    pub superseded: Vec<SupersededUpdate>,
+}
+
+impl HealthReport {
+    /// Check the library; run `Database::update_hashes` first to find duplicates
+    pub fn from_db(db: &Database) -> Result<Self> {
+        Ok(Self::build(&db.get_files()?))
+    }
+
+    pub fn build(files: &[GameFile]) -> Self {
+        let mut by_hash: BTreeMap<&str, Vec<GameFile>> = BTreeMap::new();
+        let mut by_release: BTreeMap<(&str, u32), Vec<GameFile>> = BTreeMap::new();
+        let mut updates: BTreeMap<&str, Vec<GameFile>> = BTreeMap::new();
+
+        for file in files {
+            if let Some(hash) = file.hash.as_deref() {
+                by_hash.entry(hash).or_default().push(file.clone());
+            }
+            if let (Some(title_id), Some(version)) = (file.title_id.as_deref(), file.version) {
+                by_release.entry((title_id, version)).or_default().push(file.clone());
+                if title::content_type(&file.filename) == ContentType::Update {
+                    updates.entry(title_id).or_default().push(file.clone());
+                }
+            }
+        }
+
+        let duplicates = by_hash
+            .into_iter()
+            .filter(|(_, files)| files.len() > 1)
+            .map(|(hash, mut files)| {
+                files.sort_by_key(|f| std::cmp::Reverse((f.last_modified, f.added_at)));
+                DuplicateGroup {
+                    hash: hash.to_string(),
+                    files,
+                }
+            })
+            .collect();
+
+        let redundant = by_release
+            .into_iter()
+            .filter(|(_, files)| {
+                files.iter().any(|f| is_compressed(&f.filename))
+                    && files.iter().any(|f| !is_compressed(&f.filename))
+            })
+            .map(|((title_id, version), mut files)| {
+                files.sort_by_key(|f| {
+                    (!is_compressed(&f.filename), std::cmp::Reverse((f.last_modified, f.added_at)))
+                });
+                RedundantGroup {
+                    title_id: title_id.to_string(),
+                    version,
+                    files,
+                }
+            })
+            .collect();
+
+        let superseded = updates
+            .into_iter()
+            .filter_map(|(title_id, mut files)| {
+                files.sort_by_key(|f| (f.version, f.last_modified));
+                let latest = files.pop()?;
+                let obsolete: Vec<GameFile> = files
+                    .into_iter()
+                    .filter(|f| f.version < latest.version)
+                    .collect();
+                (!obsolete.is_empty()).then(|| SupersededUpdate {
+                    title_id: title_id.to_string(),
+                    latest,
+                    obsolete,
+                })
+            })
+            .collect();
+
+        Self {
+            duplicates,
+            redundant,
+            superseded,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.duplicates.is_empty() && self.redundant.is_empty() && self.superseded.is_empty()
+    }
+
+    /// "Keep newest": extra copies of duplicates and obsolete updates
+    pub fn keep_newest(&self) -> Vec<GameFile> {
+        let extra_copies = self.duplicates.iter().flat_map(|g| g.files.iter().skip(1));
+        let obsolete = self.superseded.iter().flat_map(|s| s.obsolete.iter());
+        unique(extra_copies.chain(obsolete))
+    }
+
+    /// "Prefer compressed": uncompressed files with a compressed copy of the same release
+    pub fn prefer_compressed(&self) -> Vec<GameFile> {
+        unique(self.redundant.iter().flat_map(|g| g.files.iter().skip(1)))
+    }
+
+    /// Bytes freed by applying both actions
+    pub fn reclaimable(&self) -> u64 {
+        let mut files = self.keep_newest();
+        files.extend(self.prefer_compressed());
+        unique(files.iter()).iter().map(|f| f.size).sum()
+    }
+}
+
+/// NSZ and XCZ are compressed NSP and XCI
+pub fn is_compressed(filename: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("nsz") || e.eq_ignore_ascii_case("xcz"))
+}
+
+fn unique<'a>(files: impl Iterator<Item = &'a GameFile>) -> Vec<GameFile> {
+    let mut seen = HashSet::new();
+    files.filter(|f| seen.insert(f.id)).cloned().collect()
+}
+
+/// Move files into `trash_dir` and put them in the library trash, from where they can be restored
+///
+/// Returns where each file went. Files already gone from disk are only
+/// trashed.
+pub fn move_to_trash(db: &Database, files: &[GameFile], trash_dir: &Path) -> Result<Vec<PathBuf>> {
+    crate::trash::quarantine(db, files, trash_dir)
+}
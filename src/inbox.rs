@@ -0,0 +1,301 @@
+/// Inbox for files uploaded from the Switch
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::protocol::UploadCategory;
+
+#[derive(Debug, Error)]
+pub enum InboxError {
+    #[error("invalid upload name: {0}")]
+    InvalidName(String),
+    #[error("no upload in progress for {0}")]
+    UnknownUpload(String),
+    #[error("{0} is being uploaded in more than one category")]
+    AmbiguousUpload(String),
+    #[error("range {offset}+{len} is outside the announced size {file_size}")]
+    OutOfRange { offset: u64, len: u64, file_size: u64 },
+    #[error("size mismatch: expected {expected} bytes, received {actual}")]
+    SizeMismatch { expected: u64, actual: u64 },
+    #[error("hash mismatch: expected {expected}, got {actual}")]
+    HashMismatch { expected: String, actual: String },
+}
+
+#[derive(Debug, Clone)]
+struct PendingUpload {
+    category: UploadCategory,
+    file_size: u64,
+    part_path: PathBuf,
+    /// Everything before this offset has been written
+    contiguous: u64,
+    /// Ranges written past `contiguous`, start -> end
+    ahead: BTreeMap<u64, u64>,
+}
+
+impl PendingUpload {
+    /// Note a written range, moving `contiguous` up over it and any ranges it now reaches
+    fn mark_written(&mut self, start: u64, end: u64) {
+        if start > self.contiguous {
+            let entry = self.ahead.entry(start).or_insert(end);
+            *entry = (*entry).max(end);
+            return;
+        }
+        self.contiguous = self.contiguous.max(end);
+        while let Some((&start, &end)) = self.ahead.first_key_value() {
+            if start > self.contiguous {
+                break;
+            }
+            self.ahead.remove(&start);
+            self.contiguous = self.contiguous.max(end);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompletedUpload {
+    pub name: String,
+    pub path: PathBuf,
+    pub category: UploadCategory,
+    pub size: u64,
+    pub hash: String,
+}
+
+pub struct Inbox {
+    root: PathBuf,
+    // Where `.part` files are kept while uploads are in progress
+    partial: PathBuf,
+    // Keyed by category as well, so the same name can be uploaded to two categories at once
+    uploads: HashMap<(UploadCategory, String), PendingUpload>,
+}
+
+impl Inbox {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            partial: root.join(".partial"),
+            root,
+            uploads: HashMap::new(),
+        }
+    }
+
+    /// Keep partial uploads in a folder of their own for one console
+    ///
+    /// Sessions share the inbox folder; without this, two consoles uploading
+    /// the same name would write to and resume from the same `.part` file.
+    pub fn for_session(mut self, session_id: &str) -> Self {
+        self.partial = self.root.join(".partial").join(session_id);
+        self
+    }
+
+    /// Start or resume an upload, returning the offset the client should continue from
+    ///
+    /// That's the end of the data written without gaps, which is remembered
+    /// next to the `.part` file so it survives a restart.
+    pub fn begin(&mut self, name: &str, category: UploadCategory, file_size: u64) -> Result<u64> {
+        let name = sanitize_name(name)?;
+        let key = (category, name.clone());
+        if let Some(upload) = self.uploads.get(&key).filter(|u| u.file_size == file_size) {
+            return Ok(upload.contiguous);
+        }
+
+        let part_dir = self.partial.join(category.dir_name());
+        std::fs::create_dir_all(&part_dir)?;
+
+        let part_path = part_dir.join(format!("{}.part", name));
+        let existing = std::fs::metadata(&part_path).map(|m| m.len()).unwrap_or(0);
+        let written = std::fs::read_to_string(progress_path(&part_path))
+            .ok()
+            .and_then(|offset| offset.trim().parse::<u64>().ok())
+            .unwrap_or(0);
+
+        // A partial file larger than the announced size belongs to a different file
+        let resume_offset = if existing > file_size || written > existing {
+            File::create(&part_path)?;
+            std::fs::remove_file(progress_path(&part_path)).ok();
+            0
+        } else {
+            written
+        };
+
+        self.uploads.insert(
+            key,
+            PendingUpload {
+                category,
+                file_size,
+                part_path,
+                contiguous: resume_offset,
+                ahead: BTreeMap::new(),
+            },
+        );
+
+        Ok(resume_offset)
+    }
+
+    /// Write a chunk of an upload at the given offset
+    ///
+    /// `category` picks between uploads of the same name; without it the name must be unique.
+    pub fn write_range(
+        &mut self,
+        name: &str,
+        category: Option<UploadCategory>,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<()> {
+        let upload = self.pending(name, category)?;
+
+        let len = data.len() as u64;
+        let end = offset.checked_add(len).filter(|end| *end <= upload.file_size);
+        let Some(end) = end else {
+            return Err(InboxError::OutOfRange {
+                offset,
+                len,
+                file_size: upload.file_size,
+            }
+            .into());
+        };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&upload.part_path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(data)?;
+
+        let before = upload.contiguous;
+        upload.mark_written(offset, end);
+        if upload.contiguous != before {
+            std::fs::write(progress_path(&upload.part_path), upload.contiguous.to_string())?;
+        }
+
+        Ok(())
+    }
+
+    /// Verify a finished upload and move it into its category folder
+    pub fn finish(
+        &mut self,
+        name: &str,
+        category: Option<UploadCategory>,
+        expected_hash: &[u8; 32],
+    ) -> Result<CompletedUpload> {
+        let upload = self.pending(name, category)?.clone();
+        let name = sanitize_name(name)?;
+        let key = (upload.category, name.clone());
+
+        if upload.contiguous != upload.file_size {
+            return Err(InboxError::SizeMismatch {
+                expected: upload.file_size,
+                actual: upload.contiguous,
+            }
+            .into());
+        }
+        let actual_size = upload.contiguous;
+
+        let actual_hash = hash_file(&upload.part_path)?;
+        let expected_hash = to_hex(expected_hash);
+        if actual_hash != expected_hash {
+            // The partial data is corrupt, make the client start over
+            std::fs::remove_file(&upload.part_path).ok();
+            std::fs::remove_file(progress_path(&upload.part_path)).ok();
+            self.uploads.remove(&key);
+            return Err(InboxError::HashMismatch {
+                expected: expected_hash,
+                actual: actual_hash,
+            }
+            .into());
+        }
+
+        let dest_dir = self.root.join(upload.category.dir_name());
+        std::fs::create_dir_all(&dest_dir)?;
+        let dest = unique_path(&dest_dir, &name);
+        std::fs::rename(&upload.part_path, &dest)?;
+        std::fs::remove_file(progress_path(&upload.part_path)).ok();
+        self.uploads.remove(&key);
+
+        Ok(CompletedUpload {
+            name,
+            path: dest,
+            category: upload.category,
+            size: actual_size,
+            hash: actual_hash,
+        })
+    }
+
+    fn pending(&mut self, name: &str, category: Option<UploadCategory>) -> Result<&mut PendingUpload> {
+        let name = sanitize_name(name)?;
+        let mut matches = self
+            .uploads
+            .iter_mut()
+            .filter(|((c, n), _)| *n == name && category.is_none_or(|category| *c == category))
+            .map(|(_, upload)| upload);
+        match (matches.next(), matches.next()) {
+            (Some(upload), None) => Ok(upload),
+            (Some(_), Some(_)) => Err(InboxError::AmbiguousUpload(name).into()),
+            (None, _) => Err(InboxError::UnknownUpload(name).into()),
+        }
+    }
+}
+
+/// Where the end of the gap-free data of a `.part` file is kept
+fn progress_path(part_path: &Path) -> PathBuf {
+    let mut path = part_path.as_os_str().to_owned();
+    path.push(".offset");
+    PathBuf::from(path)
+}
+
+/// Keep only the final path component so uploads can't escape the inbox
+pub(crate) fn sanitize_name(name: &str) -> Result<String> {
+    let file_name = Path::new(name.trim_end_matches('\0'))
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| n.to_string());
+
+    match file_name {
+        Some(n) if !n.is_empty() && n != "." && n != ".." => Ok(n),
+        _ => Err(InboxError::InvalidName(name.to_string()).into()),
+    }
+}
+
+/// Pick a destination that doesn't overwrite an earlier upload
+pub(crate) fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let candidate = dir.join(name);
+    if !candidate.exists() {
+        return candidate;
+    }
+
+    let path = Path::new(name);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(name);
+    let ext = path.extension().and_then(|e| e.to_str());
+
+    (1..)
+        .map(|i| match ext {
+            Some(ext) => dir.join(format!("{} ({}).{}", stem, i, ext)),
+            None => dir.join(format!("{} ({})", stem, i)),
+        })
+        .find(|p| !p.exists())
+        .unwrap()
+}
+
+/// SHA-256 of a file as lowercase hex
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
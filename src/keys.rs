@@ -0,0 +1,91 @@
+/// Console keys from a user-supplied `prod.keys`, needed to read encrypted NCAs
+///
+/// SwitchLink ships no keys; users dump their own with Lockpick_RCM.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::nca::NcaError;
+
+/// Where hactool and most Switch tools look for keys: `~/.switch/prod.keys`
+pub fn default_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".switch")
+        .join("prod.keys")
+}
+
+/// Named keys as hex strings, decoded when used
+#[derive(Debug, Clone, Default)]
+pub struct KeySet {
+    keys: HashMap<String, String>,
+}
+
+impl KeySet {
+    pub fn load(path: &Path) -> Result<Self, NcaError> {
+        if !path.is_file() {
+            return Err(NcaError::NoKeys(path.to_path_buf()));
+        }
+        Ok(Self::parse(&std::fs::read_to_string(path)?))
+    }
+
+    /// `name = hex` lines; blank lines and `;` or `#` comments are skipped
+    pub fn parse(text: &str) -> Self {
+        let keys = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with(';') && !line.starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+            .collect();
+        Self { keys }
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn get<const N: usize>(&self, name: &str) -> Result<[u8; N], NcaError> {
+        let value = self
+            .keys
+            .get(name)
+            .ok_or_else(|| NcaError::MissingKey(name.to_string()))?;
+        decode_hex(value)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| NcaError::InvalidKey(name.to_string()))
+    }
+
+    /// Decrypts the first 0xC00 bytes of every NCA
+    pub fn header_key(&self) -> Result<[u8; 32], NcaError> {
+        self.get("header_key")
+    }
+
+    /// Decrypts NCA key areas; `index` is 0 (application), 1 (ocean) or 2 (system)
+    pub fn key_area_key(&self, index: u8, revision: u8) -> Result<[u8; 16], NcaError> {
+        let kind = match index {
+            0 => "application",
+            1 => "ocean",
+            2 => "system",
+            _ => return Err(NcaError::Invalid(format!("key area key index {}", index))),
+        };
+        self.get(&format!("key_area_key_{}_{:02x}", kind, revision))
+    }
+
+    /// Decrypts title keys from common tickets
+    pub fn titlekek(&self, revision: u8) -> Result<[u8; 16], NcaError> {
+        self.get(&format!("titlekek_{:02x}", revision))
+    }
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
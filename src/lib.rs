@@ -0,0 +1,29 @@
+/// SwitchLink backend library - shared by the GUI and CLI front-ends
+pub mod gui;
+pub mod async_usb;
+pub mod backup;
+pub mod bandwidth;
+pub mod browse;
+pub mod catalog;
+pub mod collection;
+pub mod cli;
+pub mod config;
+pub mod control;
+pub mod devices;
+pub mod file_manager;
+pub mod health;
+pub mod inbox;
+pub mod keys;
+pub mod listing;
+pub mod mapped_file;
+pub mod nca;
+pub mod usb;
+pub mod protocol;
+pub mod saves;
+pub mod search;
+pub mod title;
+pub mod titledb;
+pub mod trash;
+pub mod verify;
+pub mod volumes;
+pub mod database;
@@ -0,0 +1,155 @@
+/// Paged and incremental LIST responses
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+#[derive(Debug, Clone)]
+struct TrackedEntry {
+    size: u64,
+    modified: Option<SystemTime>,
+    generation: u64,
+}
+
+/// One page of a LIST response
+#[derive(Debug, Clone)]
+pub struct ListPage {
+    /// Number of entries matching the request (before paging)
+    pub total: usize,
+    /// Token to send as `since` on the next request
+    pub token: u64,
+    /// The client's token is from another session, it should drop its cached list
+    pub reset: bool,
+    pub entries: Vec<(String, u64)>,
+    pub removed: Vec<String>,
+}
+
+impl ListPage {
+    /// Paged payload: a `total|token|reset` line, then `name|size` per entry
+    /// and `name|-` for entries removed since the client's token
+    pub fn to_payload(&self) -> String {
+        let mut payload = format!("{}|{}|{}\n", self.total, self.token, self.reset as u8);
+        for (name, size) in &self.entries {
+            payload.push_str(name);
+            payload.push('|');
+            payload.push_str(&size.to_string());
+            payload.push('\n');
+        }
+        for name in &self.removed {
+            payload.push_str(name);
+            payload.push_str("|-\n");
+        }
+        payload
+    }
+}
+
+/// Tracks changes to the served file list so clients can re-sync incrementally
+///
+/// Every change bumps a generation counter. Generations start at the session's
+/// start time, so tokens from an earlier session are recognised as stale.
+pub struct ListState {
+    base_generation: u64,
+    generation: u64,
+    entries: BTreeMap<String, TrackedEntry>,
+    removed: BTreeMap<String, u64>,
+}
+
+impl Default for ListState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ListState {
+    pub fn new() -> Self {
+        let base_generation = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(1);
+
+        Self {
+            base_generation,
+            generation: base_generation,
+            entries: BTreeMap::new(),
+            removed: BTreeMap::new(),
+        }
+    }
+
+    /// Re-read the file list and record what changed, returning the current token
+    pub fn refresh(&mut self, file_list: &HashMap<String, PathBuf>) -> u64 {
+        let mut changed = false;
+        let next_generation = self.generation + 1;
+
+        for (name, path) in file_list {
+            let metadata = std::fs::metadata(path).ok();
+            let size = metadata.as_ref().map(|m| m.len()).unwrap_or(0);
+            let modified = metadata.and_then(|m| m.modified().ok());
+
+            let unchanged = self
+                .entries
+                .get(name)
+                .is_some_and(|e| e.size == size && e.modified == modified);
+            if !unchanged {
+                self.entries.insert(
+                    name.clone(),
+                    TrackedEntry {
+                        size,
+                        modified,
+                        generation: next_generation,
+                    },
+                );
+                self.removed.remove(name);
+                changed = true;
+            }
+        }
+
+        let gone: Vec<String> = self
+            .entries
+            .keys()
+            .filter(|name| !file_list.contains_key(*name))
+            .cloned()
+            .collect();
+        for name in gone {
+            self.entries.remove(&name);
+            self.removed.insert(name, next_generation);
+            changed = true;
+        }
+
+        if changed {
+            self.generation = next_generation;
+        }
+        self.generation
+    }
+
+    /// Entries sorted by name, filtered to those changed after `since` (0 = everything)
+    pub fn page(&self, since: u64, offset: usize, limit: usize) -> ListPage {
+        // Tokens from another session can't be diffed against, send everything
+        let reset = since != 0 && (since < self.base_generation || since > self.generation);
+        let since = if reset { 0 } else { since };
+
+        let changed: Vec<(String, u64)> = self
+            .entries
+            .iter()
+            .filter(|(_, e)| e.generation > since)
+            .map(|(name, e)| (name.clone(), e.size))
+            .collect();
+
+        // Removals only make sense for an incremental request; send them with the first page
+        let removed = if since > 0 && offset == 0 {
+            self.removed
+                .iter()
+                .filter(|(_, &generation)| generation > since)
+                .map(|(name, _)| name.clone())
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        ListPage {
+            total: changed.len(),
+            token: self.generation,
+            reset,
+            entries: changed.into_iter().skip(offset).take(limit).collect(),
+            removed,
+        }
+    }
+}
@@ -1,9 +1,7 @@
-mod gui;
-mod cli;
-mod file_manager;
-mod usb;
-mod protocol;
-mod database;
+use std::path::PathBuf;
+
+use switchlink_backend::config::Config;
+use switchlink_backend::{cli, gui};
 
 fn main() {
     // Initialize logging with DEBUG level to see detailed protocol communication
@@ -14,13 +12,52 @@ fn main() {
     // Check if running in CLI mode
     let args: Vec<String> = std::env::args().collect();
 
-    if args.contains(&"--cli".to_string()) {
+    let mut config = Config::load();
+    if let Err(e) = config.apply_args(&args[1..]) {
+        eprintln!("Error: {}", e);
+        std::process::exit(2);
+    }
+
+    if args.get(1).is_some_and(|arg| arg == "serve") {
+        // Serve a saved collection, e.g. `serve --collection "Family console"`
+        cli::run_serve(config, &args[2..]);
+    } else if args.get(1).is_some_and(|arg| arg == "import-titles") {
+        // Import a local titledb dump, or refresh the last one
+        cli::run_import_titles(config, args.get(2).map(PathBuf::from));
+    } else if args.get(1).is_some_and(|arg| arg == "read-icons") {
+        // Names and icons from control NCAs, using the user's prod.keys
+        cli::run_read_icons(config, args.get(2).map(PathBuf::from));
+    } else if args.get(1).is_some_and(|arg| arg == "verify") {
+        // Check files against their hashes and signatures, using the user's prod.keys
+        cli::run_verify(config, &args[2..]);
+    } else if args.get(1).is_some_and(|arg| arg == "export-library") {
+        // Tags, favorites, install history and collections, for another machine
+        cli::run_export_library(config, &args[2..]);
+    } else if args.get(1).is_some_and(|arg| arg == "import-library") {
+        // Merge an export, e.g. `import-library lib.json --rewrite /mnt/games=D:\Games`
+        cli::run_import_library(config, &args[2..]);
+    } else if args.get(1).is_some_and(|arg| arg == "backup") {
+        // Back up the database now, or `backup --restore FILE`
+        cli::run_backup(config, &args[2..]);
+    } else if args.get(1).is_some_and(|arg| arg == "roots") {
+        // Library folders and the drives they're on, e.g. `roots add /media/usb/Switch`
+        cli::run_roots(config, &args[2..]);
+    } else if args.get(1).is_some_and(|arg| arg == "trash") {
+        // Restore, quarantine or delete trashed files, e.g. `trash restore 12 13`
+        cli::run_trash(config, &args[2..]);
+    } else if args.get(1).is_some_and(|arg| arg == "cleanup") {
+        // List missing files; `cleanup --apply` moves them to the trash
+        cli::run_cleanup(config, &args[2..]);
+    } else if args.get(1).is_some_and(|arg| arg == "history") {
+        // What was sent and installed, and when the console stalled
+        cli::run_history(config, &args[2..]);
+    } else if args.contains(&"--cli".to_string()) {
         // Run CLI version
-        cli::run_cli();
+        cli::run_cli(config);
     } else {
         // Launch the eGUI
         println!("🎮 Launching SwitchLink with eGUI...");
         println!("✅ eGUI works perfectly in Linux!");
-        gui::launch_gui();
+        gui::launch_gui(config);
     }
 }
@@ -0,0 +1,152 @@
+/// Files being served, memory-mapped so ranges go to the transport without a copy
+use anyhow::Result;
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::{Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// An open file served either from a read-only mapping or with buffered reads
+///
+/// Mapping is skipped on network filesystems, where a server-side change can
+/// fault the mapping at any moment, and whenever `mmap` itself fails.
+pub struct ServedFile {
+    path: PathBuf,
+    file: File,
+    map: Option<Mmap>,
+}
+
+impl ServedFile {
+    pub fn open(path: &Path, use_mmap: bool) -> Result<Self> {
+        let file = File::open(path)?;
+        let map = if !use_mmap {
+            None
+        } else if is_network_fs(path, &file) {
+            debug!("{} is on a network filesystem, using buffered reads", path.display());
+            None
+        } else {
+            match map_file(&file) {
+                Ok(map) => Some(map),
+                Err(e) => {
+                    warn!("Cannot map {} ({}), using buffered reads", path.display(), e);
+                    None
+                }
+            }
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            map,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        self.map.is_some()
+    }
+
+    /// The mapped bytes for a range, or `None` to read it with `reader_at` instead
+    ///
+    /// Touching mapped pages past the end of a truncated file raises SIGBUS,
+    /// so the file's current size is checked first. A file that shrank is
+    /// unmapped for good; one that grew is remapped.
+    pub fn mapped_range(&mut self, offset: u64, size: usize) -> Option<&[u8]> {
+        let mapped_len = self.map.as_ref()?.len() as u64;
+        let end = offset.checked_add(size as u64)?;
+        let current_len = match self.file.metadata() {
+            Ok(metadata) => metadata.len(),
+            Err(e) => {
+                warn!("Cannot stat {} ({}), using buffered reads", self.path.display(), e);
+                self.map = None;
+                return None;
+            }
+        };
+
+        if current_len < mapped_len {
+            warn!(
+                "{} shrank from {} to {} bytes while mapped, using buffered reads",
+                self.path.display(), mapped_len, current_len
+            );
+            self.map = None;
+            return None;
+        }
+        if end > current_len {
+            return None;
+        }
+        if end > mapped_len {
+            self.map = map_file(&self.file).ok();
+        }
+
+        let map = self.map.as_ref()?;
+        Some(&map[offset as usize..end as usize])
+    }
+
+    /// The file positioned at `offset`, for buffered reads
+    pub fn reader_at(&mut self, offset: u64) -> Result<&mut File> {
+        self.file.seek(SeekFrom::Start(offset))?;
+        Ok(&mut self.file)
+    }
+}
+
+fn map_file(file: &File) -> std::io::Result<Mmap> {
+    // SAFETY: the mapping is read-only and every access goes through
+    // `mapped_range`, which re-checks the file's size beforehand
+    unsafe { Mmap::map(file) }
+}
+
+#[cfg(target_os = "linux")]
+fn is_network_fs(_path: &Path, file: &File) -> bool {
+    use std::os::unix::io::AsRawFd;
+
+    // Filesystem magics from linux/magic.h and the filesystems themselves
+    const NETWORK_MAGICS: &[u32] = &[
+        0x6969,      // NFS
+        0x517B,      // SMB
+        0xFF53_4D42, // CIFS
+        0xFE53_4D42, // SMB2
+        0x6573_5546, // FUSE (sshfs, rclone, ...)
+        0x0102_1997, // 9P
+        0x5346_414F, // AFS
+        0x7375_7245, // Coda
+        0x00C3_6400, // CephFS
+    ];
+
+    // SAFETY: statfs is plain data and the descriptor is open for the call
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstatfs(file.as_raw_fd(), &mut stat) } != 0 {
+        return false;
+    }
+    // f_type's width varies by architecture; magics are 32-bit
+    NETWORK_MAGICS.contains(&(stat.f_type as u64 as u32))
+}
+
+#[cfg(target_os = "macos")]
+fn is_network_fs(_path: &Path, file: &File) -> bool {
+    use std::ffi::CStr;
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: statfs is plain data and the descriptor is open for the call
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstatfs(file.as_raw_fd(), &mut stat) } != 0 {
+        return false;
+    }
+    // SAFETY: the kernel NUL-terminates f_fstypename
+    let name = unsafe { CStr::from_ptr(stat.f_fstypename.as_ptr()) };
+    matches!(name.to_bytes(), b"nfs" | b"smbfs" | b"afpfs" | b"webdav" | b"macfuse" | b"osxfuse")
+}
+
+#[cfg(windows)]
+fn is_network_fs(path: &Path, _file: &File) -> bool {
+    // UNC shares (\\server\share, \\?\UNC\...); mapped drive letters aren't detected
+    let path = path.to_string_lossy();
+    (path.starts_with(r"\\") && !path.starts_with(r"\\?\")) || path.starts_with(r"\\?\UNC\")
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn is_network_fs(_path: &Path, _file: &File) -> bool {
+    false
+}
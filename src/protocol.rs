@@ -7,6 +7,14 @@ pub const CMD_ID_EXIT: u32 = 0;
 pub const CMD_ID_LIST_OLD: u32 = 1;
 pub const CMD_ID_FILE_RANGE: u32 = 2;
 pub const CMD_ID_LIST: u32 = 3;
+pub const CMD_ID_UPLOAD_BEGIN: u32 = 4;
+pub const CMD_ID_UPLOAD_RANGE: u32 = 5;
+pub const CMD_ID_UPLOAD_END: u32 = 6;
+pub const CMD_ID_SAVE_BEGIN: u32 = 7;
+pub const CMD_ID_BROWSE: u32 = 8;
+pub const CMD_ID_LIST_CATALOG: u32 = 9;
+pub const CMD_ID_PING: u32 = 10;
+pub const CMD_ID_STATUS: u32 = 11;
 
 // Command Types
 pub const CMD_TYPE_REQUEST: u32 = 0;
@@ -16,8 +24,23 @@ pub const CMD_TYPE_ACK: u32 = 2;
 // Buffer size
 pub const BUFFER_SEGMENT_DATA_SIZE: usize = 0x100000; // 1MB
 
+// Upload status codes (sent in the data_size field of UPLOAD_END responses)
+pub const UPLOAD_STATUS_OK: u32 = 0;
+pub const UPLOAD_STATUS_HASH_MISMATCH: u32 = 1;
+pub const UPLOAD_STATUS_SIZE_MISMATCH: u32 = 2;
+pub const UPLOAD_STATUS_UNKNOWN_FILE: u32 = 3;
+pub const UPLOAD_STATUS_ERROR: u32 = 4;
+
+// Largest page a paged LIST request may ask for
+pub const LIST_MAX_PAGE_SIZE: u32 = 1000;
+
+// Largest page a BROWSE request may ask for
+pub const BROWSE_MAX_PAGE_SIZE: u32 = 256;
+
 // Magic bytes
 pub const MAGIC: &[u8; 4] = b"DBI0";
+// Sent by the SwitchLink client; responses always use MAGIC
+pub const MAGIC_SWITCHLINK: &[u8; 4] = b"SWLK";
 
 #[derive(Debug, Clone)]
 pub struct CommandHeader {
@@ -54,11 +77,11 @@ impl CommandHeader {
             ));
         }
 
-        let mut buf = &data[..];
+        let mut buf = data;
         let mut magic = [0u8; 4];
         buf.read_exact(&mut magic)?;
 
-        if &magic != MAGIC {
+        if &magic != MAGIC && &magic != MAGIC_SWITCHLINK {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 "Invalid magic bytes",
@@ -137,3 +160,475 @@ impl FileRangeHeader {
         })
     }
 }
+
+/// Destination category announced by the Switch for an upload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UploadCategory {
+    Misc,
+    Screenshot,
+    Save,
+    Log,
+    Dump,
+}
+
+impl UploadCategory {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            1 => Self::Screenshot,
+            2 => Self::Save,
+            3 => Self::Log,
+            4 => Self::Dump,
+            _ => Self::Misc,
+        }
+    }
+
+    /// Inbox subdirectory for this category
+    pub fn dir_name(&self) -> &'static str {
+        match self {
+            Self::Misc => "misc",
+            Self::Screenshot => "screenshots",
+            Self::Save => "saves",
+            Self::Log => "logs",
+            Self::Dump => "dumps",
+        }
+    }
+}
+
+/// Optional category trailing a name, as sent by newer clients
+fn read_category(data: &[u8], start: usize) -> Option<UploadCategory> {
+    let bytes = data.get(start..start + 4)?;
+    Some(UploadCategory::from_u32(u32::from_le_bytes(bytes.try_into().ok()?)))
+}
+
+fn read_name(data: &[u8], start: usize, len: u32) -> io::Result<String> {
+    let end = start + len as usize;
+    if data.len() < end {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Filename length exceeds available data",
+        ));
+    }
+
+    String::from_utf8(data[start..end].to_vec())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// UPLOAD_BEGIN payload: file_size(8) + category(4) + name_len(4) + name
+#[derive(Debug, Clone)]
+pub struct UploadBeginHeader {
+    pub file_size: u64,
+    pub category: UploadCategory,
+    pub name: String,
+}
+
+impl UploadBeginHeader {
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        if data.len() < 16 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid upload begin header size",
+            ));
+        }
+
+        let mut buf = data;
+        let file_size = buf.get_u64_le();
+        let category = UploadCategory::from_u32(buf.get_u32_le());
+        let name_len = buf.get_u32_le();
+        let name = read_name(data, 16, name_len)?;
+
+        Ok(Self {
+            file_size,
+            category,
+            name,
+        })
+    }
+}
+
+/// SAVE_BEGIN payload: title_id(8) + user_id(16) + file_size(8) + name_len(4) + name
+///
+/// Starts an upload like UPLOAD_BEGIN, but the finished archive is filed as a save backup
+#[derive(Debug, Clone)]
+pub struct SaveBeginHeader {
+    pub title_id: u64,
+    pub user_id: u128,
+    pub file_size: u64,
+    pub name: String,
+}
+
+impl SaveBeginHeader {
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        if data.len() < 36 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid save begin header size",
+            ));
+        }
+
+        let mut buf = data;
+        let title_id = buf.get_u64_le();
+        let user_id = buf.get_u128_le();
+        let file_size = buf.get_u64_le();
+        let name_len = buf.get_u32_le();
+        let name = read_name(data, 36, name_len)?;
+
+        Ok(Self {
+            title_id,
+            user_id,
+            file_size,
+            name,
+        })
+    }
+}
+
+/// UPLOAD_RANGE payload: range_offset(8) + range_size(4) + name_len(4) + name [+ category(4)]
+///
+/// The category tells apart uploads with the same name; older clients leave it out.
+#[derive(Debug, Clone)]
+pub struct UploadRangeHeader {
+    pub range_offset: u64,
+    pub range_size: u32,
+    pub name: String,
+    pub category: Option<UploadCategory>,
+}
+
+impl UploadRangeHeader {
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        if data.len() < 16 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid upload range header size",
+            ));
+        }
+
+        let mut buf = data;
+        let range_offset = buf.get_u64_le();
+        let range_size = buf.get_u32_le();
+        let name_len = buf.get_u32_le();
+        let name = read_name(data, 16, name_len)?;
+        let category = read_category(data, 16 + name_len as usize);
+
+        Ok(Self {
+            range_offset,
+            range_size,
+            name,
+            category,
+        })
+    }
+}
+
+/// UPLOAD_END payload: sha256(32) + name_len(4) + name [+ category(4)]
+#[derive(Debug, Clone)]
+pub struct UploadEndHeader {
+    pub sha256: [u8; 32],
+    pub name: String,
+    pub category: Option<UploadCategory>,
+}
+
+impl UploadEndHeader {
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        if data.len() < 36 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid upload end header size",
+            ));
+        }
+
+        let mut sha256 = [0u8; 32];
+        sha256.copy_from_slice(&data[..32]);
+        let name_len = u32::from_le_bytes([data[32], data[33], data[34], data[35]]);
+        let name = read_name(data, 36, name_len)?;
+        let category = read_category(data, 36 + name_len as usize);
+
+        Ok(Self { sha256, name, category })
+    }
+}
+
+/// Paged LIST payload: offset(4) + limit(4) + since_token(8)
+///
+/// Legacy clients send LIST without a payload and get the full flat list.
+#[derive(Debug, Clone)]
+pub struct ListRequest {
+    pub offset: u32,
+    pub limit: u32,
+    pub since_token: u64,
+}
+
+impl ListRequest {
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        if data.len() < 16 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid list request size",
+            ));
+        }
+
+        let mut buf = data;
+        Ok(Self {
+            offset: buf.get_u32_le(),
+            limit: buf.get_u32_le(),
+            since_token: buf.get_u64_le(),
+        })
+    }
+}
+
+/// BROWSE payload: offset(4) + limit(4) + path_len(4) + path
+#[derive(Debug, Clone)]
+pub struct BrowseRequest {
+    pub offset: u32,
+    pub limit: u32,
+    pub path: String,
+}
+
+impl BrowseRequest {
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        if data.len() < 12 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid browse request size",
+            ));
+        }
+
+        let mut buf = data;
+        let offset = buf.get_u32_le();
+        let limit = buf.get_u32_le();
+        let path_len = buf.get_u32_le();
+        let path = read_name(data, 12, path_len)?;
+
+        Ok(Self {
+            offset,
+            limit,
+            path,
+        })
+    }
+}
+
+/// LIST_CATALOG payload: client_version(4) + grouping(4) [+ tag_len(4) + tag]
+///
+/// The response is a JSON catalog; clients that don't send a payload get the defaults.
+/// A non-empty tag limits the catalog to files carrying it.
+#[derive(Debug, Clone, Default)]
+pub struct CatalogRequest {
+    pub client_version: u32,
+    pub grouping: u32,
+    pub tag: Option<String>,
+}
+
+impl CatalogRequest {
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        if data.len() < 8 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid catalog request size",
+            ));
+        }
+
+        let mut buf = data;
+        let client_version = buf.get_u32_le();
+        let grouping = buf.get_u32_le();
+        let tag = if data.len() >= 12 {
+            let tag_len = buf.get_u32_le();
+            Some(read_name(data, 12, tag_len)?).filter(|tag| !tag.is_empty())
+        } else {
+            None
+        };
+
+        Ok(Self {
+            client_version,
+            grouping,
+            tag,
+        })
+    }
+}
+
+/// What the client says it is doing in a PING
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClientActivity {
+    #[default]
+    Idle,
+    Browsing,
+    Installing,
+    Uploading,
+}
+
+impl ClientActivity {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            1 => Self::Browsing,
+            2 => Self::Installing,
+            3 => Self::Uploading,
+            _ => Self::Idle,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Idle => "Idle",
+            Self::Browsing => "Browsing",
+            Self::Installing => "Installing",
+            Self::Uploading => "Uploading",
+        }
+    }
+}
+
+/// PING payload: activity(4) + percent(4) + detail_len(4) + detail
+///
+/// A PING without payload means the client is idle. The server replies with
+/// an empty PING response (PONG).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientState {
+    pub activity: ClientActivity,
+    pub percent: u32,
+    /// e.g. the file being installed
+    pub detail: String,
+}
+
+impl ClientState {
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        if data.len() < 12 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid ping payload size",
+            ));
+        }
+
+        let mut buf = data;
+        let activity = ClientActivity::from_u32(buf.get_u32_le());
+        let percent = buf.get_u32_le().min(100);
+        let detail_len = buf.get_u32_le();
+        let detail = read_name(data, 12, detail_len)?;
+
+        Ok(Self {
+            activity,
+            percent,
+            detail,
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        match self.activity {
+            ClientActivity::Idle | ClientActivity::Browsing => self.activity.label().to_string(),
+            _ if self.detail.is_empty() => format!("{} ({}%)", self.activity.label(), self.percent),
+            _ => format!("{} {} ({}%)", self.activity.label(), self.detail, self.percent),
+        }
+    }
+}
+
+/// Install step the client is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallPhase {
+    /// Writing NCAs to storage
+    Writing,
+    NcaRegister,
+    TicketImport,
+    CnmtCommit,
+    Done,
+}
+
+impl InstallPhase {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            1 => Self::NcaRegister,
+            2 => Self::TicketImport,
+            3 => Self::CnmtCommit,
+            4 => Self::Done,
+            _ => Self::Writing,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Writing => "Writing",
+            Self::NcaRegister => "Registering NCA",
+            Self::TicketImport => "Importing ticket",
+            Self::CnmtCommit => "Committing CNMT",
+            Self::Done => "Done",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallOutcome {
+    InProgress,
+    Success,
+    Failure,
+}
+
+impl InstallOutcome {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            1 => Self::Success,
+            2 => Self::Failure,
+            _ => Self::InProgress,
+        }
+    }
+}
+
+/// STATUS payload: phase(4) + outcome(4) + result_code(4) + percent(4)
+/// + content_id(16) + name_len(4) + name
+///
+/// `name` is the file as requested with FILE_RANGE. The server replies with
+/// an empty STATUS response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstallStatus {
+    pub phase: InstallPhase,
+    pub outcome: InstallOutcome,
+    /// Horizon result code, 0 on success
+    pub result_code: u32,
+    pub percent: u32,
+    pub content_id: [u8; 16],
+    pub name: String,
+}
+
+impl InstallStatus {
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        if data.len() < 36 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid status payload size",
+            ));
+        }
+
+        let mut buf = data;
+        let phase = InstallPhase::from_u32(buf.get_u32_le());
+        let outcome = InstallOutcome::from_u32(buf.get_u32_le());
+        let result_code = buf.get_u32_le();
+        let percent = buf.get_u32_le().min(100);
+        let mut content_id = [0u8; 16];
+        buf.copy_to_slice(&mut content_id);
+        let name_len = buf.get_u32_le();
+        let name = read_name(data, 36, name_len)?;
+
+        Ok(Self {
+            phase,
+            outcome,
+            result_code,
+            percent,
+            content_id,
+            name,
+        })
+    }
+
+    /// Content ID as lowercase hex, as used in NCA filenames
+    pub fn content_id_hex(&self) -> String {
+        self.content_id.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Result code in the `2XXX-YYYY` form shown by the console
+    pub fn result_code_display(&self) -> String {
+        let module = self.result_code & 0x1FF;
+        let description = (self.result_code >> 9) & 0x1FFF;
+        format!("{:04}-{:04}", 2000 + module, description)
+    }
+
+    pub fn describe(&self) -> String {
+        match self.outcome {
+            InstallOutcome::InProgress => format!("{} ({}%)", self.phase.label(), self.percent),
+            InstallOutcome::Success => "Installed".to_string(),
+            InstallOutcome::Failure => format!(
+                "Failed: {} ({})",
+                self.phase.label(),
+                self.result_code_display()
+            ),
+        }
+    }
+}
@@ -0,0 +1,256 @@
+/// Save-data backup library
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::database::{Database, SaveSnapshot};
+use crate::inbox::{hash_file, unique_path};
+
+/// How many snapshots to keep per title and user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// Keep at most this many unpinned snapshots; 0 is refused, as it would drop the one just received
+    pub keep_last: Option<NonZeroUsize>,
+    /// Drop unpinned snapshots older than this (the newest one is always kept)
+    pub max_age_days: Option<u64>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            keep_last: NonZeroUsize::new(10),
+            max_age_days: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffChange {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Debug, Clone)]
+pub struct SaveDiffEntry {
+    pub path: String,
+    pub change: DiffChange,
+    pub old_size: u64,
+    pub new_size: u64,
+}
+
+pub struct SaveLibrary {
+    root: PathBuf,
+}
+
+impl SaveLibrary {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Folder holding the snapshots of one title and user
+    pub fn snapshot_dir(&self, title_id: &str, user_id: &str) -> PathBuf {
+        self.root.join(title_id).join(user_id)
+    }
+
+    /// Move a received archive into the library as a new timestamped snapshot
+    pub fn import(
+        &self,
+        db: &Database,
+        title_id: u64,
+        user_id: u128,
+        archive: &Path,
+        hash: &str,
+    ) -> Result<SaveSnapshot> {
+        let title_id = format_title_id(title_id);
+        let user_id = format_user_id(user_id);
+        let dir = self.snapshot_dir(&title_id, &user_id);
+        std::fs::create_dir_all(&dir)?;
+
+        let ext = archive
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("zip");
+        let name = format!("{}.{}", chrono::Local::now().format("%Y%m%d-%H%M%S"), ext);
+        let dest = unique_path(&dir, &name);
+
+        // Inbox and library usually share a filesystem, copy if they don't
+        if std::fs::rename(archive, &dest).is_err() {
+            std::fs::copy(archive, &dest)?;
+            std::fs::remove_file(archive)?;
+        }
+
+        let size = std::fs::metadata(&dest)?.len();
+        let created_at = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs() as i64;
+        let id = db.add_save_snapshot(&title_id, &user_id, &dest, size, hash, created_at)?;
+
+        Ok(SaveSnapshot {
+            id,
+            title_id,
+            user_id,
+            path: dest.to_string_lossy().to_string(),
+            size,
+            hash: hash.to_string(),
+            created_at,
+            pinned: false,
+        })
+    }
+
+    /// Delete snapshots that fall outside the retention policy, returning how many were removed
+    pub fn apply_retention(
+        &self,
+        db: &Database,
+        title_id: &str,
+        user_id: &str,
+        policy: &RetentionPolicy,
+    ) -> Result<usize> {
+        let snapshots = db.get_save_snapshots(title_id, user_id)?;
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs() as i64;
+
+        let mut removed = 0;
+        let mut kept = 0;
+
+        // Snapshots are newest first
+        for (index, snapshot) in snapshots.iter().enumerate() {
+            if snapshot.pinned {
+                continue;
+            }
+
+            let over_count = policy.keep_last.is_some_and(|keep| kept >= keep.get());
+            // An age too large to count in seconds never expires
+            let too_old = index > 0
+                && policy
+                    .max_age_days
+                    .and_then(|days| i64::try_from(days).ok()?.checked_mul(86_400))
+                    .is_some_and(|max_age| now.saturating_sub(snapshot.created_at) > max_age);
+
+            if over_count || too_old {
+                self.delete(db, snapshot)?;
+                removed += 1;
+            } else {
+                kept += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Apply the retention policy to every title and user
+    pub fn apply_retention_all(&self, db: &Database, policy: &RetentionPolicy) -> Result<usize> {
+        let mut removed = 0;
+        for group in db.get_save_groups()? {
+            removed += self.apply_retention(db, &group.title_id, &group.user_id, policy)?;
+        }
+        Ok(removed)
+    }
+
+    /// Remove a snapshot from disk and from the database
+    pub fn delete(&self, db: &Database, snapshot: &SaveSnapshot) -> Result<()> {
+        let path = Path::new(&snapshot.path);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        db.remove_save_snapshot(snapshot.id)?;
+        Ok(())
+    }
+}
+
+/// Name under which a snapshot is offered to the console for restoring
+pub fn restore_name(snapshot: &SaveSnapshot) -> String {
+    let file_name = Path::new(&snapshot.path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("save.zip");
+    format!("SAVE_{}_{}_{}", snapshot.title_id, snapshot.user_id, file_name)
+}
+
+pub fn format_title_id(title_id: u64) -> String {
+    format!("{:016X}", title_id)
+}
+
+pub fn format_user_id(user_id: u128) -> String {
+    format!("{:032X}", user_id)
+}
+
+/// Compare the entries of two snapshot archives
+///
+/// Archives that aren't ZIP files are compared as a whole.
+pub fn diff_snapshots(old: &Path, new: &Path) -> Result<Vec<SaveDiffEntry>> {
+    let (old_entries, new_entries) = match (archive_entries(old), archive_entries(new)) {
+        (Ok(old_entries), Ok(new_entries)) => (old_entries, new_entries),
+        _ => {
+            let old_size = std::fs::metadata(old)?.len();
+            let new_size = std::fs::metadata(new)?.len();
+            if hash_file(old)? == hash_file(new)? {
+                return Ok(vec![]);
+            }
+            return Ok(vec![SaveDiffEntry {
+                path: "(archive)".to_string(),
+                change: DiffChange::Modified,
+                old_size,
+                new_size,
+            }]);
+        }
+    };
+
+    let mut diff = Vec::new();
+
+    for (path, &(old_size, old_crc)) in &old_entries {
+        match new_entries.get(path) {
+            None => diff.push(SaveDiffEntry {
+                path: path.clone(),
+                change: DiffChange::Removed,
+                old_size,
+                new_size: 0,
+            }),
+            Some(&(new_size, new_crc)) if new_size != old_size || new_crc != old_crc => {
+                diff.push(SaveDiffEntry {
+                    path: path.clone(),
+                    change: DiffChange::Modified,
+                    old_size,
+                    new_size,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+
+    for (path, &(new_size, _)) in &new_entries {
+        if !old_entries.contains_key(path) {
+            diff.push(SaveDiffEntry {
+                path: path.clone(),
+                change: DiffChange::Added,
+                old_size: 0,
+                new_size,
+            });
+        }
+    }
+
+    diff.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(diff)
+}
+
+/// Entry sizes and CRCs from the ZIP central directory
+fn archive_entries(path: &Path) -> Result<BTreeMap<String, (u64, u32)>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let mut entries = BTreeMap::new();
+
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        if entry.is_dir() {
+            continue;
+        }
+        entries.insert(entry.name().to_string(), (entry.size(), entry.crc32()));
+    }
+
+    Ok(entries)
+}
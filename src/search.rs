@@ -0,0 +1,194 @@
+/// Library search queries: free text plus `type:`, `tag:`, `size` and `installed:` operators
+use crate::title::ContentType;
+
+const KB: u64 = 1024;
+const MB: u64 = KB * 1024;
+const GB: u64 = MB * 1024;
+const TB: u64 = GB * 1024;
+
+/// A parsed search box entry
+///
+/// `zelda type:dlc size>1GB` matches DLC over 1 GB whose filename, name,
+/// title ID, publisher or tags contain a word starting with "zelda".
+/// Anything that isn't a recognised operator is searched as text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    /// Lowercase words, each matched as a prefix
+    pub terms: Vec<String>,
+    pub content_type: Option<ContentType>,
+    /// Every tag must be present
+    pub tags: Vec<String>,
+    /// Inclusive size bounds in bytes
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// `Some(false)` for never installed, `Some(true)` for installed at least once
+    pub installed: Option<bool>,
+}
+
+impl SearchQuery {
+    pub fn parse(input: &str) -> Self {
+        let mut query = Self::default();
+
+        for token in tokens(input) {
+            if !query.apply_operator(&token) {
+                query.terms.extend(words(&token));
+            }
+        }
+
+        query
+    }
+
+    /// No text and no filters: everything matches
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn apply_operator(&mut self, token: &str) -> bool {
+        if let Some((key, value)) = token.split_once(':') {
+            let value = value.to_lowercase();
+            match key.to_lowercase().as_str() {
+                "type" => {
+                    let content_type = match value.as_str() {
+                        "base" | "game" => ContentType::Base,
+                        "update" | "upd" | "patch" => ContentType::Update,
+                        "dlc" | "addon" => ContentType::Dlc,
+                        "other" | "unknown" => ContentType::Unknown,
+                        _ => return false,
+                    };
+                    self.content_type = Some(content_type);
+                    true
+                }
+                "tag" if !value.trim().is_empty() => {
+                    self.tags.push(value.split_whitespace().collect::<Vec<_>>().join(" "));
+                    true
+                }
+                "installed" => {
+                    self.installed = match value.as_str() {
+                        "never" | "no" | "false" => Some(false),
+                        "yes" | "ever" | "true" => Some(true),
+                        _ => return false,
+                    };
+                    true
+                }
+                _ => false,
+            }
+        } else if let Some(bound) = token.strip_prefix("size") {
+            self.apply_size_bound(bound)
+        } else {
+            false
+        }
+    }
+
+    /// `>4GB`, `>=4GB`, `<500MB` or `<=500MB` after `size`
+    fn apply_size_bound(&mut self, bound: &str) -> bool {
+        let (op, value) = [">=", "<=", ">", "<"]
+            .iter()
+            .find_map(|op| bound.strip_prefix(op).map(|rest| (*op, rest)))
+            .unwrap_or(("", bound));
+        let Some(size) = parse_size(value) else {
+            return false;
+        };
+
+        match op {
+            ">" => self.min_size = Some(size.saturating_add(1)),
+            ">=" => self.min_size = Some(size),
+            "<" => self.max_size = Some(size.saturating_sub(1)),
+            "<=" => self.max_size = Some(size),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Whitespace-separated tokens; double quotes keep spaces, as in `tag:"board games"`
+fn tokens(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// `4GB`, `1.5 GB` without the space, `700M` or a plain byte count
+fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim().to_uppercase();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+
+    let unit = match unit {
+        "" | "B" => 1,
+        "K" | "KB" => KB,
+        "M" | "MB" => MB,
+        "G" | "GB" => GB,
+        "T" | "TB" => TB,
+        _ => return None,
+    };
+    Some((number * unit as f64) as u64)
+}
+
+/// Split text the way the index does: lowercase runs of letters and digits
+///
+/// Scene-style names such as `Some.Game.NSW-GRP` become separate words.
+pub fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+/// Typos tolerated in a word of this many characters
+pub fn typo_budget(len: usize) -> usize {
+    match len {
+        0..=3 => 0,
+        4..=6 => 1,
+        _ => 2,
+    }
+}
+
+/// Edits (insert, delete, substitute, swap adjacent) to turn `a` into `b`,
+/// or `None` if it takes more than `max`
+pub fn edit_distance(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+
+    // Optimal string alignment over three rolling rows
+    let mut before: Vec<usize> = Vec::new();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (prev[j] + 1).min(row[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+        if row.iter().min().is_some_and(|&m| m > max) {
+            return None;
+        }
+        before = std::mem::replace(&mut prev, row);
+    }
+
+    let distance = prev[b.len()];
+    (distance <= max).then_some(distance)
+}
@@ -0,0 +1,115 @@
+/// Title ID, version and content type parsing from Switch dump filenames
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentType {
+    Base,
+    Update,
+    Dlc,
+    Unknown,
+}
+
+impl ContentType {
+    /// Derive the content type from the low bits of a title ID
+    ///
+    /// Base titles end in 000, updates in 800 and DLC use the rest.
+    pub fn from_title_id(title_id: u64) -> Self {
+        match title_id & 0xFFF {
+            0x000 => Self::Base,
+            0x800 => Self::Update,
+            _ => Self::Dlc,
+        }
+    }
+
+    /// Lowercase name, as stored in the library and used in `type:` searches
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Base => "base",
+            Self::Update => "update",
+            Self::Dlc => "dlc",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Base => "Base",
+            Self::Update => "Update",
+            Self::Dlc => "DLC",
+            Self::Unknown => "Other",
+        }
+    }
+}
+
+/// Find a 16-digit hex title ID (e.g. `[0100ABCD12345000]`) in a filename
+pub fn parse_title_id(filename: &str) -> Option<u64> {
+    bracketed(filename)
+        .filter(|s| s.len() == 16 && s.chars().all(|c| c.is_ascii_hexdigit()))
+        .find_map(|s| u64::from_str_radix(s, 16).ok())
+}
+
+/// Find a version tag (e.g. `[v131072]`) in a filename
+pub fn parse_version(filename: &str) -> Option<u32> {
+    bracketed(filename)
+        .filter_map(|s| s.strip_prefix('v').or_else(|| s.strip_prefix('V')))
+        .find_map(|s| s.parse().ok())
+}
+
+pub fn content_type(filename: &str) -> ContentType {
+    match parse_title_id(filename) {
+        Some(title_id) => ContentType::from_title_id(title_id),
+        None => {
+            let upper = filename.to_uppercase();
+            if upper.contains("[UPD]") || upper.contains("[UPDATE]") {
+                ContentType::Update
+            } else if upper.contains("[DLC]") {
+                ContentType::Dlc
+            } else {
+                ContentType::Unknown
+            }
+        }
+    }
+}
+
+/// The base game's title ID for a base, update or DLC title ID
+pub fn base_title_id(title_id: u64) -> u64 {
+    match ContentType::from_title_id(title_id) {
+        ContentType::Dlc => title_id.checked_sub(0x1000).unwrap_or(title_id) & !0xFFF,
+        _ => title_id & !0xFFF,
+    }
+}
+
+/// Filename without extension and bracketed tags, e.g. `Game Name` for
+/// `Game Name [0100ABCD12345000][v0].nsp`
+pub fn display_name(filename: &str) -> String {
+    let stem = match filename.rfind('.') {
+        Some(i) if i > 0 => &filename[..i],
+        _ => filename,
+    };
+
+    let mut name = String::new();
+    let mut depth = 0;
+    for c in stem.chars() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' if depth > 0 => depth -= 1,
+            _ if depth == 0 => name.push(c),
+            _ => {}
+        }
+    }
+
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    if name.is_empty() {
+        stem.to_string()
+    } else {
+        name
+    }
+}
+
+fn bracketed(filename: &str) -> impl Iterator<Item = &str> {
+    filename
+        .split('[')
+        .skip(1)
+        .filter_map(|part| part.split(']').next())
+}
@@ -0,0 +1,147 @@
+/// Offline title database import from titledb-style JSON or CSV dumps
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::database::{Database, TitleInfo};
+
+/// Result of importing a dump
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportSummary {
+    pub imported: usize,
+    /// Entries without a usable title ID
+    pub skipped: usize,
+}
+
+/// Import a dump into the library's `titles` table, replacing the previous import
+///
+/// `.csv` files are read as CSV with a header row, anything else as JSON.
+/// User overrides are kept.
+pub fn import_file(db: &Database, path: &Path) -> Result<ImportSummary> {
+    let (titles, skipped) = parse_file(path)?;
+    let imported = db.import_titles(&titles)?;
+    Ok(ImportSummary { imported, skipped })
+}
+
+/// Read a dump without importing it, returning the titles and how many entries were skipped
+pub fn parse_file(path: &Path) -> Result<(Vec<TitleInfo>, usize)> {
+    let data = std::fs::read(path)?;
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    let is_csv = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("csv"));
+
+    let entries = if is_csv { parse_csv(&data)? } else { parse_json(&data)? };
+
+    let total = entries.len();
+    let titles: Vec<TitleInfo> = entries
+        .into_iter()
+        .filter_map(|fields| title_from_fields(&fields, base_dir))
+        .collect();
+    let skipped = total - titles.len();
+    Ok((titles, skipped))
+}
+
+/// titledb's `{"<nsuId>": {"id": ..., "name": ...}, ...}` or a plain array of entries
+fn parse_json(data: &[u8]) -> Result<Vec<HashMap<String, String>>> {
+    let entries = match serde_json::from_slice(data)? {
+        Value::Object(map) => map.into_iter().map(|(_, entry)| entry).collect(),
+        Value::Array(entries) => entries,
+        _ => return Err(anyhow!("Expected a JSON object or array of titles")),
+    };
+
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| match entry {
+            Value::Object(fields) => Some(
+                fields
+                    .into_iter()
+                    .filter_map(|(key, value)| {
+                        let value = match value {
+                            Value::String(s) => s,
+                            Value::Number(n) => n.to_string(),
+                            _ => return None,
+                        };
+                        Some((normalize_key(&key), value))
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        })
+        .collect())
+}
+
+fn parse_csv(data: &[u8]) -> Result<Vec<HashMap<String, String>>> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(data);
+    let headers: Vec<String> = reader.headers()?.iter().map(normalize_key).collect();
+
+    let mut entries = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        entries.push(
+            headers
+                .iter()
+                .cloned()
+                .zip(record.iter().map(str::to_string))
+                .collect(),
+        );
+    }
+    Ok(entries)
+}
+
+/// `releaseDate`, `release_date` and `Release Date` all become `releasedate`
+fn normalize_key(key: &str) -> String {
+    key.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn title_from_fields(fields: &HashMap<String, String>, base_dir: &Path) -> Option<TitleInfo> {
+    let get = |keys: &[&str]| {
+        keys.iter()
+            .filter_map(|key| fields.get(*key))
+            .map(|value| value.trim())
+            .find(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+
+    let title_id = get(&["id", "titleid"])?.to_uppercase();
+    if title_id.len() != 16 || !title_id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    // Only local images; URLs would need the network
+    let icon_path = get(&["iconpath", "icon"])
+        .filter(|icon| !icon.contains("://"))
+        .map(|icon| base_dir.join(icon))
+        .or_else(|| {
+            ["jpg", "jpeg", "png"]
+                .iter()
+                .map(|ext| base_dir.join("icons").join(format!("{}.{}", title_id, ext)))
+                .find(|path| path.is_file())
+        })
+        .map(|path| path.to_string_lossy().to_string());
+
+    Some(TitleInfo {
+        name: get(&["name", "title"]),
+        publisher: get(&["publisher"]),
+        release_date: get(&["releasedate", "released"]).map(|date| normalize_date(&date)),
+        region: get(&["region"]),
+        description: get(&["description", "intro"]),
+        icon_path,
+        title_id,
+        overridden: false,
+    })
+}
+
+/// titledb writes dates as `20170303`; store them as `2017-03-03`
+fn normalize_date(date: &str) -> String {
+    if date.len() == 8 && date.chars().all(|c| c.is_ascii_digit()) {
+        format!("{}-{}-{}", &date[..4], &date[4..6], &date[6..])
+    } else {
+        date.to_string()
+    }
+}
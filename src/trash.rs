@@ -0,0 +1,92 @@
+/// The library trash: quarantining trashed files on disk, restoring them, and deleting them for good
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+
+use crate::database::{Database, GameFile};
+use crate::inbox::unique_path;
+
+/// Trash files and move them into `dir`, remembering where each went so restoring moves it back
+///
+/// Returns the new locations. Files already gone from disk are only trashed.
+pub fn quarantine(db: &Database, files: &[GameFile], dir: &Path) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir)?;
+
+    let mut moved = Vec::new();
+    for file in files {
+        db.trash_files(&[file.id])?;
+        let source = Path::new(&file.path);
+        if file.quarantined_path.is_some() || !source.exists() {
+            continue;
+        }
+        let target = unique_path(dir, &file.filename);
+        move_file(source, &target)?;
+        db.set_quarantined_path(file.id, &target)?;
+        moved.push(target);
+    }
+    Ok(moved)
+}
+
+/// Take a file out of the trash, first moving it back from quarantine
+///
+/// Fails without changing anything if another file now sits at its old path.
+pub fn restore(db: &Database, file: &GameFile) -> Result<()> {
+    if let Some(quarantined) = file.quarantined_path.as_deref().map(Path::new) {
+        let original = Path::new(&file.path);
+        if quarantined.exists() {
+            if original.exists() {
+                return Err(anyhow!("Can't restore {}: another file is in its place", file.path));
+            }
+            if let Some(parent) = original.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            move_file(quarantined, original)?;
+        }
+    }
+    db.restore_file(file.id)
+}
+
+/// Delete trashed files from disk (from quarantine if they were moved) and drop them from the library
+///
+/// Each file is read again first: ones restored since are skipped, and ones
+/// cleanup trashed because they were gone only lose their entry, so a new
+/// file at the same path is kept. This can't be undone. Returns how many
+/// files were removed from disk.
+pub fn delete_files(db: &Database, files: &[GameFile]) -> Result<usize> {
+    let mut deleted = 0;
+    for file in files {
+        if file.deleted_at.is_none() {
+            return Err(anyhow!("{} isn't in the trash", file.filename));
+        }
+        let Some(file) = db.get_file(file.id)?.filter(|file| file.deleted_at.is_some()) else {
+            continue;
+        };
+        let on_disk = match file.quarantined_path.as_deref() {
+            Some(quarantined) => Some(Path::new(quarantined)),
+            None if file.trashed_missing => None,
+            None => Some(Path::new(&file.path)),
+        };
+        if let Some(on_disk) = on_disk.filter(|path| path.is_file()) {
+            std::fs::remove_file(on_disk)?;
+            deleted += 1;
+        }
+        db.remove_file(file.id)?;
+    }
+    Ok(deleted)
+}
+
+/// Drop trashed entries from the library, leaving the files on disk alone
+pub fn forget(db: &Database, files: &[GameFile]) -> Result<()> {
+    for file in files.iter().filter(|file| file.deleted_at.is_some()) {
+        db.remove_file(file.id)?;
+    }
+    Ok(())
+}
+
+fn move_file(source: &Path, target: &Path) -> Result<()> {
+    // Renaming fails across filesystems; copy instead
+    if std::fs::rename(source, target).is_err() {
+        std::fs::copy(source, target)?;
+        std::fs::remove_file(source)?;
+    }
+    Ok(())
+}
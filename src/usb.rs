@@ -1,15 +1,26 @@
 /// USB communication module for Nintendo Switch
 use anyhow::{anyhow, Result};
-use rusb::{Context, Device, DeviceHandle, Direction, UsbContext};
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
-use std::path::PathBuf;
+use rusb::{Context, Device, DeviceHandle, Direction, TransferType, UsbContext};
+use serde::{Deserialize, Serialize};
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 
+use crate::async_usb::{BulkPipeline, LibusbQueue, MAX_TRANSFER_SIZE};
+use crate::bandwidth::{Bandwidth, TokenBucket};
+use crate::browse::BrowseRoots;
+use crate::catalog::{Catalog, Grouping};
+use crate::config::Config;
+use crate::database::Database;
+use crate::inbox::{sanitize_name, CompletedUpload, Inbox, InboxError};
+use crate::listing::ListState;
+use crate::mapped_file::ServedFile;
 use crate::protocol::*;
+use crate::saves::SaveLibrary;
 
 #[derive(Debug, Clone, Default)]
 pub struct TransferProgress {
@@ -18,8 +29,22 @@ pub struct TransferProgress {
     pub total_size: u64,
     pub speed_mbps: f64,
     pub logs: Vec<String>,
+    /// Latest state reported by the client's PING
+    pub client_state: Option<ClientState>,
+    /// The client went silent for longer than the stall timeout
+    pub stalled: bool,
+    /// Latest install step reported by the client's STATUS
+    pub install_status: Option<InstallStatus>,
+    /// Throughput over the current file so far
+    pub average_mbps: f64,
+    pub peak_mbps: f64,
+    /// Bulk transfers kept in flight (1 = synchronous)
+    pub queue_depth: usize,
+    /// File data is being held by "pause all" or a schedule
+    pub paused: bool,
     last_update: Option<Instant>,
     bytes_at_last_update: u64,
+    file_started: Option<Instant>,
 }
 
 impl TransferProgress {
@@ -29,6 +54,7 @@ impl TransferProgress {
             if elapsed >= 0.5 {
                 let bytes_diff = self.bytes_sent - self.bytes_at_last_update;
                 self.speed_mbps = (bytes_diff as f64 / elapsed) / 1_000_000.0;
+                self.peak_mbps = self.peak_mbps.max(self.speed_mbps);
                 self.last_update = Some(Instant::now());
                 self.bytes_at_last_update = self.bytes_sent;
             }
@@ -36,6 +62,25 @@ impl TransferProgress {
             self.last_update = Some(Instant::now());
             self.bytes_at_last_update = self.bytes_sent;
         }
+        
+        let started = *self.file_started.get_or_insert_with(Instant::now);
+        let elapsed = started.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            self.average_mbps = (self.bytes_sent as f64 / elapsed) / 1_000_000.0;
+        }
+    }
+    
+    /// Start tracking a new file
+    pub fn begin_file(&mut self, name: String, total_size: u64) {
+        self.current_file = name;
+        self.bytes_sent = 0;
+        self.total_size = total_size;
+        self.speed_mbps = 0.0;
+        self.average_mbps = 0.0;
+        self.peak_mbps = 0.0;
+        self.last_update = None;
+        self.bytes_at_last_update = 0;
+        self.file_started = None;
     }
     
     pub fn add_log(&mut self, message: String) {
@@ -51,57 +96,406 @@ impl TransferProgress {
 const SWITCH_VENDOR_ID: u16 = 0x057E;
 const SWITCH_PRODUCT_ID: u16 = 0x3000;
 
-const TIMEOUT: Duration = Duration::from_millis(100);
-const TIMEOUT_LONG: Duration = Duration::from_secs(30); // For waiting ACK from Switch
+/// A vendor/product ID pair, written as `"057E:3000"` in the config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct UsbId {
+    pub vendor_id: u16,
+    pub product_id: u16,
+}
+
+impl TryFrom<String> for UsbId {
+    type Error = String;
+
+    /// Hex IDs with or without a `0x` prefix, e.g. `"057E:3000"` or `"0x057e:0x3000"`
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        let (vendor, product) = value
+            .split_once(':')
+            .ok_or_else(|| format!("expected VID:PID, got {:?}", value))?;
+        let parse = |s: &str| {
+            let s = s.trim();
+            let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+            // from_str_radix accepts a leading `+`, which isn't an ID
+            if hex.starts_with('+') {
+                return Err(format!("invalid USB ID {:?}", value));
+            }
+            u16::from_str_radix(hex, 16).map_err(|e| format!("invalid USB ID {:?}: {}", value, e))
+        };
+        Ok(Self {
+            vendor_id: parse(vendor)?,
+            product_id: parse(product)?,
+        })
+    }
+}
+
+impl From<UsbId> for String {
+    fn from(id: UsbId) -> Self {
+        format!("{:04X}:{:04X}", id.vendor_id, id.product_id)
+    }
+}
+
+/// Which USB devices to serve and how to open them
+///
+/// The default matches a Switch running DBI or the SwitchLink client.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceProfile {
+    /// Devices to match, in order of preference
+    pub ids: Vec<UsbId>,
+    pub interface: u8,
+    /// Alternate setting to select; `None` leaves the device's current one
+    pub alt_setting: Option<u8>,
+    /// Endpoint addresses (e.g. 129 for 0x81); `None` discovers them on the interface
+    pub in_endpoint: Option<u8>,
+    pub out_endpoint: Option<u8>,
+    /// Reset the device before claiming it
+    pub reset: bool,
+}
+
+impl Default for DeviceProfile {
+    fn default() -> Self {
+        Self {
+            ids: vec![UsbId {
+                vendor_id: SWITCH_VENDOR_ID,
+                product_id: SWITCH_PRODUCT_ID,
+            }],
+            interface: 0,
+            alt_setting: None,
+            in_endpoint: None,
+            out_endpoint: None,
+            reset: true,
+        }
+    }
+}
+
+impl DeviceProfile {
+    fn matches(&self, device: &Device<Context>) -> Result<bool> {
+        let device_desc = device.device_descriptor()?;
+        Ok(self.ids.iter().any(|id| {
+            device_desc.vendor_id() == id.vendor_id && device_desc.product_id() == id.product_id
+        }))
+    }
+}
+
+/// Timeouts and retries for USB transfers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransferPolicy {
+    /// Reads while idling for the next command; short so stop requests are noticed
+    pub poll_timeout_ms: u64,
+    /// Waiting for a payload or ACK from the Switch
+    pub response_timeout_ms: u64,
+    /// Command headers and other short writes
+    pub write_timeout_ms: u64,
+    /// Each bulk write of file data or a large payload
+    pub data_timeout_ms: u64,
+    /// Attempts after the first for a write refused because the endpoint was busy
+    pub retries: u32,
+    /// Delay before the first retry, doubled for each further one
+    pub backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// Bulk transfers kept in flight while streaming file data; 1 sends synchronously
+    pub queue_depth: usize,
+    /// Bytes per bulk transfer of file data, at most `MAX_TRANSFER_SIZE`
+    #[serde(deserialize_with = "transfer_size")]
+    pub transfer_size: usize,
+    /// Serve files from a memory mapping instead of copying them through a buffer
+    pub mmap: bool,
+}
+
+impl Default for TransferPolicy {
+    fn default() -> Self {
+        Self {
+            poll_timeout_ms: 100,
+            response_timeout_ms: 30_000,
+            write_timeout_ms: 1_000,
+            data_timeout_ms: 10_000,
+            retries: 3,
+            backoff_ms: 100,
+            max_backoff_ms: 2_000,
+            queue_depth: 1,
+            transfer_size: BUFFER_SEGMENT_DATA_SIZE,
+            mmap: false,
+        }
+    }
+}
+
+/// A transfer size libusb can take in one transfer
+pub fn check_transfer_size(size: usize) -> std::result::Result<usize, String> {
+    if (1..=MAX_TRANSFER_SIZE).contains(&size) {
+        Ok(size)
+    } else {
+        Err(format!("transfer size must be 1 to {} bytes, got {}", MAX_TRANSFER_SIZE, size))
+    }
+}
+
+fn transfer_size<'de, D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<usize, D::Error> {
+    check_transfer_size(usize::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+impl TransferPolicy {
+    fn poll_timeout(&self) -> Duration {
+        Duration::from_millis(self.poll_timeout_ms)
+    }
+
+    fn response_timeout(&self) -> Duration {
+        Duration::from_millis(self.response_timeout_ms)
+    }
+
+    fn write_timeout(&self) -> Duration {
+        Duration::from_millis(self.write_timeout_ms)
+    }
+
+    fn data_timeout(&self) -> Duration {
+        Duration::from_millis(self.data_timeout_ms)
+    }
+
+    /// Delay before retry number `attempt` (starting at 1)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(16);
+        Duration::from_millis(self.backoff_ms.saturating_mul(factor).min(self.max_backoff_ms))
+    }
+}
+
+/// How a failed operation affects the session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// The command is abandoned but the session carries on
+    Recoverable,
+    /// The console is gone; reconnect
+    Disconnected,
+    /// The session can't continue
+    Fatal,
+}
+
+impl ErrorClass {
+    pub fn of(e: &anyhow::Error) -> Self {
+        if let Some(rusb_err) = e.downcast_ref::<rusb::Error>() {
+            return match rusb_err {
+                rusb::Error::Timeout
+                | rusb::Error::Interrupted
+                | rusb::Error::Busy
+                | rusb::Error::Overflow
+                | rusb::Error::Pipe
+                | rusb::Error::Io => Self::Recoverable,
+                rusb::Error::NoDevice => Self::Disconnected,
+                _ => Self::Fatal,
+            };
+        }
+        // Anything else only spoils the command it belongs to: a missing or
+        // unreadable file, a malformed payload, a library error
+        Self::Recoverable
+    }
+}
+
+/// Worth repeating the same write: the endpoint was busy, so nothing was transferred
+///
+/// A timed-out or interrupted write may have sent part of the data, and libusb
+/// doesn't say how much, so repeating it could send bytes twice.
+fn is_retryable(e: &anyhow::Error) -> bool {
+    matches!(e.downcast_ref::<rusb::Error>(), Some(rusb::Error::Busy))
+}
 
 pub struct UsbConnection {
     handle: DeviceHandle<Context>,
     in_endpoint: u8,
     out_endpoint: u8,
+    policy: TransferPolicy,
+    // Transfer buffers reused across FILE_RANGE requests
+    buffer_pool: Mutex<Vec<Vec<u8>>>,
+    info: DeviceInfo,
+}
+
+/// A Switch console on the USB bus
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub bus: u8,
+    /// Port path from the root hub, e.g. [2, 3] for bus port 2.3
+    pub ports: Vec<u8>,
+    pub address: u8,
+    pub serial: Option<String>,
+}
+
+impl DeviceInfo {
+    /// Stable identifier for the physical port, e.g. `1-2.3`
+    pub fn id(&self) -> String {
+        let ports: Vec<String> = self.ports.iter().map(|p| p.to_string()).collect();
+        format!("{}-{}", self.bus, ports.join("."))
+    }
+
+    pub fn label(&self) -> String {
+        match &self.serial {
+            Some(serial) => format!("{} ({})", serial, self.id()),
+            None => self.id(),
+        }
+    }
+
+    /// Same attachment of the same console; the address changes on every replug
+    pub fn is_same_device(&self, other: &DeviceInfo) -> bool {
+        self.bus == other.bus && self.address == other.address
+    }
+
+    fn of(device: &Device<Context>, serial: Option<String>) -> Self {
+        Self {
+            bus: device.bus_number(),
+            ports: device.port_numbers().unwrap_or_default(),
+            address: device.address(),
+            serial,
+        }
+    }
+
+    fn matches(&self, device: &Device<Context>) -> bool {
+        device.bus_number() == self.bus
+            && device.port_numbers().map(|p| p == self.ports).unwrap_or(false)
+    }
 }
 
 impl UsbConnection {
-    pub fn connect() -> Result<Self> {
+    /// Connect to the first device matching the profile
+    pub fn connect(profile: &DeviceProfile) -> Result<Self> {
         info!("Searching for Nintendo Switch...");
         
         let context = Context::new()?;
-        let device = Self::find_switch(&context)?;
+        let device = Self::find_switch(&context, profile)?;
+        
+        Self::open(device, profile)
+    }
+    
+    /// Connect to the matching device on a specific bus/port
+    pub fn connect_to(info: &DeviceInfo, profile: &DeviceProfile) -> Result<Self> {
+        info!("Searching for Nintendo Switch at {}...", info.id());
+        
+        let context = Context::new()?;
+        for device in context.devices()?.iter() {
+            if profile.matches(&device)? && info.matches(&device) {
+                return Self::open(device, profile);
+            }
+        }
+        Err(anyhow!("Nintendo Switch not found at {}", info.id()))
+    }
+    
+    /// List every connected device matching the profile
+    ///
+    /// Devices in `held` are left out without being opened, so a running
+    /// session's transfer isn't disturbed.
+    pub fn enumerate(profile: &DeviceProfile, held: &[DeviceInfo]) -> Result<Vec<DeviceInfo>> {
+        let context = Context::new()?;
+        let mut devices = Vec::new();
+        
+        for device in context.devices()?.iter() {
+            if !profile.matches(&device)? {
+                continue;
+            }
+            let info = DeviceInfo::of(&device, None);
+            if held.iter().any(|h| h.is_same_device(&info)) {
+                continue;
+            }
+            
+            // Reading the serial needs the device open; it's optional for identification
+            let serial = device.open().ok().and_then(|handle| read_serial(&device, &handle));
+            devices.push(DeviceInfo { serial, ..info });
+        }
         
+        devices.sort_by(|a, b| (a.bus, &a.ports).cmp(&(b.bus, &b.ports)));
+        Ok(devices)
+    }
+    
+    fn open(device: Device<Context>, profile: &DeviceProfile) -> Result<Self> {
         info!("Switch found, opening device...");
         let mut handle = device.open()?;
         
-        // Reset device only on first connection
-        // Don't reset on reconnect as it causes I/O errors
-        if let Err(e) = handle.reset() {
-            debug!("Reset failed (might be already reset): {}", e);
+        if profile.reset {
+            // Reset device only on first connection
+            // Don't reset on reconnect as it causes I/O errors
+            if let Err(e) = handle.reset() {
+                debug!("Reset failed (might be already reset): {}", e);
+            }
+            std::thread::sleep(Duration::from_millis(500));
+            
+            // Reopen after reset
+            drop(handle);
+            std::thread::sleep(Duration::from_millis(500));
+            handle = device.open()?;
         }
-        std::thread::sleep(Duration::from_millis(500));
-        
-        // Reopen after reset
-        drop(handle);
-        std::thread::sleep(Duration::from_millis(500));
-        handle = device.open()?;
         
         // Set configuration
         if let Err(e) = handle.set_active_configuration(1) {
             debug!("Set configuration failed: {}", e);
         }
         
-        // Claim interface 0
-        handle.claim_interface(0)?;
-        info!("Claimed interface 0");
+        handle.claim_interface(profile.interface)?;
+        info!("Claimed interface {}", profile.interface);
         
-        // Find endpoints
+        if let Some(alt_setting) = profile.alt_setting {
+            handle.set_alternate_setting(profile.interface, alt_setting)?;
+            info!("Selected alternate setting {}", alt_setting);
+        }
+        
+        let mut interface = profile.interface;
+        let (discovered_in, discovered_out) = match (profile.in_endpoint, profile.out_endpoint) {
+            (Some(_), Some(_)) => (None, None),
+            _ => match Self::find_endpoints(&device, profile.interface, profile.alt_setting)? {
+                (None, None) => {
+                    // Some client builds expose the bulk pair on another interface
+                    let found = Self::find_bulk_interface(&device)?
+                        .ok_or_else(|| anyhow!("No bulk endpoints on interface {} or any other", profile.interface))?;
+                    warn!("No bulk endpoints on interface {}, using interface {}", profile.interface, found.interface);
+                    let _ = handle.release_interface(profile.interface);
+                    handle.claim_interface(found.interface)?;
+                    if found.alt_setting != 0 {
+                        handle.set_alternate_setting(found.interface, found.alt_setting)?;
+                    }
+                    interface = found.interface;
+                    (Some(found.in_endpoint), Some(found.out_endpoint))
+                }
+                found => found,
+            },
+        };
+        
+        let in_endpoint = profile.in_endpoint.or(discovered_in)
+            .ok_or_else(|| anyhow!("IN endpoint not found on interface {}", interface))?;
+        let out_endpoint = profile.out_endpoint.or(discovered_out)
+            .ok_or_else(|| anyhow!("OUT endpoint not found on interface {}", interface))?;
+        
+        info!("Connected to Switch (Interface: {}, IN: 0x{:02X}, OUT: 0x{:02X})", interface, in_endpoint, out_endpoint);
+        
+        let info = DeviceInfo::of(&device, read_serial(&device, &handle));
+        Ok(Self {
+            handle,
+            in_endpoint,
+            out_endpoint,
+            policy: TransferPolicy::default(),
+            buffer_pool: Mutex::new(Vec::new()),
+            info,
+        })
+    }
+    
+    /// The console this connection is open to
+    pub fn device_info(&self) -> &DeviceInfo {
+        &self.info
+    }
+    
+    pub fn with_policy(mut self, policy: TransferPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+    
+    /// Last bulk IN and OUT endpoint of an interface (and alt setting, if set)
+    fn find_endpoints(device: &Device<Context>, number: u8, alt_setting: Option<u8>) -> Result<(Option<u8>, Option<u8>)> {
         let config_desc = device.config_descriptor(0)?;
         
         let mut in_endpoint = None;
         let mut out_endpoint = None;
-        let mut interface_number = 0;
         
-        for interface in config_desc.interfaces() {
-            interface_number = interface.number();
+        for interface in config_desc.interfaces().filter(|i| i.number() == number) {
             for descriptor in interface.descriptors() {
+                if alt_setting.is_some_and(|alt| descriptor.setting_number() != alt) {
+                    continue;
+                }
                 for endpoint in descriptor.endpoint_descriptors() {
+                    if endpoint.transfer_type() != TransferType::Bulk {
+                        continue;
+                    }
                     match endpoint.direction() {
                         Direction::In => in_endpoint = Some(endpoint.address()),
                         Direction::Out => out_endpoint = Some(endpoint.address()),
@@ -110,78 +504,370 @@ impl UsbConnection {
             }
         }
         
-        let in_endpoint = in_endpoint.ok_or_else(|| anyhow!("IN endpoint not found"))?;
-        let out_endpoint = out_endpoint.ok_or_else(|| anyhow!("OUT endpoint not found"))?;
+        Ok((in_endpoint, out_endpoint))
+    }
+    
+    /// First interface and alt setting with both a bulk IN and a bulk OUT endpoint
+    fn find_bulk_interface(device: &Device<Context>) -> Result<Option<BulkInterface>> {
+        let config_desc = device.config_descriptor(0)?;
         
-        info!("Connected to Switch (Interface: {}, IN: 0x{:02X}, OUT: 0x{:02X})", interface_number, in_endpoint, out_endpoint);
+        for interface in config_desc.interfaces() {
+            for descriptor in interface.descriptors() {
+                let bulk = |direction| {
+                    descriptor
+                        .endpoint_descriptors()
+                        .filter(|e| e.transfer_type() == TransferType::Bulk && e.direction() == direction)
+                        .last()
+                        .map(|e| e.address())
+                };
+                if let (Some(in_endpoint), Some(out_endpoint)) = (bulk(Direction::In), bulk(Direction::Out)) {
+                    return Ok(Some(BulkInterface {
+                        interface: interface.number(),
+                        alt_setting: descriptor.setting_number(),
+                        in_endpoint,
+                        out_endpoint,
+                    }));
+                }
+            }
+        }
         
-        Ok(Self {
-            handle,
-            in_endpoint,
-            out_endpoint,
-        })
+        Ok(None)
     }
     
-    fn find_switch(context: &Context) -> Result<Device<Context>> {
-        for device in context.devices()?.iter() {
-            let device_desc = device.device_descriptor()?;
-            if device_desc.vendor_id() == SWITCH_VENDOR_ID
-                && device_desc.product_id() == SWITCH_PRODUCT_ID
-            {
-                return Ok(device);
+    fn find_switch(context: &Context, profile: &DeviceProfile) -> Result<Device<Context>> {
+        let devices = context.devices()?;
+        
+        // Earlier IDs in the profile win when several kinds of device are plugged in
+        for id in &profile.ids {
+            for device in devices.iter() {
+                let device_desc = device.device_descriptor()?;
+                if device_desc.vendor_id() == id.vendor_id && device_desc.product_id() == id.product_id {
+                    return Ok(device);
+                }
             }
         }
         Err(anyhow!("Nintendo Switch not found"))
     }
     
-    pub fn read(&self, size: usize) -> Result<Vec<u8>> {
-        let mut buf = vec![0u8; size];
-        let bytes_read = self.handle.read_bulk(self.in_endpoint, &mut buf, TIMEOUT)?;
-        buf.truncate(bytes_read);
-        Ok(buf)
+}
+
+/// An interface setting with a bulk endpoint pair
+struct BulkInterface {
+    interface: u8,
+    alt_setting: u8,
+    in_endpoint: u8,
+    out_endpoint: u8,
+}
+
+fn read_serial(device: &Device<Context>, handle: &DeviceHandle<Context>) -> Option<String> {
+    let desc = device.device_descriptor().ok()?;
+    handle.read_serial_number_string_ascii(&desc).ok()
+}
+
+/// Byte transport the server talks over
+///
+/// Implemented by `UsbConnection`; tests can substitute a scripted transport.
+pub trait Transport: Send {
+    fn read(&self, size: usize) -> Result<Vec<u8>>;
+    
+    fn read_with_long_timeout(&self, size: usize) -> Result<Vec<u8>>;
+    
+    fn write(&self, data: &[u8]) -> Result<usize>;
+    
+    fn write_with_long_timeout(&self, data: &[u8]) -> Result<usize>;
+    
+    /// One bulk write of file data; may write fewer bytes than given
+    fn write_data(&self, data: &[u8]) -> Result<usize> {
+        self.write_with_long_timeout(data)
     }
     
-    pub fn read_with_long_timeout(&self, size: usize) -> Result<Vec<u8>> {
-        let mut buf = vec![0u8; size];
-        let bytes_read = self.handle.read_bulk(self.in_endpoint, &mut buf, TIMEOUT_LONG)?;
-        buf.truncate(bytes_read);
-        Ok(buf)
+    /// Write all of `data`, continuing after partial writes and retrying
+    /// writes to a busy endpoint with backoff
+    fn write_all(&self, data: &[u8], policy: &TransferPolicy) -> Result<()> {
+        let mut written = 0;
+        let mut attempt = 0;
+        
+        while written < data.len() {
+            match self.write_data(&data[written..]) {
+                Ok(0) => return Err(anyhow!("Write stalled at {} / {} bytes", written, data.len())),
+                Ok(n) => {
+                    if written + n < data.len() {
+                        debug!("Partial write: {} of {} bytes", n, data.len() - written);
+                    }
+                    written += n;
+                    attempt = 0;
+                }
+                Err(e) if is_retryable(&e) && attempt < policy.retries => {
+                    attempt += 1;
+                    let delay = policy.backoff(attempt);
+                    warn!("Write failed at {} / {} bytes ({}), retry {}/{} in {:?}",
+                          written, data.len(), e, attempt, policy.retries, delay);
+                    std::thread::sleep(delay);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
     }
     
-    pub fn write(&self, data: &[u8]) -> Result<usize> {
-        Ok(self.handle.write_bulk(self.out_endpoint, data, TIMEOUT)?)
+    /// Send `size` bytes from `reader`, calling `pace` before each transfer
+    /// goes out and `on_sent` after it completes
+    ///
+    /// `pace` may block to hold the transfer back. Returns the bytes sent,
+    /// fewer than `size` if the reader ends early.
+    fn send_stream(
+        &self,
+        reader: &mut dyn Read,
+        size: usize,
+        policy: &TransferPolicy,
+        pace: &mut dyn FnMut(usize),
+        on_sent: &mut dyn FnMut(usize),
+    ) -> Result<usize> {
+        send_stream_sync(self, reader, size, policy, pace, on_sent)
+    }
+    
+    /// Send `data` as it is, calling `pace` and `on_sent` as `send_stream` does
+    fn send_slice(
+        &self,
+        data: &[u8],
+        policy: &TransferPolicy,
+        pace: &mut dyn FnMut(usize),
+        on_sent: &mut dyn FnMut(usize),
+    ) -> Result<usize> {
+        send_slice_sync(self, data, policy, pace, on_sent)
+    }
+    
+    /// Read exactly `size` bytes, which may span several bulk transfers
+    fn read_exact_with_long_timeout(&self, size: usize) -> Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(size);
+        while buf.len() < size {
+            let chunk = self.read_with_long_timeout(size - buf.len())?;
+            if chunk.is_empty() {
+                return Err(anyhow!("Short read: {} / {} bytes", buf.len(), size));
+            }
+            buf.extend_from_slice(&chunk);
+        }
+        Ok(buf)
     }
     
-    pub fn read_command_header(&self) -> Result<CommandHeader> {
+    fn read_command_header(&self) -> Result<CommandHeader> {
         let data = self.read(16)?;
         Ok(CommandHeader::from_bytes(&data)?)
     }
     
-    pub fn read_command_header_with_long_timeout(&self) -> Result<CommandHeader> {
+    fn read_command_header_with_long_timeout(&self) -> Result<CommandHeader> {
         let data = self.read_with_long_timeout(16)?;
         Ok(CommandHeader::from_bytes(&data)?)
     }
     
-    pub fn write_command_header(&self, header: &CommandHeader) -> Result<()> {
+    fn write_command_header(&self, header: &CommandHeader) -> Result<()> {
         self.write(&header.to_bytes())?;
         Ok(())
     }
 }
 
+impl Transport for UsbConnection {
+    fn read(&self, size: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; size];
+        let bytes_read = self.handle.read_bulk(self.in_endpoint, &mut buf, self.policy.poll_timeout())?;
+        buf.truncate(bytes_read);
+        Ok(buf)
+    }
+    
+    fn read_with_long_timeout(&self, size: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; size];
+        let bytes_read = self.handle.read_bulk(self.in_endpoint, &mut buf, self.policy.response_timeout())?;
+        buf.truncate(bytes_read);
+        Ok(buf)
+    }
+    
+    fn write(&self, data: &[u8]) -> Result<usize> {
+        Ok(self.handle.write_bulk(self.out_endpoint, data, self.policy.write_timeout())?)
+    }
+    
+    fn write_with_long_timeout(&self, data: &[u8]) -> Result<usize> {
+        Ok(self.handle.write_bulk(self.out_endpoint, data, self.policy.response_timeout())?)
+    }
+    
+    fn write_data(&self, data: &[u8]) -> Result<usize> {
+        Ok(self.handle.write_bulk(self.out_endpoint, data, self.policy.data_timeout())?)
+    }
+    
+    fn send_stream(
+        &self,
+        reader: &mut dyn Read,
+        size: usize,
+        policy: &TransferPolicy,
+        pace: &mut dyn FnMut(usize),
+        on_sent: &mut dyn FnMut(usize),
+    ) -> Result<usize> {
+        if policy.queue_depth <= 1 {
+            return send_stream_sync(self, reader, size, policy, pace, on_sent);
+        }
+        
+        // Unlike write_all, failed transfers end the range without a retry; see BulkPipeline
+        let mut pool = self.buffer_pool.lock().map_err(|_| anyhow!("Buffer pool poisoned"))?;
+        let queue = LibusbQueue::new(&self.handle, self.out_endpoint, policy.data_timeout());
+        let mut pipeline = BulkPipeline::new(queue, policy.queue_depth, policy.transfer_size, &mut pool);
+        pipeline.stream(reader, size, pace, on_sent)
+    }
+    
+    fn send_slice(
+        &self,
+        data: &[u8],
+        policy: &TransferPolicy,
+        pace: &mut dyn FnMut(usize),
+        on_sent: &mut dyn FnMut(usize),
+    ) -> Result<usize> {
+        if policy.queue_depth <= 1 {
+            return send_slice_sync(self, data, policy, pace, on_sent);
+        }
+        
+        // Unlike write_all, failed transfers end the range without a retry; see BulkPipeline
+        let mut pool = self.buffer_pool.lock().map_err(|_| anyhow!("Buffer pool poisoned"))?;
+        let queue = LibusbQueue::new(&self.handle, self.out_endpoint, policy.data_timeout());
+        let mut pipeline = BulkPipeline::new(queue, policy.queue_depth, policy.transfer_size, &mut pool);
+        pipeline.send_slice(data, pace, on_sent)
+    }
+}
+
+/// One transfer at a time: read a chunk, write it, repeat
+fn send_stream_sync<T: Transport + ?Sized>(
+    transport: &T,
+    reader: &mut dyn Read,
+    size: usize,
+    policy: &TransferPolicy,
+    pace: &mut dyn FnMut(usize),
+    on_sent: &mut dyn FnMut(usize),
+) -> Result<usize> {
+    let mut sent = 0;
+    let mut buffer = vec![0u8; policy.transfer_size.clamp(1, size.max(1))];
+    
+    while sent < size {
+        let read_size = std::cmp::min(buffer.len(), size - sent);
+        let bytes_read = reader.read(&mut buffer[..read_size])?;
+        if bytes_read == 0 {
+            break;
+        }
+        
+        pace(bytes_read);
+        transport.write_all(&buffer[..bytes_read], policy)?;
+        sent += bytes_read;
+        on_sent(bytes_read);
+    }
+    
+    Ok(sent)
+}
+
+fn send_slice_sync<T: Transport + ?Sized>(
+    transport: &T,
+    data: &[u8],
+    policy: &TransferPolicy,
+    pace: &mut dyn FnMut(usize),
+    on_sent: &mut dyn FnMut(usize),
+) -> Result<usize> {
+    for chunk in data.chunks(policy.transfer_size.max(1)) {
+        pace(chunk.len());
+        transport.write_all(chunk, policy)?;
+        on_sent(chunk.len());
+    }
+    Ok(data.len())
+}
+
+/// Block while transfers are held, flagging it in the progress, until resumed or stopped
+fn hold_while_paused(
+    bandwidth: &Bandwidth,
+    running: &Mutex<bool>,
+    progress: Option<&Arc<Mutex<TransferProgress>>>,
+) {
+    let set_paused = |paused: bool| {
+        if let Some(Ok(mut p)) = progress.map(|p| p.lock()) {
+            p.paused = paused;
+        }
+    };
+    
+    let mut held = false;
+    while bandwidth.is_held() && running.lock().map(|r| *r).unwrap_or(false) {
+        if !held {
+            set_paused(true);
+            held = true;
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+    if held {
+        set_paused(false);
+    }
+}
+
+/// Mapped bytes sent between checks that the file hasn't been truncated
+const MAPPED_SEGMENT_SIZE: usize = 8 * 1024 * 1024;
+
+fn is_timeout(e: &anyhow::Error) -> bool {
+    matches!(e.downcast_ref::<rusb::Error>(), Some(rusb::Error::Timeout))
+}
+
+/// The inbox for a session, with partial uploads kept apart per console
+fn session_inbox(config: &Config, device: Option<&DeviceInfo>) -> Inbox {
+    let inbox = Inbox::new(config.inbox_dir.clone());
+    match device {
+        Some(device) => inbox.for_session(&device.id()),
+        None => inbox,
+    }
+}
+
 pub struct SwitchLinkServer {
-    connection: Option<UsbConnection>,
+    connection: Option<Box<dyn Transport>>,
     file_list: Arc<Mutex<HashMap<String, PathBuf>>>,
     running: Arc<Mutex<bool>>,
     progress: Option<Arc<Mutex<TransferProgress>>>,
+    config: Config,
+    inbox: Inbox,
+    // Uploads announced with SAVE_BEGIN, by name -> (title_id, user_id)
+    pending_saves: HashMap<String, (u64, u128)>,
+    browse: BrowseRoots,
+    list_state: ListState,
+    last_seen: Option<Instant>,
+    // The client sends PINGs, so silence from it means something is wrong
+    heartbeat_seen: bool,
+    stalled: bool,
+    /// Console this server is bound to; `None` takes the first one found
+    device: Option<DeviceInfo>,
+    // The console the current connection is open to, for the device manager
+    held: Arc<Mutex<Option<DeviceInfo>>>,
+    // Kept open across FILE_RANGE requests for the same file
+    served: Option<ServedFile>,
+    bandwidth: Arc<Bandwidth>,
+    // Per-console share of the bandwidth limit
+    device_bucket: TokenBucket,
+    // Files never served, even when asked for by a stale name or a browse path
+    refused: Arc<Mutex<HashSet<PathBuf>>>,
+    // Opened on first use and kept for the session
+    database: OnceCell<Database>,
 }
 
 impl SwitchLinkServer {
     pub fn new(file_list: Arc<Mutex<HashMap<String, PathBuf>>>) -> Self {
+        let config = Config::default();
         Self {
             connection: None,
             file_list,
             running: Arc::new(Mutex::new(false)),
             progress: None,
+            inbox: Inbox::new(config.inbox_dir.clone()),
+            pending_saves: HashMap::new(),
+            browse: BrowseRoots::default(),
+            list_state: ListState::new(),
+            last_seen: None,
+            heartbeat_seen: false,
+            stalled: false,
+            device: None,
+            held: Arc::new(Mutex::new(None)),
+            served: None,
+            bandwidth: Arc::new(Bandwidth::new(config.bandwidth.clone())),
+            device_bucket: TokenBucket::new(),
+            refused: Arc::new(Mutex::new(HashSet::new())),
+            database: OnceCell::new(),
+            config,
         }
     }
     
@@ -189,19 +875,85 @@ impl SwitchLinkServer {
         file_list: Arc<Mutex<HashMap<String, PathBuf>>>,
         progress: Arc<Mutex<TransferProgress>>,
     ) -> Self {
+        let config = Config::default();
         Self {
             connection: None,
             file_list,
             running: Arc::new(Mutex::new(false)),
             progress: Some(progress),
+            inbox: Inbox::new(config.inbox_dir.clone()),
+            pending_saves: HashMap::new(),
+            browse: BrowseRoots::default(),
+            list_state: ListState::new(),
+            last_seen: None,
+            heartbeat_seen: false,
+            stalled: false,
+            device: None,
+            held: Arc::new(Mutex::new(None)),
+            served: None,
+            bandwidth: Arc::new(Bandwidth::new(config.bandwidth.clone())),
+            device_bucket: TokenBucket::new(),
+            refused: Arc::new(Mutex::new(HashSet::new())),
+            database: OnceCell::new(),
+            config,
         }
     }
     
+    /// Serve over an already open transport instead of connecting over USB
+    pub fn with_transport(mut self, transport: Box<dyn Transport>) -> Self {
+        self.connection = Some(transport);
+        self
+    }
+    
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.inbox = session_inbox(&config, self.device.as_ref());
+        self.browse = BrowseRoots::new(&config.browse_roots);
+        self.bandwidth = Arc::new(Bandwidth::new(config.bandwidth.clone()));
+        self.database = OnceCell::new();
+        self.config = config;
+        self
+    }
+    
+    /// Share limits and pausing with other sessions; call after `with_config`
+    pub fn with_bandwidth(mut self, bandwidth: Arc<Bandwidth>) -> Self {
+        self.bandwidth = bandwidth;
+        self
+    }
+    
+    /// Refuse these files; shared so a running session sees the set change
+    pub fn with_refused(mut self, refused: Arc<Mutex<HashSet<PathBuf>>>) -> Self {
+        self.refused = refused;
+        self
+    }
+    
+    /// Serve one specific console
+    pub fn with_device(mut self, device: DeviceInfo) -> Self {
+        self.inbox = session_inbox(&self.config, Some(&device));
+        self.device = Some(device);
+        self
+    }
+    
     pub fn connect(&mut self) -> Result<()> {
-        self.connection = Some(UsbConnection::connect()?);
+        let connection = match &self.device {
+            Some(device) => UsbConnection::connect_to(device, &self.config.usb)?,
+            None => UsbConnection::connect(&self.config.usb)?,
+        }
+        .with_policy(self.config.transfer.clone());
+        *self.held.lock().unwrap() = Some(connection.device_info().clone());
+        self.connection = Some(Box::new(connection));
         Ok(())
     }
     
+    /// The console currently connected to, once known
+    pub fn held_device(&self) -> Arc<Mutex<Option<DeviceInfo>>> {
+        self.held.clone()
+    }
+    
+    /// Flag the command loop checks; clearing it stops the server from another thread
+    pub fn running_flag(&self) -> Arc<Mutex<bool>> {
+        self.running.clone()
+    }
+    
     pub fn is_connected(&self) -> bool {
         self.connection.is_some()
     }
@@ -217,6 +969,19 @@ impl SwitchLinkServer {
         self.poll_commands()
     }
     
+    /// Like `start`, but leaves the running flag to the caller
+    ///
+    /// A stop requested while the console is still being opened is honoured
+    /// instead of being overwritten.
+    pub fn run(&mut self) -> Result<()> {
+        if self.connection.is_none() {
+            self.connect()?;
+        }
+        
+        info!("SwitchLink Server started, entering command loop");
+        self.poll_commands()
+    }
+    
     pub fn stop(&mut self) {
         *self.running.lock().unwrap() = false;
         self.connection = None;
@@ -258,58 +1023,73 @@ impl SwitchLinkServer {
             match header_result {
                 Ok(header) => {
                     reconnect_attempts = 0; // Reset on successful read
+                    self.note_activity();
                     debug!("Received command: type={}, id={}, size={}",
                            header.cmd_type, header.cmd_id, header.data_size);
                     
-                    match header.cmd_id {
+                    // An ACK that arrives after we stopped waiting for it isn't a new command
+                    if header.cmd_type != CMD_TYPE_REQUEST {
+                        debug!("Ignoring stray header: type={}, id={}", header.cmd_type, header.cmd_id);
+                        continue;
+                    }
+                    
+                    let result = match header.cmd_id {
                         CMD_ID_EXIT => {
                             self.process_exit_command()?;
                             break;
                         }
-                        CMD_ID_FILE_RANGE => {
-                            self.process_file_range_command(header.data_size)?;
-                        }
-                        CMD_ID_LIST => {
-                            self.process_list_command()?;
-                        }
+                        CMD_ID_LIST_OLD => self.process_list_old_command(),
+                        CMD_ID_FILE_RANGE => self.process_file_range_command(header.data_size),
+                        CMD_ID_LIST => self.process_list_command(header.data_size),
+                        CMD_ID_PING => self.process_ping_command(header.data_size),
+                        CMD_ID_STATUS => self.process_status_command(header.data_size),
+                        CMD_ID_LIST_CATALOG => self.process_list_catalog_command(header.data_size),
+                        CMD_ID_BROWSE => self.process_browse_command(header.data_size),
+                        CMD_ID_UPLOAD_BEGIN => self.process_upload_begin_command(header.data_size),
+                        CMD_ID_UPLOAD_RANGE => self.process_upload_range_command(header.data_size),
+                        CMD_ID_SAVE_BEGIN => self.process_save_begin_command(header.data_size),
+                        CMD_ID_UPLOAD_END => self.process_upload_end_command(header.data_size),
                         _ => {
                             warn!("Unknown command ID: {}", header.cmd_id);
+                            Ok(())
                         }
-                    }
-                }
-                Err(e) => {
-                    if let Some(rusb_err) = e.downcast_ref::<rusb::Error>() {
-                        match rusb_err {
-                            rusb::Error::Timeout => {
-                                // Timeout is normal - just continue polling
-                                continue;
-                            }
-                            rusb::Error::Pipe | rusb::Error::Io => {
-                                // Pipe/IO error might be normal during connection setup
-                                // Just continue and wait for actual commands
-                                debug!("USB pipe/IO error (might be normal): {}", e);
-                                std::thread::sleep(Duration::from_millis(100));
-                                continue;
+                    };
+                    
+                    if let Err(e) = result {
+                        match ErrorClass::of(&e) {
+                            ErrorClass::Recoverable => {
+                                warn!("Command {} aborted: {}", header.cmd_id, e);
+                                self.log_progress(format!("[!] Command aborted: {}", e));
                             }
-                            rusb::Error::NoDevice => {
-                                warn!("Switch disconnected, will try to reconnect...");
+                            ErrorClass::Disconnected => {
+                                warn!("Switch disconnected during command {}, will try to reconnect...", header.cmd_id);
                                 self.connection = None;
-                                std::thread::sleep(Duration::from_secs(1));
-                                continue;
-                            }
-                            _ => {
-                                error!("USB error: {}", e);
-                                // Don't reconnect immediately, just continue
-                                std::thread::sleep(Duration::from_millis(100));
-                                continue;
                             }
+                            ErrorClass::Fatal => return Err(e),
                         }
-                    } else {
-                        error!("Error reading command: {}", e);
-                        std::thread::sleep(Duration::from_millis(100));
-                        continue;
                     }
                 }
+                Err(e) => match ErrorClass::of(&e) {
+                    ErrorClass::Recoverable if is_timeout(&e) => {
+                        // Timeout is normal - just check the client isn't stuck
+                        self.check_stall();
+                    }
+                    ErrorClass::Recoverable => {
+                        // Pipe/IO error might be normal during connection setup
+                        // Just continue and wait for actual commands
+                        debug!("USB error while polling (recoverable): {}", e);
+                        std::thread::sleep(self.config.transfer.backoff(1));
+                    }
+                    ErrorClass::Disconnected => {
+                        warn!("Switch disconnected, will try to reconnect...");
+                        self.connection = None;
+                        std::thread::sleep(Duration::from_secs(1));
+                    }
+                    ErrorClass::Fatal => {
+                        error!("Error reading command: {}", e);
+                        return Err(e);
+                    }
+                },
             }
         }
         
@@ -326,36 +1106,57 @@ impl SwitchLinkServer {
         Ok(())
     }
     
-    fn process_list_command(&self) -> Result<()> {
-        info!("Processing LIST command");
+    fn process_list_command(&mut self, data_size: u32) -> Result<()> {
+        info!("Processing LIST command (data_size={})", data_size);
         let conn = self.connection.as_ref().unwrap();
-        let file_list = self.file_list.lock().unwrap();
-        
-        let mut nsp_path_list = String::new();
-        for (name, path) in file_list.iter() {
-            // Get file size
-            let size = if let Ok(metadata) = std::fs::metadata(path) {
-                metadata.len()
-            } else {
-                0
-            };
-            
-            // Format: filename|size\n
-            nsp_path_list.push_str(name);
-            nsp_path_list.push('|');
-            nsp_path_list.push_str(&size.to_string());
-            nsp_path_list.push('\n');
+        
+        // Paged clients send a request payload, legacy clients don't
+        let request = if data_size > 0 {
+            let ack = CommandHeader::new(CMD_TYPE_ACK, CMD_ID_LIST, data_size);
+            conn.write_command_header(&ack)?;
+            let request_data = conn.read_with_long_timeout(data_size as usize)?;
+            Some(ListRequest::from_bytes(&request_data)?)
+        } else {
+            None
+        };
+        
+        {
+            let file_list = self.file_list.lock().unwrap();
+            self.list_state.refresh(&file_list);
         }
         
+        let nsp_path_list = match request {
+            Some(request) => {
+                let limit = match request.limit {
+                    0 => LIST_MAX_PAGE_SIZE,
+                    n => n.min(LIST_MAX_PAGE_SIZE),
+                };
+                let page = self.list_state.page(request.since_token, request.offset as usize, limit as usize);
+                info!(
+                    "Sending LIST page: offset={}, {} of {} entries, {} removed, token={}",
+                    request.offset, page.entries.len(), page.total, page.removed.len(), page.token
+                );
+                page.to_payload()
+            }
+            None => {
+                // Format: filename|size\n, sorted by name
+                let page = self.list_state.page(0, 0, usize::MAX);
+                info!("Sending full file list: {} files", page.total);
+                page.entries
+                    .iter()
+                    .map(|(name, size)| format!("{}|{}\n", name, size))
+                    .collect::<String>()
+            }
+        };
+        
         let nsp_path_list_bytes = nsp_path_list.as_bytes();
         let list_len = nsp_path_list_bytes.len() as u32;
         
-        info!("Sending file list response: {} files, {} bytes", file_list.len(), list_len);
         debug!("File list content: {}", nsp_path_list.trim());
         
         let response = CommandHeader::new(CMD_TYPE_RESPONSE, CMD_ID_LIST, list_len);
         conn.write_command_header(&response)?;
-        info!("Sent LIST response header");
+        info!("Sent LIST response header ({} bytes)", list_len);
         
         if list_len > 0 {
             // Wait for ACK before sending file list (like Python version)
@@ -364,7 +1165,7 @@ impl SwitchLinkServer {
             let _ack = conn.read_command_header_with_long_timeout()?;
             info!("Received ACK from Switch");
             
-            conn.write(nsp_path_list_bytes)?;
+            conn.write_all(nsp_path_list_bytes, &self.config.transfer)?;
             info!("Sent file list data ({} bytes)", list_len);
         } else {
             info!("File list is empty, no data to send");
@@ -373,7 +1174,318 @@ impl SwitchLinkServer {
         Ok(())
     }
     
-    fn process_file_range_command(&self, data_size: u32) -> Result<()> {
+    /// Look up a requested file in the queue, or by path in the browse roots
+    fn resolve_file(&self, name: &str) -> Option<PathBuf> {
+        let queued = self.file_list.lock().unwrap().get(name).cloned();
+        let path = match queued {
+            Some(path) => path,
+            None if name.starts_with('/') && !self.browse.is_empty() => match self.browse.resolve(name) {
+                Ok(path) if path.is_file() => path,
+                Ok(_) => return None,
+                Err(e) => {
+                    warn!("Browse path rejected: {}", e);
+                    return None;
+                }
+            },
+            None => return None,
+        };
+        
+        if self.is_refused(&path) {
+            warn!("Refusing {}: it failed verification", path.display());
+            return None;
+        }
+        Some(path)
+    }
+    
+    /// Browse paths are canonical while library paths may not be, so compare both ways
+    fn is_refused(&self, path: &Path) -> bool {
+        let Ok(refused) = self.refused.lock() else {
+            return false;
+        };
+        if refused.is_empty() {
+            return false;
+        }
+        if refused.contains(path) {
+            return true;
+        }
+        let Ok(canonical) = path.canonicalize() else {
+            return false;
+        };
+        refused.iter().any(|r| r.canonicalize().is_ok_and(|r| r == canonical))
+    }
+    
+    fn process_browse_command(&self, data_size: u32) -> Result<()> {
+        info!("Processing BROWSE command (data_size={})", data_size);
+        let conn = self.connection.as_ref().unwrap();
+        
+        let ack = CommandHeader::new(CMD_TYPE_ACK, CMD_ID_BROWSE, data_size);
+        conn.write_command_header(&ack)?;
+        
+        let request_data = conn.read_with_long_timeout(data_size as usize)?;
+        let request = BrowseRequest::from_bytes(&request_data)?;
+        
+        let limit = match request.limit {
+            0 => BROWSE_MAX_PAGE_SIZE,
+            n => n.min(BROWSE_MAX_PAGE_SIZE),
+        };
+        
+        let payload = match self.browse.list(&request.path, request.offset as usize, limit as usize) {
+            Ok(page) => {
+                info!("Browse {}: {} of {} entries", request.path, page.entries.len(), page.total);
+                page.to_payload()
+            }
+            Err(e) => {
+                warn!("Browse {} failed: {}", request.path, e);
+                String::new()
+            }
+        };
+        
+        // Empty response signals an error, like an empty LIST
+        let payload_len = payload.len() as u32;
+        let response = CommandHeader::new(CMD_TYPE_RESPONSE, CMD_ID_BROWSE, payload_len);
+        conn.write_command_header(&response)?;
+        
+        if payload_len > 0 {
+            let _ack = conn.read_command_header_with_long_timeout()?;
+            conn.write_all(payload.as_bytes(), &self.config.transfer)?;
+        }
+        
+        Ok(())
+    }
+    
+    fn process_ping_command(&mut self, data_size: u32) -> Result<()> {
+        let conn = self.connection.as_ref().unwrap();
+        
+        let state = if data_size > 0 {
+            let ack = CommandHeader::new(CMD_TYPE_ACK, CMD_ID_PING, data_size);
+            conn.write_command_header(&ack)?;
+            let data = conn.read_with_long_timeout(data_size as usize)?;
+            ClientState::from_bytes(&data)?
+        } else {
+            ClientState::default()
+        };
+        
+        debug!("PING: {}", state.describe());
+        
+        let pong = CommandHeader::new(CMD_TYPE_RESPONSE, CMD_ID_PING, 0);
+        conn.write_command_header(&pong)?;
+        
+        self.heartbeat_seen = true;
+        if let Some(progress) = &self.progress {
+            if let Ok(mut p) = progress.lock() {
+                p.client_state = Some(state);
+            }
+        }
+        
+        Ok(())
+    }
+    
+    fn process_status_command(&mut self, data_size: u32) -> Result<()> {
+        info!("Processing STATUS command (data_size={})", data_size);
+        let conn = self.connection.as_ref().unwrap();
+        
+        let ack = CommandHeader::new(CMD_TYPE_ACK, CMD_ID_STATUS, data_size);
+        conn.write_command_header(&ack)?;
+        let data = conn.read_with_long_timeout(data_size as usize)?;
+        let status = InstallStatus::from_bytes(&data)?;
+        
+        let response = CommandHeader::new(CMD_TYPE_RESPONSE, CMD_ID_STATUS, 0);
+        conn.write_command_header(&response)?;
+        
+        debug!("STATUS: {} {}", status.name, status.describe());
+        
+        match status.outcome {
+            InstallOutcome::InProgress => {}
+            InstallOutcome::Success => {
+                info!("Installed: {}", status.name);
+                self.log_progress(format!("[✓] Installed: {}", status.name));
+            }
+            InstallOutcome::Failure => {
+                error!("Install failed: {} ({})", status.name, status.describe());
+                self.log_progress(format!("[!] Install failed: {} - {}", status.name, status.describe()));
+            }
+        }
+        
+        if status.outcome != InstallOutcome::InProgress {
+            let path = self
+                .resolve_file(&status.name)
+                .unwrap_or_else(|| PathBuf::from(&status.name));
+            self.with_database(|db| {
+                db.record_install_outcome(
+                    &status.name,
+                    &path,
+                    &status.content_id_hex(),
+                    status.outcome == InstallOutcome::Success,
+                    status.phase.label(),
+                    status.result_code,
+                )
+            });
+        }
+        
+        if let Some(progress) = &self.progress {
+            if let Ok(mut p) = progress.lock() {
+                p.install_status = Some(status);
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Called for every command from the client
+    fn note_activity(&mut self) {
+        self.last_seen = Some(Instant::now());
+        
+        if self.stalled {
+            self.stalled = false;
+            info!("Client is responding again");
+            self.log_progress("[✓] Client is responding again".to_string());
+            self.record_session_event("recovered", "");
+            if let Some(progress) = &self.progress {
+                if let Ok(mut p) = progress.lock() {
+                    p.stalled = false;
+                }
+            }
+        }
+    }
+    
+    /// Mark the session as stalled when a heartbeat client or an unfinished transfer goes quiet
+    fn check_stall(&mut self) {
+        let Some(last_seen) = self.last_seen else {
+            return;
+        };
+        if self.stalled || last_seen.elapsed() < Duration::from_secs(self.config.stall_timeout_secs) {
+            return;
+        }
+        
+        let (transfer_pending, detail) = match &self.progress {
+            Some(progress) => match progress.lock() {
+                Ok(p) => {
+                    let pending = p.total_size > 0 && p.bytes_sent < p.total_size;
+                    let detail = match &p.client_state {
+                        Some(state) if self.heartbeat_seen => state.describe(),
+                        _ => format!("{} at {} / {} bytes", p.current_file, p.bytes_sent, p.total_size),
+                    };
+                    (pending, detail)
+                }
+                Err(_) => (false, String::new()),
+            },
+            None => (false, String::new()),
+        };
+        
+        // An idle client without heartbeat support is indistinguishable from a stalled one
+        if !self.heartbeat_seen && !transfer_pending {
+            return;
+        }
+        
+        self.stalled = true;
+        warn!("Session stalled: no command for {}s ({})", last_seen.elapsed().as_secs(), detail);
+        self.log_progress(format!("[!] Session stalled: {}", detail));
+        self.record_session_event("stalled", &detail);
+        if let Some(progress) = &self.progress {
+            if let Ok(mut p) = progress.lock() {
+                p.stalled = true;
+            }
+        }
+    }
+    
+    fn record_session_event(&self, event: &str, detail: &str) {
+        self.with_database(|db| db.record_session_event(event, detail));
+    }
+    
+    /// The library database, opened the first time it's needed; `None` if it can't be opened
+    fn database(&self) -> Option<&Database> {
+        if self.database.get().is_none() {
+            match Database::new(&self.config.db_path) {
+                Ok(db) => {
+                    let _ = self.database.set(db);
+                }
+                Err(e) => {
+                    warn!("Failed to open database: {}", e);
+                    return None;
+                }
+            }
+        }
+        self.database.get()
+    }
+    
+    /// Run a best-effort history write; failures are logged, not sent to the client
+    fn with_database(&self, f: impl FnOnce(&Database) -> Result<()>) {
+        if let Some(db) = self.database() {
+            if let Err(e) = f(db) {
+                warn!("Failed to update history: {}", e);
+            }
+        }
+    }
+    
+    /// LIST_OLD from older DBI builds: bare names, one per line, without sizes
+    fn process_list_old_command(&mut self) -> Result<()> {
+        info!("Processing LIST_OLD command (legacy DBI client)");
+        let conn = self.connection.as_ref().unwrap();
+        
+        let page = {
+            let file_list = self.file_list.lock().unwrap();
+            self.list_state.refresh(&file_list);
+            self.list_state.page(0, 0, usize::MAX)
+        };
+        let names: String = page.entries
+            .iter()
+            .map(|(name, _)| format!("{}\n", name))
+            .collect();
+        let list_len = names.len() as u32;
+        
+        let response = CommandHeader::new(CMD_TYPE_RESPONSE, CMD_ID_LIST_OLD, list_len);
+        conn.write_command_header(&response)?;
+        
+        if list_len > 0 {
+            // Some old builds ACK the header before reading the list, others read right away
+            match conn.read_command_header() {
+                Ok(_) => debug!("Received ACK from legacy client"),
+                Err(e) if is_timeout(&e) => debug!("No ACK from legacy client, sending list anyway"),
+                Err(e) => return Err(e),
+            }
+            conn.write_all(names.as_bytes(), &self.config.transfer)?;
+        }
+        
+        info!("Sent legacy file list: {} files, {} bytes", page.total, list_len);
+        Ok(())
+    }
+    
+    fn process_list_catalog_command(&self, data_size: u32) -> Result<()> {
+        info!("Processing LIST_CATALOG command (data_size={})", data_size);
+        let conn = self.connection.as_ref().unwrap();
+        
+        let request = if data_size > 0 {
+            let ack = CommandHeader::new(CMD_TYPE_ACK, CMD_ID_LIST_CATALOG, data_size);
+            conn.write_command_header(&ack)?;
+            let request_data = conn.read_with_long_timeout(data_size as usize)?;
+            CatalogRequest::from_bytes(&request_data)?
+        } else {
+            CatalogRequest::default()
+        };
+        
+        let grouping = Grouping::from_u32(request.grouping);
+        let catalog = {
+            let file_list = self.file_list.lock().unwrap();
+            Catalog::build(&file_list, self.database(), grouping, request.tag.as_deref())
+        };
+        
+        let payload = serde_json::to_vec(&catalog)?;
+        let payload_len = payload.len() as u32;
+        info!(
+            "Sending catalog v{} (client v{}): {} groups, {} bytes",
+            catalog.version, request.client_version, catalog.groups.len(), payload_len
+        );
+        
+        let response = CommandHeader::new(CMD_TYPE_RESPONSE, CMD_ID_LIST_CATALOG, payload_len);
+        conn.write_command_header(&response)?;
+        
+        let _ack = conn.read_command_header_with_long_timeout()?;
+        conn.write_all(&payload, &self.config.transfer)?;
+        
+        Ok(())
+    }
+    
+    fn process_file_range_command(&mut self, data_size: u32) -> Result<()> {
         info!("Processing FILE_RANGE command (data_size={})", data_size);
         let conn = self.connection.as_ref().unwrap();
         
@@ -401,9 +1513,8 @@ impl SwitchLinkServer {
         );
         
         // Calculate actual size to send BEFORE sending response header
-        let file_list = self.file_list.lock().unwrap();
-        let file_path = match file_list.get(&file_range.nsp_name) {
-            Some(path) => path.clone(),
+        let file_path = match self.resolve_file(&file_range.nsp_name) {
+            Some(path) => path,
             None => {
                 error!("File not found: {}", file_range.nsp_name);
                 // Send response with 0 bytes to signal error
@@ -412,7 +1523,6 @@ impl SwitchLinkServer {
                 return Err(anyhow!("File not found"));
             }
         };
-        drop(file_list); // Release lock early
         
         // Get file size and calculate actual bytes to send
         let metadata = std::fs::metadata(&file_path)?;
@@ -430,6 +1540,14 @@ impl SwitchLinkServer {
         info!("Calculated actual_size={} (requested={}, available from offset {})", 
               actual_size, requested_size, offset);
         
+        // The client keeps waiting for the response, so holding here pauses it without a disconnect
+        if actual_size > 0 && self.bandwidth.is_held() {
+            info!("Transfers paused, holding FILE_RANGE for {}", file_range.nsp_name);
+            hold_while_paused(&self.bandwidth, &self.running, self.progress.as_ref());
+            // The silence while held was ours, not a stalled client
+            self.last_seen = Some(Instant::now());
+        }
+        
         // Send response with ACTUAL size (not requested size)
         let response = CommandHeader::new(
             CMD_TYPE_RESPONSE,
@@ -447,10 +1565,9 @@ impl SwitchLinkServer {
         // Update progress - set current file and total size
         if let Some(progress) = &self.progress {
             if let Ok(mut p) = progress.lock() {
+                p.queue_depth = self.config.transfer.queue_depth;
                 if p.current_file != file_range.nsp_name {
-                    p.current_file = file_range.nsp_name.clone();
-                    p.bytes_sent = 0;
-                    p.total_size = file_size;
+                    p.begin_file(file_range.nsp_name.clone(), file_size);
                     p.add_log(format!("[>] Transferring: {}", file_range.nsp_name));
                 }
             }
@@ -459,6 +1576,13 @@ impl SwitchLinkServer {
         // Send file data (only if there's data to send)
         if actual_size > 0 {
             self.send_file_range(&file_path, offset, actual_size)?;
+            
+            // Sent is not installed; the console confirms that with STATUS
+            if offset + actual_size as u64 == file_size {
+                // Reopen next time in case the file is replaced
+                self.served = None;
+                self.with_database(|db| db.record_sent(&file_range.nsp_name, &file_path));
+            }
         } else {
             info!("No data to send (offset {} >= file_size {})", offset, file_size);
         }
@@ -467,48 +1591,320 @@ impl SwitchLinkServer {
     }
     
     fn send_file_range(
-        &self,
+        &mut self,
         file_path: &PathBuf,
         offset: u64,
         size: usize,
     ) -> Result<()> {
+        let policy = &self.config.transfer;
+        if self.served.as_ref().is_none_or(|f| f.path() != file_path) {
+            let served = ServedFile::open(file_path, policy.mmap)?;
+            if policy.mmap {
+                debug!("Serving {} {}", file_path.display(),
+                       if served.is_mapped() { "from a mapping" } else { "with buffered reads" });
+            }
+            self.served = Some(served);
+        }
+        let served = self.served.as_mut().unwrap();
         let conn = self.connection.as_ref().unwrap();
         
-        // Size is already calculated correctly by process_file_range_command
-        // Just open file and send the exact number of bytes
-        let mut file = File::open(file_path)?;
-        file.seek(SeekFrom::Start(offset))?;
-        
-        let mut curr_off = 0;
-        let mut buffer = vec![0u8; BUFFER_SEGMENT_DATA_SIZE];
-        
-        while curr_off < size {
-            let read_size = std::cmp::min(BUFFER_SEGMENT_DATA_SIZE, size - curr_off);
-            let bytes_read = file.read(&mut buffer[..read_size])?;
-            
-            if bytes_read == 0 {
-                warn!("Unexpected EOF at offset {}, sent {} / {} bytes", 
-                      offset + curr_off as u64, curr_off, size);
-                break;
+        let progress = self.progress.clone();
+        let bandwidth = self.bandwidth.clone();
+        let running = self.running.clone();
+        let device_bucket = &mut self.device_bucket;
+        let mut reported = 0usize;
+        // Pacing happens before a transfer is queued, so a limit or pause
+        // holds back the pipeline rather than only the next request
+        let mut pace = |bytes: usize| {
+            let wait = bandwidth.consume(device_bucket, bytes);
+            if !wait.is_zero() {
+                std::thread::sleep(wait);
             }
-            
-            conn.write(&buffer[..bytes_read])?;
-            curr_off += bytes_read;
-            
+            hold_while_paused(&bandwidth, &running, progress.as_ref());
+        };
+        let mut on_sent = |bytes_sent: usize| {
             // Update progress
-            if let Some(progress) = &self.progress {
+            if let Some(progress) = &progress {
                 if let Ok(mut p) = progress.lock() {
-                    p.bytes_sent += bytes_read as u64;
+                    p.bytes_sent += bytes_sent as u64;
                     p.update_speed();
                 }
             }
             
-            if curr_off % (10 * 1024 * 1024) == 0 {
-                debug!("Sent {} / {} bytes", curr_off, size);
+            reported += bytes_sent;
+            if reported.is_multiple_of(10 * 1024 * 1024) {
+                debug!("Sent {} / {} bytes", reported, size);
             }
+        };
+        
+        // Mapped segments go straight to the transport; the size is re-checked
+        // before each one in case the file is truncated mid-range
+        let mut curr_off = 0;
+        while curr_off < size {
+            let len = std::cmp::min(MAPPED_SEGMENT_SIZE, size - curr_off);
+            let Some(segment) = served.mapped_range(offset + curr_off as u64, len) else {
+                break;
+            };
+            curr_off += conn.send_slice(segment, policy, &mut pace, &mut on_sent)?;
+        }
+        
+        // Whatever couldn't be mapped is read through a buffer
+        if curr_off < size {
+            let file = served.reader_at(offset + curr_off as u64)?;
+            curr_off += conn.send_stream(file, size - curr_off, policy, &mut pace, &mut on_sent)?;
+        }
+        
+        if curr_off < size {
+            warn!("Unexpected EOF at offset {}, sent {} / {} bytes", 
+                  offset + curr_off as u64, curr_off, size);
         }
         
         info!("File transfer complete: {} bytes sent", curr_off);
         Ok(())
     }
+    
+    fn log_progress(&self, message: String) {
+        if let Some(progress) = &self.progress {
+            if let Ok(mut p) = progress.lock() {
+                p.add_log(message);
+            }
+        }
+    }
+    
+    fn process_upload_begin_command(&mut self, data_size: u32) -> Result<()> {
+        info!("Processing UPLOAD_BEGIN command (data_size={})", data_size);
+        let conn = self.connection.as_ref().unwrap();
+        
+        let ack = CommandHeader::new(CMD_TYPE_ACK, CMD_ID_UPLOAD_BEGIN, data_size);
+        conn.write_command_header(&ack)?;
+        
+        let header_data = conn.read_with_long_timeout(data_size as usize)?;
+        let upload = UploadBeginHeader::from_bytes(&header_data)?;
+        
+        info!(
+            "Upload announced: name={}, size={}, category={:?}",
+            upload.name, upload.file_size, upload.category
+        );
+        
+        if upload.category == UploadCategory::Save {
+            // A plain upload replaces an earlier save announcement of the same name
+            if let Ok(name) = sanitize_name(&upload.name) {
+                self.pending_saves.remove(&name);
+            }
+        }
+        self.accept_upload(CMD_ID_UPLOAD_BEGIN, &upload.name, upload.category, upload.file_size)?;
+        Ok(())
+    }
+    
+    fn process_save_begin_command(&mut self, data_size: u32) -> Result<()> {
+        info!("Processing SAVE_BEGIN command (data_size={})", data_size);
+        let conn = self.connection.as_ref().unwrap();
+        
+        let ack = CommandHeader::new(CMD_TYPE_ACK, CMD_ID_SAVE_BEGIN, data_size);
+        conn.write_command_header(&ack)?;
+        
+        let header_data = conn.read_with_long_timeout(data_size as usize)?;
+        let save = SaveBeginHeader::from_bytes(&header_data)?;
+        
+        info!(
+            "Save backup announced: title={:016X}, user={:032X}, name={}, size={}",
+            save.title_id, save.user_id, save.name, save.file_size
+        );
+        
+        // Only an accepted upload is filed as a save; a refused one leaves nothing behind
+        let name = sanitize_name(&save.name).ok();
+        if let Some(name) = &name {
+            self.pending_saves.remove(name);
+        }
+        let accepted = self.accept_upload(CMD_ID_SAVE_BEGIN, &save.name, UploadCategory::Save, save.file_size)?;
+        if let (true, Some(name)) = (accepted, name) {
+            self.pending_saves.insert(name, (save.title_id, save.user_id));
+        }
+        Ok(())
+    }
+    
+    /// Register an upload with the inbox and tell the Switch where to resume from
+    ///
+    /// Returns whether the upload was accepted.
+    fn accept_upload(
+        &mut self,
+        cmd_id: u32,
+        name: &str,
+        category: UploadCategory,
+        file_size: u64,
+    ) -> Result<bool> {
+        let conn = self.connection.as_ref().unwrap();
+        
+        let resume_offset = match self.inbox.begin(name, category, file_size) {
+            Ok(offset) => offset,
+            Err(e) => {
+                error!("Cannot accept upload {}: {}", name, e);
+                // Response with 0 bytes signals the upload was refused
+                let response = CommandHeader::new(CMD_TYPE_RESPONSE, cmd_id, 0);
+                conn.write_command_header(&response)?;
+                return Ok(false);
+            }
+        };
+        
+        // Respond with the offset to resume from
+        let response = CommandHeader::new(CMD_TYPE_RESPONSE, cmd_id, 8);
+        conn.write_command_header(&response)?;
+        
+        info!("Waiting for ACK from Switch...");
+        let _ack = conn.read_command_header_with_long_timeout()?;
+        conn.write(&resume_offset.to_le_bytes())?;
+        
+        if let Some(progress) = &self.progress {
+            if let Ok(mut p) = progress.lock() {
+                p.current_file = name.to_string();
+                p.bytes_sent = resume_offset;
+                p.total_size = file_size;
+                if resume_offset > 0 {
+                    p.add_log(format!("[<] Resuming upload: {} at {} bytes", name, resume_offset));
+                } else {
+                    p.add_log(format!("[<] Receiving: {}", name));
+                }
+            }
+        }
+        
+        Ok(true)
+    }
+    
+    fn process_upload_range_command(&mut self, data_size: u32) -> Result<()> {
+        info!("Processing UPLOAD_RANGE command (data_size={})", data_size);
+        let conn = self.connection.as_ref().unwrap();
+        
+        let ack = CommandHeader::new(CMD_TYPE_ACK, CMD_ID_UPLOAD_RANGE, data_size);
+        conn.write_command_header(&ack)?;
+        
+        let header_data = conn.read_with_long_timeout(data_size as usize)?;
+        let range = UploadRangeHeader::from_bytes(&header_data)?;
+        
+        debug!(
+            "Upload range: name={}, offset={}, size={}",
+            range.name, range.range_offset, range.range_size
+        );
+        
+        // Tell the Switch we're ready for the data
+        let ready = CommandHeader::new(CMD_TYPE_ACK, CMD_ID_UPLOAD_RANGE, range.range_size);
+        conn.write_command_header(&ready)?;
+        
+        // Always drain the full range so the stream stays in sync, even after a write error
+        let size = range.range_size as usize;
+        let mut curr_off = 0;
+        let mut write_error = None;
+        
+        while curr_off < size {
+            let chunk_size = std::cmp::min(BUFFER_SEGMENT_DATA_SIZE, size - curr_off);
+            let chunk = conn.read_exact_with_long_timeout(chunk_size)?;
+            
+            if write_error.is_none() {
+                let offset = range.range_offset + curr_off as u64;
+                if let Err(e) = self.inbox.write_range(&range.name, range.category, offset, &chunk) {
+                    error!("Failed to write upload {}: {}", range.name, e);
+                    write_error = Some(e);
+                }
+            }
+            curr_off += chunk.len();
+        }
+        
+        let written = if write_error.is_some() { 0 } else { range.range_size };
+        let response = CommandHeader::new(CMD_TYPE_RESPONSE, CMD_ID_UPLOAD_RANGE, written);
+        conn.write_command_header(&response)?;
+        
+        if let Some(progress) = &self.progress {
+            if let Ok(mut p) = progress.lock() {
+                if write_error.is_none() {
+                    p.bytes_sent = range.range_offset + range.range_size as u64;
+                    p.update_speed();
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    fn process_upload_end_command(&mut self, data_size: u32) -> Result<()> {
+        info!("Processing UPLOAD_END command (data_size={})", data_size);
+        let conn = self.connection.as_ref().unwrap();
+        
+        let ack = CommandHeader::new(CMD_TYPE_ACK, CMD_ID_UPLOAD_END, data_size);
+        conn.write_command_header(&ack)?;
+        
+        let header_data = conn.read_with_long_timeout(data_size as usize)?;
+        let end = UploadEndHeader::from_bytes(&header_data)?;
+        
+        let status = match self.inbox.finish(&end.name, end.category, &end.sha256) {
+            Ok(upload) => {
+                info!("Upload complete: {} -> {}", upload.name, upload.path.display());
+                let save = match upload.category {
+                    UploadCategory::Save => self.pending_saves.remove(&upload.name),
+                    _ => None,
+                };
+                if let Some(db) = self.database() {
+                    if let Some((title_id, user_id)) = save {
+                        self.file_save_backup(db, title_id, user_id, &upload);
+                    } else if let Err(e) = db.record_upload(
+                        &upload.name,
+                        &upload.path,
+                        upload.category.dir_name(),
+                        upload.size,
+                        &upload.hash,
+                    ) {
+                        warn!("Failed to record upload {}: {}", upload.name, e);
+                    }
+                }
+                self.log_progress(format!("[<] Received: {} ({})", upload.name, upload.category.dir_name()));
+                UPLOAD_STATUS_OK
+            }
+            Err(e) => {
+                error!("Upload {} failed: {}", end.name, e);
+                self.log_progress(format!("[!] Upload failed: {} ({})", end.name, e));
+                // Unless the client can still resume it, the upload is gone and so is its save announcement
+                if !matches!(e.downcast_ref::<InboxError>(), Some(InboxError::SizeMismatch { .. })) {
+                    if let Ok(name) = sanitize_name(&end.name) {
+                        self.pending_saves.remove(&name);
+                    }
+                }
+                match e.downcast_ref::<InboxError>() {
+                    Some(InboxError::HashMismatch { .. }) => UPLOAD_STATUS_HASH_MISMATCH,
+                    Some(InboxError::SizeMismatch { .. }) => UPLOAD_STATUS_SIZE_MISMATCH,
+                    Some(InboxError::UnknownUpload(_))
+                    | Some(InboxError::AmbiguousUpload(_))
+                    | Some(InboxError::InvalidName(_)) => {
+                        UPLOAD_STATUS_UNKNOWN_FILE
+                    }
+                    _ => UPLOAD_STATUS_ERROR,
+                }
+            }
+        };
+        
+        let conn = self.connection.as_ref().unwrap();
+        let response = CommandHeader::new(CMD_TYPE_RESPONSE, CMD_ID_UPLOAD_END, status);
+        conn.write_command_header(&response)?;
+        
+        Ok(())
+    }
+    
+    /// Move a received save archive into the save library and prune old snapshots
+    fn file_save_backup(&self, db: &Database, title_id: u64, user_id: u128, upload: &CompletedUpload) {
+        let library = SaveLibrary::new(self.config.saves_dir.clone());
+        match library.import(db, title_id, user_id, &upload.path, &upload.hash) {
+            Ok(snapshot) => {
+                info!("Save backup stored: {}", snapshot.path);
+                match library.apply_retention(db, &snapshot.title_id, &snapshot.user_id, &self.config.save_retention) {
+                    Ok(removed) if removed > 0 => {
+                        info!("Retention removed {} old snapshots", removed);
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("Failed to apply save retention: {}", e),
+                }
+                self.log_progress(format!("[<] Save backup: {}", snapshot.title_id));
+            }
+            Err(e) => {
+                error!("Failed to store save backup {}: {}", upload.name, e);
+                self.log_progress(format!("[!] Save backup failed: {} ({})", upload.name, e));
+            }
+        }
+    }
 }
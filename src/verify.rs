@@ -0,0 +1,456 @@
+/// Offline integrity checks of NSPs and XCIs against their own hashes and signatures
+///
+/// With the user's keys, each NCA's header signature is checked against the
+/// fixed-key RSA modulus, tickets are looked for, section hash trees are
+/// walked and every NCA is hashed and compared to its record in the CNMT.
+use anyhow::Result;
+use rsa::{BigUint, Pss, RsaPublicKey};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::database::{Database, Verification, VerifyCheck};
+use crate::inbox::to_hex;
+use crate::keys::KeySet;
+use crate::nca::{read_u32, read_u64, Nca, NcaContentType, NcaError, Package, Section, SectionCrypto};
+
+// Whole NCAs are hashed in pieces of this size
+const CHUNK_SIZE: u64 = 0x100000;
+// CNMT content type of delta fragments, which are often left out of updates
+const DELTA_FRAGMENT: u8 = 6;
+// Largest hash level read in one go (a 32 GiB RomFS needs 64 MiB of hashes)
+const MAX_HASH_LEVEL: u64 = 0x8000000;
+
+/// Outcome of verifying the library
+#[derive(Debug, Clone, Default)]
+pub struct VerifySummary {
+    pub verified: usize,
+    /// Files with failed checks, as (filename, failed checks)
+    pub failed: Vec<(String, Vec<VerifyCheck>)>,
+    /// Problems with the user's keys and how many files each kept from being checked
+    pub key_errors: BTreeMap<String, usize>,
+    /// Files that couldn't be checked for other reasons, as (filename, error)
+    pub errors: Vec<(String, String)>,
+}
+
+/// Result of one check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    /// The keys or format at hand can't show whether the file is fine
+    Skipped,
+}
+
+impl CheckStatus {
+    /// Lowercase name, as stored in the library
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Passed => "passed",
+            Self::Failed => "failed",
+            Self::Skipped => "skipped",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "passed" => Some(Self::Passed),
+            "failed" => Some(Self::Failed),
+            "skipped" => Some(Self::Skipped),
+            _ => None,
+        }
+    }
+}
+
+/// Result of verifying a whole file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VerifyStatus {
+    Verified,
+    /// At least one check failed
+    Failed,
+    /// The file couldn't be checked at all
+    #[default]
+    Error,
+}
+
+impl VerifyStatus {
+    /// Lowercase name, as stored in the library
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Verified => "verified",
+            Self::Failed => "failed",
+            Self::Error => "error",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "verified" => Some(Self::Verified),
+            "failed" => Some(Self::Failed),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+}
+
+/// One content entry of a CNMT
+struct ContentRecord {
+    hash: [u8; 32],
+    content_id: [u8; 16],
+    size: u64,
+    content_type: u8,
+}
+
+/// Check an NSP or XCI, returning every check made
+///
+/// Errors mean the file couldn't be checked at all: it isn't a package, it
+/// can't be read, or the header key is missing or wrong. Problems confined to
+/// one check, like a missing title key, are reported as skipped checks.
+pub fn verify_file(path: &Path, keys: &KeySet) -> Result<Vec<VerifyCheck>, NcaError> {
+    let package = Package::open(path)?;
+    let ncas = package.ncas(keys)?;
+    if ncas.is_empty() {
+        return Err(if package.entries().iter().any(|e| e.name.to_lowercase().ends_with(".ncz")) {
+            NcaError::Unsupported("compressed NCZ files (decompress to verify)".to_string())
+        } else {
+            NcaError::Invalid("container: no NCAs".to_string())
+        });
+    }
+
+    let mut checks = Vec::new();
+    for nca in &ncas {
+        let name = &nca.entry.name;
+        checks.push(settle(name, "header signature", header_signature(nca, keys))?);
+        if let Some(rights_id) = nca.header.rights_id() {
+            checks.push(ticket(&package, nca, &rights_id));
+        }
+        for section in nca.header.sections() {
+            let check = format!("section {} hashes", section.index);
+            checks.push(settle(name, &check, section_hashes(&package, nca, &section, keys, &check))?);
+        }
+    }
+    checks.extend(content_hashes(&package, &ncas, keys)?);
+    Ok(checks)
+}
+
+/// The report stored for a file: failed if any check failed
+pub fn report(result: &Result<Vec<VerifyCheck>, NcaError>) -> Verification {
+    match result {
+        Ok(checks) => Verification {
+            status: match checks.iter().any(|c| c.status == CheckStatus::Failed) {
+                true => VerifyStatus::Failed,
+                false => VerifyStatus::Verified,
+            },
+            checks: checks.clone(),
+            ..Default::default()
+        },
+        Err(e) => Verification {
+            status: VerifyStatus::Error,
+            error: Some(e.to_string()),
+            ..Default::default()
+        },
+    }
+}
+
+/// Verify new and changed library files, or every file with `all`, storing a report for each
+pub fn verify_library(
+    db: &Database,
+    keys: &KeySet,
+    all: bool,
+    mut on_progress: impl FnMut(usize, usize),
+) -> Result<VerifySummary> {
+    let files = db.files_needing_verification(all)?;
+    let mut summary = VerifySummary::default();
+
+    for (done, file) in files.iter().enumerate() {
+        on_progress(done, files.len());
+        let result = verify_file(Path::new(&file.path), keys);
+        let verification = report(&result);
+        db.save_verification(file.id, &verification)?;
+
+        match result {
+            Ok(checks) if verification.status == VerifyStatus::Failed => {
+                let failed = checks.into_iter().filter(|c| c.status == CheckStatus::Failed).collect();
+                summary.failed.push((file.filename.clone(), failed));
+            }
+            Ok(_) => summary.verified += 1,
+            Err(e) if e.is_key_error() => *summary.key_errors.entry(e.to_string()).or_default() += 1,
+            Err(e) => summary.errors.push((file.filename.clone(), e.to_string())),
+        }
+    }
+    on_progress(files.len(), files.len());
+    Ok(summary)
+}
+
+/// Files the server must not offer: those that failed verification, if the user refuses them
+pub fn refused_paths(db: &Database, refuse_failed: bool) -> Result<HashSet<PathBuf>> {
+    if !refuse_failed {
+        return Ok(HashSet::new());
+    }
+    Ok(db.failed_verification_paths()?.into_iter().collect())
+}
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+fn check(nca: &str, check: &str, status: CheckStatus, detail: impl Into<String>) -> VerifyCheck {
+    VerifyCheck {
+        nca: nca.to_string(),
+        check: check.to_string(),
+        status,
+        detail: detail.into(),
+    }
+}
+
+/// Turn an error from one check into its result; only I/O errors stop verification
+fn settle(nca: &str, name: &str, result: Result<VerifyCheck, NcaError>) -> Result<VerifyCheck, NcaError> {
+    match result {
+        Ok(result) => Ok(result),
+        Err(NcaError::Io(e)) => Err(NcaError::Io(e)),
+        // The file may be fine; these keys or formats just can't show it
+        Err(e) if e.is_key_error() || matches!(e, NcaError::Unsupported(_) | NcaError::MissingTicket(_)) => {
+            Ok(check(nca, name, CheckStatus::Skipped, e.to_string()))
+        }
+        Err(e) => Ok(check(nca, name, CheckStatus::Failed, e.to_string())),
+    }
+}
+
+/// RSA-2048-PSS signature of header bytes 0x200..0x400 by Nintendo's fixed key
+fn header_signature(nca: &Nca, keys: &KeySet) -> Result<VerifyCheck, NcaError> {
+    let header = nca.header.bytes();
+    let key_name = format!("nca_hdr_fixed_key_modulus_{:02x}", header[0x221]);
+    // Older key dumps have a single, unnumbered modulus
+    let modulus: [u8; 0x100] = match keys.get(&key_name) {
+        Err(NcaError::MissingKey(_)) if header[0x221] == 0 => {
+            keys.get("nca_hdr_fixed_key_modulus").map_err(|_| NcaError::MissingKey(key_name.clone()))?
+        }
+        other => other?,
+    };
+    let key = RsaPublicKey::new(BigUint::from_bytes_be(&modulus), BigUint::from(65537u32))
+        .map_err(|_| NcaError::InvalidKey(key_name.clone()))?;
+
+    let digest = sha256(&header[0x200..0x400]);
+    Ok(match key.verify(Pss::new::<Sha256>(), &digest, &header[..0x100]) {
+        Ok(()) => check(&nca.entry.name, "header signature", CheckStatus::Passed, format!("signed with {}", key_name)),
+        Err(_) => check(&nca.entry.name, "header signature", CheckStatus::Failed, "the header was modified or re-signed"),
+    })
+}
+
+/// NCAs encrypted with a title key need their ticket alongside
+fn ticket(package: &Package, nca: &Nca, rights_id: &[u8; 16]) -> VerifyCheck {
+    let name = format!("{}.tik", to_hex(rights_id));
+    match package.find(&name) {
+        Some(_) => check(&nca.entry.name, "ticket", CheckStatus::Passed, name),
+        None => check(&nca.entry.name, "ticket", CheckStatus::Failed, format!("{} is missing", name)),
+    }
+}
+
+/// The FS header against its hash in the NCA header, then the section's hash tree
+fn section_hashes(
+    package: &Package,
+    nca: &Nca,
+    section: &Section,
+    keys: &KeySet,
+    name: &str,
+) -> Result<VerifyCheck, NcaError> {
+    let nca_name = &nca.entry.name;
+    let expected = &nca.header.bytes()[0x280 + section.index * 0x20..0x2A0 + section.index * 0x20];
+    if sha256(&section.fs_header) != expected {
+        return Ok(check(nca_name, name, CheckStatus::Failed, "FS header does not match its hash in the NCA header"));
+    }
+    if !matches!(section.crypto, SectionCrypto::None | SectionCrypto::Ctr) {
+        return Err(NcaError::Unsupported(format!("section encryption {:?}", section.crypto)));
+    }
+
+    match section.hash_type {
+        2 => pfs0_hashes(package, nca, section, keys, name),
+        3 => ivfc_hashes(package, nca, section, keys, name),
+        other => Ok(check(nca_name, name, CheckStatus::Skipped, format!("no hash tree (hash type {})", other))),
+    }
+}
+
+/// A PFS0 section: the hash table against the master hash, then every data block
+fn pfs0_hashes(
+    package: &Package,
+    nca: &Nca,
+    section: &Section,
+    keys: &KeySet,
+    name: &str,
+) -> Result<VerifyCheck, NcaError> {
+    let nca_name = &nca.entry.name;
+    // Reading the file table first tells wrong keys from bad data
+    package.pfs0_files(nca, section, keys)?;
+
+    let fs = &section.fs_header;
+    let master = &fs[0x8..0x28];
+    let block_size = read_u32(fs, 0x28) as u64;
+    let (table_offset, table_size) = (read_u64(fs, 0x30), read_u64(fs, 0x38));
+    let (data_offset, data_size) = (read_u64(fs, 0x40), read_u64(fs, 0x48));
+    if block_size == 0 || table_size < data_size.div_ceil(block_size) * 0x20 || table_size > MAX_HASH_LEVEL {
+        return Ok(check(nca_name, name, CheckStatus::Failed, "hash table does not fit the data"));
+    }
+
+    let table = package.read_section(nca, section, keys, table_offset, table_size as usize)?;
+    if sha256(&table) != master {
+        return Ok(check(nca_name, name, CheckStatus::Failed, "hash table does not match the master hash"));
+    }
+    let blocks = data_size.div_ceil(block_size);
+    for block in 0..blocks {
+        let offset = block * block_size;
+        // The last block is hashed as it is, without padding
+        let data = package.read_section(nca, section, keys, data_offset + offset, block_size.min(data_size - offset) as usize)?;
+        let hash = &table[block as usize * 0x20..(block as usize + 1) * 0x20];
+        if sha256(&data) != hash {
+            return Ok(check(nca_name, name, CheckStatus::Failed, format!("PFS0 block {} does not match its hash", block)));
+        }
+    }
+    Ok(check(nca_name, name, CheckStatus::Passed, format!("{} PFS0 blocks match", blocks)))
+}
+
+/// An IVFC (RomFS) section: each hash level against the one above, from the master hash down
+///
+/// The data level itself is covered by the NCA's hash in the CNMT.
+fn ivfc_hashes(
+    package: &Package,
+    nca: &Nca,
+    section: &Section,
+    keys: &KeySet,
+    name: &str,
+) -> Result<VerifyCheck, NcaError> {
+    let nca_name = &nca.entry.name;
+    if section.fs_type == 0 {
+        // Reading the file table first tells wrong keys from bad data
+        package.romfs_files(nca, section, keys)?;
+    }
+
+    let fs = &section.fs_header;
+    // The count includes the master hash; the last level holds the data
+    let levels = read_u32(fs, 0x14) as usize;
+    if !(2..=7).contains(&levels) {
+        return Ok(check(nca_name, name, CheckStatus::Failed, format!("IVFC header has {} levels", levels)));
+    }
+
+    let mut hashes = fs[0xC8..0xE8].to_vec();
+    for level in 0..levels - 2 {
+        let entry = 0x18 + level * 0x18;
+        let (offset, size, order) = (read_u64(fs, entry), read_u64(fs, entry + 8), read_u32(fs, entry + 0x10));
+        if !(9..32).contains(&order) || size > MAX_HASH_LEVEL {
+            return Ok(check(nca_name, name, CheckStatus::Failed, format!("IVFC level {} header", level + 1)));
+        }
+        let data = package.read_section(nca, section, keys, offset, size as usize)?;
+
+        // Blocks are hashed padded with zeros to the full block size
+        for (block, chunk) in data.chunks(1 << order).enumerate() {
+            let mut padded = chunk.to_vec();
+            padded.resize(1 << order, 0);
+            if hashes.get(block * 0x20..(block + 1) * 0x20) != Some(&sha256(&padded)[..]) {
+                return Ok(check(
+                    nca_name,
+                    name,
+                    CheckStatus::Failed,
+                    format!("IVFC level {} block {} does not match its hash", level + 1, block),
+                ));
+            }
+        }
+        hashes = data;
+    }
+    Ok(check(nca_name, name, CheckStatus::Passed, format!("{} IVFC hash levels match", levels - 2)))
+}
+
+/// Every NCA a CNMT lists, hashed and compared with its recorded SHA-256
+fn content_hashes(package: &Package, ncas: &[Nca], keys: &KeySet) -> Result<Vec<VerifyCheck>, NcaError> {
+    let metas: Vec<&Nca> = ncas.iter().filter(|n| n.header.content_type() == NcaContentType::Meta).collect();
+    if metas.is_empty() {
+        return Ok(vec![check("", "content hashes", CheckStatus::Failed, "no meta NCA records the expected hashes")]);
+    }
+
+    let mut checks = Vec::new();
+    let mut records = Vec::new();
+    for meta in metas {
+        match cnmt_records(package, meta, keys) {
+            Ok(found) => records.extend(found),
+            Err(e) => checks.push(settle(&meta.entry.name, "CNMT", Err(e))?),
+        }
+    }
+
+    for record in records {
+        let id = to_hex(&record.content_id);
+        let name = format!("{}.nca", id);
+        let Some(entry) = package.find(&name) else {
+            checks.push(if package.find(&format!("{}.ncz", id)).is_some() {
+                check(&name, "content hash", CheckStatus::Skipped, "compressed as NCZ")
+            } else if record.content_type == DELTA_FRAGMENT {
+                check(&name, "content hash", CheckStatus::Skipped, "delta fragment not included")
+            } else {
+                check(&name, "content hash", CheckStatus::Failed, "listed in the CNMT but missing from the package")
+            });
+            continue;
+        };
+        if entry.size != record.size {
+            checks.push(check(
+                &name,
+                "content hash",
+                CheckStatus::Failed,
+                format!("{} bytes, but the CNMT records {}", entry.size, record.size),
+            ));
+            continue;
+        }
+
+        let mut hasher = Sha256::new();
+        let mut offset = 0;
+        while offset < entry.size {
+            let len = CHUNK_SIZE.min(entry.size - offset);
+            hasher.update(package.read_at(entry.offset + offset, len as usize)?);
+            offset += len;
+        }
+        let hash: [u8; 32] = hasher.finalize().into();
+        checks.push(match hash == record.hash {
+            true => check(&name, "content hash", CheckStatus::Passed, "matches the CNMT"),
+            false => check(&name, "content hash", CheckStatus::Failed, "does not match the CNMT; the file is corrupt"),
+        });
+    }
+    Ok(checks)
+}
+
+/// Content records of the `.cnmt` in a meta NCA's PFS0
+fn cnmt_records(package: &Package, meta: &Nca, keys: &KeySet) -> Result<Vec<ContentRecord>, NcaError> {
+    let section = meta
+        .header
+        .sections()
+        .into_iter()
+        .find(|section| section.fs_type == 1)
+        .ok_or_else(|| NcaError::Invalid("meta NCA: no PFS0 section".to_string()))?;
+    let file = package
+        .pfs0_files(meta, &section, keys)?
+        .into_iter()
+        .find(|f| f.name.ends_with(".cnmt"))
+        .ok_or_else(|| NcaError::Invalid("meta NCA: no .cnmt".to_string()))?;
+    if file.size > 0x100000 {
+        return Err(NcaError::Invalid("CNMT: too large".to_string()));
+    }
+    let cnmt = package.read_section(meta, &section, keys, file.offset, file.size as usize)?;
+    if cnmt.len() < 0x20 {
+        return Err(NcaError::Invalid("CNMT: too short".to_string()));
+    }
+
+    let extended_header = u16::from_le_bytes([cnmt[0xE], cnmt[0xF]]) as usize;
+    let count = u16::from_le_bytes([cnmt[0x10], cnmt[0x11]]) as usize;
+    let start = 0x20 + extended_header;
+    if cnmt.len() < start + count * 0x38 {
+        return Err(NcaError::Invalid("CNMT: content records".to_string()));
+    }
+    Ok((0..count)
+        .map(|i| {
+            let record = &cnmt[start + i * 0x38..start + (i + 1) * 0x38];
+            let mut size = [0u8; 8];
+            size[..6].copy_from_slice(&record[0x30..0x36]);
+            ContentRecord {
+                hash: record[..0x20].try_into().unwrap(),
+                content_id: record[0x20..0x30].try_into().unwrap(),
+                size: u64::from_le_bytes(size),
+                content_type: record[0x36],
+            }
+        })
+        .collect())
+}
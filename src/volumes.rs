@@ -0,0 +1,235 @@
+/// Library roots on removable and network drives: which volume a folder is on, and finding it again
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use crate::database::{Database, LibraryRoot};
+
+/// Filesystem types whose source names a share rather than a device
+const NETWORK_FILESYSTEMS: &[&str] = &["nfs", "nfs4", "cifs", "smb3", "smbfs", "fuse.sshfs", "9p", "afs"];
+
+/// A mounted filesystem that can be recognised when it comes back somewhere else
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    pub mount_point: PathBuf,
+    /// `uuid:…` or `label:…` for disks, `net:…` with the share for network mounts
+    pub volume_id: String,
+    pub label: Option<String>,
+}
+
+/// Mounted volumes that can be identified; empty where mounts can't be listed
+pub fn current_mounts() -> Vec<Mount> {
+    #[cfg(target_os = "linux")]
+    {
+        let Ok(mountinfo) = std::fs::read_to_string("/proc/self/mountinfo") else {
+            return Vec::new();
+        };
+        parse_mountinfo(&mountinfo, &disk_links("/dev/disk/by-uuid"), &disk_links("/dev/disk/by-label"))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Vec::new()
+    }
+}
+
+/// Device -> name for the symlinks in a `/dev/disk/by-*` folder
+#[cfg(target_os = "linux")]
+fn disk_links(dir: &str) -> HashMap<PathBuf, String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return HashMap::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let device = std::fs::canonicalize(entry.path()).ok()?;
+            Some((device, unescape(&entry.file_name().to_string_lossy())))
+        })
+        .collect()
+}
+
+/// Parse `/proc/self/mountinfo`, naming disks by their UUID (or label) from `/dev/disk`
+///
+/// `uuids` and `labels` map device paths such as `/dev/sdb1` to names.
+/// Mounts of a subfolder (bind mounts) and filesystems without a stable
+/// name, like tmpfs, are left out; a volume mounted twice is listed once.
+pub fn parse_mountinfo(
+    mountinfo: &str,
+    uuids: &HashMap<PathBuf, String>,
+    labels: &HashMap<PathBuf, String>,
+) -> Vec<Mount> {
+    let mut mounts: Vec<Mount> = Vec::new();
+    for line in mountinfo.lines() {
+        // ID PARENT MAJOR:MINOR ROOT MOUNT_POINT OPTIONS [OPTIONAL...] - FSTYPE SOURCE SUPER_OPTIONS
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let Some(separator) = fields.iter().position(|f| *f == "-") else {
+            continue;
+        };
+        let (Some(root), Some(mount_point), Some(fstype), Some(source)) =
+            (fields.get(3), fields.get(4), fields.get(separator + 1), fields.get(separator + 2))
+        else {
+            continue;
+        };
+        if *root != "/" {
+            continue;
+        }
+
+        let source = unescape(source);
+        let device = PathBuf::from(&source);
+        let label = labels.get(&device).cloned();
+        let volume_id = if NETWORK_FILESYSTEMS.contains(fstype) {
+            format!("net:{}", source)
+        } else if let Some(uuid) = uuids.get(&device) {
+            format!("uuid:{}", uuid)
+        } else if let Some(label) = &label {
+            format!("label:{}", label)
+        } else {
+            continue;
+        };
+
+        if mounts.iter().all(|m| m.volume_id != volume_id) {
+            mounts.push(Mount { mount_point: PathBuf::from(unescape(mount_point)), volume_id, label });
+        }
+    }
+    mounts
+}
+
+/// Undo the `\040` (mount tables) and `\x20` (disk links) escapes for spaces and other bytes
+fn unescape(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match bytes.get(i + 1..i + 4) {
+            Some([b'x', hi, lo]) if bytes[i] == b'\\' => {
+                std::str::from_utf8(&[*hi, *lo]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            }
+            Some(octal) if bytes[i] == b'\\' => {
+                std::str::from_utf8(octal).ok().and_then(|octal| u8::from_str_radix(octal, 8).ok())
+            }
+            _ => None,
+        };
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// The mount a path is on (the deepest mount point containing it) and the path within it
+pub fn volume_of<'a>(path: &Path, mounts: &'a [Mount]) -> Option<(&'a Mount, String)> {
+    mounts
+        .iter()
+        .filter_map(|mount| relative_path(&mount.mount_point, path).map(|rel| (mount, rel)))
+        .max_by_key(|(mount, _)| mount.mount_point.components().count())
+}
+
+/// `path` relative to `root` with `/` separators, if it's inside it
+pub fn relative_path(root: &Path, path: &Path) -> Option<String> {
+    let rel = path.strip_prefix(root).ok()?;
+    let parts: Vec<String> = rel
+        .components()
+        .map(|c| match c {
+            Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    Some(parts.join("/"))
+}
+
+/// Whether `nested` sits inside `outer` on the same volume, so relocating
+/// `outer` carries it along
+pub(crate) fn moves_with(outer: &LibraryRoot, nested: &LibraryRoot) -> bool {
+    nested.id != outer.id
+        && relative_path(&outer.path, &nested.path).is_some()
+        && (nested.volume_id.is_none() || nested.volume_id == outer.volume_id)
+}
+
+/// The inverse of `relative_path`
+pub fn join_relative(root: &Path, rel: &str) -> PathBuf {
+    rel.split('/').filter(|part| !part.is_empty()).fold(root.to_path_buf(), |path, part| path.join(part))
+}
+
+/// Register a folder as a library root, remembering the volume it's on
+pub fn add_root(db: &Database, path: &Path, mounts: &[Mount]) -> Result<i64> {
+    let volume = volume_of(path, mounts);
+    db.add_root(
+        path,
+        volume.as_ref().map(|(mount, _)| mount.volume_id.as_str()),
+        volume.as_ref().and_then(|(mount, _)| mount.label.as_deref()),
+        volume.as_ref().map_or("", |(_, rel)| rel.as_str()),
+    )
+}
+
+/// What changed since the roots were last checked
+#[derive(Debug, Clone, Default)]
+pub struct RootCheck {
+    /// Roots whose volume turned up at a new mount point, with the new path
+    pub relocated: Vec<(LibraryRoot, PathBuf)>,
+    pub went_offline: Vec<LibraryRoot>,
+    /// Roots back online at the same path
+    pub came_back: Vec<LibraryRoot>,
+}
+
+impl RootCheck {
+    pub fn is_empty(&self) -> bool {
+        self.relocated.is_empty() && self.went_offline.is_empty() && self.came_back.is_empty()
+    }
+}
+
+/// See whether each root is still where it was, following volumes to new mount points
+///
+/// A root is online if its folder exists and, when its volume is known, is
+/// still on that volume. Otherwise the volume is looked for among `mounts`;
+/// if it's found with the root's folder on it the root moves there, and if
+/// not the root is marked offline. Nothing is deleted either way.
+pub fn resolve_roots(db: &Database, mounts: &[Mount]) -> Result<RootCheck> {
+    let mut check = RootCheck::default();
+    let roots = db.get_roots()?;
+    // Nested roots already moved with the root containing them
+    let mut moved_along: HashMap<i64, PathBuf> = HashMap::new();
+    for root in roots.iter().cloned() {
+        if let Some(path) = moved_along.remove(&root.id) {
+            check.relocated.push((root, path));
+            continue;
+        }
+        let same_volume = match (&root.volume_id, volume_of(&root.path, mounts)) {
+            (Some(id), Some((mount, _))) => mount.volume_id == *id,
+            _ => true,
+        };
+        if same_volume && root.path.is_dir() {
+            if !root.online {
+                db.set_root_online(root.id, true)?;
+                check.came_back.push(root);
+            }
+            continue;
+        }
+
+        let moved = root.volume_id.as_ref().and_then(|id| {
+            let mount = mounts.iter().find(|m| m.volume_id == *id)?;
+            Some(join_relative(&mount.mount_point, &root.volume_path)).filter(|path| path.is_dir())
+        });
+        match moved {
+            Some(path) => {
+                db.relocate_root(root.id, &path)?;
+                for nested in roots.iter().filter(|nested| moves_with(&root, nested)) {
+                    let rel = relative_path(&root.path, &nested.path).unwrap_or_default();
+                    moved_along.insert(nested.id, join_relative(&path, &rel));
+                }
+                check.relocated.push((root, path));
+            }
+            None if root.online => {
+                db.set_root_online(root.id, false)?;
+                check.went_offline.push(root);
+            }
+            None => {}
+        }
+    }
+    Ok(check)
+}
@@ -0,0 +1,121 @@
+//! Bandwidth limits: token bucket pacing, daily schedules and command-line parsing
+use chrono::NaiveTime;
+use std::time::{Duration, Instant};
+
+use switchlink_backend::bandwidth::{Bandwidth, BandwidthConfig, ScheduleRule, TimeWindow, TokenBucket};
+use switchlink_backend::config::Config;
+
+fn time(h: u32, m: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt(h, m, 0).unwrap()
+}
+
+fn window(s: &str) -> TimeWindow {
+    TimeWindow::try_from(s.to_string()).unwrap()
+}
+
+#[test]
+fn bucket_paces_to_the_rate() {
+    let mut bucket = TokenBucket::new();
+    let rate = 1_000_000.0;
+
+    // Idling fills the bucket, so the first megabyte goes at once
+    let start = Instant::now() + Duration::from_secs(2);
+    assert_eq!(bucket.take(1_000_000, rate, start), Duration::ZERO);
+    // The next half megabyte has to wait for its tokens
+    let wait = bucket.take(500_000, rate, start);
+    assert!((wait.as_secs_f64() - 0.5).abs() < 1e-6, "waited {:?}", wait);
+    // Long idle periods don't build up more than a second's worth
+    let later = start + Duration::from_secs(10);
+    assert_eq!(bucket.take(1_000_000, rate, later), Duration::ZERO);
+    assert!(!bucket.take(1, rate, later).is_zero());
+}
+
+#[test]
+fn windows_wrap_past_midnight() {
+    let night = window("22:00-06:00");
+    assert!(night.contains(time(23, 30)));
+    assert!(night.contains(time(5, 59)));
+    assert!(!night.contains(time(6, 0)));
+    assert!(!night.contains(time(12, 0)));
+
+    let day = window("09:00-17:00");
+    assert!(day.contains(time(9, 0)));
+    assert!(!day.contains(time(17, 0)));
+    assert_eq!(String::from(day), "09:00-17:00");
+
+    assert!(TimeWindow::try_from("9-17".to_string()).is_err());
+    assert!(TimeWindow::try_from("25:00-06:00".to_string()).is_err());
+}
+
+#[test]
+fn first_matching_schedule_rule_wins() {
+    let config = BandwidthConfig {
+        limit_mbps: Some(50.0),
+        device_limit_mbps: None,
+        schedule: vec![
+            ScheduleRule { window: window("09:00-17:00"), limit_mbps: Some(0.0) },
+            ScheduleRule { window: window("08:00-18:00"), limit_mbps: Some(5.0) },
+            ScheduleRule { window: window("22:00-06:00"), limit_mbps: None },
+        ],
+    };
+    assert_eq!(config.limit_at(time(12, 0)), Some(0.0));
+    assert_eq!(config.limit_at(time(8, 30)), Some(5.0));
+    assert_eq!(config.limit_at(time(23, 0)), None);
+    assert_eq!(config.limit_at(time(20, 0)), Some(50.0));
+}
+
+#[test]
+fn pause_holds_transfers() {
+    let bandwidth = Bandwidth::new(BandwidthConfig::default());
+    assert!(!bandwidth.is_held());
+    bandwidth.set_paused(true);
+    assert!(bandwidth.is_held());
+    bandwidth.set_paused(false);
+    assert!(!bandwidth.is_held());
+}
+
+#[test]
+fn per_device_limit_applies_to_each_bucket() {
+    let bandwidth = Bandwidth::new(BandwidthConfig {
+        device_limit_mbps: Some(1.0),
+        ..BandwidthConfig::default()
+    });
+    let mut first = TokenBucket::new();
+    let mut second = TokenBucket::new();
+    // Both start full, then each pays for what goes past its first second
+    for bucket in [&mut first, &mut second] {
+        assert_eq!(bandwidth.consume(bucket, 1_000_000), Duration::ZERO);
+        assert!(bandwidth.consume(bucket, 2_000_000) >= Duration::from_millis(1900));
+    }
+
+    let unlimited = Bandwidth::new(BandwidthConfig::default());
+    assert_eq!(unlimited.consume(&mut first, 2_000_000), Duration::ZERO);
+}
+
+#[test]
+fn limits_and_schedules_from_the_command_line() {
+    let args: Vec<String> = [
+        "--limit-mbps", "40",
+        "--device-limit-mbps=none",
+        "--schedule", "09:00-17:00=0",
+        "--schedule=22:00-06:00=none",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+
+    let mut config = Config::default();
+    config.bandwidth.device_limit_mbps = Some(10.0);
+    config.apply_args(&args).unwrap();
+
+    assert_eq!(config.bandwidth.limit_mbps, Some(40.0));
+    assert_eq!(config.bandwidth.device_limit_mbps, None);
+    assert_eq!(config.bandwidth.schedule.len(), 2);
+    assert_eq!(config.bandwidth.schedule[0].limit_mbps, Some(0.0));
+    assert_eq!(config.bandwidth.schedule[1].window, window("22:00-06:00"));
+
+    for bad in [["--limit-mbps", "fast"], ["--schedule", "09:00-17:00"], ["--schedule", "9-5=1"]] {
+        let args: Vec<String> = bad.iter().map(|s| s.to_string()).collect();
+        assert!(Config::default().apply_args(&args).is_err(), "{:?} was accepted", bad);
+    }
+}
@@ -0,0 +1,103 @@
+//! Remote browsing stays inside the exposed roots
+use std::path::{Path, PathBuf};
+
+use switchlink_backend::browse::{BrowseError, BrowseRoots};
+
+struct Tree {
+    dir: tempfile::TempDir,
+    roots: BrowseRoots,
+}
+
+impl Tree {
+    /// `games/` is exposed; `private/` next to it is not
+    fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let games = dir.path().join("games");
+        std::fs::create_dir_all(games.join("Zelda")).unwrap();
+        std::fs::write(games.join("Zelda/zelda.nsp"), b"zelda").unwrap();
+        std::fs::write(games.join(".hidden.nsp"), b"hidden").unwrap();
+        std::fs::create_dir_all(games.join(".cache")).unwrap();
+        std::fs::write(games.join(".cache/index"), b"index").unwrap();
+        std::fs::create_dir_all(dir.path().join("private")).unwrap();
+        std::fs::write(dir.path().join("private/keys.txt"), b"secret").unwrap();
+        let roots = BrowseRoots::new(&[games]);
+        Self { dir, roots }
+    }
+
+    fn games(&self) -> PathBuf {
+        self.dir.path().join("games").canonicalize().unwrap()
+    }
+
+    fn refused(&self, path: &str) -> BrowseError {
+        self.roots.resolve(path).unwrap_err().downcast().unwrap()
+    }
+}
+
+#[test]
+fn paths_resolve_inside_their_root() {
+    let tree = Tree::new();
+    assert_eq!(tree.roots.resolve("/games").unwrap(), tree.games());
+    assert_eq!(tree.roots.resolve("/games/Zelda/./zelda.nsp").unwrap(), tree.games().join("Zelda/zelda.nsp"));
+    // The leading slash is optional
+    assert_eq!(tree.roots.resolve("games/Zelda").unwrap(), tree.games().join("Zelda"));
+
+    let page = tree.roots.list("/", 0, 10).unwrap();
+    assert_eq!(page.to_payload(), "1\nD|games|0\n");
+    let page = tree.roots.list("/games", 0, 10).unwrap();
+    assert_eq!(page.to_payload(), "1\nD|Zelda|0\n");
+
+    assert!(matches!(tree.refused("/games/Zelda/missing.nsp"), BrowseError::NotFound(_)));
+    assert!(matches!(tree.refused("/other"), BrowseError::NotFound(_)));
+    assert!(matches!(BrowseRoots::new(&[]).resolve("/games").unwrap_err().downcast().unwrap(), BrowseError::Disabled));
+}
+
+#[test]
+fn parent_components_are_refused() {
+    let tree = Tree::new();
+    for path in ["/games/../private/keys.txt", "/games/Zelda/../../private", "/..", "../games", "/games/Zelda/.."] {
+        assert!(matches!(tree.refused(path), BrowseError::NotAllowed(_)), "{}", path);
+    }
+}
+
+#[test]
+fn absolute_host_paths_are_not_roots() {
+    let tree = Tree::new();
+    let keys = tree.dir.path().join("private/keys.txt");
+    // An absolute path is read as a root name followed by a subpath
+    assert!(tree.roots.resolve(&keys.to_string_lossy()).is_err());
+    assert!(tree.roots.resolve("/etc/passwd").is_err());
+    assert!(matches!(tree.refused("/"), BrowseError::NotAllowed(_)));
+}
+
+#[test]
+fn hidden_entries_cannot_be_opened() {
+    let tree = Tree::new();
+    for path in ["/games/.hidden.nsp", "/games/.cache", "/games/.cache/index"] {
+        assert!(matches!(tree.refused(path), BrowseError::NotAllowed(_)), "{}", path);
+    }
+    assert!(tree.roots.list("/games/.cache", 0, 10).is_err());
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) {
+    std::os::unix::fs::symlink(target, link).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_out_of_the_root_are_refused() {
+    let tree = Tree::new();
+    let games = tree.dir.path().join("games");
+    symlink(&tree.dir.path().join("private"), &games.join("escape"));
+    symlink(&tree.dir.path().join("private/keys.txt"), &games.join("keys.nsp"));
+    // A visible name leading to a hidden entry
+    symlink(&games.join(".hidden.nsp"), &games.join("visible.nsp"));
+    // Links that stay inside the root are fine
+    symlink(&games.join("Zelda"), &games.join("Link"));
+
+    for path in ["/games/escape", "/games/escape/keys.txt", "/games/keys.nsp", "/games/visible.nsp"] {
+        assert!(matches!(tree.refused(path), BrowseError::NotAllowed(_)), "{}", path);
+    }
+    assert!(tree.roots.list("/games/escape", 0, 10).is_err());
+    assert_eq!(tree.roots.resolve("/games/Link/zelda.nsp").unwrap(), tree.games().join("Zelda/zelda.nsp"));
+}
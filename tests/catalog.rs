@@ -0,0 +1,148 @@
+//! Title ID parsing and the grouped catalog served with LIST_CATALOG
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use switchlink_backend::catalog::{Catalog, Grouping};
+use switchlink_backend::database::Database;
+use switchlink_backend::title::{self, ContentType};
+
+const BASE: &str = "Mario [0100000000010000][v0].nsp";
+const UPDATE: &str = "Mario [0100000000010800][v131072].nsp";
+const DLC: &str = "Mario DLC [0100000000011001][v0].nsp";
+
+#[test]
+fn title_ids_are_read_from_brackets() {
+    assert_eq!(title::parse_title_id(BASE), Some(0x0100000000010000));
+    assert_eq!(title::parse_title_id("Game [v0][01007ef00011e000].xci"), Some(0x01007EF00011E000));
+    // Wrong length or not hex
+    assert_eq!(title::parse_title_id("Game [010000000001000].nsp"), None);
+    assert_eq!(title::parse_title_id("Game [010000000001000G].nsp"), None);
+    assert_eq!(title::parse_title_id("Game (0100000000010000).nsp"), None);
+
+    assert_eq!(title::parse_version(UPDATE), Some(131072));
+    assert_eq!(title::parse_version("Game [V2].nsp"), Some(2));
+    assert_eq!(title::parse_version("Game.nsp"), None);
+}
+
+#[test]
+fn content_types_follow_the_title_id() {
+    assert_eq!(title::content_type(BASE), ContentType::Base);
+    assert_eq!(title::content_type(UPDATE), ContentType::Update);
+    assert_eq!(title::content_type(DLC), ContentType::Dlc);
+    // Tags only count when there is no title ID
+    assert_eq!(title::content_type("Game [UPD].nsp"), ContentType::Update);
+    assert_eq!(title::content_type("Game [update].nsp"), ContentType::Update);
+    assert_eq!(title::content_type("Game [DLC].nsp"), ContentType::Dlc);
+    assert_eq!(title::content_type("Game [DLC][0100000000010000].nsp"), ContentType::Base);
+    assert_eq!(title::content_type("Game.nsp"), ContentType::Unknown);
+}
+
+#[test]
+fn dlc_maps_back_to_its_base_game() {
+    assert_eq!(title::base_title_id(0x0100000000010000), 0x0100000000010000);
+    assert_eq!(title::base_title_id(0x0100000000010800), 0x0100000000010000);
+    assert_eq!(title::base_title_id(0x0100000000011001), 0x0100000000010000);
+    assert_eq!(title::base_title_id(0x0100000000011FFF), 0x0100000000010000);
+    // Too small to come from a base game, but must not underflow
+    assert_eq!(title::base_title_id(0x0000000000000001), 0);
+    assert_eq!(title::base_title_id(0x0000000000000FFF), 0);
+}
+
+struct Library {
+    dir: tempfile::TempDir,
+    db: Database,
+    files: HashMap<String, PathBuf>,
+}
+
+impl Library {
+    fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(&dir.path().join("games.db")).unwrap();
+        Self { dir, db, files: HashMap::new() }
+    }
+
+    fn add(&mut self, folder: &str, name: &str) -> i64 {
+        let folder = self.dir.path().join(folder);
+        std::fs::create_dir_all(&folder).unwrap();
+        let path = folder.join(name);
+        std::fs::write(&path, name.as_bytes()).unwrap();
+        self.files.insert(name.to_string(), path.clone());
+        self.db.add_file(&path).unwrap()
+    }
+
+    fn groups(&self, grouping: Grouping, tag: Option<&str>) -> Vec<(String, Vec<String>)> {
+        Catalog::build(&self.files, Some(&self.db), grouping, tag)
+            .groups
+            .into_iter()
+            .map(|g| (g.name, g.entries.into_iter().map(|e| e.name).collect()))
+            .collect()
+    }
+}
+
+fn group(name: &str, entries: &[&str]) -> (String, Vec<String>) {
+    (name.to_string(), entries.iter().map(|e| e.to_string()).collect())
+}
+
+#[test]
+fn the_catalog_is_grouped_by_folder_tag_favorite_or_type() {
+    let mut lib = Library::new();
+    let base = lib.add("games", BASE);
+    lib.add("updates", UPDATE);
+    let dlc = lib.add("games", DLC);
+    lib.db.add_tag(base, "Party").unwrap();
+    lib.db.add_tag(base, "Mario").unwrap();
+    lib.db.add_tag(dlc, "Mario").unwrap();
+    lib.db.toggle_favorite(dlc).unwrap();
+
+    assert_eq!(lib.groups(Grouping::Root, None), [group("games", &[BASE, DLC]), group("updates", &[UPDATE])]);
+    assert_eq!(
+        lib.groups(Grouping::Tag, None),
+        [group("Mario", &[BASE, DLC]), group("Party", &[BASE]), group("Untagged", &[UPDATE])]
+    );
+    assert_eq!(lib.groups(Grouping::Favorites, None), [group("Favorites", &[DLC]), group("Other", &[BASE, UPDATE])]);
+    assert_eq!(
+        lib.groups(Grouping::ContentType, None),
+        [group("Base", &[BASE]), group("DLC", &[DLC]), group("Update", &[UPDATE])]
+    );
+    assert_eq!(lib.groups(Grouping::Root, Some(" party ")), [group("games", &[BASE])]);
+
+    let catalog = Catalog::build(&lib.files, Some(&lib.db), Grouping::ContentType, None);
+    let dlc = &catalog.groups[1].entries[0];
+    assert_eq!(dlc.title_id.as_deref(), Some("0100000000011001"));
+    assert!(dlc.favorite && dlc.id > 0);
+    assert_eq!(Grouping::from_u32(3), Grouping::ContentType);
+    assert_eq!(Grouping::from_u32(99), Grouping::Root);
+}
+
+#[test]
+fn files_outside_the_library_are_still_listed() {
+    let mut lib = Library::new();
+    lib.add("games", BASE);
+    let catalog = Catalog::build(&lib.files, None, Grouping::Tag, None);
+    assert_eq!(catalog.groups.len(), 1);
+    assert_eq!(catalog.groups[0].name, "Untagged");
+    let entry = &catalog.groups[0].entries[0];
+    assert_eq!((entry.id, entry.display_name.as_str(), entry.size), (0, "Mario", BASE.len() as u64));
+}
+
+#[test]
+fn root_groups_follow_library_roots() {
+    let mut lib = Library::new();
+    lib.add("nas/Switch/games", BASE);
+    lib.add("nas/Switch/updates", UPDATE);
+    lib.add("usb/games", DLC);
+    lib.add("loose", "Loose.nsp");
+    let nas = lib.dir.path().join("nas/Switch");
+    let usb = lib.dir.path().join("usb");
+    lib.db.add_root(&nas, None, None, "").unwrap();
+    lib.db.add_root(&usb, None, None, "").unwrap();
+
+    // Both roots have a games folder, and subfolders stay with their root
+    let mut expected = vec![
+        group(&nas.to_string_lossy(), &[BASE, UPDATE]),
+        group(&usb.to_string_lossy(), &[DLC]),
+        group("loose", &["Loose.nsp"]),
+    ];
+    expected.sort();
+    assert_eq!(lib.groups(Grouping::Root, None), expected);
+}
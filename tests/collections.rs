@@ -0,0 +1,123 @@
+//! Collections: ordering, editing, duplicating, export and the saved queue
+mod common;
+
+use std::path::PathBuf;
+
+use common::Library;
+use switchlink_backend::collection::{CollectionExport, ServedList, EXPORT_VERSION};
+use switchlink_backend::database::Database;
+
+impl Library {
+    /// A file on disk, not necessarily in the library
+    fn file(&self, name: &str) -> PathBuf {
+        self.write(name, name)
+    }
+
+    fn names(&self) -> Vec<(String, usize)> {
+        self.db
+            .get_collections()
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.name, c.item_count))
+            .collect()
+    }
+}
+
+#[test]
+fn collections_keep_order_and_drop_repeats() {
+    let lib = Library::new();
+    let (c, a, b) = (lib.file("C.nsp"), lib.file("A.nsp"), lib.file("B.nsp"));
+
+    lib.db.create_collection("  Lab   test set ", &[c.clone(), a.clone(), c.clone(), b.clone()]).unwrap();
+    assert_eq!(lib.names(), [("Lab test set".to_string(), 3)]);
+    assert_eq!(lib.db.get_collection_items("lab test set").unwrap(), [c.clone(), a.clone(), b.clone()]);
+
+    // Names are unique ignoring case
+    assert!(lib.db.create_collection("LAB TEST SET", &[]).is_err());
+    assert!(lib.db.create_collection(" ", &[]).is_err());
+
+    lib.db.set_collection_items("Lab test set", &[b.clone(), a.clone()]).unwrap();
+    assert_eq!(lib.db.get_collection_items("Lab test set").unwrap(), [b, a]);
+    assert!(lib.db.set_collection_items("Missing", &[]).is_err());
+}
+
+#[test]
+fn rename_duplicate_and_delete() {
+    let lib = Library::new();
+    let a = lib.file("A.nsp");
+    lib.db.create_collection("Family console", std::slice::from_ref(&a)).unwrap();
+    lib.db.create_collection("Updates Oct 2026", &[]).unwrap();
+
+    assert!(lib.db.rename_collection("Family console", "updates oct 2026").is_err());
+    lib.db.rename_collection("family console", "Family Console").unwrap();
+
+    lib.db.duplicate_collection("Family Console", "Kids").unwrap();
+    assert!(lib.db.duplicate_collection("Family Console", "kids").is_err());
+    lib.db.set_collection_items("Kids", &[]).unwrap();
+    // The copy is independent of the original
+    assert_eq!(lib.db.get_collection_items("Family Console").unwrap(), [a]);
+
+    lib.db.delete_collection("Family Console").unwrap();
+    assert_eq!(lib.names(), [("Kids".to_string(), 0), ("Updates Oct 2026".to_string(), 0)]);
+    assert!(lib.db.get_collection_items("Family Console").is_err());
+
+    // Names are matched as they were stored, with inner spaces collapsed
+    lib.db.delete_collection(" Updates   Oct 2026").unwrap();
+    assert_eq!(lib.names(), [("Kids".to_string(), 0)]);
+    lib.db.delete_collection("   ").unwrap();
+}
+
+#[test]
+fn served_list_skips_missing_files_and_repeated_names() {
+    let lib = Library::new();
+    let a = lib.file("A.nsp");
+    let other_a = lib.file("other/A.nsp");
+    let b = lib.file("B.nsp");
+    let gone = lib.dir.path().join("Gone.nsp");
+    lib.db.create_collection("Mixed", &[a.clone(), gone.clone(), other_a.clone(), b.clone()]).unwrap();
+
+    let list = ServedList::load(&lib.db, "mixed").unwrap();
+    assert_eq!(list.files.len(), 2);
+    assert_eq!(list.files["A.nsp"], a);
+    assert_eq!(list.files["B.nsp"], b);
+    assert_eq!(list.missing, [gone]);
+    assert_eq!(list.duplicates, [other_a]);
+}
+
+#[test]
+fn export_writes_versioned_json_with_library_metadata() {
+    let lib = Library::new();
+    let a = lib.file("A.nsp");
+    lib.db.add_file(&a).unwrap();
+    let gone = lib.dir.path().join("Gone.nsp");
+    lib.db.create_collection("Family/console", &[a.clone(), gone]).unwrap();
+
+    let export = CollectionExport::from_db(&lib.db, "family/console").unwrap();
+    assert_eq!(export.name, "Family/console");
+    assert_eq!(export.default_filename(), "Family_console.collection.json");
+
+    let out = lib.dir.path().join(export.default_filename());
+    export.write(&out).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&out).unwrap()).unwrap();
+    assert_eq!(json["version"], EXPORT_VERSION);
+    assert_eq!(json["items"][0]["filename"], "A.nsp");
+    assert_eq!(json["items"][0]["size"], 5);
+    assert_eq!(json["items"][0]["path"], a.to_string_lossy().as_ref());
+    assert!(json["items"][1]["size"].is_null());
+}
+
+#[test]
+fn queue_survives_reopening_the_database() {
+    let lib = Library::new();
+    let (b, a) = (lib.file("B.nsp"), lib.file("A.nsp"));
+    lib.db.save_queue(&[b.clone(), a.clone()]).unwrap();
+    lib.db.save_queue(&[b.clone(), a.clone()]).unwrap();
+
+    let db_path = lib.dir.path().join("games.db");
+    drop(lib.db);
+    let db = Database::new(&db_path).unwrap();
+    assert_eq!(db.load_queue().unwrap(), [b, a]);
+
+    db.save_queue(&[]).unwrap();
+    assert!(db.load_queue().unwrap().is_empty());
+}
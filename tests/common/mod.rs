@@ -0,0 +1,256 @@
+//! Shared test helpers: captured client traffic replayed against the server, and temporary libraries
+#![allow(dead_code)]
+
+use anyhow::Result;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use switchlink_backend::config::Config;
+use switchlink_backend::database::Database;
+use switchlink_backend::usb::{SwitchLinkServer, Transport};
+
+/// What the client does when the server reads
+#[derive(Debug, Clone)]
+pub enum ClientStep {
+    Send(Vec<u8>),
+    /// The client sends nothing; the read times out
+    Silence,
+}
+
+/// A capture: client steps, each with the server output expected before it
+#[derive(Debug, Clone, Default)]
+pub struct Capture {
+    pub steps: Vec<(Vec<u8>, ClientStep)>,
+    pub trailing_output: Vec<u8>,
+}
+
+impl Capture {
+    /// Parse a `.hex` capture: `> hex` is sent by the client, `< hex` is expected
+    /// from the server, `> timeout` means the client stays silent; `#` starts a comment
+    pub fn parse(text: &str) -> Self {
+        let mut capture = Capture::default();
+        let mut pending_output = Vec::new();
+
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (direction, data) = line.split_at(1);
+            let data = data.trim();
+            match direction {
+                ">" if data == "timeout" => {
+                    capture
+                        .steps
+                        .push((std::mem::take(&mut pending_output), ClientStep::Silence));
+                }
+                ">" => {
+                    capture
+                        .steps
+                        .push((std::mem::take(&mut pending_output), ClientStep::Send(decode_hex(data))));
+                }
+                "<" => pending_output.extend(decode_hex(data)),
+                _ => panic!("line {}: expected '>' or '<'", line_no + 1),
+            }
+        }
+
+        capture.trailing_output = pending_output;
+        capture
+    }
+
+    pub fn load(path: &Path) -> Self {
+        let text = std::fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("cannot read {}: {}", path.display(), e));
+        Self::parse(&text)
+    }
+}
+
+fn decode_hex(data: &str) -> Vec<u8> {
+    let digits: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+    assert!(digits.len().is_multiple_of(2), "odd number of hex digits: {}", data);
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).expect("invalid hex"))
+        .collect()
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Transport that feeds the server the client side of a capture and checks its replies
+pub struct ReplayTransport {
+    steps: Mutex<VecDeque<(Vec<u8>, ClientStep)>>,
+    written: Arc<Mutex<Vec<u8>>>,
+    expected: Mutex<Vec<u8>>,
+}
+
+impl ReplayTransport {
+    pub fn new(capture: &Capture) -> (Self, Arc<Mutex<Vec<u8>>>) {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let transport = Self {
+            steps: Mutex::new(capture.steps.iter().cloned().collect()),
+            written: written.clone(),
+            expected: Mutex::new(Vec::new()),
+        };
+        (transport, written)
+    }
+
+    fn next_step(&self, size: usize) -> Result<Vec<u8>> {
+        let (output_before, step) = self
+            .steps
+            .lock()
+            .unwrap()
+            .pop_front()
+            .expect("server read past the end of the capture");
+
+        // Everything the server should have sent before this client step must match
+        let mut expected = self.expected.lock().unwrap();
+        expected.extend(output_before);
+        let written = self.written.lock().unwrap();
+        assert_eq!(
+            to_hex(&written),
+            to_hex(&expected),
+            "server output diverged from the capture"
+        );
+
+        match step {
+            ClientStep::Send(data) => {
+                assert!(
+                    data.len() <= size,
+                    "client sent {} bytes but the server only read {}",
+                    data.len(),
+                    size
+                );
+                Ok(data)
+            }
+            ClientStep::Silence => Err(rusb::Error::Timeout.into()),
+        }
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn read(&self, size: usize) -> Result<Vec<u8>> {
+        self.next_step(size)
+    }
+
+    fn read_with_long_timeout(&self, size: usize) -> Result<Vec<u8>> {
+        self.next_step(size)
+    }
+
+    fn write(&self, data: &[u8]) -> Result<usize> {
+        self.written.lock().unwrap().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn write_with_long_timeout(&self, data: &[u8]) -> Result<usize> {
+        self.write(data)
+    }
+}
+
+/// A temporary library with known files for the captures to request
+pub struct Fixture {
+    pub dir: tempfile::TempDir,
+    pub files: HashMap<String, PathBuf>,
+    /// Paths the server must refuse, as if they failed verification
+    pub refused: HashSet<PathBuf>,
+}
+
+impl Fixture {
+    /// `game.nsp` holds bytes 0..32, `demo.nsz` holds 10 bytes
+    pub fn standard() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let mut files = HashMap::new();
+
+        let game = dir.path().join("game.nsp");
+        std::fs::write(&game, (0u8..32).collect::<Vec<_>>()).unwrap();
+        files.insert("game.nsp".to_string(), game);
+
+        let demo = dir.path().join("demo.nsz");
+        std::fs::write(&demo, [0xAA; 10]).unwrap();
+        files.insert("demo.nsz".to_string(), demo);
+
+        Self { dir, files, refused: HashSet::new() }
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            dir: tempfile::tempdir().unwrap(),
+            files: HashMap::new(),
+            refused: HashSet::new(),
+        }
+    }
+
+    pub fn config(&self) -> Config {
+        Config {
+            db_path: self.dir.path().join("games.db"),
+            inbox_dir: self.dir.path().join("inbox"),
+            saves_dir: self.dir.path().join("saves"),
+            ..Config::default()
+        }
+    }
+}
+
+/// Replay a capture against a server serving the fixture's files
+pub fn replay(capture: &Capture, fixture: &Fixture) {
+    replay_with_config(capture, fixture, fixture.config());
+}
+
+/// Replay a capture with the fixture's files but a caller-adjusted config
+pub fn replay_with_config(capture: &Capture, fixture: &Fixture, config: Config) {
+    let (transport, written) = ReplayTransport::new(capture);
+    let file_list = Arc::new(Mutex::new(fixture.files.clone()));
+
+    let mut server = SwitchLinkServer::new(file_list)
+        .with_config(config)
+        .with_refused(Arc::new(Mutex::new(fixture.refused.clone())))
+        .with_transport(Box::new(transport));
+    server.start().expect("server returned an error");
+
+    let mut expected: Vec<u8> = capture
+        .steps
+        .iter()
+        .flat_map(|(output, _)| output.clone())
+        .collect();
+    expected.extend(&capture.trailing_output);
+    assert_eq!(
+        to_hex(&written.lock().unwrap()),
+        to_hex(&expected),
+        "server output diverged from the capture"
+    );
+}
+
+/// A database in a temporary folder, with files written next to it
+pub struct Library {
+    pub dir: tempfile::TempDir,
+    pub db: Database,
+}
+
+impl Library {
+    pub fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(&dir.path().join("games.db")).unwrap();
+        Self { dir, db }
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    /// Write a file, creating the folders it goes in
+    pub fn write(&self, name: &str, content: impl AsRef<[u8]>) -> PathBuf {
+        let path = self.path(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    /// The `games` folder, created on first use
+    pub fn games(&self) -> PathBuf {
+        let games = self.path("games");
+        std::fs::create_dir_all(&games).unwrap();
+        games
+    }
+}
@@ -0,0 +1,100 @@
+//! Compatibility matrix: captured sessions of each supported client replayed against the server
+mod common;
+
+use common::{replay, replay_with_config, Capture, Fixture};
+use std::path::PathBuf;
+
+use switchlink_backend::database::Database;
+
+fn capture(name: &str) -> Capture {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("captures")
+        .join(name);
+    Capture::load(&path)
+}
+
+#[test]
+fn dbi_legacy_list_old_with_ack() {
+    replay(&capture("dbi_list_old_ack.hex"), &Fixture::standard());
+}
+
+#[test]
+fn dbi_legacy_list_old_without_ack() {
+    replay(&capture("dbi_list_old_no_ack.hex"), &Fixture::standard());
+}
+
+#[test]
+fn dbi_legacy_list_old_late_ack_is_ignored() {
+    replay(&capture("dbi_list_old_late_ack.hex"), &Fixture::standard());
+}
+
+#[test]
+fn dbi_legacy_list_old_empty_queue() {
+    replay(&capture("empty_list_old.hex"), &Fixture::empty());
+}
+
+#[test]
+fn dbi_list_and_file_range() {
+    replay(&capture("dbi_list_file_range.hex"), &Fixture::standard());
+}
+
+#[test]
+fn switchlink_list_and_file_range_past_eof() {
+    replay(&capture("switchlink_list_file_range.hex"), &Fixture::standard());
+}
+
+#[test]
+fn file_ranges_served_from_mapping() {
+    for name in ["dbi_list_file_range.hex", "switchlink_list_file_range.hex"] {
+        let fixture = Fixture::standard();
+        let mut config = fixture.config();
+        config.transfer.mmap = true;
+        replay_with_config(&capture(name), &fixture, config);
+    }
+}
+
+#[test]
+fn unknown_command_is_ignored() {
+    replay(&capture("unknown_command.hex"), &Fixture::standard());
+}
+
+#[test]
+fn switchlink_ping_reports_client_state() {
+    replay(&capture("switchlink_ping.hex"), &Fixture::standard());
+}
+
+#[test]
+fn switchlink_status_reports_install_outcome() {
+    let fixture = Fixture::standard();
+    replay(&capture("switchlink_status.hex"), &fixture);
+
+    // Only the final outcome reaches the install history
+    let history = Database::new(&fixture.config().db_path).unwrap().get_install_history(10).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!((history[0].status.as_str(), history[0].result_code), ("failed", 0x410));
+}
+
+#[test]
+fn missing_file_is_recoverable() {
+    replay(&capture("missing_file.hex"), &Fixture::standard());
+}
+
+#[test]
+fn refused_files_are_not_served_by_name_or_browse_path() {
+    let mut fixture = Fixture::standard();
+    let browsed = fixture.dir.path().join("library");
+    std::fs::create_dir(&browsed).unwrap();
+    std::fs::copy(&fixture.files["game.nsp"], browsed.join("game.nsp")).unwrap();
+    fixture.refused.insert(fixture.files["game.nsp"].clone());
+    fixture.refused.insert(browsed.join("game.nsp"));
+
+    let mut config = fixture.config();
+    config.browse_roots = vec![browsed];
+    replay_with_config(&capture("refused_file.hex"), &fixture, config);
+}
+
+#[test]
+fn malformed_payload_is_recoverable() {
+    replay(&capture("malformed_payload.hex"), &Fixture::standard());
+}
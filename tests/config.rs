@@ -0,0 +1,85 @@
+//! Settings from the config file and the command line
+use switchlink_backend::config::Config;
+use switchlink_backend::usb::{DeviceProfile, UsbId};
+
+fn usb_id(text: &str) -> Result<UsbId, serde_json::Error> {
+    serde_json::from_value(serde_json::Value::String(text.to_string()))
+}
+
+#[test]
+fn usb_ids_are_hex_with_or_without_a_prefix() {
+    let switch = UsbId { vendor_id: 0x057E, product_id: 0x3000 };
+    for text in ["057E:3000", "057e:3000", "0x057E:0x3000", "0X057e:3000", " 057E : 3000 "] {
+        assert_eq!(usb_id(text).unwrap(), switch, "{:?}", text);
+    }
+    assert_eq!(serde_json::to_string(&switch).unwrap(), r#""057E:3000""#);
+
+    for text in ["057E3000", "057E:", ":3000", "0x:3000", "057E:+300", "057E:30000", "GGGG:3000", ""] {
+        assert!(usb_id(text).is_err(), "{:?}", text);
+    }
+}
+
+#[test]
+fn device_profiles_read_from_json() {
+    let profile: DeviceProfile =
+        serde_json::from_str(r#"{"ids": ["0x16C0:0x27DD", "057E:3000"], "interface": 1}"#).unwrap();
+    assert_eq!(profile.ids[0], UsbId { vendor_id: 0x16C0, product_id: 0x27DD });
+    assert_eq!(profile.interface, 1);
+    assert!(profile.reset);
+
+    let error = serde_json::from_str::<Config>(r#"{"usb": {"ids": ["nope"]}}"#).unwrap_err();
+    assert!(error.to_string().contains("VID:PID"), "{}", error);
+}
+
+fn args(text: &str) -> Vec<String> {
+    text.split_whitespace().map(String::from).collect()
+}
+
+#[test]
+fn command_line_flags_override_the_config() {
+    let mut config = Config::default();
+    config
+        .apply_args(&args("--cli --data-timeout-ms 20000 --retries=5 --backoff-ms=50 --queue-depth 4 --mmap serve"))
+        .unwrap();
+    assert_eq!(config.transfer.data_timeout_ms, 20_000);
+    assert_eq!(config.transfer.retries, 5);
+    assert_eq!(config.transfer.backoff_ms, 50);
+    assert_eq!(config.transfer.queue_depth, 4);
+    assert!(config.transfer.mmap);
+
+    // The last one wins
+    config.apply_args(&args("--no-mmap --limit-mbps=none --limit-mbps 12.5")).unwrap();
+    assert!(!config.transfer.mmap);
+    assert_eq!(config.bandwidth.limit_mbps, Some(12.5));
+}
+
+#[test]
+fn bad_flag_values_are_refused() {
+    for text in [
+        "--retries",
+        "--retries many",
+        "--retries=-1",
+        "--data-timeout-ms=",
+        "--poll-timeout-ms 1.5",
+        "--queue-depth=4k",
+        "--limit-mbps -3",
+        "--schedule 09:00-17:00",
+    ] {
+        let mut config = Config::default();
+        assert!(config.apply_args(&args(text)).is_err(), "{}", text);
+    }
+
+    let error = Config::default().apply_args(&args("--retries=lots")).unwrap_err();
+    assert_eq!(error.to_string(), r#"--retries expects a number, got "lots""#);
+}
+
+#[test]
+fn backoff_doubles_up_to_the_cap() {
+    let mut config = Config::default();
+    config.apply_args(&args("--backoff-ms 100 --max-backoff-ms 1000")).unwrap();
+    let delays: Vec<u128> = (1..=6).map(|attempt| config.transfer.backoff(attempt).as_millis()).collect();
+    assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+    // Far past the cap, without overflowing
+    assert_eq!(config.transfer.backoff(u32::MAX).as_millis(), 1000);
+    assert_eq!(config.transfer.backoff(0).as_millis(), 100);
+}
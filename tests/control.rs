@@ -0,0 +1,146 @@
+//! Control NCA extraction: decryption, NACP parsing, icons and key errors
+mod common;
+mod packages;
+
+use std::path::{Path, PathBuf};
+
+use common::Library;
+use packages::{game_nsp, hex, keys, nacp, nca, pfs0, romfs, ticket, HEADER_KEY, ICON, TITLE_ID};
+use switchlink_backend::control::{extract_library, read_control};
+use switchlink_backend::keys::KeySet;
+use switchlink_backend::nca::NcaError;
+
+impl Library {
+    fn add(&self, name: &str, content: &[u8]) -> i64 {
+        self.db.add_file(&self.write(name, content)).unwrap()
+    }
+
+    fn file(&self, id: i64) -> switchlink_backend::database::GameFile {
+        self.db.get_files().unwrap().into_iter().find(|f| f.id == id).unwrap()
+    }
+
+    fn icons(&self) -> PathBuf {
+        self.path("icons")
+    }
+}
+
+#[test]
+fn reads_names_version_and_icon() {
+    let lib = Library::new();
+    let path = lib.write("Game.nsp", game_nsp(TITLE_ID));
+
+    let control = read_control(&path, &keys()).unwrap();
+    assert_eq!(control.title_id, "0100AAAA00000000");
+    assert_eq!(control.display_version, "1.2.0");
+    assert_eq!(control.titles.len(), 2);
+    assert_eq!(control.titles[0].language, "AmericanEnglish");
+    assert_eq!(control.titles[0].name, "Test Game");
+    assert_eq!(control.titles[0].publisher, "Test Publisher");
+    assert_eq!(control.titles[1].language, "Japanese");
+    assert_eq!(control.titles[1].name, "テストゲーム");
+    assert_eq!(control.icon.as_deref(), Some(ICON));
+}
+
+#[test]
+fn title_keys_come_from_the_ticket() {
+    let lib = Library::new();
+    let rights_id = [0x42; 16];
+    let romfs = romfs(&[("control.nacp", &nacp(&[(0, "eShop Game", "Pub")], "2.0.0"))]);
+    let nca = nca(2, TITLE_ID, &romfs, Some(rights_id));
+
+    let with_ticket = lib.write(
+        "eShop.nsp",
+        pfs0(&[
+            ("00000000000000000000000000000001.nca".to_string(), nca.clone()),
+            (format!("{}.tik", hex(&rights_id)), ticket(rights_id)),
+        ]),
+    );
+    let control = read_control(&with_ticket, &keys()).unwrap();
+    assert_eq!(control.titles[0].name, "eShop Game");
+    assert_eq!(control.icon, None);
+
+    let without_ticket = lib.write("NoTicket.nsp", pfs0(&[("00000000000000000000000000000001.nca".to_string(), nca)]));
+    assert!(matches!(read_control(&without_ticket, &keys()), Err(NcaError::MissingTicket(id)) if id == hex(&rights_id)));
+}
+
+#[test]
+fn missing_and_wrong_keys_are_named() {
+    let lib = Library::new();
+    let path = lib.write("Game.nsp", game_nsp(TITLE_ID));
+
+    let no_file = KeySet::load(&lib.dir.path().join("prod.keys")).unwrap_err();
+    assert!(matches!(no_file, NcaError::NoKeys(_)));
+
+    let empty = read_control(&path, &KeySet::parse("")).unwrap_err();
+    assert_eq!(empty.to_string(), "header_key is missing from prod.keys");
+
+    let wrong_header = KeySet::parse(&format!("header_key = {}", hex(&[0x01; 32])));
+    let e = read_control(&path, &wrong_header).unwrap_err();
+    assert!(matches!(&e, NcaError::WrongKey(name) if name == "header_key"));
+    assert!(e.is_key_error());
+
+    let wrong_key_area = KeySet::parse(&format!(
+        "header_key = {}\nkey_area_key_application_00 = {}",
+        hex(&HEADER_KEY),
+        hex(&[0x02; 16])
+    ));
+    let e = read_control(&path, &wrong_key_area).unwrap_err();
+    assert!(matches!(&e, NcaError::WrongKey(name) if name == "key_area_key_application_00"));
+
+    let short = KeySet::parse(&format!("header_key = {}", hex(&HEADER_KEY[..16])));
+    assert!(matches!(read_control(&path, &short), Err(NcaError::InvalidKey(_))));
+}
+
+#[test]
+fn extraction_enriches_the_library() {
+    let lib = Library::new();
+    let game = lib.add("game [0100AAAA00000000][v0].nsp", &game_nsp(TITLE_ID));
+    let dlc = lib.add("dlc [0100AAAA00001001][v0].nsp", &pfs0(&[]));
+    let broken = lib.add("broken [0100BBBB00000000][v0].nsp", b"not an nsp");
+
+    let summary = extract_library(&lib.db, &keys(), &lib.icons(), false, |_, _| {}).unwrap();
+    assert_eq!(summary.extracted, 1);
+    assert_eq!(summary.without_control, 1);
+    assert_eq!(summary.failed.len(), 1);
+    assert_eq!(summary.failed[0].0, "broken [0100BBBB00000000][v0].nsp");
+
+    let file = lib.file(game);
+    assert_eq!(file.display_name, "Test Game");
+    let icon = lib.icons().join("0100AAAA00000000.jpg");
+    assert_eq!(file.icon_path.as_deref().map(Path::new), Some(icon.as_path()));
+    assert_eq!(std::fs::read(&icon).unwrap(), ICON);
+    // DLC has no control data of its own and borrows the base game's
+    assert_eq!(lib.file(dlc).display_name, "Test Game");
+    assert_eq!(lib.file(dlc).icon_path, file.icon_path);
+    let mut found: Vec<i64> = lib.db.search("publisher").unwrap().iter().map(|f| f.id).collect();
+    found.sort();
+    assert_eq!(found, [game, dlc]);
+
+    let info = lib.db.get_control_info(game).unwrap().unwrap();
+    assert_eq!(info.display_version, "1.2.0");
+    assert_eq!(info.titles[1].language, "Japanese");
+    assert!(lib.db.get_control_error(broken).unwrap().is_some());
+
+    // Nothing changed, so nothing is read again; failures wait for a retry
+    let again = extract_library(&lib.db, &keys(), &lib.icons(), false, |_, _| {}).unwrap();
+    assert_eq!(again.extracted + again.without_control + again.failed.len(), 0);
+    let retried = extract_library(&lib.db, &keys(), &lib.icons(), true, |_, _| {}).unwrap();
+    assert_eq!(retried.failed.len() + retried.without_control, 2);
+}
+
+#[test]
+fn key_problems_are_summarized_not_fatal() {
+    let lib = Library::new();
+    let game = lib.add("game [0100AAAA00000000][v0].nsp", &game_nsp(TITLE_ID));
+    lib.add("other [0100CCCC00000000][v0].nsp", &game_nsp(0x0100_CCCC_0000_0000));
+
+    let summary = extract_library(&lib.db, &KeySet::parse(""), &lib.icons(), false, |_, _| {}).unwrap();
+    assert_eq!(summary.extracted, 0);
+    assert_eq!(summary.key_errors.get("header_key is missing from prod.keys"), Some(&2));
+    assert_eq!(lib.file(game).display_name, "game");
+
+    // Adding the keys later picks the files up again
+    let summary = extract_library(&lib.db, &keys(), &lib.icons(), true, |_, _| {}).unwrap();
+    assert_eq!(summary.extracted, 2);
+    assert_eq!(lib.file(game).display_name, "Test Game");
+}
@@ -0,0 +1,61 @@
+//! Sharing the queue between consoles: device IDs, pins and withheld files
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use switchlink_backend::config::Config;
+use switchlink_backend::devices::DeviceManager;
+use switchlink_backend::usb::DeviceInfo;
+
+fn console(ports: &[u8], address: u8, serial: Option<&str>) -> DeviceInfo {
+    DeviceInfo { bus: 1, ports: ports.to_vec(), address, serial: serial.map(String::from) }
+}
+
+#[test]
+fn consoles_are_identified_by_port() {
+    let left = console(&[2], 5, Some("XAW1000"));
+    let right = console(&[2, 3], 6, None);
+    assert_eq!(left.id(), "1-2");
+    assert_eq!(right.id(), "1-2.3");
+    assert_eq!(left.label(), "XAW1000 (1-2)");
+    assert_eq!(right.label(), "1-2.3");
+
+    // Replugged into the same port: same ID, but a new attachment
+    let replugged = console(&[2], 9, Some("XAW1000"));
+    assert_eq!(replugged.id(), left.id());
+    assert!(!replugged.is_same_device(&left));
+    assert!(console(&[2], 5, None).is_same_device(&left));
+}
+
+fn names(queue: &HashMap<String, PathBuf>) -> Vec<&str> {
+    let mut names: Vec<&str> = queue.keys().map(String::as_str).collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn pinned_and_withheld_files_are_kept_from_other_consoles() {
+    let left = console(&[2], 5, None).id();
+    let right = console(&[3], 6, None).id();
+    let catalog: HashMap<String, PathBuf> = ["a.nsp", "b.nsp", "c.nsp", "d.nsp"]
+        .into_iter()
+        .map(|name| (name.to_string(), PathBuf::from("/games").join(name)))
+        .collect();
+
+    let mut devices = DeviceManager::new(Config::default());
+    devices.set_catalog(&catalog);
+    assert_eq!(names(&devices.queue_for(&left)), ["a.nsp", "b.nsp", "c.nsp", "d.nsp"]);
+
+    devices.pin("a.nsp", Some(left.clone()));
+    devices.pin("b.nsp", Some(right.clone()));
+    devices.set_withheld(HashSet::from([PathBuf::from("/games/c.nsp")]));
+    assert_eq!(devices.pinned_to("a.nsp"), Some(left.as_str()));
+    assert_eq!(names(&devices.queue_for(&left)), ["a.nsp", "d.nsp"]);
+    assert_eq!(names(&devices.queue_for(&right)), ["b.nsp", "d.nsp"]);
+    // A console nobody pinned to gets the unpinned files only
+    assert_eq!(names(&devices.queue_for("2-1")), ["d.nsp"]);
+
+    devices.pin("a.nsp", None);
+    devices.set_withheld(HashSet::new());
+    assert_eq!(names(&devices.queue_for(&right)), ["a.nsp", "b.nsp", "c.nsp", "d.nsp"]);
+    assert_eq!(names(&devices.queue_for(&left)), ["a.nsp", "c.nsp", "d.nsp"]);
+}
@@ -0,0 +1,183 @@
+//! Emulated 057E:3000 console on dummy_hcd + FunctionFS, for exercising `UsbConnection`
+//!
+//! `tests/gadget/setup.sh` creates the gadget and prints the environment
+//! variables below. Each test writes the FunctionFS descriptors, binds the
+//! gadget to the dummy UDC and plays the client on the device-side endpoints.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use switchlink_backend::protocol::CommandHeader;
+use switchlink_backend::usb::{DeviceProfile, UsbConnection};
+
+// FunctionFS blob magics and flags (linux/usb/functionfs.h)
+const FUNCTIONFS_DESCRIPTORS_MAGIC_V2: u32 = 3;
+const FUNCTIONFS_STRINGS_MAGIC: u32 = 2;
+const FUNCTIONFS_HAS_FS_DESC: u32 = 1;
+const FUNCTIONFS_HAS_HS_DESC: u32 = 2;
+
+// Only one gadget exists, so tests using it take turns
+static GADGET_LOCK: Mutex<()> = Mutex::new(());
+
+/// A bound gadget; unbinds when dropped
+pub struct Gadget {
+    gadget_dir: PathBuf,
+    ffs_dir: PathBuf,
+    _ep0: File,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl Gadget {
+    /// Bind the gadget prepared by `setup.sh`, or `None` if it isn't set up
+    pub fn bind() -> Option<Self> {
+        let gadget_dir = PathBuf::from(std::env::var_os("SWITCHLINK_GADGET")?);
+        let ffs_dir = PathBuf::from(std::env::var_os("SWITCHLINK_GADGET_FFS")?);
+        let guard = GADGET_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut ep0 = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(ffs_dir.join("ep0"))
+            .expect("cannot open FunctionFS ep0 (run setup.sh as root)");
+        ep0.write_all(&descriptors()).expect("FunctionFS rejected the descriptors");
+        ep0.write_all(&strings()).expect("FunctionFS rejected the strings");
+
+        let udc = std::fs::read_dir("/sys/class/udc")
+            .expect("no UDCs (is dummy_hcd loaded?)")
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .find(|name| name.starts_with("dummy_udc"))
+            .expect("dummy_udc not found");
+        std::fs::write(gadget_dir.join("UDC"), &udc).expect("cannot bind the gadget");
+
+        let gadget = Self {
+            gadget_dir,
+            ffs_dir,
+            _ep0: ep0,
+            _guard: guard,
+        };
+        gadget.wait_for_host();
+        Some(gadget)
+    }
+
+    /// Wait until libusb sees the emulated console
+    fn wait_for_host(&self) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if UsbConnection::enumerate(&DeviceProfile::default(), &[]).is_ok_and(|d| !d.is_empty()) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        panic!("the gadget never showed up on the host");
+    }
+
+    /// Device-side endpoints, to script the client with
+    pub fn client(&self) -> GadgetClient {
+        let open = |name: &str| {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(self.ffs_dir.join(name))
+                .unwrap_or_else(|e| panic!("cannot open {}: {}", name, e))
+        };
+        GadgetClient {
+            to_host: open("ep1"),
+            from_host: open("ep2"),
+        }
+    }
+
+    /// Simulate unplugging the console
+    pub fn unplug(&self) {
+        std::fs::write(self.gadget_dir.join("UDC"), "\n").expect("cannot unbind the gadget");
+    }
+}
+
+impl Drop for Gadget {
+    fn drop(&mut self) {
+        let _ = std::fs::write(self.gadget_dir.join("UDC"), "\n");
+    }
+}
+
+/// The Switch side of the emulated console
+pub struct GadgetClient {
+    /// Bulk IN endpoint as seen by the host
+    to_host: File,
+    /// Bulk OUT endpoint as seen by the host
+    from_host: File,
+}
+
+impl GadgetClient {
+    pub fn send(&mut self, data: &[u8]) {
+        self.to_host.write_all(data).expect("bulk IN write failed");
+    }
+
+    pub fn send_header(&mut self, cmd_type: u32, cmd_id: u32, data_size: u32) {
+        self.send(&CommandHeader::new(cmd_type, cmd_id, data_size).to_bytes());
+    }
+
+    /// Receive one transfer of at most `max` bytes
+    pub fn recv(&mut self, max: usize) -> Vec<u8> {
+        let mut buf = vec![0u8; max];
+        let n = self.from_host.read(&mut buf).expect("bulk OUT read failed");
+        buf.truncate(n);
+        buf
+    }
+
+    pub fn recv_exact(&mut self, size: usize) -> Vec<u8> {
+        let mut data = Vec::with_capacity(size);
+        while data.len() < size {
+            let chunk = self.recv(size - data.len());
+            assert!(!chunk.is_empty(), "host stopped sending");
+            data.extend(chunk);
+        }
+        data
+    }
+
+    pub fn recv_header(&mut self) -> CommandHeader {
+        CommandHeader::from_bytes(&self.recv(16)).expect("host sent an invalid header")
+    }
+}
+
+/// One vendor-specific interface with a bulk IN and a bulk OUT endpoint, at full and high speed
+fn descriptors() -> Vec<u8> {
+    let interface = [9u8, 4, 0, 0, 2, 0xFF, 0xFF, 0xFF, 1];
+    let endpoints = |max_packet: u16| {
+        let [lo, hi] = max_packet.to_le_bytes();
+        [[7u8, 5, 0x81, 2, lo, hi, 0], [7u8, 5, 0x02, 2, lo, hi, 0]]
+    };
+
+    let mut body = Vec::new();
+    for max_packet in [64, 512] {
+        body.extend(interface);
+        for endpoint in endpoints(max_packet) {
+            body.extend(endpoint);
+        }
+    }
+
+    let mut blob = Vec::new();
+    blob.extend(FUNCTIONFS_DESCRIPTORS_MAGIC_V2.to_le_bytes());
+    blob.extend((20 + body.len() as u32).to_le_bytes());
+    blob.extend((FUNCTIONFS_HAS_FS_DESC | FUNCTIONFS_HAS_HS_DESC).to_le_bytes());
+    blob.extend(3u32.to_le_bytes());
+    blob.extend(3u32.to_le_bytes());
+    blob.extend(body);
+    blob
+}
+
+fn strings() -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend(0x0409u16.to_le_bytes());
+    body.extend(b"SwitchLink test\0");
+
+    let mut blob = Vec::new();
+    blob.extend(FUNCTIONFS_STRINGS_MAGIC.to_le_bytes());
+    blob.extend((16 + body.len() as u32).to_le_bytes());
+    blob.extend(1u32.to_le_bytes());
+    blob.extend(1u32.to_le_bytes());
+    blob.extend(body);
+    blob
+}
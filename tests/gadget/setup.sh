@@ -0,0 +1,30 @@
+#!/bin/sh
+# Create a 057E:3000 USB gadget on dummy_hcd, backed by FunctionFS.
+# Run as root; prints the environment the gadget tests need.
+set -eu
+
+GADGET=/sys/kernel/config/usb_gadget/switchlink-test
+FFS=/dev/ffs-switchlink
+
+modprobe libcomposite
+modprobe dummy_hcd
+mountpoint -q /sys/kernel/config || mount -t configfs none /sys/kernel/config
+
+mkdir -p "$GADGET"
+cd "$GADGET"
+echo 0x057e > idVendor
+echo 0x3000 > idProduct
+mkdir -p strings/0x409
+echo "SWLKTEST0001" > strings/0x409/serialnumber
+echo "Nintendo Co., Ltd." > strings/0x409/manufacturer
+echo "Nintendo Switch (test gadget)" > strings/0x409/product
+
+mkdir -p configs/c.1 functions/ffs.switchlink
+[ -e configs/c.1/ffs.switchlink ] || ln -s functions/ffs.switchlink configs/c.1/
+
+mkdir -p "$FFS"
+mountpoint -q "$FFS" || mount -t functionfs switchlink "$FFS"
+
+# The tests write the FunctionFS descriptors and bind the gadget themselves
+echo "export SWITCHLINK_GADGET=$GADGET"
+echo "export SWITCHLINK_GADGET_FFS=$FFS"
@@ -0,0 +1,14 @@
+#!/bin/sh
+# Remove the gadget created by setup.sh
+set -u
+
+GADGET=/sys/kernel/config/usb_gadget/switchlink-test
+FFS=/dev/ffs-switchlink
+
+[ -d "$GADGET" ] || exit 0
+echo "" > "$GADGET/UDC" 2>/dev/null
+umount "$FFS" 2>/dev/null
+rm -f "$GADGET/configs/c.1/ffs.switchlink"
+rmdir "$GADGET/configs/c.1" "$GADGET/functions/ffs.switchlink" "$GADGET/strings/0x409" "$GADGET"
+rmdir "$FFS" 2>/dev/null
+exit 0
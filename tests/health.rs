@@ -0,0 +1,163 @@
+//! Library health: hashing, duplicates, NSP/NSZ copies, superseded updates and trashing
+mod common;
+
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use common::Library;
+use switchlink_backend::database::Database;
+use switchlink_backend::health::{move_to_trash, HealthReport};
+
+impl Library {
+    /// Add a file with the given content, modified `age` seconds ago
+    fn add(&self, name: &str, content: &[u8], age: u64) -> PathBuf {
+        let path = self.write(name, content);
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(age)).unwrap();
+        self.db.add_file(&path).unwrap();
+        path
+    }
+
+    fn report(&self) -> HealthReport {
+        self.db.update_hashes(|_, _| {}).unwrap();
+        HealthReport::from_db(&self.db).unwrap()
+    }
+}
+
+fn names(files: &[switchlink_backend::database::GameFile]) -> Vec<String> {
+    files.iter().map(|f| f.path.rsplit(['/', '\\']).next().unwrap().to_string()).collect()
+}
+
+#[test]
+fn only_files_sharing_a_size_are_hashed() {
+    let lib = Library::new();
+    lib.add("a/Game.nsp", b"same", 100);
+    lib.add("b/Game.nsp", b"same", 50);
+    lib.add("Other.nsp", b"different size", 10);
+
+    assert_eq!(lib.db.update_hashes(|_, _| {}).unwrap().hashed, 2);
+    // Unchanged files keep their hash
+    assert_eq!(lib.db.update_hashes(|_, _| {}).unwrap().hashed, 0);
+
+    let hashed: Vec<bool> = lib.db.get_files().unwrap().iter().map(|f| f.hash.is_some()).collect();
+    assert_eq!(hashed.iter().filter(|h| **h).count(), 2);
+}
+
+#[test]
+fn unreadable_files_are_skipped() {
+    let lib = Library::new();
+    lib.add("a/Game.nsp", b"same", 100);
+    lib.add("b/Game.nsp", b"same", 50);
+    lib.add("c/Game.nsp", b"same", 10);
+    lib.db.update_hashes(|_, _| {}).unwrap();
+
+    // Replaced by something that can't be read as a file
+    let broken = lib.path("b/Game.nsp");
+    std::fs::remove_file(&broken).unwrap();
+    std::fs::create_dir(&broken).unwrap();
+    lib.add("d/Game.nsp", b"same", 5);
+
+    let mut progress = Vec::new();
+    let summary = lib.db.update_hashes(|done, total| progress.push((done, total))).unwrap();
+    assert_eq!(summary.hashed, 1);
+    assert_eq!(summary.failed.len(), 1);
+    assert_eq!(summary.failed[0].0, broken.to_string_lossy());
+    assert_eq!(progress, [(1, 2), (2, 2)]);
+
+    // Its old hash no longer counts it as a duplicate
+    let file = lib.db.get_file_by_path(&broken).unwrap().unwrap();
+    assert!(file.hash.is_none());
+    assert_eq!(lib.report().duplicates[0].files.len(), 3);
+}
+
+#[test]
+fn duplicates_keep_the_newest_copy() {
+    let lib = Library::new();
+    lib.add("old/Game.nsp", b"same", 100);
+    lib.add("new/Game.nsp", b"same", 10);
+    lib.add("Lookalike.nsp", b"diff", 5);
+
+    let report = lib.report();
+    assert_eq!(report.duplicates.len(), 1);
+    assert_eq!(report.duplicates[0].files.len(), 2);
+    assert!(report.duplicates[0].files[0].path.contains("new"));
+
+    let removed = report.keep_newest();
+    assert_eq!(removed.len(), 1);
+    assert!(removed[0].path.contains("old"));
+    assert_eq!(report.reclaimable(), 4);
+}
+
+#[test]
+fn changed_files_are_rehashed() {
+    let lib = Library::new();
+    let a = lib.add("a/Game.nsp", b"same", 100);
+    lib.add("b/Game.nsp", b"same", 50);
+    assert_eq!(lib.report().duplicates.len(), 1);
+
+    std::fs::write(&a, b"edit").unwrap();
+    assert_eq!(lib.report().duplicates.len(), 0);
+}
+
+#[test]
+fn compressed_copies_and_superseded_updates() {
+    let lib = Library::new();
+    lib.add("Game [0100AAAA00000000][v0].nsp", b"base uncompressed", 30);
+    lib.add("Game [0100AAAA00000000][v0].nsz", b"base", 40);
+    lib.add("Game [0100AAAA00000800][v131072].nsp", b"update 2", 20);
+    lib.add("Game [0100AAAA00000800][v65536].nsp", b"update 1", 30);
+    lib.add("Game [0100AAAA00000800][v196608].nsz", b"update 3", 10);
+    // Different title, only one version
+    lib.add("Other [0100BBBB00000800][v65536].nsp", b"other", 10);
+
+    let report = lib.report();
+    assert!(report.duplicates.is_empty());
+
+    assert_eq!(report.redundant.len(), 1);
+    assert_eq!(report.redundant[0].title_id, "0100AAAA00000000");
+    assert_eq!(names(&report.prefer_compressed()), ["Game [0100AAAA00000000][v0].nsp"]);
+
+    assert_eq!(report.superseded.len(), 1);
+    assert_eq!(report.superseded[0].latest.version, Some(196608));
+    assert_eq!(
+        names(&report.superseded[0].obsolete),
+        ["Game [0100AAAA00000800][v65536].nsp", "Game [0100AAAA00000800][v131072].nsp"]
+    );
+    assert_eq!(report.reclaimable(), 17 + 8 + 8);
+}
+
+#[test]
+fn trashing_moves_files_and_forgets_them() {
+    let lib = Library::new();
+    lib.add("old/Game.nsp", b"same", 100);
+    lib.add("new/Game.nsp", b"same", 10);
+    let trash = lib.dir.path().join("trash");
+
+    let report = lib.report();
+    let moved = move_to_trash(&lib.db, &report.keep_newest(), &trash).unwrap();
+    assert_eq!(moved, [trash.join("Game.nsp")]);
+    assert_eq!(std::fs::read(&moved[0]).unwrap(), b"same");
+    assert!(!lib.dir.path().join("old/Game.nsp").exists());
+
+    let files = lib.db.get_files().unwrap();
+    assert_eq!(files.len(), 1);
+    assert!(files[0].path.contains("new"));
+    assert!(lib.report().is_empty());
+}
+
+#[test]
+fn versions_are_filled_in_for_older_libraries() {
+    let lib = Library::new();
+    lib.add("Game [0100AAAA00000800][v65536].nsp", b"1", 10);
+    let db_path = lib.dir.path().join("games.db");
+    drop(lib.db);
+
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    conn.execute("ALTER TABLE files DROP COLUMN version", []).unwrap();
+    drop(conn);
+
+    let db = Database::new(&db_path).unwrap();
+    let files = db.get_files().unwrap();
+    assert_eq!(files[0].version, Some(65536));
+    assert_eq!(files[0].title_id.as_deref(), Some("0100AAAA00000800"));
+}
@@ -0,0 +1,51 @@
+//! Session events and install history recorded while serving
+use std::path::Path;
+
+use switchlink_backend::database::Database;
+
+const GAME: &str = "Game [0100000000010000][v0].nsp";
+
+#[test]
+fn resending_the_last_range_keeps_one_entry() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::new(&dir.path().join("games.db")).unwrap();
+    let path = Path::new("/games").join(GAME);
+
+    // The client retried the final range twice
+    for _ in 0..3 {
+        db.record_sent(GAME, &path).unwrap();
+    }
+    let history = db.get_install_history(10).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].status, "sent");
+    assert!(history[0].sent_at.is_some() && history[0].finished_at.is_none());
+
+    db.record_install_outcome(GAME, &path, "", true, "done", 0).unwrap();
+    let history = db.get_install_history(10).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].status, "installed");
+    assert!(history[0].finished_at.is_some());
+
+    // Installing it again is a new entry
+    db.record_sent(GAME, &path).unwrap();
+    db.record_sent(GAME, &path).unwrap();
+    db.record_install_outcome(GAME, &path, "", false, "write", 0x2A).unwrap();
+    let history = db.get_install_history(10).unwrap();
+    let statuses: Vec<_> = history.iter().map(|r| r.status.as_str()).collect();
+    assert_eq!(statuses, ["failed", "installed"]);
+    assert_eq!((history[0].phase.as_str(), history[0].result_code), ("write", 0x2A));
+}
+
+#[test]
+fn session_events_are_listed_newest_first() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::new(&dir.path().join("games.db")).unwrap();
+    db.record_session_event("stalled", "no command for 30s").unwrap();
+    db.record_session_event("recovered", "").unwrap();
+
+    let events = db.get_session_events(10).unwrap();
+    let names: Vec<_> = events.iter().map(|e| e.event.as_str()).collect();
+    assert_eq!(names, ["recovered", "stalled"]);
+    assert_eq!(events[1].detail, "no command for 30s");
+    assert_eq!(db.get_session_events(1).unwrap().len(), 1);
+}
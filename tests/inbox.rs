@@ -0,0 +1,128 @@
+//! Uploads from the Switch: resuming, hash checks, name sanitizing and categories
+use sha2::{Digest, Sha256};
+
+use switchlink_backend::inbox::{Inbox, InboxError};
+use switchlink_backend::protocol::UploadCategory;
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+fn inbox_error(e: anyhow::Error) -> InboxError {
+    e.downcast().unwrap()
+}
+
+const DATA: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+#[test]
+fn uploads_resume_from_the_gap_free_end() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut inbox = Inbox::new(dir.path().to_path_buf());
+    let size = DATA.len() as u64;
+    assert_eq!(inbox.begin("shot.jpg", UploadCategory::Screenshot, size).unwrap(), 0);
+
+    // A write past a gap doesn't move the resume point
+    inbox.write_range("shot.jpg", None, 0, &DATA[..10]).unwrap();
+    inbox.write_range("shot.jpg", None, 20, &DATA[20..30]).unwrap();
+    assert_eq!(inbox.begin("shot.jpg", UploadCategory::Screenshot, size).unwrap(), 10);
+
+    // Even after a restart, when only the files are left
+    let mut inbox = Inbox::new(dir.path().to_path_buf());
+    assert_eq!(inbox.begin("shot.jpg", UploadCategory::Screenshot, size).unwrap(), 10);
+    inbox.write_range("shot.jpg", None, 10, &DATA[10..]).unwrap();
+    let error = inbox.finish("shot.jpg", None, &[0; 32]).unwrap_err();
+    assert!(matches!(inbox_error(error), InboxError::HashMismatch { .. }));
+
+    // The corrupt partial is dropped and the client starts over
+    assert_eq!(inbox.begin("shot.jpg", UploadCategory::Screenshot, size).unwrap(), 0);
+    inbox.write_range("shot.jpg", None, 20, &DATA[20..]).unwrap();
+    let error = inbox.finish("shot.jpg", None, &sha256(DATA)).unwrap_err();
+    assert!(matches!(inbox_error(error), InboxError::SizeMismatch { expected: 36, actual: 0 }));
+    // Filling the gap reaches the ranges written ahead of it
+    inbox.write_range("shot.jpg", None, 0, &DATA[..20]).unwrap();
+    let upload = inbox.finish("shot.jpg", None, &sha256(DATA)).unwrap();
+    assert_eq!(upload.path, dir.path().join("screenshots/shot.jpg"));
+    assert_eq!(std::fs::read(&upload.path).unwrap(), DATA);
+    assert_eq!(upload.size, size);
+}
+
+#[test]
+fn out_of_range_writes_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut inbox = Inbox::new(dir.path().to_path_buf());
+    inbox.begin("log.txt", UploadCategory::Log, 8).unwrap();
+
+    let error = inbox.write_range("log.txt", None, 4, b"12345").unwrap_err();
+    assert!(matches!(inbox_error(error), InboxError::OutOfRange { offset: 4, len: 5, file_size: 8 }));
+    // Would wrap around instead of failing the bounds check
+    let error = inbox.write_range("log.txt", None, u64::MAX - 1, b"12345").unwrap_err();
+    assert!(matches!(inbox_error(error), InboxError::OutOfRange { .. }));
+    let error = inbox.write_range("other.txt", None, 0, b"1").unwrap_err();
+    assert!(matches!(inbox_error(error), InboxError::UnknownUpload(_)));
+}
+
+#[test]
+fn names_are_confined_to_the_inbox() {
+    let dir = tempfile::tempdir().unwrap();
+    let inbox_dir = dir.path().join("inbox");
+    let mut inbox = Inbox::new(inbox_dir.clone());
+
+    inbox.begin("../../evil.sh", UploadCategory::Misc, 4).unwrap();
+    inbox.write_range("evil.sh", None, 0, b"echo").unwrap();
+    let upload = inbox.finish("/tmp/evil.sh", None, &sha256(b"echo")).unwrap();
+    assert_eq!(upload.name, "evil.sh");
+    assert_eq!(upload.path, inbox_dir.join("misc/evil.sh"));
+    assert!(!dir.path().join("evil.sh").exists());
+
+    for name in ["", "..", "/", "dir/.."] {
+        let error = inbox.begin(name, UploadCategory::Misc, 4).unwrap_err();
+        assert!(matches!(inbox_error(error), InboxError::InvalidName(_)), "{:?}", name);
+    }
+
+    // Finished uploads never overwrite earlier ones
+    inbox.begin("evil.sh", UploadCategory::Misc, 4).unwrap();
+    inbox.write_range("evil.sh", None, 0, b"echo").unwrap();
+    let second = inbox.finish("evil.sh", None, &sha256(b"echo")).unwrap();
+    assert_eq!(second.path, inbox_dir.join("misc/evil (1).sh"));
+}
+
+#[test]
+fn the_same_name_in_two_categories() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut inbox = Inbox::new(dir.path().to_path_buf());
+    inbox.begin("dump.bin", UploadCategory::Dump, 4).unwrap();
+    inbox.begin("dump.bin", UploadCategory::Log, 3).unwrap();
+
+    // Without a category the name is ambiguous
+    let error = inbox.write_range("dump.bin", None, 0, b"abc").unwrap_err();
+    assert!(matches!(inbox_error(error), InboxError::AmbiguousUpload(_)));
+
+    inbox.write_range("dump.bin", Some(UploadCategory::Log), 0, b"abc").unwrap();
+    inbox.write_range("dump.bin", Some(UploadCategory::Dump), 0, b"abcd").unwrap();
+    let log = inbox.finish("dump.bin", Some(UploadCategory::Log), &sha256(b"abc")).unwrap();
+    assert_eq!(log.path, dir.path().join("logs/dump.bin"));
+    // Once the other is done, the name alone is enough again
+    let dump = inbox.finish("dump.bin", None, &sha256(b"abcd")).unwrap();
+    assert_eq!(std::fs::read(dump.path).unwrap(), b"abcd");
+}
+
+#[test]
+fn consoles_uploading_the_same_name_keep_their_own_partial_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut left = Inbox::new(dir.path().to_path_buf()).for_session("1-2");
+    let mut right = Inbox::new(dir.path().to_path_buf()).for_session("1-3");
+    let other = b"ZYXWVUTSRQPONMLKJIHGFEDCBA9876543210";
+
+    assert_eq!(left.begin("shot.jpg", UploadCategory::Screenshot, 36).unwrap(), 0);
+    left.write_range("shot.jpg", None, 0, &DATA[..20]).unwrap();
+    // The other console neither resumes nor truncates the first one's upload
+    assert_eq!(right.begin("shot.jpg", UploadCategory::Screenshot, 36).unwrap(), 0);
+    right.write_range("shot.jpg", None, 0, other).unwrap();
+    left.write_range("shot.jpg", None, 20, &DATA[20..]).unwrap();
+
+    let first = left.finish("shot.jpg", None, &sha256(DATA)).unwrap();
+    let second = right.finish("shot.jpg", None, &sha256(other)).unwrap();
+    assert_eq!(std::fs::read(&first.path).unwrap(), DATA);
+    assert_eq!(std::fs::read(&second.path).unwrap(), other);
+    assert_eq!(second.path, dir.path().join("screenshots/shot (1).jpg"));
+}
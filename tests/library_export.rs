@@ -0,0 +1,254 @@
+//! Library export and import across machines, and database backups
+mod common;
+
+use std::path::Path;
+
+use common::Library;
+use switchlink_backend::backup::{
+    backup_database, backup_if_due, hash_unhashed, import_library, list_backups, prune_backups, restore_database,
+    BackupConfig, ExportedCollection, ImportOptions, LibraryExport, MatchBy, PathRewrite,
+};
+use switchlink_backend::database::{Database, GameFile};
+
+impl Library {
+    fn add(&self, name: &str, content: &[u8]) -> i64 {
+        let path = self.games().join(name);
+        std::fs::write(&path, content).unwrap();
+        self.db.add_file(&path).unwrap()
+    }
+
+    fn file(&self, name: &str) -> GameFile {
+        self.db.get_files().unwrap().into_iter().find(|f| f.filename == name).unwrap()
+    }
+}
+
+const ZELDA: &str = "Zelda [01007EF00011E000][v0].nsp";
+const MARIO: &str = "Mario [0100000000010000][v0].nsp";
+
+/// A library with a favorite, tags, an install and a collection
+fn source() -> Library {
+    let lib = Library::new();
+    let zelda = lib.add(ZELDA, b"zelda");
+    lib.add(MARIO, b"mario");
+    lib.db.toggle_favorite(zelda).unwrap();
+    lib.db.add_tag(zelda, "RPG").unwrap();
+    lib.db.set_tag_color("RPG", "#ff0000").unwrap();
+    lib.db.create_tag("Unused").unwrap();
+    lib.db.record_install(zelda).unwrap();
+    lib.db.record_install(zelda).unwrap();
+    lib.db
+        .create_collection("Family", &[lib.games().join(MARIO), lib.games().join(ZELDA)])
+        .unwrap();
+    lib
+}
+
+fn rewrite(from: &Path, to: &Path) -> PathRewrite {
+    PathRewrite { from: from.to_string_lossy().to_string(), to: to.to_string_lossy().to_string() }
+}
+
+#[test]
+fn json_exports_import_by_rewritten_path() {
+    let from = source();
+    let path = from.dir.path().join("library.json");
+    LibraryExport::from_db(&from.db).unwrap().write(&path).unwrap();
+
+    let to = Library::new();
+    to.add(ZELDA, b"zelda");
+    to.add(MARIO, b"mario");
+    let options = ImportOptions { rewrites: vec![rewrite(&from.games(), &to.games())], ..Default::default() };
+    let summary = import_library(&to.db, &LibraryExport::read(&path).unwrap(), &options).unwrap();
+    assert_eq!(summary.matched, 2);
+    assert!(summary.unmatched.is_empty());
+    assert_eq!(summary.collections_created, 1);
+
+    let zelda = to.file(ZELDA);
+    assert!(zelda.favorite);
+    assert_eq!(zelda.tags, ["RPG"]);
+    assert_eq!(zelda.install_count, 2);
+    assert!(zelda.last_installed.is_some());
+    assert!(!to.file(MARIO).favorite);
+    let tags = to.db.get_tags().unwrap();
+    assert_eq!(tags.iter().find(|t| t.name == "RPG").unwrap().color, "#ff0000");
+    assert!(tags.iter().any(|t| t.name == "Unused"));
+    assert_eq!(
+        to.db.get_collection_items("Family").unwrap(),
+        [to.games().join(MARIO), to.games().join(ZELDA)]
+    );
+
+    // Without the rewrite nothing lines up
+    let again = import_library(&to.db, &LibraryExport::read(&path).unwrap(), &ImportOptions::default()).unwrap();
+    assert_eq!(again.matched, 0);
+    assert_eq!(again.unmatched.len(), 2);
+}
+
+#[test]
+fn csv_exports_keep_files_and_tags() {
+    let from = source();
+    let path = from.dir.path().join("library.csv");
+    LibraryExport::from_db(&from.db).unwrap().write(&path).unwrap();
+    assert!(std::fs::read_to_string(&path).unwrap().starts_with("# switchlink library export v1\n"));
+
+    let export = LibraryExport::read(&path).unwrap();
+    assert_eq!(export.version, 1);
+    assert_eq!(export.files.len(), 2);
+    let zelda = export.files.iter().find(|f| f.filename == ZELDA).unwrap();
+    assert_eq!(zelda.tags, ["RPG"]);
+    assert_eq!(zelda.install_count, 2);
+    assert_eq!(zelda.title_id.as_deref(), Some("01007EF00011E000"));
+    assert!(export.files.iter().find(|f| f.filename == MARIO).unwrap().last_installed.is_none());
+    assert!(export.collections.is_empty());
+
+    // Exports from a newer format are refused rather than half read
+    let newer = from.dir.path().join("newer.json");
+    std::fs::write(&newer, r#"{"version": 99, "exported_at": 0, "files": []}"#).unwrap();
+    assert!(LibraryExport::read(&newer).unwrap_err().to_string().contains("v99"));
+    // A CSV without the marker line isn't one of ours
+    let foreign = from.dir.path().join("foreign.csv");
+    std::fs::write(&foreign, "path,size\n/a,1\n").unwrap();
+    assert!(LibraryExport::read(&foreign).unwrap_err().to_string().contains("Not a SwitchLink library export"));
+
+    // Only the first line is the marker; rows starting with # are files like any other
+    let picks = from.dir.path().join("picks.csv");
+    let mut export = LibraryExport::from_db(&from.db).unwrap();
+    export.files[0].path = "#1 picks/Game.nsp".to_string();
+    export.write(&picks).unwrap();
+    let read = LibraryExport::read(&picks).unwrap();
+    assert_eq!(read.files.len(), 2);
+    assert_eq!(read.files[0].path, "#1 picks/Game.nsp");
+}
+
+#[test]
+fn a_failed_import_keeps_nothing() {
+    let from = source();
+    let mut export = LibraryExport::from_db(&from.db).unwrap();
+    // Collections are merged last, and this one can't be created
+    export.collections.push(ExportedCollection { name: " ".to_string(), items: Vec::new() });
+
+    let to = Library::new();
+    to.add(ZELDA, b"zelda");
+    let options = ImportOptions { rewrites: vec![rewrite(&from.games(), &to.games())], ..Default::default() };
+    assert!(import_library(&to.db, &export, &options).is_err());
+    let zelda = to.file(ZELDA);
+    assert!(!zelda.favorite);
+    assert!(zelda.tags.is_empty());
+    assert_eq!(zelda.install_count, 0);
+    assert!(to.db.get_tags().unwrap().is_empty());
+    assert!(to.db.get_collections().unwrap().is_empty());
+
+    export.collections.pop();
+    assert_eq!(import_library(&to.db, &export, &options).unwrap().matched, 1);
+    assert!(to.file(ZELDA).favorite);
+    assert_eq!(to.db.get_collections().unwrap().len(), 1);
+}
+
+#[test]
+fn hashes_match_renamed_files() {
+    let from = source();
+    assert_eq!(hash_unhashed(&from.db, |_, _| {}).unwrap(), 2);
+    let export = LibraryExport::from_db(&from.db).unwrap();
+    assert!(export.files.iter().all(|f| f.hash.is_some()));
+
+    // Renamed on the other machine, and never hashed there
+    let to = Library::new();
+    to.add("zelda.nsp", b"zelda");
+    to.add("other.nsp", b"other");
+    let options = ImportOptions { match_by: MatchBy::Hash, ..Default::default() };
+    let summary = import_library(&to.db, &export, &options).unwrap();
+    assert_eq!(summary.matched, 1);
+    assert_eq!(summary.unmatched, [from.games().join(MARIO).to_string_lossy().to_string()]);
+    assert!(to.file("zelda.nsp").favorite);
+    // Collection items follow the match to the local file
+    assert_eq!(to.db.get_collection_items("Family").unwrap()[1], to.games().join("zelda.nsp"));
+}
+
+#[test]
+fn title_ids_and_merging_into_existing_data() {
+    let from = source();
+    let export = LibraryExport::from_db(&from.db).unwrap();
+
+    let to = Library::new();
+    let zelda = to.add("zelda [01007EF00011E000][v0].xci", b"different dump");
+    for _ in 0..5 {
+        to.db.record_install(zelda).unwrap();
+    }
+    to.db.add_tag(zelda, "Mine").unwrap();
+    to.db.create_collection("family", &[to.games().join("zelda [01007EF00011E000][v0].xci")]).unwrap();
+    // Mario was copied over but isn't in this library yet
+    std::fs::write(to.games().join(MARIO), b"mario").unwrap();
+
+    let options = ImportOptions {
+        match_by: MatchBy::TitleId,
+        rewrites: vec![rewrite(&from.games(), &to.games())],
+        add_missing: true,
+    };
+    let summary = import_library(&to.db, &export, &options).unwrap();
+    assert_eq!((summary.matched, summary.added), (1, 1));
+    assert_eq!((summary.collections_created, summary.collections_merged), (0, 1));
+
+    // Higher local counts win, tags and favorites are added
+    let zelda = to.file("zelda [01007EF00011E000][v0].xci");
+    assert_eq!(zelda.install_count, 5);
+    assert_eq!(zelda.tags, ["Mine", "RPG"]);
+    assert!(zelda.favorite);
+    assert_eq!(
+        to.db.get_collection_items("family").unwrap(),
+        [to.games().join("zelda [01007EF00011E000][v0].xci"), to.games().join(MARIO)]
+    );
+
+    // Importing again changes nothing
+    let again = import_library(&to.db, &export, &options).unwrap();
+    assert_eq!((again.matched, again.added, again.collections_merged), (2, 0, 0));
+    assert_eq!(to.db.get_files().unwrap().len(), 2);
+}
+
+#[test]
+fn path_rewrites() {
+    let unix = PathRewrite::parse("/mnt/games=D:\\Games").unwrap();
+    assert_eq!(unix.apply("/mnt/games/Switch/a.nsp").as_deref(), Some("D:\\Games\\Switch\\a.nsp"));
+    assert_eq!(unix.apply("/mnt/games").as_deref(), Some("D:\\Games"));
+    assert_eq!(unix.apply("/mnt/games2/a.nsp"), None);
+
+    let windows = PathRewrite::parse("D:\\Games\\=/media/usb/").unwrap();
+    assert_eq!(windows.apply("D:\\Games\\Switch\\a.nsp").as_deref(), Some("/media/usb/Switch/a.nsp"));
+
+    assert!(PathRewrite::parse("/mnt/games").is_err());
+    assert!(PathRewrite::parse("=/media").is_err());
+    assert_eq!(MatchBy::parse("Title").unwrap(), MatchBy::TitleId);
+    assert!(MatchBy::parse("size").is_err());
+}
+
+#[test]
+fn backups_are_scheduled_pruned_and_restored() {
+    let lib = source();
+    let dir = lib.dir.path().join("backups");
+    let config = BackupConfig { dir: dir.clone(), interval_hours: 24, keep: 2 };
+
+    // The first check backs up, later ones wait for the interval
+    let first = backup_if_due(&lib.db, &config).unwrap().unwrap();
+    assert!(backup_if_due(&lib.db, &config).unwrap().is_none());
+    assert!(backup_if_due(&lib.db, &BackupConfig { interval_hours: 0, ..config.clone() }).unwrap().is_none());
+
+    let second = backup_database(&lib.db, &dir).unwrap();
+    let third = backup_database(&lib.db, &dir).unwrap();
+    assert_eq!(list_backups(&dir).unwrap(), [first.clone(), second.clone(), third.clone()]);
+    assert_eq!(prune_backups(&dir, 2).unwrap(), [first]);
+    assert_eq!(list_backups(&dir).unwrap(), [second.clone(), third]);
+
+    // The backup is a working library
+    let copy = Database::new(&second).unwrap();
+    assert_eq!(copy.get_files().unwrap().len(), 2);
+    drop(copy);
+
+    // Restoring keeps the library it replaces
+    let db_path = lib.dir.path().join("games.db");
+    lib.db.remove_file(lib.file(MARIO).id).unwrap();
+    let Library { dir: tmp, db } = lib;
+    drop(db);
+    let replaced = restore_database(&db_path, &second, &dir).unwrap().unwrap();
+    assert_eq!(Database::new(&db_path).unwrap().get_files().unwrap().len(), 2);
+    assert_eq!(Database::new(&replaced).unwrap().get_files().unwrap().len(), 1);
+
+    let not_a_db = tmp.path().join("notes.txt");
+    std::fs::write(&not_a_db, "hello").unwrap();
+    assert!(restore_database(&db_path, &not_a_db, &dir).is_err());
+}
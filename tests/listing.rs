@@ -0,0 +1,104 @@
+//! Paged LIST responses and incremental re-sync tokens
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use switchlink_backend::listing::ListState;
+
+struct Served {
+    dir: tempfile::TempDir,
+    files: HashMap<String, PathBuf>,
+}
+
+impl Served {
+    fn new(names: &[&str]) -> Self {
+        let mut served = Self { dir: tempfile::tempdir().unwrap(), files: HashMap::new() };
+        for name in names {
+            served.write(name, name.len());
+        }
+        served
+    }
+
+    fn write(&mut self, name: &str, size: usize) {
+        let path = self.dir.path().join(name);
+        std::fs::write(&path, vec![0u8; size]).unwrap();
+        self.files.insert(name.to_string(), path);
+    }
+
+    fn remove(&mut self, name: &str) {
+        self.files.remove(name);
+    }
+}
+
+fn names(entries: &[(String, u64)]) -> Vec<&str> {
+    entries.iter().map(|(name, _)| name.as_str()).collect()
+}
+
+#[test]
+fn full_listings_are_paged_by_name() {
+    let served = Served::new(&["c.nsp", "a.nsp", "e.nsp", "b.nsp", "d.nsp"]);
+    let mut state = ListState::new();
+    let token = state.refresh(&served.files);
+
+    let first = state.page(0, 0, 2);
+    assert_eq!((first.total, first.token, first.reset), (5, token, false));
+    assert_eq!(names(&first.entries), ["a.nsp", "b.nsp"]);
+    assert_eq!(names(&state.page(0, 2, 2).entries), ["c.nsp", "d.nsp"]);
+    assert_eq!(names(&state.page(0, 4, 2).entries), ["e.nsp"]);
+    assert!(state.page(0, 10, 2).entries.is_empty());
+
+    assert_eq!(first.to_payload(), format!("5|{}|0\na.nsp|5\nb.nsp|5\n", token));
+    // Nothing changed, so the token stays put
+    assert_eq!(state.refresh(&served.files), token);
+}
+
+#[test]
+fn tokens_send_only_what_changed() {
+    let mut served = Served::new(&["a.nsp", "b.nsp", "c.nsp"]);
+    let mut state = ListState::new();
+    let first = state.refresh(&served.files);
+    assert!(state.page(first, 0, 10).entries.is_empty());
+
+    served.write("b.nsp", 100);
+    served.write("d.nsp", 4);
+    served.remove("c.nsp");
+    let second = state.refresh(&served.files);
+    assert!(second > first);
+
+    let page = state.page(first, 0, 10);
+    assert_eq!((page.total, page.reset), (2, false));
+    assert_eq!(page.entries, [("b.nsp".to_string(), 100), ("d.nsp".to_string(), 4)]);
+    assert_eq!(page.removed, ["c.nsp"]);
+    assert_eq!(page.to_payload(), format!("2|{}|0\nb.nsp|100\nd.nsp|4\nc.nsp|-\n", second));
+
+    // Removals go out with the first page only
+    let later = state.page(first, 1, 10);
+    assert_eq!(names(&later.entries), ["d.nsp"]);
+    assert!(later.removed.is_empty());
+
+    // A name that comes back is an entry again, not a removal
+    served.write("c.nsp", 7);
+    let third = state.refresh(&served.files);
+    let page = state.page(second, 0, 10);
+    assert_eq!(page.entries, [("c.nsp".to_string(), 7)]);
+    assert!(page.removed.is_empty());
+    assert!(state.page(third, 0, 10).entries.is_empty());
+}
+
+#[test]
+fn tokens_from_another_session_reset_the_client() {
+    let served = Served::new(&["a.nsp", "b.nsp"]);
+    let mut old = ListState::new();
+    let old_token = old.refresh(&served.files);
+    std::thread::sleep(Duration::from_millis(5));
+
+    let mut state = ListState::new();
+    let token = state.refresh(&served.files);
+    for stale in [old_token, token + 1000] {
+        let page = state.page(stale, 0, 10);
+        assert!(page.reset, "{}", stale);
+        assert_eq!(page.total, 2);
+        assert!(page.removed.is_empty());
+    }
+    assert!(!state.page(token, 0, 10).reset);
+}
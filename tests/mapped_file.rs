@@ -0,0 +1,53 @@
+//! Mapped file serving falls back to buffered reads when the file changes size
+use std::fs::OpenOptions;
+use std::io::Read;
+
+use switchlink_backend::mapped_file::ServedFile;
+
+fn write_file(len: usize) -> (tempfile::TempDir, std::path::PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("game.nsp");
+    std::fs::write(&path, (0..len).map(|i| i as u8).collect::<Vec<_>>()).unwrap();
+    (dir, path)
+}
+
+#[test]
+fn ranges_come_from_the_mapping() {
+    let (_dir, path) = write_file(4096);
+    let mut served = ServedFile::open(&path, true).unwrap();
+    assert!(served.is_mapped());
+    assert_eq!(served.mapped_range(10, 4), Some(&[10u8, 11, 12, 13][..]));
+    assert_eq!(served.mapped_range(4000, 200), None, "range past EOF was mapped");
+}
+
+#[test]
+fn truncated_file_is_unmapped() {
+    let (_dir, path) = write_file(4096);
+    let mut served = ServedFile::open(&path, true).unwrap();
+    assert!(served.mapped_range(0, 4096).is_some());
+
+    OpenOptions::new().write(true).open(&path).unwrap().set_len(100).unwrap();
+    assert_eq!(served.mapped_range(0, 16), None);
+    assert!(!served.is_mapped());
+
+    let mut rest = Vec::new();
+    served.reader_at(90).unwrap().read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, (90..100).map(|i| i as u8).collect::<Vec<_>>());
+}
+
+#[test]
+fn grown_file_is_remapped() {
+    let (_dir, path) = write_file(100);
+    let mut served = ServedFile::open(&path, true).unwrap();
+
+    std::fs::write(&path, (0..200).map(|i| i as u8).collect::<Vec<_>>()).unwrap();
+    assert_eq!(served.mapped_range(150, 2), Some(&[150u8, 151][..]));
+}
+
+#[test]
+fn mapping_is_optional() {
+    let (_dir, path) = write_file(100);
+    let mut served = ServedFile::open(&path, false).unwrap();
+    assert!(!served.is_mapped());
+    assert_eq!(served.mapped_range(0, 10), None);
+}
@@ -0,0 +1,356 @@
+//! NSP fixtures: encrypted and signed NCAs with hash trees, tickets and CNMTs
+#![allow(dead_code)]
+
+use aes::cipher::{BlockEncrypt, KeyInit, KeyIvInit, StreamCipher, StreamCipherSeek};
+use aes::Aes128;
+use rsa::rand_core::{CryptoRng, RngCore};
+use rsa::{BigUint, Pss, RsaPrivateKey};
+use sha2::{Digest, Sha256};
+
+use switchlink_backend::keys::KeySet;
+
+pub const HEADER_KEY: [u8; 32] = [0x5a; 32];
+pub const KEY_AREA_KEY: [u8; 16] = [0x3c; 16];
+pub const TITLEKEK: [u8; 16] = [0x7e; 16];
+pub const SECTION_KEY: [u8; 16] = [0xa1; 16];
+pub const TITLE_ID: u64 = 0x0100_AAAA_0000_0000;
+pub const ICON: &[u8] = b"\xff\xd8\xff\xe0 not really a JPEG";
+pub const PROGRAM: &str = "0123456789abcdef0123456789abcdef.nca";
+pub const CONTROL: &str = "fedcba9876543210fedcba9876543210.nca";
+pub const META: &str = "00112233445566778899aabbccddeeff.cnmt.nca";
+
+// Stands in for Nintendo's fixed NCA header key, which only Nintendo can sign with
+const SIGNING_MODULUS: &str = "b57ac2693df7a9eaeae9fa7b7977b7caef0b4e482b481ae1f4744db2e4262d02c8c7699cd9ae19e1949be2965d9e713e910902cb5f58b97647e7d421bba3ca2befb8f9a760c723f5cf3c19901a2047c480f6b071351982c9309378018267df2846a4bd4bf0ef01f8e55cd6dcd7640f829111cbc68dfdb523d0458dd13c9aead0c252e9a4f902c31ebc0706304f80d78d2fbf516b4e9d0b347281c84713f48ee811f7bd03cca614064a1a96575387a6c87acf9154a13fe31a2fe254ab1ad7875fc0871308cfe5a7606f37768b6f922dad780e20c0d50593177120b14857ba1d240af850232e723310d6f307d07ffe70b86d4e30716465e85fcb8f07b51fdda635";
+const SIGNING_EXPONENT: &str = "2945e1bc8f220a041a087c15c44e889f6fc59db8569597bd8f3bbb2705f1b3b76823c7fe6ad8485d100ff2d499368d513a297737faf781f8674a8f695b9418c8ecc4600ce3baee8ffb21533bfd611d89507e2a2b18f534141f06ceda6306ce0f0a1ff1c83963960a7dd96329471823d9e135cfa718c85c3fa8a084ec12f13599422a0f127f4bd8b92c3cde10b52d5605d822e2db6064ef041f0e4b5307c4c6ac1a6f949a9d10aabac34283546856e177741a79ac5057b0d20f852612c4ba37e068da4548e0b33c404c9c7001e4194be83d9ea6d7db25d106513fb53cb1f40476764367237d88e299827fcb2da63d0922748382a60d5464cdc5196d1ee2289ec1";
+const SIGNING_PRIMES: [&str; 2] = [
+    "d79b3e96d54615a16d486d469e2ced57cc2a3e670bee9a4f931e1645b90562a34545aaa246686694ec3c42299401d40d2ab3ee44c4f6727648f2960da711c96ecd7e079470702eadca5f82cf2fdf450de671430e3d5cd44b0b22044c0f7d8fb45f1f584f99f6db8fb81fa280ef8e481d66775ca8837a98aeabbb7b9b74849df5",
+    "d77abeffe50390debded384d68129216f36bbb7f5ac9592b4ab8d77fa6ac8a6a51707b35af493e9e1d2ac37a939a8ebfaa6fb5199fe7cf722b0f79a383af9bf72557075f6f990b12b7ffc02240f0dda15548eb9585cc3804a60688ab4734e5d94155a4e41885bb1750f2ab71d065b9fe77a54b420ca993d573e18aad8e617f41",
+];
+
+// Sections start right after the header and count up from this CTR value
+const SECTION_START: u64 = 0xC00;
+const SECTION_CTR: u64 = 0x0000_0001_0000_0000;
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Keys for the fixtures, including the signing modulus
+pub fn keys() -> KeySet {
+    KeySet::parse(&format!(
+        "; dumped keys\nheader_key = {}\nkey_area_key_application_00 = {}\ntitlekek_00 = {}\nnca_hdr_fixed_key_modulus_00 = {}\n",
+        hex(&HEADER_KEY),
+        hex(&KEY_AREA_KEY),
+        hex(&TITLEKEK),
+        SIGNING_MODULUS
+    ))
+}
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+fn ecb_encrypt(key: &[u8; 16], block: [u8; 16]) -> [u8; 16] {
+    let mut data = block.into();
+    Aes128::new(&(*key).into()).encrypt_block(&mut data);
+    data.into()
+}
+
+/// AES-XTS with Nintendo's big-endian sector numbers
+fn xts_encrypt(data: &mut [u8], key: &[u8; 32]) {
+    let data_key = Aes128::new_from_slice(&key[..16]).unwrap();
+    let tweak_key = Aes128::new_from_slice(&key[16..]).unwrap();
+    for (sector, chunk) in data.chunks_mut(0x200).enumerate() {
+        let mut tweak = (sector as u128).to_be_bytes().into();
+        tweak_key.encrypt_block(&mut tweak);
+        for block in chunk.chunks_exact_mut(16) {
+            block.iter_mut().zip(tweak.iter()).for_each(|(b, t)| *b ^= t);
+            let mut cipher = <[u8; 16]>::try_from(&*block).unwrap().into();
+            data_key.encrypt_block(&mut cipher);
+            block.copy_from_slice(&cipher);
+            block.iter_mut().zip(tweak.iter()).for_each(|(b, t)| *b ^= t);
+            let mut carry = 0;
+            for byte in tweak.iter_mut() {
+                let next = *byte >> 7;
+                *byte = (*byte << 1) | carry;
+                carry = next;
+            }
+            if carry != 0 {
+                tweak[0] ^= 0x87;
+            }
+        }
+    }
+}
+
+/// Deterministic randomness for PSS salts
+struct TestRng(u64);
+
+impl RngCore for TestRng {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        self.0 >> 16
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        dest.iter_mut().for_each(|b| *b = self.next_u64() as u8);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rsa::rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for TestRng {}
+
+/// RSA-PSS signature of header bytes 0x200..0x400, as Nintendo signs them
+fn sign(header: &[u8]) -> Vec<u8> {
+    let number = |hex: &str| BigUint::parse_bytes(hex.as_bytes(), 16).unwrap();
+    let key = RsaPrivateKey::from_components(
+        number(SIGNING_MODULUS),
+        BigUint::from(65537u32),
+        number(SIGNING_EXPONENT),
+        SIGNING_PRIMES.iter().map(|p| number(p)).collect(),
+    )
+    .unwrap();
+    key.sign_with_rng(&mut TestRng(1), Pss::new::<Sha256>(), &sha256(&header[0x200..0x400]))
+        .unwrap()
+}
+
+pub fn nacp(names: &[(usize, &str, &str)], version: &str) -> Vec<u8> {
+    let mut nacp = vec![0; 0x4000];
+    for (language, name, publisher) in names {
+        nacp[language * 0x300..][..name.len()].copy_from_slice(name.as_bytes());
+        nacp[language * 0x300 + 0x200..][..publisher.len()].copy_from_slice(publisher.as_bytes());
+    }
+    nacp[0x3060..][..version.len()].copy_from_slice(version.as_bytes());
+    nacp
+}
+
+/// A RomFS with files in its root directory
+pub fn romfs(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut meta = Vec::new();
+    let mut data = Vec::new();
+    for (name, content) in files {
+        meta.extend_from_slice(&0u32.to_le_bytes());
+        meta.extend_from_slice(&u32::MAX.to_le_bytes());
+        meta.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        meta.extend_from_slice(&(content.len() as u64).to_le_bytes());
+        meta.extend_from_slice(&u32::MAX.to_le_bytes());
+        meta.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        meta.extend_from_slice(name.as_bytes());
+        meta.resize(meta.len().next_multiple_of(4), 0);
+        data.extend_from_slice(content);
+        data.resize(data.len().next_multiple_of(0x10), 0);
+    }
+
+    let data_offset = (0x50 + meta.len()).next_multiple_of(0x10) as u64;
+    let mut header = Vec::new();
+    for value in [0x50, 0x50, 0, 0x50, 0, 0x50, 0, 0x50, meta.len() as u64, data_offset] {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+    header.extend_from_slice(&meta);
+    header.resize(data_offset as usize, 0);
+    header.extend_from_slice(&data);
+    header
+}
+
+pub fn pfs0(files: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut strings = Vec::new();
+    let mut entries = Vec::new();
+    let mut offset = 0u64;
+    for (name, data) in files {
+        entries.extend_from_slice(&offset.to_le_bytes());
+        entries.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        entries.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+        entries.extend_from_slice(&0u32.to_le_bytes());
+        strings.extend_from_slice(name.as_bytes());
+        strings.push(0);
+        offset += data.len() as u64;
+    }
+
+    let mut nsp = b"PFS0".to_vec();
+    nsp.extend_from_slice(&(files.len() as u32).to_le_bytes());
+    nsp.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+    nsp.extend_from_slice(&0u32.to_le_bytes());
+    nsp.extend_from_slice(&entries);
+    nsp.extend_from_slice(&strings);
+    for (_, data) in files {
+        nsp.extend_from_slice(data);
+    }
+    nsp
+}
+
+/// Hashes of each block of `data`, zero-padded to the block size when `pad` is set
+fn block_hashes(data: &[u8], block_size: usize, pad: bool) -> Vec<u8> {
+    data.chunks(block_size)
+        .flat_map(|chunk| {
+            let mut block = chunk.to_vec();
+            if pad {
+                block.resize(block_size, 0);
+            }
+            sha256(&block)
+        })
+        .collect()
+}
+
+/// An IVFC section holding `romfs`: FS header and plain section data
+fn ivfc_section(romfs: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    // Five hash levels of 0x200-byte blocks above 0x4000-byte data blocks
+    let orders = [9u32, 9, 9, 9, 9, 14];
+    let mut levels = vec![romfs.to_vec()];
+    for level in (0..5).rev() {
+        let below = &levels[0];
+        levels.insert(0, block_hashes(below, 1 << orders[level + 1], true));
+    }
+    let master = block_hashes(&levels[0], 1 << orders[0], true);
+
+    let mut fs = vec![0u8; 0x200];
+    fs[0x2] = 0;
+    fs[0x3] = 3;
+    fs[0x4] = 3;
+    fs[0x8..0xC].copy_from_slice(b"IVFC");
+    fs[0xC..0x10].copy_from_slice(&0x20000u32.to_le_bytes());
+    fs[0x10..0x14].copy_from_slice(&0x20u32.to_le_bytes());
+    fs[0x14..0x18].copy_from_slice(&7u32.to_le_bytes());
+    fs[0xC8..0xE8].copy_from_slice(&master);
+
+    let mut section = Vec::new();
+    for (level, data) in levels.iter().enumerate() {
+        section.resize(section.len().next_multiple_of(if level == 5 { 0x1000 } else { 0x200 }), 0);
+        let entry = 0x18 + level * 0x18;
+        fs[entry..entry + 8].copy_from_slice(&(section.len() as u64).to_le_bytes());
+        fs[entry + 8..entry + 0x10].copy_from_slice(&(data.len() as u64).to_le_bytes());
+        fs[entry + 0x10..entry + 0x14].copy_from_slice(&orders[level].to_le_bytes());
+        section.extend_from_slice(data);
+    }
+    (fs, section)
+}
+
+/// A hashed PFS0 section holding `files`: FS header and plain section data
+fn pfs0_section(files: &[(String, Vec<u8>)]) -> (Vec<u8>, Vec<u8>) {
+    const BLOCK_SIZE: usize = 0x1000;
+    let pfs0 = pfs0(files);
+    let table = block_hashes(&pfs0, BLOCK_SIZE, false);
+    let pfs0_offset = table.len().next_multiple_of(0x200);
+
+    let mut fs = vec![0u8; 0x200];
+    fs[0x2] = 1;
+    fs[0x3] = 2;
+    fs[0x4] = 3;
+    fs[0x8..0x28].copy_from_slice(&sha256(&table));
+    fs[0x28..0x2C].copy_from_slice(&(BLOCK_SIZE as u32).to_le_bytes());
+    fs[0x2C..0x30].copy_from_slice(&2u32.to_le_bytes());
+    fs[0x38..0x40].copy_from_slice(&(table.len() as u64).to_le_bytes());
+    fs[0x40..0x48].copy_from_slice(&(pfs0_offset as u64).to_le_bytes());
+    fs[0x48..0x50].copy_from_slice(&(pfs0.len() as u64).to_le_bytes());
+
+    let mut section = table;
+    section.resize(pfs0_offset, 0);
+    section.extend_from_slice(&pfs0);
+    (fs, section)
+}
+
+/// Encrypt and sign one-section NCA; `edit` may change the plain header after signing
+fn seal(
+    content_type: u8,
+    title_id: u64,
+    (mut fs, mut section): (Vec<u8>, Vec<u8>),
+    rights_id: Option<[u8; 16]>,
+    edit: impl FnOnce(&mut [u8]),
+) -> Vec<u8> {
+    section.resize(section.len().next_multiple_of(0x200), 0);
+    let mut iv = [0u8; 16];
+    iv[..8].copy_from_slice(&SECTION_CTR.to_be_bytes());
+    let mut cipher = ctr::Ctr128BE::<Aes128>::new(&SECTION_KEY.into(), &iv.into());
+    cipher.seek(SECTION_START);
+    cipher.apply_keystream(&mut section);
+    fs[0x140..0x148].copy_from_slice(&SECTION_CTR.to_le_bytes());
+
+    let end = SECTION_START + section.len() as u64;
+    let mut header = vec![0u8; 0xC00];
+    header[0x200..0x204].copy_from_slice(b"NCA3");
+    header[0x205] = content_type;
+    header[0x208..0x210].copy_from_slice(&end.to_le_bytes());
+    header[0x210..0x218].copy_from_slice(&title_id.to_le_bytes());
+    header[0x240..0x244].copy_from_slice(&((SECTION_START / 0x200) as u32).to_le_bytes());
+    header[0x244..0x248].copy_from_slice(&((end / 0x200) as u32).to_le_bytes());
+    header[0x280..0x2A0].copy_from_slice(&sha256(&fs));
+    match rights_id {
+        Some(rights_id) => header[0x230..0x240].copy_from_slice(&rights_id),
+        None => header[0x320..0x330].copy_from_slice(&ecb_encrypt(&KEY_AREA_KEY, SECTION_KEY)),
+    }
+    header[0x400..0x600].copy_from_slice(&fs);
+    let signature = sign(&header);
+    header[..0x100].copy_from_slice(&signature);
+    edit(&mut header);
+
+    xts_encrypt(&mut header, &HEADER_KEY);
+    header.extend_from_slice(&section);
+    header
+}
+
+/// An NCA whose RomFS is encrypted with the key area, or with a title key when `rights_id` is set
+pub fn nca(content_type: u8, title_id: u64, romfs: &[u8], rights_id: Option<[u8; 16]>) -> Vec<u8> {
+    seal(content_type, title_id, ivfc_section(romfs), rights_id, |_| {})
+}
+
+/// An NCA changed after it was signed
+pub fn tampered_nca(content_type: u8, title_id: u64, romfs: &[u8], edit: impl FnOnce(&mut [u8])) -> Vec<u8> {
+    seal(content_type, title_id, ivfc_section(romfs), None, edit)
+}
+
+/// A meta NCA whose CNMT records the SHA-256 of each `(file name, NCA, content type)`
+pub fn meta_nca(title_id: u64, contents: &[(&str, &[u8], u8)]) -> Vec<u8> {
+    let mut cnmt = vec![0u8; 0x20];
+    cnmt[..8].copy_from_slice(&title_id.to_le_bytes());
+    cnmt[0xC] = 0x80;
+    cnmt[0xE..0x10].copy_from_slice(&0x10u16.to_le_bytes());
+    cnmt[0x10..0x12].copy_from_slice(&(contents.len() as u16).to_le_bytes());
+    cnmt.resize(0x30, 0);
+    for (name, nca, content_type) in contents {
+        let id: Vec<u8> = (0..16).map(|i| u8::from_str_radix(&name[i * 2..i * 2 + 2], 16).unwrap()).collect();
+        cnmt.extend_from_slice(&sha256(nca));
+        cnmt.extend_from_slice(&id);
+        cnmt.extend_from_slice(&(nca.len() as u64).to_le_bytes()[..6]);
+        cnmt.push(*content_type);
+        cnmt.push(0);
+    }
+    let name = format!("Application_{:016x}.cnmt", title_id);
+    seal(1, title_id, pfs0_section(&[(name, cnmt)]), None, |_| {})
+}
+
+/// A common ticket holding the section key
+pub fn ticket(rights_id: [u8; 16]) -> Vec<u8> {
+    let mut ticket = vec![0u8; 0x2C0];
+    ticket[..4].copy_from_slice(&0x10004u32.to_le_bytes());
+    ticket[0x180..0x190].copy_from_slice(&ecb_encrypt(&TITLEKEK, SECTION_KEY));
+    ticket[0x2A0..0x2B0].copy_from_slice(&rights_id);
+    ticket
+}
+
+/// RomFS of a program NCA, with its data 0x80 bytes in
+pub fn program_romfs() -> Vec<u8> {
+    romfs(&[("data.bin", &[0xEE; 0x100])])
+}
+
+/// A game with a program, control data and a CNMT listing both
+pub fn game_nsp(title_id: u64) -> Vec<u8> {
+    game_nsp_with_program(title_id, nca(0, title_id, &program_romfs(), None))
+}
+
+pub fn game_nsp_with_program(title_id: u64, program: Vec<u8>) -> Vec<u8> {
+    let nacp = nacp(&[(0, "Test Game", "Test Publisher"), (2, "テストゲーム", "テスト")], "1.2.0");
+    let romfs = romfs(&[("control.nacp", &nacp), ("icon_AmericanEnglish.dat", ICON)]);
+    let control = nca(2, title_id, &romfs, None);
+    let meta = meta_nca(title_id, &[(PROGRAM, &program, 1), (CONTROL, &control, 3)]);
+    pfs0(&[
+        (PROGRAM.to_string(), program),
+        (CONTROL.to_string(), control),
+        (META.to_string(), meta),
+    ])
+}
@@ -0,0 +1,184 @@
+//! Pipelined bulk transfers, driven against a stand-in for libusb's transfer queue
+use anyhow::Result;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use switchlink_backend::async_usb::{BulkPipeline, TransferQueue};
+use switchlink_backend::config::Config;
+
+/// What the queue saw, shared with the test
+#[derive(Default)]
+struct Wire {
+    received: Vec<u8>,
+    submitted: Vec<usize>,
+    most_queued: usize,
+    cancelled: usize,
+}
+
+/// Copies each transfer's data when it's queued and completes them in order
+#[derive(Default)]
+struct FakeQueue {
+    wire: Rc<RefCell<Wire>>,
+    in_flight: VecDeque<Vec<u8>>,
+    // Transfer number (from 0) that fails when waited for
+    fail_at: Option<usize>,
+    completed: usize,
+}
+
+impl TransferQueue for FakeQueue {
+    unsafe fn submit(&mut self, data: *const u8, len: usize) -> Result<()> {
+        // SAFETY: the pipeline keeps the data alive until the transfer is waited for
+        let bytes = unsafe { std::slice::from_raw_parts(data, len) }.to_vec();
+        self.in_flight.push_back(bytes);
+        let mut wire = self.wire.borrow_mut();
+        wire.submitted.push(len);
+        wire.most_queued = wire.most_queued.max(self.in_flight.len());
+        Ok(())
+    }
+
+    fn wait_oldest(&mut self) -> Result<Option<usize>> {
+        let Some(bytes) = self.in_flight.pop_front() else {
+            return Ok(None);
+        };
+        let number = self.completed;
+        self.completed += 1;
+        if self.fail_at == Some(number) {
+            return Err(rusb::Error::Timeout.into());
+        }
+        self.wire.borrow_mut().received.extend_from_slice(&bytes);
+        Ok(Some(bytes.len()))
+    }
+
+    fn queued(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    fn cancel_all(&mut self) {
+        self.wire.borrow_mut().cancelled += self.in_flight.len();
+        self.in_flight.clear();
+    }
+}
+
+fn content(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+#[test]
+fn streams_arrive_in_order_with_buffers_reused() {
+    let queue = FakeQueue::default();
+    let wire = queue.wire.clone();
+    let data = content(10 * 64 + 5);
+    let mut pool = Vec::new();
+
+    let mut completions = Vec::new();
+    let sent = BulkPipeline::new(queue, 3, 64, &mut pool)
+        .stream(&mut data.as_slice(), data.len(), &mut |_| {}, &mut |len| completions.push(len))
+        .unwrap();
+
+    assert_eq!(sent, data.len());
+    let wire = wire.borrow();
+    assert_eq!(wire.received, data);
+    assert_eq!(wire.most_queued, 3);
+    assert_eq!(completions, [64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 5]);
+    // Never more buffers than transfers in flight
+    assert!(pool.len() <= 3 && !pool.is_empty());
+}
+
+#[test]
+fn streams_stop_at_the_end_of_the_reader() {
+    let queue = FakeQueue::default();
+    let wire = queue.wire.clone();
+    let data = content(100);
+    let mut pool = Vec::new();
+
+    let sent = BulkPipeline::new(queue, 4, 64, &mut pool)
+        .stream(&mut data.as_slice(), 1000, &mut |_| {}, &mut |_| {})
+        .unwrap();
+    assert_eq!(sent, 100);
+    assert_eq!(wire.borrow().received, data);
+}
+
+#[test]
+fn slices_are_sent_in_transfer_sized_chunks() {
+    let queue = FakeQueue::default();
+    let wire = queue.wire.clone();
+    let data = content(300);
+    let mut pool = Vec::new();
+
+    let sent = BulkPipeline::new(queue, 2, 128, &mut pool).send_slice(&data, &mut |_| {}, &mut |_| {}).unwrap();
+    assert_eq!(sent, 300);
+    let wire = wire.borrow();
+    assert_eq!(wire.received, data);
+    assert_eq!(wire.submitted, [128, 128, 44]);
+    assert_eq!(wire.most_queued, 2);
+    // Nothing was copied into pooled buffers
+    assert!(pool.is_empty());
+}
+
+#[test]
+fn a_failed_transfer_cancels_the_rest_without_retrying() {
+    let queue = FakeQueue { fail_at: Some(1), ..FakeQueue::default() };
+    let wire = queue.wire.clone();
+    let data = content(8 * 16);
+    let mut pool = Vec::new();
+
+    let mut completions = 0;
+    let error = BulkPipeline::new(queue, 4, 16, &mut pool)
+        .stream(&mut data.as_slice(), data.len(), &mut |_| {}, &mut |_| completions += 1)
+        .unwrap_err();
+    assert_eq!(error.downcast_ref::<rusb::Error>(), Some(&rusb::Error::Timeout));
+    assert_eq!(completions, 1);
+    let wire = wire.borrow();
+    assert_eq!(wire.received, &data[..16]);
+    // The failed transfer was not queued again, and whatever was behind it was cancelled
+    assert_eq!(wire.submitted.len(), 5);
+    assert_eq!(wire.cancelled, 3);
+    assert_eq!(pool.len(), 4);
+
+    let queue = FakeQueue { fail_at: Some(0), ..FakeQueue::default() };
+    let wire = queue.wire.clone();
+    let mut pool = Vec::new();
+    assert!(BulkPipeline::new(queue, 2, 16, &mut pool).send_slice(&data, &mut |_| {}, &mut |_| {}).is_err());
+    assert_eq!(wire.borrow().cancelled, 1);
+}
+
+#[test]
+fn pacing_happens_before_each_transfer_is_queued() {
+    let queue = FakeQueue::default();
+    let wire = queue.wire.clone();
+    let data = content(4 * 32);
+    let mut pool = Vec::new();
+
+    // How many transfers were already queued each time pacing was asked
+    let mut queued_at_pace = Vec::new();
+    let sent = BulkPipeline::new(queue, 4, 32, &mut pool)
+        .stream(&mut data.as_slice(), data.len(), &mut |_| queued_at_pace.push(wire.borrow().submitted.len()), &mut |_| {})
+        .unwrap();
+    assert_eq!(sent, data.len());
+    assert_eq!(queued_at_pace, [0, 1, 2, 3]);
+
+    let queue = FakeQueue::default();
+    let wire = queue.wire.clone();
+    let mut paced = Vec::new();
+    BulkPipeline::new(queue, 4, 48, &mut pool)
+        .send_slice(&data, &mut |len| paced.push((len, wire.borrow().submitted.len())), &mut |_| {})
+        .unwrap();
+    assert_eq!(paced, [(48, 0), (48, 1), (32, 2)]);
+}
+
+#[test]
+fn transfer_sizes_must_fit_a_libusb_transfer() {
+    let mut config = Config::default();
+    config.apply_args(&["--transfer-size=2147483647".to_string()]).unwrap();
+    assert_eq!(config.transfer.transfer_size, i32::MAX as usize);
+    for size in ["0", "2147483648", "99999999999"] {
+        let error = config.apply_args(&["--transfer-size".to_string(), size.to_string()]).unwrap_err();
+        assert!(error.to_string().contains("transfer size must be"), "{}", error);
+    }
+
+    let error = serde_json::from_str::<Config>(r#"{"transfer": {"transfer_size": 4294967296}}"#).unwrap_err();
+    assert!(error.to_string().contains("transfer size must be"), "{}", error);
+    let config: Config = serde_json::from_str(r#"{"transfer": {"transfer_size": 65536}}"#).unwrap();
+    assert_eq!(config.transfer.transfer_size, 65536);
+}
@@ -0,0 +1,227 @@
+//! Library roots on drives that go offline or come back at another mount point
+mod common;
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use common::Library;
+use switchlink_backend::database::{Database, GameFile};
+use switchlink_backend::volumes::{add_root, parse_mountinfo, resolve_roots, volume_of, Mount};
+
+impl Library {
+    /// A folder standing in for a mount point, with files under `Switch/`
+    fn drive(&self, name: &str, files: &[&str]) -> PathBuf {
+        let mount = self.path(name);
+        std::fs::create_dir_all(mount.join("Switch/Updates")).unwrap();
+        for file in files {
+            std::fs::write(mount.join("Switch").join(file), file.as_bytes()).unwrap();
+        }
+        mount
+    }
+
+    fn file(&self, name: &str) -> GameFile {
+        self.db.get_files().unwrap().into_iter().find(|f| f.filename == name).unwrap()
+    }
+}
+
+fn usb(mount_point: &Path) -> Mount {
+    Mount { mount_point: mount_point.to_path_buf(), volume_id: "uuid:1234-ABCD".into(), label: Some("GAMES".into()) }
+}
+
+const GAME: &str = "Game [0100AAAA00000000][v0].nsp";
+const UPDATE: &str = "Updates/Game [0100AAAA00000800][v65536].nsp";
+
+#[test]
+fn mountinfo_names_disks_and_shares() {
+    let mountinfo = "\
+22 1 8:2 / / rw,relatime - ext4 /dev/sda2 rw
+25 22 0:21 / /run/user/1000 rw - tmpfs tmpfs rw
+40 22 8:17 / /media/me/My\\040Drive rw,nosuid - exfat /dev/sdb1 rw
+41 22 8:17 /Switch /srv/switch rw - exfat /dev/sdb1 rw
+42 22 8:33 / /mnt/stick rw shared:5 - vfat /dev/sdc1 rw
+50 22 0:50 / /mnt/nas rw - cifs //nas/games rw
+";
+    let uuids = HashMap::from([
+        (PathBuf::from("/dev/sda2"), "root-uuid".to_string()),
+        (PathBuf::from("/dev/sdb1"), "1234-ABCD".to_string()),
+    ]);
+    let labels = HashMap::from([
+        (PathBuf::from("/dev/sdb1"), "My Drive".to_string()),
+        (PathBuf::from("/dev/sdc1"), "STICK".to_string()),
+    ]);
+
+    let mounts = parse_mountinfo(mountinfo, &uuids, &labels);
+    let ids: Vec<_> = mounts.iter().map(|m| m.volume_id.as_str()).collect();
+    assert_eq!(ids, ["uuid:root-uuid", "uuid:1234-ABCD", "label:STICK", "net://nas/games"]);
+    assert_eq!(mounts[1].mount_point, PathBuf::from("/media/me/My Drive"));
+    assert_eq!(mounts[1].label.as_deref(), Some("My Drive"));
+
+    // The deepest mount point wins
+    let (mount, rel) = volume_of(&PathBuf::from("/media/me/My Drive/Switch/a.nsp"), &mounts).unwrap();
+    assert_eq!(mount.volume_id, "uuid:1234-ABCD");
+    assert_eq!(rel, "Switch/a.nsp");
+    assert_eq!(volume_of(&PathBuf::from("/home/me"), &mounts).unwrap().0.volume_id, "uuid:root-uuid");
+}
+
+#[test]
+fn roots_follow_their_volume_to_a_new_mount_point() {
+    let lib = Library::new();
+    let old_mount = lib.drive("sdb1", &[GAME, UPDATE]);
+    let root = old_mount.join("Switch");
+    add_root(&lib.db, &root, &[usb(&old_mount)]).unwrap();
+    lib.db.add_directory(&root, &["nsp"]).unwrap();
+
+    let roots = lib.db.get_roots().unwrap();
+    assert_eq!(roots.len(), 1);
+    assert_eq!(roots[0].volume_id.as_deref(), Some("uuid:1234-ABCD"));
+    assert_eq!(roots[0].volume_path, "Switch");
+    assert_eq!(roots[0].file_count, 2);
+
+    let game = lib.file(GAME);
+    lib.db.toggle_favorite(game.id).unwrap();
+    lib.db.add_tag(game.id, "Kids").unwrap();
+    lib.db.create_collection("Travel", &[root.join(GAME)]).unwrap();
+    lib.db.save_queue(&[root.join(UPDATE)]).unwrap();
+
+    // Unchanged mounts change nothing
+    assert!(resolve_roots(&lib.db, &[usb(&old_mount)]).unwrap().is_empty());
+
+    // The drive comes back somewhere else
+    let new_mount = lib.dir.path().join("run-media-GAMES");
+    std::fs::rename(&old_mount, &new_mount).unwrap();
+    let check = resolve_roots(&lib.db, &[usb(&new_mount)]).unwrap();
+    assert_eq!(check.relocated.len(), 1);
+    assert_eq!(check.relocated[0].1, new_mount.join("Switch"));
+
+    let game = lib.file(GAME);
+    assert_eq!(PathBuf::from(&game.path), new_mount.join("Switch").join(GAME));
+    assert!(game.favorite);
+    assert_eq!(game.tags, ["Kids"]);
+    assert!(!game.offline);
+    assert_eq!(
+        PathBuf::from(&lib.file("Game [0100AAAA00000800][v65536].nsp").path),
+        new_mount.join("Switch/Updates/Game [0100AAAA00000800][v65536].nsp")
+    );
+    assert_eq!(lib.db.get_collection_items("Travel").unwrap(), [new_mount.join("Switch").join(GAME)]);
+    assert_eq!(lib.db.load_queue().unwrap(), [new_mount.join("Switch").join(UPDATE)]);
+    assert_eq!(lib.db.get_roots().unwrap()[0].path, new_mount.join("Switch"));
+}
+
+#[test]
+fn nested_roots_move_with_the_root_around_them() {
+    let lib = Library::new();
+    let old_mount = lib.drive("sdb1", &[GAME, UPDATE]);
+    let outer = old_mount.join("Switch");
+    let inner = outer.join("Updates");
+    add_root(&lib.db, &outer, &[usb(&old_mount)]).unwrap();
+    // Registered before volumes were recorded, so it has none of its own
+    lib.db.add_root(&inner, None, None, "").unwrap();
+    lib.db.add_directory(&outer, &["nsp"]).unwrap();
+    lib.db.create_collection("Travel", &[inner.join("Game [0100AAAA00000800][v65536].nsp")]).unwrap();
+
+    let new_mount = lib.dir.path().join("run-media-GAMES");
+    std::fs::rename(&old_mount, &new_mount).unwrap();
+    let check = resolve_roots(&lib.db, &[usb(&new_mount)]).unwrap();
+    assert!(check.went_offline.is_empty());
+    let relocated: Vec<_> = check.relocated.iter().map(|(_, path)| path.clone()).collect();
+    assert_eq!(relocated, [new_mount.join("Switch"), new_mount.join("Switch/Updates")]);
+
+    let paths: Vec<_> = lib.db.get_roots().unwrap().iter().map(|r| (r.path.clone(), r.online, r.file_count)).collect();
+    assert_eq!(paths, [(new_mount.join("Switch"), true, 1), (new_mount.join("Switch/Updates"), true, 1)]);
+    let update = lib.file("Game [0100AAAA00000800][v65536].nsp");
+    assert_eq!(PathBuf::from(&update.path), new_mount.join("Switch/Updates/Game [0100AAAA00000800][v65536].nsp"));
+    assert!(!update.offline);
+    assert_eq!(
+        lib.db.get_collection_items("Travel").unwrap(),
+        [new_mount.join("Switch/Updates/Game [0100AAAA00000800][v65536].nsp")]
+    );
+}
+
+#[test]
+fn offline_roots_are_kept_by_cleanup() {
+    let lib = Library::new();
+    let mount = lib.drive("sdb1", &[GAME]);
+    let root = mount.join("Switch");
+    add_root(&lib.db, &root, &[usb(&mount)]).unwrap();
+    lib.db.add_directory(&root, &["nsp"]).unwrap();
+
+    // A file outside any root that's gone is still cleaned up
+    let loose = lib.dir.path().join("loose.nsp");
+    std::fs::write(&loose, b"loose").unwrap();
+    lib.db.add_file(&loose).unwrap();
+    std::fs::remove_file(&loose).unwrap();
+    assert_eq!(lib.db.unrooted_paths().unwrap(), HashSet::from([loose.clone()]));
+
+    // Unplugged: the mount point is left empty, on another volume
+    let unplugged = lib.dir.path().join("unplugged");
+    std::fs::rename(&mount, &unplugged).unwrap();
+    std::fs::create_dir_all(&mount).unwrap();
+    let rootfs = || Mount { mount_point: lib.dir.path().to_path_buf(), volume_id: "uuid:rootfs".into(), label: None };
+    let check = resolve_roots(&lib.db, &[rootfs()]).unwrap();
+    assert_eq!(check.went_offline.len(), 1);
+    assert!(lib.file(GAME).offline);
+    assert!(!lib.db.get_roots().unwrap()[0].online);
+
+    assert_eq!(lib.db.cleanup().unwrap(), 1);
+    assert!(lib.db.get_files().unwrap().iter().all(|f| f.filename != "loose.nsp"));
+    assert!(lib.db.get_files().unwrap().iter().any(|f| f.filename == GAME));
+    // Still offline, so nothing new to report
+    assert!(resolve_roots(&lib.db, &[rootfs()]).unwrap().is_empty());
+
+    // Plugged back in at the same place
+    std::fs::remove_dir(&mount).unwrap();
+    std::fs::rename(&unplugged, &mount).unwrap();
+    let check = resolve_roots(&lib.db, &[rootfs(), usb(&mount)]).unwrap();
+    assert_eq!(check.came_back.len(), 1);
+    assert!(!lib.file(GAME).offline);
+}
+
+#[test]
+fn roots_without_a_volume_and_nested_roots() {
+    let lib = Library::new();
+    let share = lib.drive("nas", &[GAME, UPDATE]);
+    let outer = share.join("Switch");
+    let inner = outer.join("Updates");
+    lib.db.add_directory(&outer, &["nsp"]).unwrap();
+
+    // Files added before their root still attach to it, to the deepest root
+    add_root(&lib.db, &outer, &[]).unwrap();
+    let inner_id = add_root(&lib.db, &inner, &[]).unwrap();
+    let counts: Vec<_> = lib.db.get_roots().unwrap().iter().map(|r| (r.path.clone(), r.file_count)).collect();
+    assert_eq!(counts, [(outer.clone(), 1), (inner.clone(), 1)]);
+
+    lib.db.remove_root(inner_id).unwrap();
+    assert_eq!(lib.db.get_roots().unwrap()[0].file_count, 2);
+
+    // Without a volume ID the folder just has to exist
+    std::fs::rename(&share, lib.dir.path().join("elsewhere")).unwrap();
+    let check = resolve_roots(&lib.db, &[]).unwrap();
+    assert_eq!(check.went_offline.len(), 1);
+    assert!(check.relocated.is_empty());
+    assert!(lib.file(GAME).offline);
+}
+
+#[test]
+fn folders_from_before_roots_become_roots() {
+    let lib = Library::new();
+    for name in ["drive/Switch/a.nsp", "drive/Switch/Updates/b.nsp", "other/c.nsp"] {
+        lib.db.add_file(&lib.write(name, name)).unwrap();
+    }
+    // A library from before roots existed
+    let conn = rusqlite::Connection::open(lib.path("games.db")).unwrap();
+    conn.execute_batch("UPDATE files SET root_id = NULL, rel_path = NULL; DROP TABLE library_roots").unwrap();
+    let db = Database::new(&lib.path("games.db")).unwrap();
+
+    let roots: Vec<_> = db.get_roots().unwrap().iter().map(|r| (r.path.clone(), r.file_count)).collect();
+    assert_eq!(roots, [(lib.path("drive/Switch"), 2), (lib.path("other"), 1)]);
+    assert!(db.unrooted_paths().unwrap().is_empty());
+
+    // So an unplugged drive keeps its files out of cleanup
+    std::fs::rename(lib.path("drive"), lib.path("unplugged")).unwrap();
+    assert_eq!(resolve_roots(&db, &[]).unwrap().went_offline.len(), 1);
+    assert!(db.missing_files().unwrap().is_empty());
+
+    // Reopening doesn't register anything again
+    drop(db);
+    assert_eq!(Database::new(&lib.path("games.db")).unwrap().get_roots().unwrap().len(), 2);
+}
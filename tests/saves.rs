@@ -0,0 +1,158 @@
+//! Save backup retention and snapshot diffing
+use std::io::Write;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+use switchlink_backend::config::Config;
+use switchlink_backend::database::Database;
+use switchlink_backend::saves::{diff_snapshots, DiffChange, RetentionPolicy, SaveLibrary};
+
+const TITLE: &str = "0100AAAA00000000";
+const USER: &str = "000000000000000000000000000000AB";
+const DAY: i64 = 86_400;
+
+struct Saves {
+    dir: tempfile::TempDir,
+    db: Database,
+    library: SaveLibrary,
+}
+
+impl Saves {
+    fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(&dir.path().join("games.db")).unwrap();
+        let library = SaveLibrary::new(dir.path().join("saves"));
+        Self { dir, db, library }
+    }
+
+    /// A snapshot file created `age_days` ago
+    fn snapshot(&self, name: &str, age_days: i64) -> i64 {
+        let dir = self.library.snapshot_dir(TITLE, USER);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, name).unwrap();
+        let created_at = chrono::Utc::now().timestamp() - age_days * DAY;
+        self.db.add_save_snapshot(TITLE, USER, &path, 4, "hash", created_at).unwrap()
+    }
+
+    fn kept(&self) -> Vec<String> {
+        self.db
+            .get_save_snapshots(TITLE, USER)
+            .unwrap()
+            .into_iter()
+            .map(|s| Path::new(&s.path).file_name().unwrap().to_string_lossy().to_string())
+            .collect()
+    }
+
+    fn apply(&self, keep_last: Option<usize>, max_age_days: Option<u64>) -> usize {
+        let policy = RetentionPolicy { keep_last: keep_last.and_then(NonZeroUsize::new), max_age_days };
+        self.library.apply_retention(&self.db, TITLE, USER, &policy).unwrap()
+    }
+}
+
+#[test]
+fn retention_keeps_the_newest_and_pinned_snapshots() {
+    let saves = Saves::new();
+    saves.snapshot("a.zip", 4);
+    let pinned = saves.snapshot("b.zip", 3);
+    saves.snapshot("c.zip", 2);
+    saves.snapshot("d.zip", 1);
+    saves.snapshot("e.zip", 0);
+    saves.db.toggle_save_pinned(pinned).unwrap();
+
+    // Nothing is removed without limits
+    assert_eq!(saves.apply(None, None), 0);
+
+    assert_eq!(saves.apply(Some(2), None), 2);
+    assert_eq!(saves.kept(), ["e.zip", "d.zip", "b.zip"]);
+    let dir = saves.library.snapshot_dir(TITLE, USER);
+    assert!(!dir.join("a.zip").exists() && !dir.join("c.zip").exists());
+    assert!(dir.join("b.zip").exists());
+}
+
+#[test]
+fn retention_by_age_always_keeps_the_newest() {
+    let saves = Saves::new();
+    saves.snapshot("old.zip", 40);
+    saves.snapshot("older.zip", 60);
+    assert_eq!(saves.apply(None, Some(30)), 1);
+    assert_eq!(saves.kept(), ["old.zip"]);
+
+    saves.snapshot("new.zip", 0);
+    assert_eq!(saves.apply(None, Some(30)), 1);
+    assert_eq!(saves.kept(), ["new.zip"]);
+}
+
+#[test]
+fn huge_ages_never_expire() {
+    let saves = Saves::new();
+    saves.snapshot("old.zip", 4000);
+    saves.snapshot("new.zip", 0);
+    for days in [u64::MAX, i64::MAX as u64, i64::MAX as u64 / 86_400 + 1] {
+        assert_eq!(saves.apply(None, Some(days)), 0, "{}", days);
+    }
+    assert_eq!(saves.kept(), ["new.zip", "old.zip"]);
+}
+
+#[test]
+fn keeping_zero_snapshots_is_refused() {
+    let error = serde_json::from_str::<Config>(r#"{"save_retention": {"keep_last": 0}}"#).unwrap_err();
+    assert!(error.to_string().contains("nonzero"), "{}", error);
+
+    let config: Config = serde_json::from_str(r#"{"save_retention": {"keep_last": null}}"#).unwrap();
+    assert_eq!(config.save_retention.keep_last, None);
+    let config: Config = serde_json::from_str(r#"{"save_retention": {"keep_last": 3}}"#).unwrap();
+    assert_eq!(config.save_retention.keep_last, NonZeroUsize::new(3));
+}
+
+fn zip(dir: &Path, name: &str, entries: &[(&str, &[u8])]) -> PathBuf {
+    let path = dir.join(name);
+    let mut writer = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+    let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for (entry, data) in entries {
+        writer.start_file(*entry, options).unwrap();
+        writer.write_all(data).unwrap();
+    }
+    writer.finish().unwrap();
+    path
+}
+
+#[test]
+fn snapshots_are_diffed_by_entry() {
+    let saves = Saves::new();
+    let dir = saves.dir.path();
+    let old = zip(dir, "old.zip", &[("save.dat", b"level 1"), ("gone.dat", b"x"), ("same.dat", b"same")]);
+    let new = zip(dir, "new.zip", &[("save.dat", b"level 20"), ("same.dat", b"same"), ("extra/new.dat", b"yz")]);
+
+    let diff = diff_snapshots(&old, &new).unwrap();
+    let changes: Vec<_> = diff.iter().map(|d| (d.path.as_str(), d.change, d.old_size, d.new_size)).collect();
+    assert_eq!(
+        changes,
+        [
+            ("extra/new.dat", DiffChange::Added, 0, 2),
+            ("gone.dat", DiffChange::Removed, 1, 0),
+            ("save.dat", DiffChange::Modified, 7, 8),
+        ]
+    );
+    assert!(diff_snapshots(&old, &old).unwrap().is_empty());
+
+    // Same size, different content: caught by the CRC
+    let edited = zip(dir, "edited.zip", &[("save.dat", b"level 2"), ("gone.dat", b"x"), ("same.dat", b"same")]);
+    let diff = diff_snapshots(&old, &edited).unwrap();
+    assert_eq!(diff.len(), 1);
+    assert_eq!((diff[0].path.as_str(), diff[0].change), ("save.dat", DiffChange::Modified));
+}
+
+#[test]
+fn other_archives_are_compared_whole() {
+    let saves = Saves::new();
+    let dir = saves.dir.path();
+    std::fs::write(dir.join("a.bin"), b"raw save").unwrap();
+    std::fs::write(dir.join("b.bin"), b"raw save").unwrap();
+    std::fs::write(dir.join("c.bin"), b"changed!!").unwrap();
+
+    assert!(diff_snapshots(&dir.join("a.bin"), &dir.join("b.bin")).unwrap().is_empty());
+    let diff = diff_snapshots(&dir.join("a.bin"), &dir.join("c.bin")).unwrap();
+    assert_eq!(diff.len(), 1);
+    assert_eq!((diff[0].path.as_str(), diff[0].change, diff[0].old_size, diff[0].new_size), ("(archive)", DiffChange::Modified, 8, 9));
+}
@@ -0,0 +1,139 @@
+//! Library search: query operators, ranking, typo tolerance and paging
+mod common;
+
+use std::path::Path;
+
+use common::Library;
+use switchlink_backend::database::Database;
+use switchlink_backend::search::{edit_distance, SearchQuery};
+use switchlink_backend::title::ContentType;
+
+const GB: u64 = 1024 * 1024 * 1024;
+
+impl Library {
+    /// Add a sparse file of `size` bytes
+    fn add(&self, name: &str, size: u64) -> i64 {
+        let path = self.path(name);
+        std::fs::File::create(&path).unwrap().set_len(size).unwrap();
+        self.db.add_file(&path).unwrap()
+    }
+
+    fn names(&self, query: &str) -> Vec<String> {
+        self.db
+            .search_page(&SearchQuery::parse(query), 0, 100)
+            .unwrap()
+            .files
+            .into_iter()
+            .map(|f| f.filename)
+            .collect()
+    }
+}
+
+#[test]
+fn parses_operators_and_text() {
+    let query = SearchQuery::parse("Zelda.BotW type:dlc tag:Kids size>4GB size<=10G installed:never");
+    assert_eq!(query.terms, ["zelda", "botw"]);
+    assert_eq!(query.content_type, Some(ContentType::Dlc));
+    assert_eq!(query.tags, ["kids"]);
+    assert_eq!(query.min_size, Some(4 * GB + 1));
+    assert_eq!(query.max_size, Some(10 * GB));
+    assert_eq!(query.installed, Some(false));
+
+    // Unknown operators are plain text
+    assert_eq!(SearchQuery::parse("type:movie size>huge").terms, ["type", "movie", "size", "huge"]);
+    assert!(SearchQuery::parse("   ").is_empty());
+}
+
+#[test]
+fn edit_distance_counts_swaps_as_one() {
+    assert_eq!(edit_distance("zelda", "zelda", 2), Some(0));
+    assert_eq!(edit_distance("zeldaa", "zelde", 2), Some(2));
+    assert_eq!(edit_distance("zelda", "zedla", 1), Some(1));
+    assert_eq!(edit_distance("mario", "maro", 1), Some(1));
+    assert_eq!(edit_distance("mario", "luigi", 2), None);
+}
+
+#[test]
+fn prefix_search_matches_scene_names_and_title_ids() {
+    let lib = Library::new();
+    lib.add("Super Mario Odyssey [0100000000010000][v0].nsp", 10);
+    lib.add("Kart Racing [0100152000022000][v0].nsp", 10);
+    lib.add("Mario.Kart.8.Deluxe.NSW-GRP [0100152000022000][v0].nsz", 10);
+
+    assert_eq!(lib.names("odys"), ["Super Mario Odyssey [0100000000010000][v0].nsp"]);
+    let kart = lib.names("mario kart");
+    assert_eq!(kart, ["Mario.Kart.8.Deluxe.NSW-GRP [0100152000022000][v0].nsz"]);
+    assert_eq!(lib.names("0100152000022000").len(), 2);
+}
+
+#[test]
+fn typos_fall_back_to_close_matches() {
+    let lib = Library::new();
+    lib.add("The Legend of Zelda [01007EF00011E000][v0].nsp", 10);
+    lib.add("Metroid Dread [010093801237C000][v0].nsp", 10);
+
+    let results = lib.db.search_page(&SearchQuery::parse("zedla"), 0, 10).unwrap();
+    assert!(results.fuzzy);
+    assert_eq!(results.files.len(), 1);
+    assert!(results.files[0].filename.contains("Zelda"));
+
+    let results = lib.db.search_page(&SearchQuery::parse("metroid"), 0, 10).unwrap();
+    assert!(!results.fuzzy);
+    assert!(lib.names("xyzzy").is_empty());
+}
+
+#[test]
+fn filters_by_type_tag_size_and_installs() {
+    let lib = Library::new();
+    let base = lib.add("Game [0100AAAA00000000][v0].nsp", 5 * GB);
+    lib.add("Game [0100AAAA00000800][v65536].nsp", GB);
+    lib.add("Game Pack [0100AAAA00001001][v0].nsp", 100);
+    lib.db.add_tag(base, "kids").unwrap();
+    lib.db.record_install(base).unwrap();
+
+    assert_eq!(lib.names("type:dlc"), ["Game Pack [0100AAAA00001001][v0].nsp"]);
+    assert_eq!(lib.names("game type:update").len(), 1);
+    assert_eq!(lib.names("size>4GB"), ["Game [0100AAAA00000000][v0].nsp"]);
+    assert_eq!(lib.names("tag:kids"), ["Game [0100AAAA00000000][v0].nsp"]);
+    assert_eq!(lib.names("kids"), ["Game [0100AAAA00000000][v0].nsp"]);
+    assert_eq!(lib.names("installed:never").len(), 2);
+    assert_eq!(lib.names("installed:yes size<2GB").len(), 0);
+}
+
+#[test]
+fn pages_through_results_with_a_total() {
+    let lib = Library::new();
+    for i in 0..25 {
+        lib.add(&format!("Title {:02}.nsp", i), 10);
+    }
+
+    let query = SearchQuery::parse("title");
+    let first = lib.db.search_page(&query, 0, 10).unwrap();
+    let last = lib.db.search_page(&query, 20, 10).unwrap();
+    assert_eq!((first.total, first.files.len()), (25, 10));
+    assert_eq!((last.total, last.files.len()), (25, 5));
+
+    // The whole library, newest first
+    let all = lib.db.search_page(&SearchQuery::default(), 0, 100).unwrap();
+    assert_eq!(all.total, 25);
+}
+
+#[test]
+fn index_follows_removals_and_reopening() {
+    let lib = Library::new();
+    let id = lib.add("Removable [0100BBBB00000000][v0].nsp", 10);
+    lib.add("Keeper.nsp", 10);
+    lib.db.remove_file(id).unwrap();
+    assert!(lib.names("removable").is_empty());
+
+    // Re-adding an existing path keeps its ID
+    let keeper = lib.dir.path().join("Keeper.nsp");
+    let first = lib.db.add_file(&keeper).unwrap();
+    assert_eq!(lib.db.add_file(&keeper).unwrap(), first);
+
+    drop(lib.db);
+    let db = Database::new(&lib.dir.path().join("games.db")).unwrap();
+    let results = db.search_page(&SearchQuery::parse("keeper"), 0, 10).unwrap();
+    assert_eq!(results.total, 1);
+    assert!(Path::new(&results.files[0].path).ends_with("Keeper.nsp"));
+}
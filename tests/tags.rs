@@ -0,0 +1,175 @@
+//! Tag tables: tagging, rename/merge/delete, colors, legacy migration and filters
+mod common;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use common::Library;
+use switchlink_backend::catalog::{Catalog, Grouping};
+use switchlink_backend::database::Database;
+use switchlink_backend::protocol::CatalogRequest;
+use switchlink_backend::search::SearchQuery;
+
+impl Library {
+    fn add(&self, name: &str) -> i64 {
+        self.db.add_file(&self.write(name, "data")).unwrap()
+    }
+
+    fn tags(&self) -> Vec<(String, usize)> {
+        self.db
+            .get_tags()
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.name, t.file_count))
+            .collect()
+    }
+
+    fn names(&self, query: &str) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .db
+            .search_page(&SearchQuery::parse(query), 0, 100)
+            .unwrap()
+            .files
+            .into_iter()
+            .map(|f| f.filename)
+            .collect();
+        names.sort();
+        names
+    }
+}
+
+#[test]
+fn tagging_is_idempotent_and_case_insensitive() {
+    let lib = Library::new();
+    let id = lib.add("Game.nsp");
+
+    lib.db.add_tag(id, "  Kids   Games ").unwrap();
+    lib.db.add_tag(id, "kids games").unwrap();
+    lib.db.add_tag(id, "RPG").unwrap();
+    assert_eq!(lib.db.get_file_tags(id).unwrap(), ["Kids Games", "RPG"]);
+    assert!(lib.db.add_tag(id, "   ").is_err());
+
+    lib.db.remove_tag(id, "rpg").unwrap();
+    assert_eq!(lib.db.get_file_tags(id).unwrap(), ["Kids Games"]);
+    // Removing a tag the file doesn't have is not an error
+    lib.db.remove_tag(id, "rpg").unwrap();
+}
+
+#[test]
+fn bulk_tagging_and_search_filter_match_whole_tags() {
+    let lib = Library::new();
+    let a = lib.add("Alpha.nsp");
+    let b = lib.add("Beta.nsp");
+    let c = lib.add("Gamma.nsp");
+
+    lib.db.tag_files(&[a, b], "rpg").unwrap();
+    lib.db.tag_files(&[c], "arpg").unwrap();
+    lib.db.tag_files(&[b, c], "board games").unwrap();
+
+    assert_eq!(lib.names("tag:rpg"), ["Alpha.nsp", "Beta.nsp"]);
+    assert_eq!(lib.names("tag:RPG tag:\"board   games\""), ["Beta.nsp"]);
+    assert_eq!(lib.names("tag:arpg"), ["Gamma.nsp"]);
+    // Tags are searchable as text too
+    assert_eq!(lib.names("board"), ["Beta.nsp", "Gamma.nsp"]);
+
+    lib.db.untag_files(&[a, b, c], "rpg").unwrap();
+    assert!(lib.names("tag:rpg").is_empty());
+    assert_eq!(lib.tags(), [("arpg".to_string(), 1), ("board games".to_string(), 2), ("rpg".to_string(), 0)]);
+}
+
+#[test]
+fn rename_refuses_existing_name_and_merge_combines() {
+    let lib = Library::new();
+    let a = lib.add("Alpha.nsp");
+    let b = lib.add("Beta.nsp");
+    lib.db.tag_files(&[a], "co-op").unwrap();
+    lib.db.tag_files(&[a, b], "coop").unwrap();
+
+    assert!(lib.db.rename_tag("co-op", "COOP").is_err());
+    lib.db.rename_tag("co-op", "Co-Op").unwrap();
+    assert_eq!(lib.db.get_file_tags(a).unwrap(), ["Co-Op", "coop"]);
+
+    lib.db.merge_tags("coop", "co-op").unwrap();
+    assert_eq!(lib.tags(), [("Co-Op".to_string(), 2)]);
+    assert_eq!(lib.db.get_file_tags(a).unwrap(), ["Co-Op"]);
+    assert_eq!(lib.names("tag:co-op"), ["Alpha.nsp", "Beta.nsp"]);
+    assert!(lib.names("coop").is_empty());
+}
+
+#[test]
+fn delete_and_colors() {
+    let lib = Library::new();
+    let a = lib.add("Alpha.nsp");
+    lib.db.add_tag(a, "kids").unwrap();
+
+    lib.db.set_tag_color("kids", "#FF8800").unwrap();
+    assert_eq!(lib.db.get_tags().unwrap()[0].color, "#FF8800");
+    assert!(lib.db.set_tag_color("kids", "orange").is_err());
+    assert!(lib.db.set_tag_color("missing", "#000000").is_err());
+    lib.db.set_tag_color("kids", "").unwrap();
+    assert_eq!(lib.db.get_tags().unwrap()[0].color, "");
+
+    lib.db.delete_tag("KIDS").unwrap();
+    assert!(lib.tags().is_empty());
+    assert!(lib.db.get_file_tags(a).unwrap().is_empty());
+    assert!(lib.names("kids").is_empty());
+}
+
+#[test]
+fn comma_joined_tags_are_migrated() {
+    let lib = Library::new();
+    let id = lib.add("Alpha.nsp");
+    let db_path = lib.dir.path().join("games.db");
+    drop(lib.db);
+
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    conn.execute("UPDATE files SET tags = 'kids, rpg,,Kids ' WHERE id = ?1", [id]).unwrap();
+    drop(conn);
+
+    let db = Database::new(&db_path).unwrap();
+    assert_eq!(db.get_file_tags(id).unwrap(), ["kids", "rpg"]);
+    assert_eq!(db.search("tag:rpg").unwrap().len(), 1);
+
+    // The old column is cleared so the next start doesn't migrate again
+    drop(db);
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    let legacy: String = conn.query_row("SELECT tags FROM files WHERE id = ?1", [id], |r| r.get(0)).unwrap();
+    assert_eq!(legacy, "");
+}
+
+#[test]
+fn catalog_filters_by_tag() {
+    let lib = Library::new();
+    let a = lib.add("Alpha.nsp");
+    lib.add("Beta.nsp");
+    lib.db.add_tag(a, "Kids").unwrap();
+
+    let files: HashMap<String, PathBuf> = ["Alpha.nsp", "Beta.nsp"]
+        .iter()
+        .map(|n| (n.to_string(), lib.path(n)))
+        .collect();
+
+    let names = |tag| -> Vec<String> {
+        Catalog::build(&files, Some(&lib.db), Grouping::Tag, tag)
+            .groups
+            .into_iter()
+            .flat_map(|g| g.entries.into_iter().map(move |e| format!("{}/{}", g.name, e.name)))
+            .collect()
+    };
+    assert_eq!(names(Some("kids")), ["Kids/Alpha.nsp"]);
+    assert_eq!(names(None), ["Kids/Alpha.nsp", "Untagged/Beta.nsp"]);
+}
+
+#[test]
+fn catalog_request_carries_optional_tag() {
+    let mut payload = Vec::new();
+    payload.extend_from_slice(&2u32.to_le_bytes());
+    payload.extend_from_slice(&1u32.to_le_bytes());
+    let old = CatalogRequest::from_bytes(&payload).unwrap();
+    assert_eq!(old.tag, None);
+
+    payload.extend_from_slice(&4u32.to_le_bytes());
+    payload.extend_from_slice(b"kids");
+    let request = CatalogRequest::from_bytes(&payload).unwrap();
+    assert_eq!((request.grouping, request.tag.as_deref()), (1, Some("kids")));
+}
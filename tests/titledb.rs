@@ -0,0 +1,158 @@
+//! Title database import: JSON and CSV dumps, enrichment and overrides
+mod common;
+
+use std::path::Path;
+
+use common::Library;
+use switchlink_backend::database::TitleInfo;
+use switchlink_backend::titledb::{import_file, parse_file, ImportSummary};
+
+const TITLEDB_JSON: &str = r#"{
+    "70010000000025": {
+        "id": "01007ef00011e000",
+        "name": "The Legend of Zelda: Breath of the Wild",
+        "publisher": "Nintendo",
+        "releaseDate": 20170303,
+        "region": "US",
+        "description": "Step into a world of discovery.",
+        "iconUrl": "https://example.invalid/icon.jpg"
+    },
+    "70010000000026": {
+        "id": "0100152000022000",
+        "name": "Mario Kart 8 Deluxe",
+        "publisher": "Nintendo",
+        "releaseDate": "2017-04-28",
+        "icon": "art/mk8.png"
+    },
+    "70010000000027": { "name": "No ID" },
+    "70010000000028": { "id": "not-a-title-id", "name": "Bad ID" }
+}"#;
+
+impl Library {
+    fn add(&self, name: &str) -> i64 {
+        let path = self.write(name, "data");
+        self.db.add_file(&path).unwrap()
+    }
+
+    fn display_name(&self, id: i64) -> String {
+        self.db.get_files().unwrap().into_iter().find(|f| f.id == id).unwrap().display_name
+    }
+
+    fn search(&self, query: &str) -> Vec<i64> {
+        self.db.search(query).unwrap().into_iter().map(|f| f.id).collect()
+    }
+}
+
+#[test]
+fn parses_titledb_json() {
+    let lib = Library::new();
+    let path = lib.write("titles.US.en.json", TITLEDB_JSON);
+
+    let (titles, skipped) = parse_file(&path).unwrap();
+    assert_eq!(skipped, 2);
+    assert_eq!(titles.len(), 2);
+    let title = |id: &str| titles.iter().find(|t| t.title_id == id).unwrap();
+
+    // IDs are stored in uppercase
+    let zelda = title("01007EF00011E000");
+    assert_eq!(zelda.release_date.as_deref(), Some("2017-03-03"));
+    assert_eq!(zelda.region.as_deref(), Some("US"));
+    // Icons are never fetched
+    assert_eq!(zelda.icon_path, None);
+
+    let kart = title("0100152000022000");
+    assert_eq!(kart.release_date.as_deref(), Some("2017-04-28"));
+    assert_eq!(kart.icon_path.as_deref().map(Path::new), Some(lib.dir.path().join("art/mk8.png").as_path()));
+}
+
+#[test]
+fn parses_csv_and_finds_icons_next_to_the_dump() {
+    let lib = Library::new();
+    let path = lib.write(
+        "titles.csv",
+        "Title ID,Name,Publisher,Release Date,Region\n\
+         0100152000022000,\"Mario Kart 8 Deluxe, Booster\",Nintendo,20170428,EU\n\
+         ,Missing,Nobody,,\n",
+    );
+    lib.write("icons/0100152000022000.png", "png");
+
+    let (titles, skipped) = parse_file(&path).unwrap();
+    assert_eq!(skipped, 1);
+    assert_eq!(titles[0].name.as_deref(), Some("Mario Kart 8 Deluxe, Booster"));
+    assert_eq!(titles[0].region.as_deref(), Some("EU"));
+    assert_eq!(
+        titles[0].icon_path.as_deref().map(Path::new),
+        Some(lib.dir.path().join("icons/0100152000022000.png").as_path())
+    );
+}
+
+#[test]
+fn import_enriches_names_and_search() {
+    let lib = Library::new();
+    let base = lib.add("BOTW [01007EF00011E000][v0].nsp");
+    let update = lib.add("botw-upd [01007EF00011E800][v786432].nsp");
+    let unknown = lib.add("Homebrew [0100AAAA00000000][v0].nsp");
+    assert!(lib.search("nintendo").is_empty());
+
+    let path = lib.write("titles.json", TITLEDB_JSON);
+    let summary = import_file(&lib.db, &path).unwrap();
+    assert_eq!(summary, ImportSummary { imported: 2, skipped: 2 });
+    assert_eq!(lib.db.title_count().unwrap(), 2);
+
+    assert_eq!(lib.display_name(base), "The Legend of Zelda: Breath of the Wild");
+    // Updates take the base game's entry
+    assert_eq!(lib.display_name(update), "The Legend of Zelda: Breath of the Wild");
+    assert_eq!(lib.display_name(unknown), "Homebrew");
+
+    let mut found = lib.search("nintendo");
+    found.sort();
+    assert_eq!(found, [base, update]);
+    assert_eq!(lib.search("breath").len(), 2);
+}
+
+#[test]
+fn overrides_survive_reimports() {
+    let lib = Library::new();
+    let base = lib.add("BOTW [01007EF00011E000][v0].nsp");
+    let path = lib.write("titles.json", TITLEDB_JSON);
+    import_file(&lib.db, &path).unwrap();
+
+    lib.db
+        .set_title_override(&TitleInfo {
+            title_id: "01007ef00011e000".to_string(),
+            name: Some("Zelda BotW".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(lib.display_name(base), "Zelda BotW");
+
+    import_file(&lib.db, &path).unwrap();
+    let info = lib.db.get_title("01007EF00011E000").unwrap().unwrap();
+    assert!(info.overridden);
+    assert_eq!(info.name.as_deref(), Some("Zelda BotW"));
+    // Fields that weren't overridden still come from the import
+    assert_eq!(info.publisher.as_deref(), Some("Nintendo"));
+    assert_eq!(lib.display_name(base), "Zelda BotW");
+
+    lib.db.clear_title_override("01007EF00011E000").unwrap();
+    assert_eq!(lib.display_name(base), "The Legend of Zelda: Breath of the Wild");
+    assert!(!lib.db.get_title("01007EF00011E000").unwrap().unwrap().overridden);
+}
+
+#[test]
+fn overrides_work_without_an_import() {
+    let lib = Library::new();
+    let file = lib.add("Homebrew [0100AAAA00000000][v0].nsp");
+
+    lib.db
+        .set_title_override(&TitleInfo {
+            title_id: "0100AAAA00000000".to_string(),
+            name: Some("My Homebrew".to_string()),
+            publisher: Some("Me".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(lib.display_name(file), "My Homebrew");
+    assert_eq!(lib.search("me"), [file]);
+    assert_eq!(lib.db.get_imported_title("0100AAAA00000000").unwrap(), None);
+}
@@ -0,0 +1,91 @@
+//! Which USB failures end a command, a session or a connection, and which writes are repeated
+use anyhow::{anyhow, Result};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use switchlink_backend::usb::{ErrorClass, Transport, TransferPolicy};
+
+#[test]
+fn only_usb_failures_end_the_session() {
+    let class = |e: anyhow::Error| ErrorClass::of(&e);
+    assert_eq!(class(rusb::Error::NoDevice.into()), ErrorClass::Disconnected);
+    assert_eq!(class(rusb::Error::Access.into()), ErrorClass::Fatal);
+    assert_eq!(class(rusb::Error::Timeout.into()), ErrorClass::Recoverable);
+    assert_eq!(class(anyhow::Error::from(rusb::Error::Pipe).context("FILE_RANGE")), ErrorClass::Recoverable);
+
+    // Failures of a single command
+    assert_eq!(class(anyhow!("File not found")), ErrorClass::Recoverable);
+    for kind in [std::io::ErrorKind::NotFound, std::io::ErrorKind::PermissionDenied, std::io::ErrorKind::InvalidData] {
+        assert_eq!(class(std::io::Error::from(kind).into()), ErrorClass::Recoverable, "{:?}", kind);
+    }
+    assert_eq!(class(rusqlite::Error::QueryReturnedNoRows.into()), ErrorClass::Recoverable);
+}
+
+/// Transport whose data writes follow a script, recording what was accepted
+struct ScriptedWrites {
+    script: Mutex<VecDeque<Result<usize, rusb::Error>>>,
+    sent: Mutex<Vec<u8>>,
+}
+
+impl ScriptedWrites {
+    fn new(script: Vec<Result<usize, rusb::Error>>) -> Self {
+        Self { script: Mutex::new(script.into()), sent: Mutex::new(Vec::new()) }
+    }
+}
+
+impl Transport for ScriptedWrites {
+    fn read(&self, _size: usize) -> Result<Vec<u8>> {
+        Err(rusb::Error::Timeout.into())
+    }
+
+    fn read_with_long_timeout(&self, _size: usize) -> Result<Vec<u8>> {
+        Err(rusb::Error::Timeout.into())
+    }
+
+    fn write(&self, data: &[u8]) -> Result<usize> {
+        Ok(data.len())
+    }
+
+    fn write_with_long_timeout(&self, data: &[u8]) -> Result<usize> {
+        Ok(data.len())
+    }
+
+    fn write_data(&self, data: &[u8]) -> Result<usize> {
+        match self.script.lock().unwrap().pop_front().unwrap_or(Ok(usize::MAX)) {
+            Ok(n) => {
+                let n = n.min(data.len());
+                self.sent.lock().unwrap().extend_from_slice(&data[..n]);
+                Ok(n)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn policy(retries: u32) -> TransferPolicy {
+    TransferPolicy { retries, backoff_ms: 1, max_backoff_ms: 1, ..TransferPolicy::default() }
+}
+
+#[test]
+fn busy_writes_are_retried_and_partial_writes_continue() {
+    let transport = ScriptedWrites::new(vec![Err(rusb::Error::Busy), Ok(3), Err(rusb::Error::Busy), Ok(4)]);
+    transport.write_all(b"0123456789", &policy(2)).unwrap();
+    assert_eq!(transport.sent.lock().unwrap().as_slice(), b"0123456789");
+
+    // Out of retries
+    let transport = ScriptedWrites::new(vec![Err(rusb::Error::Busy); 3]);
+    let error = transport.write_all(b"0123", &policy(2)).unwrap_err();
+    assert_eq!(error.downcast_ref::<rusb::Error>(), Some(&rusb::Error::Busy));
+}
+
+#[test]
+fn timed_out_writes_are_not_repeated() {
+    // Part of the write may have gone out before the timeout; sending it again would duplicate bytes
+    for e in [rusb::Error::Timeout, rusb::Error::Interrupted] {
+        let transport = ScriptedWrites::new(vec![Err(e)]);
+        let error = transport.write_all(b"0123", &policy(3)).unwrap_err();
+        assert_eq!(error.downcast_ref::<rusb::Error>(), Some(&e));
+        assert!(transport.sent.lock().unwrap().is_empty());
+        assert_eq!(transport.script.lock().unwrap().len(), 0);
+    }
+}
@@ -0,0 +1,190 @@
+//! Soft-deleting library files into the trash, cleanup dry runs, quarantine and permanent deletion
+mod common;
+
+use common::Library;
+use switchlink_backend::database::GameFile;
+use switchlink_backend::search::SearchQuery;
+use switchlink_backend::trash;
+
+impl Library {
+    fn add(&self, name: &str) -> i64 {
+        let path = self.games().join(name);
+        std::fs::write(&path, name.as_bytes()).unwrap();
+        self.db.add_file(&path).unwrap()
+    }
+
+    fn trashed(&self, id: i64) -> GameFile {
+        self.db.get_trash().unwrap().into_iter().find(|f| f.id == id).unwrap()
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.db.get_files().unwrap().into_iter().map(|f| f.filename).collect()
+    }
+}
+
+const ZELDA: &str = "Zelda [01007EF00011E000][v0].nsp";
+const MARIO: &str = "Mario [0100000000010000][v0].nsp";
+
+#[test]
+fn trashed_files_are_hidden_but_keep_their_data() {
+    let lib = Library::new();
+    let zelda = lib.add(ZELDA);
+    lib.add(MARIO);
+    lib.db.toggle_favorite(zelda).unwrap();
+    lib.db.add_tag(zelda, "RPG").unwrap();
+    lib.db.record_install(zelda).unwrap();
+
+    lib.db.trash_files(&[zelda]).unwrap();
+    assert_eq!(lib.names(), [MARIO]);
+    assert!(lib.db.get_favorites().unwrap().is_empty());
+    assert_eq!(lib.db.get_stats().unwrap().0, 1);
+    assert!(lib.db.search("zelda").unwrap().is_empty());
+    assert_eq!(lib.db.search_page(&SearchQuery::parse("tag:RPG"), 0, 50).unwrap().total, 0);
+
+    let trashed = lib.trashed(zelda);
+    assert!(trashed.deleted_at.is_some());
+    assert!(trashed.favorite);
+    assert_eq!(trashed.tags, ["RPG"]);
+    assert_eq!(trashed.install_count, 1);
+    assert!(lib.db.get_file_by_path(&lib.games().join(ZELDA)).unwrap().is_some());
+
+    trash::restore(&lib.db, &trashed).unwrap();
+    assert!(lib.db.get_trash().unwrap().is_empty());
+    let zelda = lib.db.get_favorites().unwrap();
+    assert_eq!(zelda.len(), 1);
+    assert_eq!(zelda[0].tags, ["RPG"]);
+    assert_eq!(lib.db.search("zelda").unwrap().len(), 1);
+
+    // Adding a trashed file again takes it out of the trash too
+    let before = lib.names();
+    lib.db.trash_files(&[lib.db.get_files().unwrap()[0].id]).unwrap();
+    lib.db.add_directory(&lib.games(), &["nsp"]).unwrap();
+    assert_eq!(lib.names(), before);
+    assert!(lib.db.get_trash().unwrap().is_empty());
+}
+
+#[test]
+fn cleanup_reports_before_it_trashes() {
+    let lib = Library::new();
+    let zelda = lib.add(ZELDA);
+    lib.add(MARIO);
+    lib.db.add_tag(zelda, "RPG").unwrap();
+    std::fs::remove_file(lib.games().join(ZELDA)).unwrap();
+
+    // The dry run changes nothing
+    let missing = lib.db.missing_files().unwrap();
+    assert_eq!(missing.len(), 1);
+    assert_eq!(missing[0].filename, ZELDA);
+    assert_eq!(lib.names().len(), 2);
+
+    assert_eq!(lib.db.cleanup().unwrap(), 1);
+    assert_eq!(lib.names(), [MARIO]);
+    assert!(lib.db.missing_files().unwrap().is_empty());
+    assert_eq!(lib.trashed(zelda).tags, ["RPG"]);
+    assert_eq!(lib.db.cleanup().unwrap(), 0);
+}
+
+#[test]
+fn quarantined_files_move_back_on_restore() {
+    let lib = Library::new();
+    let zelda = lib.add(ZELDA);
+    let quarantine = lib.dir.path().join("quarantine");
+    let original = lib.games().join(ZELDA);
+
+    let moved = trash::quarantine(&lib.db, &[lib.db.get_files().unwrap()[0].clone()], &quarantine).unwrap();
+    assert_eq!(moved, [quarantine.join(ZELDA)]);
+    assert!(!original.exists());
+    let trashed = lib.trashed(zelda);
+    assert_eq!(trashed.quarantined_path, Some(quarantine.join(ZELDA).to_string_lossy().to_string()));
+
+    // Quarantining again leaves it where it is
+    assert!(trash::quarantine(&lib.db, std::slice::from_ref(&trashed), &quarantine).unwrap().is_empty());
+
+    // Another file took its place, so restoring would overwrite it
+    std::fs::write(&original, b"new dump").unwrap();
+    assert!(trash::restore(&lib.db, &trashed).unwrap_err().to_string().contains("another file"));
+    assert!(quarantine.join(ZELDA).exists());
+    assert_eq!(lib.db.get_trash().unwrap().len(), 1);
+
+    std::fs::remove_file(&original).unwrap();
+    trash::restore(&lib.db, &trashed).unwrap();
+    assert_eq!(std::fs::read(&original).unwrap(), ZELDA.as_bytes());
+    assert!(!quarantine.join(ZELDA).exists());
+    let zelda = &lib.db.get_files().unwrap()[0];
+    assert!(zelda.deleted_at.is_none() && zelda.quarantined_path.is_none());
+}
+
+#[test]
+fn only_trashed_files_are_deleted_for_good() {
+    let lib = Library::new();
+    let zelda = lib.add(ZELDA);
+    let mario = lib.add(MARIO);
+    let quarantine = lib.dir.path().join("quarantine");
+
+    // Files still in the library can't be deleted
+    let live = lib.db.get_files().unwrap();
+    assert!(trash::delete_files(&lib.db, &live).is_err());
+    assert!(lib.games().join(ZELDA).exists());
+
+    lib.db.trash_files(&[zelda]).unwrap();
+    let mario_file = lib.db.get_files().unwrap().into_iter().find(|f| f.id == mario).unwrap();
+    trash::quarantine(&lib.db, &[mario_file], &quarantine).unwrap();
+
+    let trashed = lib.db.get_trash().unwrap();
+    assert_eq!(trash::delete_files(&lib.db, &trashed).unwrap(), 2);
+    assert!(!lib.games().join(ZELDA).exists());
+    assert!(!quarantine.join(MARIO).exists());
+    assert!(lib.db.get_trash().unwrap().is_empty());
+    assert!(lib.db.get_file_by_path(&lib.games().join(ZELDA)).unwrap().is_none());
+
+    // Emptying the trash forgets entries but leaves files alone
+    let zelda = lib.add(ZELDA);
+    lib.db.trash_files(&[zelda]).unwrap();
+    trash::forget(&lib.db, &lib.db.get_trash().unwrap()).unwrap();
+    assert!(lib.db.get_trash().unwrap().is_empty());
+    assert!(lib.names().is_empty());
+    assert!(lib.games().join(ZELDA).exists());
+}
+
+#[test]
+fn deleting_skips_entries_that_changed_since_they_were_listed() {
+    let lib = Library::new();
+    let zelda = lib.add(ZELDA);
+    let mario = lib.add(MARIO);
+    lib.db.trash_files(&[zelda, mario]).unwrap();
+    let listed = lib.db.get_trash().unwrap();
+
+    // Restored after the trash was listed
+    trash::restore(&lib.db, &lib.trashed(mario)).unwrap();
+    assert_eq!(trash::delete_files(&lib.db, &listed).unwrap(), 1);
+    assert!(lib.games().join(MARIO).exists());
+    assert_eq!(lib.names(), [MARIO]);
+    assert!(!lib.games().join(ZELDA).exists());
+
+    // Cleanup trashed it while it was gone; a new dump there isn't it
+    std::fs::remove_file(lib.games().join(MARIO)).unwrap();
+    assert_eq!(lib.db.cleanup().unwrap(), 1);
+    std::fs::write(lib.games().join(MARIO), b"new dump").unwrap();
+    assert!(lib.trashed(mario).trashed_missing);
+    assert_eq!(trash::delete_files(&lib.db, &lib.db.get_trash().unwrap()).unwrap(), 0);
+    assert!(lib.db.get_trash().unwrap().is_empty());
+    assert_eq!(std::fs::read(lib.games().join(MARIO)).unwrap(), b"new dump");
+}
+
+#[test]
+fn adding_a_file_again_keeps_its_quarantined_entry() {
+    let lib = Library::new();
+    let zelda = lib.add(ZELDA);
+    let quarantine = lib.dir.path().join("quarantine");
+    trash::quarantine(&lib.db, &lib.db.get_files().unwrap(), &quarantine).unwrap();
+
+    // A new dump at the old path doesn't lose track of the quarantined copy
+    std::fs::write(lib.games().join(ZELDA), b"new dump").unwrap();
+    lib.db.add_directory(&lib.games(), &["nsp"]).unwrap();
+    let trashed = lib.trashed(zelda);
+    assert_eq!(trashed.quarantined_path, Some(quarantine.join(ZELDA).to_string_lossy().to_string()));
+
+    assert_eq!(trash::delete_files(&lib.db, &[trashed]).unwrap(), 1);
+    assert!(!quarantine.join(ZELDA).exists());
+    assert_eq!(std::fs::read(lib.games().join(ZELDA)).unwrap(), b"new dump");
+}
@@ -0,0 +1,190 @@
+//! Real libusb paths against an emulated console (Linux only, opt-in)
+//!
+//! ```sh
+//! eval "$(sudo tests/gadget/setup.sh)"
+//! sudo -E env "PATH=$PATH" cargo test --test usb_gadget -- --ignored
+//! sudo tests/gadget/teardown.sh
+//! ```
+#![cfg(target_os = "linux")]
+
+mod gadget;
+
+use gadget::Gadget;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use switchlink_backend::config::Config;
+use switchlink_backend::protocol::*;
+use switchlink_backend::usb::{DeviceProfile, SwitchLinkServer, TransferPolicy, Transport, UsbConnection};
+
+fn bind() -> Gadget {
+    Gadget::bind().expect("SWITCHLINK_GADGET is not set; run tests/gadget/setup.sh")
+}
+
+/// Skip the reset so the endpoints stay enabled for the scripted client
+fn no_reset() -> DeviceProfile {
+    DeviceProfile {
+        reset: false,
+        ..DeviceProfile::default()
+    }
+}
+
+fn rusb_error(e: &anyhow::Error) -> Option<rusb::Error> {
+    e.downcast_ref::<rusb::Error>().copied()
+}
+
+#[test]
+#[ignore = "needs dummy_hcd + FunctionFS, see tests/gadget/setup.sh"]
+fn enumerates_serial_and_port() {
+    let _gadget = bind();
+    let devices = UsbConnection::enumerate(&DeviceProfile::default(), &[]).unwrap();
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].serial.as_deref(), Some("SWLKTEST0001"));
+}
+
+#[test]
+#[ignore = "needs dummy_hcd + FunctionFS, see tests/gadget/setup.sh"]
+fn connect_resets_claims_and_finds_endpoints() {
+    let _gadget = bind();
+    UsbConnection::connect(&DeviceProfile::default()).expect("connect with reset failed");
+}
+
+#[test]
+#[ignore = "needs dummy_hcd + FunctionFS, see tests/gadget/setup.sh"]
+fn explicit_endpoints_are_used() {
+    let gadget = bind();
+    let profile = DeviceProfile {
+        in_endpoint: Some(0x81),
+        out_endpoint: Some(0x02),
+        ..no_reset()
+    };
+    let conn = UsbConnection::connect(&profile).unwrap();
+
+    let mut client = gadget.client();
+    let sender = thread::spawn(move || {
+        client.send(b"ping");
+        client.recv(4)
+    });
+    assert_eq!(conn.read_with_long_timeout(4).unwrap(), b"ping");
+    conn.write(b"pong").unwrap();
+    assert_eq!(sender.join().unwrap(), b"pong");
+}
+
+#[test]
+#[ignore = "needs dummy_hcd + FunctionFS, see tests/gadget/setup.sh"]
+fn idle_read_times_out() {
+    let _gadget = bind();
+    let conn = UsbConnection::connect(&no_reset()).unwrap();
+    let err = conn.read(16).expect_err("read should time out with a silent client");
+    assert_eq!(rusb_error(&err), Some(rusb::Error::Timeout));
+}
+
+#[test]
+#[ignore = "needs dummy_hcd + FunctionFS, see tests/gadget/setup.sh"]
+fn unplug_reports_no_device() {
+    let gadget = bind();
+    let conn = UsbConnection::connect(&no_reset()).unwrap();
+    gadget.unplug();
+    thread::sleep(std::time::Duration::from_millis(500));
+    let err = conn.read(16).expect_err("read from an unplugged console succeeded");
+    assert_eq!(rusb_error(&err), Some(rusb::Error::NoDevice));
+}
+
+#[test]
+#[ignore = "needs dummy_hcd + FunctionFS, see tests/gadget/setup.sh"]
+fn server_streams_file_range_over_bulk() {
+    let gadget = bind();
+    let dir = tempfile::tempdir().unwrap();
+
+    // More than one 1 MB segment, so the transfer spans several bulk writes
+    let content: Vec<u8> = (0..(BUFFER_SEGMENT_DATA_SIZE + 4096)).map(|i| (i % 251) as u8).collect();
+    let game = dir.path().join("game.nsp");
+    std::fs::write(&game, &content).unwrap();
+    let files = HashMap::from([("game.nsp".to_string(), game)]);
+
+    let config = Config {
+        db_path: dir.path().join("games.db"),
+        inbox_dir: dir.path().join("inbox"),
+        saves_dir: dir.path().join("saves"),
+        usb: no_reset(),
+        ..Config::default()
+    };
+
+    let expected = content.clone();
+    let mut client = gadget.client();
+    let switch = thread::spawn(move || {
+        let name = b"game.nsp";
+        let mut request = Vec::new();
+        request.extend((expected.len() as u32).to_le_bytes());
+        request.extend(0u64.to_le_bytes());
+        request.extend((name.len() as u32).to_le_bytes());
+        request.extend(name);
+
+        client.send_header(CMD_TYPE_REQUEST, CMD_ID_FILE_RANGE, request.len() as u32);
+        let ack = client.recv_header();
+        assert_eq!((ack.cmd_type, ack.cmd_id), (CMD_TYPE_ACK, CMD_ID_FILE_RANGE));
+        client.send(&request);
+
+        let response = client.recv_header();
+        assert_eq!(response.data_size as usize, expected.len());
+        client.send_header(CMD_TYPE_ACK, CMD_ID_FILE_RANGE, response.data_size);
+        let data = client.recv_exact(expected.len());
+        assert!(data == expected, "file data corrupted in transit");
+
+        client.send_header(CMD_TYPE_REQUEST, CMD_ID_EXIT, 0);
+        let exit = client.recv_header();
+        assert_eq!((exit.cmd_type, exit.cmd_id), (CMD_TYPE_RESPONSE, CMD_ID_EXIT));
+    });
+
+    let mut server = SwitchLinkServer::new(Arc::new(Mutex::new(files))).with_config(config);
+    server.start().expect("server failed");
+    switch.join().expect("client script failed");
+}
+
+#[test]
+#[ignore = "needs dummy_hcd + FunctionFS, see tests/gadget/setup.sh"]
+fn async_pipeline_streams_in_order() {
+    let gadget = bind();
+    let conn = UsbConnection::connect(&no_reset()).unwrap();
+    let policy = TransferPolicy {
+        queue_depth: 4,
+        transfer_size: 64 * 1024,
+        ..TransferPolicy::default()
+    };
+
+    // Enough transfers to cycle every pooled buffer several times
+    let content: Vec<u8> = (0..(1024 * 1024 + 123)).map(|i| (i % 253) as u8).collect();
+    let expected = content.clone();
+    let mut client = gadget.client();
+    let receiver = thread::spawn(move || client.recv_exact(expected.len()) == expected);
+
+    let mut completions = 0;
+    let sent = conn
+        .send_stream(&mut content.as_slice(), content.len(), &policy, &mut |_| {}, &mut |_| completions += 1)
+        .unwrap();
+    assert_eq!(sent, content.len());
+    assert_eq!(completions, content.len().div_ceil(policy.transfer_size));
+    assert!(receiver.join().unwrap(), "data arrived out of order or corrupted");
+}
+
+#[test]
+#[ignore = "needs dummy_hcd + FunctionFS, see tests/gadget/setup.sh"]
+fn async_pipeline_sends_slices_in_place() {
+    let gadget = bind();
+    let conn = UsbConnection::connect(&no_reset()).unwrap();
+    let policy = TransferPolicy {
+        queue_depth: 4,
+        transfer_size: 64 * 1024,
+        ..TransferPolicy::default()
+    };
+
+    let content: Vec<u8> = (0..(512 * 1024 + 77)).map(|i| (i % 241) as u8).collect();
+    let expected = content.clone();
+    let mut client = gadget.client();
+    let receiver = thread::spawn(move || client.recv_exact(expected.len()) == expected);
+
+    let sent = conn.send_slice(&content, &policy, &mut |_| {}, &mut |_| {}).unwrap();
+    assert_eq!(sent, content.len());
+    assert!(receiver.join().unwrap(), "data arrived out of order or corrupted");
+}
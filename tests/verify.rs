@@ -0,0 +1,185 @@
+//! Integrity verification: signatures, CNMT hashes, tickets, hash trees and reports
+mod common;
+mod packages;
+
+use std::path::PathBuf;
+
+use common::Library;
+use packages::{
+    game_nsp, game_nsp_with_program, hex, keys, nca, pfs0, program_romfs, tampered_nca, ticket, CONTROL, HEADER_KEY,
+    PROGRAM, TITLE_ID,
+};
+use switchlink_backend::database::VerifyCheck;
+use switchlink_backend::keys::KeySet;
+use switchlink_backend::nca::Package;
+use switchlink_backend::verify::{refused_paths, report, verify_file, verify_library, CheckStatus, VerifyStatus};
+
+impl Library {
+    fn add(&self, name: &str, content: &[u8]) -> i64 {
+        self.db.add_file(&self.write(name, content)).unwrap()
+    }
+
+    fn status(&self, id: i64) -> Option<VerifyStatus> {
+        self.db.get_files().unwrap().into_iter().find(|f| f.id == id).unwrap().verification
+    }
+}
+
+fn find<'a>(checks: &'a [VerifyCheck], nca: &str, check: &str) -> &'a VerifyCheck {
+    checks
+        .iter()
+        .find(|c| c.nca == nca && c.check == check)
+        .unwrap_or_else(|| panic!("no {} check of {} in {:?}", check, nca, checks))
+}
+
+// Where the program's RomFS file data starts within its NCA
+const PROGRAM_DATA: u64 = 0xC00 + 0x1000 + 0x80;
+
+/// Flip one byte of a file, at an offset within one of its packaged files
+fn corrupt(path: &PathBuf, entry: &str, offset: u64) {
+    let start = Package::open(path).unwrap().find(entry).unwrap().offset;
+    let mut data = std::fs::read(path).unwrap();
+    data[(start + offset) as usize] ^= 0xFF;
+    std::fs::write(path, data).unwrap();
+}
+
+#[test]
+fn intact_files_pass_every_check() {
+    let lib = Library::new();
+    let path = lib.write("Game.nsp", game_nsp(TITLE_ID));
+
+    let checks = verify_file(&path, &keys()).unwrap();
+    assert!(checks.iter().all(|c| c.status == CheckStatus::Passed), "{:?}", checks);
+    // Three NCAs with one section each, and two listed in the CNMT
+    assert_eq!(checks.iter().filter(|c| c.check == "header signature").count(), 3);
+    assert_eq!(checks.iter().filter(|c| c.check == "section 0 hashes").count(), 3);
+    assert_eq!(checks.iter().filter(|c| c.check == "content hash").count(), 2);
+    assert_eq!(find(&checks, CONTROL, "section 0 hashes").detail, "5 IVFC hash levels match");
+    assert_eq!(report(&Ok(checks)).status, VerifyStatus::Verified);
+}
+
+#[test]
+fn corruption_fails_the_affected_checks() {
+    let lib = Library::new();
+
+    // Data past the hash levels is only covered by the CNMT hash
+    let data = lib.write("Data.nsp", game_nsp(TITLE_ID));
+    corrupt(&data, PROGRAM, PROGRAM_DATA);
+    let checks = verify_file(&data, &keys()).unwrap();
+    assert_eq!(find(&checks, PROGRAM, "content hash").status, CheckStatus::Failed);
+    assert_eq!(find(&checks, PROGRAM, "section 0 hashes").status, CheckStatus::Passed);
+    assert_eq!(find(&checks, CONTROL, "content hash").status, CheckStatus::Passed);
+    assert_eq!(report(&Ok(checks)).status, VerifyStatus::Failed);
+
+    // A damaged hash level fails the section too
+    let level = lib.write("Level.nsp", game_nsp(TITLE_ID));
+    corrupt(&level, CONTROL, 0xC00 + 0x400);
+    let checks = verify_file(&level, &keys()).unwrap();
+    assert_eq!(find(&checks, CONTROL, "section 0 hashes").status, CheckStatus::Failed);
+    assert_eq!(find(&checks, CONTROL, "section 0 hashes").detail, "IVFC level 3 block 0 does not match its hash");
+    assert_eq!(find(&checks, CONTROL, "content hash").status, CheckStatus::Failed);
+
+    // An NCA missing from the package
+    let nsp = std::fs::read(lib.write("Full.nsp", game_nsp(TITLE_ID))).unwrap();
+    let package = Package::open(&lib.dir.path().join("Full.nsp")).unwrap();
+    let entries: Vec<(String, Vec<u8>)> = package
+        .entries()
+        .iter()
+        .filter(|e| e.name != PROGRAM)
+        .map(|e| (e.name.clone(), nsp[e.offset as usize..(e.offset + e.size) as usize].to_vec()))
+        .collect();
+    let missing = lib.write("Missing.nsp", pfs0(&entries));
+    let checks = verify_file(&missing, &keys()).unwrap();
+    assert_eq!(find(&checks, PROGRAM, "content hash").detail, "listed in the CNMT but missing from the package");
+}
+
+#[test]
+fn modified_headers_fail_the_signature() {
+    let lib = Library::new();
+    // Changing the title ID after signing, as a "converted" dump would
+    let program = tampered_nca(0, TITLE_ID, &program_romfs(), |header| header[0x210] ^= 1);
+    let path = lib.write("Game.nsp", game_nsp_with_program(TITLE_ID, program));
+
+    let checks = verify_file(&path, &keys()).unwrap();
+    assert_eq!(find(&checks, PROGRAM, "header signature").status, CheckStatus::Failed);
+    assert_eq!(find(&checks, CONTROL, "header signature").status, CheckStatus::Passed);
+    // The CNMT was made from the modified NCA, so only the signature gives it away
+    assert_eq!(find(&checks, PROGRAM, "content hash").status, CheckStatus::Passed);
+}
+
+#[test]
+fn missing_keys_and_tickets() {
+    let lib = Library::new();
+    let path = lib.write("Game.nsp", game_nsp(TITLE_ID));
+
+    // Without the modulus the signature can't be checked, which doesn't fail the file
+    let no_modulus = KeySet::parse(&format!(
+        "header_key = {}\nkey_area_key_application_00 = {}",
+        hex(&HEADER_KEY),
+        hex(&[0x3c; 16])
+    ));
+    let checks = verify_file(&path, &no_modulus).unwrap();
+    let signature = find(&checks, PROGRAM, "header signature");
+    assert_eq!(signature.status, CheckStatus::Skipped);
+    assert_eq!(signature.detail, "nca_hdr_fixed_key_modulus_00 is missing from prod.keys");
+    assert_eq!(report(&Ok(checks)).status, VerifyStatus::Verified);
+
+    // Without the header key nothing can be checked
+    let result = verify_file(&path, &KeySet::parse(""));
+    let verification = report(&result);
+    assert_eq!(verification.status, VerifyStatus::Error);
+    assert_eq!(verification.error.as_deref(), Some("header_key is missing from prod.keys"));
+
+    // Title-key NCAs need their ticket
+    let rights_id = [0x42; 16];
+    let eshop = nca(0, TITLE_ID, &program_romfs(), Some(rights_id));
+    let name = "00000000000000000000000000000001.nca";
+    let with_ticket = lib.write(
+        "eShop.nsp",
+        pfs0(&[(name.to_string(), eshop.clone()), (format!("{}.tik", hex(&rights_id)), ticket(rights_id))]),
+    );
+    let checks = verify_file(&with_ticket, &keys()).unwrap();
+    assert_eq!(find(&checks, name, "ticket").status, CheckStatus::Passed);
+    assert_eq!(find(&checks, name, "section 0 hashes").status, CheckStatus::Passed);
+    // Nothing records what the NCA should hash to
+    assert_eq!(find(&checks, "", "content hashes").status, CheckStatus::Failed);
+
+    let without_ticket = lib.write("NoTicket.nsp", pfs0(&[(name.to_string(), eshop)]));
+    let checks = verify_file(&without_ticket, &keys()).unwrap();
+    assert_eq!(find(&checks, name, "ticket").detail, format!("{}.tik is missing", hex(&rights_id)));
+    assert_eq!(find(&checks, name, "section 0 hashes").status, CheckStatus::Skipped);
+}
+
+#[test]
+fn library_reports_are_stored_and_refused() {
+    let lib = Library::new();
+    let good = lib.add("good [0100AAAA00000000][v0].nsp", &game_nsp(TITLE_ID));
+    let bad_path = lib.write("bad [0100BBBB00000000][v0].nsp", game_nsp(0x0100_BBBB_0000_0000));
+    corrupt(&bad_path, PROGRAM, PROGRAM_DATA);
+    let bad = lib.db.add_file(&bad_path).unwrap();
+    let broken = lib.add("broken [0100CCCC00000000][v0].nsp", b"not an nsp");
+    assert_eq!(lib.status(good), None);
+
+    let summary = verify_library(&lib.db, &keys(), false, |_, _| {}).unwrap();
+    assert_eq!(summary.verified, 1);
+    assert_eq!(summary.failed.len(), 1);
+    assert_eq!(summary.failed[0].0, "bad [0100BBBB00000000][v0].nsp");
+    assert_eq!(summary.failed[0].1.len(), 1);
+    assert_eq!(summary.errors.len(), 1);
+
+    assert_eq!(lib.status(good), Some(VerifyStatus::Verified));
+    assert_eq!(lib.status(bad), Some(VerifyStatus::Failed));
+    assert_eq!(lib.status(broken), Some(VerifyStatus::Error));
+    let stored = lib.db.get_verification(bad).unwrap().unwrap();
+    assert_eq!(find(&stored.checks, PROGRAM, "content hash").status, CheckStatus::Failed);
+    assert!(stored.checked_at > 0);
+
+    // Failed files are only withheld when the user asks
+    assert!(refused_paths(&lib.db, false).unwrap().is_empty());
+    assert_eq!(refused_paths(&lib.db, true).unwrap().into_iter().collect::<Vec<_>>(), [bad_path]);
+
+    // Unchanged files aren't checked again unless asked
+    let again = verify_library(&lib.db, &keys(), false, |_, _| {}).unwrap();
+    assert_eq!(again.verified + again.failed.len() + again.errors.len(), 0);
+    let all = verify_library(&lib.db, &keys(), true, |_, _| {}).unwrap();
+    assert_eq!(all.verified + all.failed.len() + all.errors.len(), 3);
+}